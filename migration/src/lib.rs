@@ -1,12 +1,89 @@
 pub use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::DbBackend;
 
 mod m20220101_000001_create_table;
+mod m20261016_000001_add_expires_at;
+mod m20261016_000002_create_plans;
+mod m20261016_000003_add_rollout;
+mod m20261016_000004_create_revisions;
+mod m20261016_000005_add_archived_at;
+mod m20261016_000006_create_link_templates;
+mod m20261016_000007_create_key_sequence;
+mod m20261016_000008_create_aliases;
+mod m20261016_000009_create_campaigns;
+mod m20261016_000010_add_app_links;
+mod m20261016_000011_add_allow_indexing;
+mod m20261016_000012_add_public;
+mod m20261016_000013_create_bio_pages;
+mod m20261016_000014_create_slack_accounts;
+mod m20261016_000015_create_notification_preferences;
+mod m20261016_000016_create_clicks;
+mod m20261016_000017_partition_clicks;
+mod m20261016_000018_notify_link_changes;
+mod m20261016_000019_add_last_accessed_at;
+mod m20261016_000020_add_inactive_links;
+mod m20261016_000021_add_pinned;
+mod m20261016_000022_create_tags;
+mod m20261016_000023_add_created_at_index;
+mod m20261016_000024_add_normalized_target_hash;
+mod m20261016_000025_add_target_host;
+mod m20261016_000026_add_tenant_id;
+mod m20261016_000027_create_tenant_settings;
+mod m20261016_000028_create_scheduled_targets;
+mod m20261016_000029_add_social_preview;
+mod m20261016_000030_add_click_counts;
+mod m20261016_000031_ignore_click_counts_in_link_changes;
+mod m20261016_000032_scope_keys_to_tenants;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20261016_000001_add_expires_at::Migration),
+            Box::new(m20261016_000002_create_plans::Migration),
+            Box::new(m20261016_000003_add_rollout::Migration),
+            Box::new(m20261016_000004_create_revisions::Migration),
+            Box::new(m20261016_000005_add_archived_at::Migration),
+            Box::new(m20261016_000006_create_link_templates::Migration),
+            Box::new(m20261016_000007_create_key_sequence::Migration),
+            Box::new(m20261016_000008_create_aliases::Migration),
+            Box::new(m20261016_000009_create_campaigns::Migration),
+            Box::new(m20261016_000010_add_app_links::Migration),
+            Box::new(m20261016_000011_add_allow_indexing::Migration),
+            Box::new(m20261016_000012_add_public::Migration),
+            Box::new(m20261016_000013_create_bio_pages::Migration),
+            Box::new(m20261016_000014_create_slack_accounts::Migration),
+            Box::new(m20261016_000015_create_notification_preferences::Migration),
+            Box::new(m20261016_000016_create_clicks::Migration),
+            Box::new(m20261016_000017_partition_clicks::Migration),
+            Box::new(m20261016_000018_notify_link_changes::Migration),
+            Box::new(m20261016_000019_add_last_accessed_at::Migration),
+            Box::new(m20261016_000020_add_inactive_links::Migration),
+            Box::new(m20261016_000021_add_pinned::Migration),
+            Box::new(m20261016_000022_create_tags::Migration),
+            Box::new(m20261016_000023_add_created_at_index::Migration),
+            Box::new(m20261016_000024_add_normalized_target_hash::Migration),
+            Box::new(m20261016_000025_add_target_host::Migration),
+            Box::new(m20261016_000026_add_tenant_id::Migration),
+            Box::new(m20261016_000027_create_tenant_settings::Migration),
+            Box::new(m20261016_000028_create_scheduled_targets::Migration),
+            Box::new(m20261016_000029_add_social_preview::Migration),
+            Box::new(m20261016_000030_add_click_counts::Migration),
+            Box::new(m20261016_000031_ignore_click_counts_in_link_changes::Migration),
+            Box::new(m20261016_000032_scope_keys_to_tenants::Migration),
+        ]
+    }
+}
+
+/// The current time, written the way bound timestamps are. SQLite's own
+/// `CURRENT_TIMESTAMP` has neither the `T` nor the offset, so it would not
+/// compare correctly against them.
+fn now(manager: &SchemaManager) -> SimpleExpr {
+    match manager.get_database_backend() {
+        DbBackend::Sqlite => Expr::cust("(strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now'))"),
+        _ => Expr::current_timestamp().into(),
     }
 }
@@ -1,5 +1,7 @@
 use sea_orm_migration::{prelude::*, schema::*};
 
+use crate::now;
+
 #[derive(DeriveMigrationName)]
 pub struct Migration;
 
@@ -17,11 +19,11 @@ impl MigrationTrait for Migration {
                     .col(string(UrlRedirects::Target))
                     .col(
                         timestamp_with_time_zone(UrlRedirects::CreatedAt)
-                            .default(Expr::current_timestamp()),
+                            .default(now(manager)),
                     )
                     .col(
                         timestamp_with_time_zone(UrlRedirects::UpdatedAt)
-                            .default(Expr::current_timestamp()),
+                            .default(now(manager)),
                     )
                     .to_owned(),
             )
@@ -0,0 +1,83 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::now;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Plans::Table)
+                    .if_not_exists()
+                    .col(string(Plans::Name).primary_key())
+                    .col(big_integer_null(Plans::MaxLinks))
+                    .col(big_integer_null(Plans::MaxCustomDomains))
+                    .col(big_integer_null(Plans::AnalyticsRetentionDays))
+                    .to_owned(),
+            )
+            .await?;
+
+        // both built-in plans start unlimited, so nobody loses anything until
+        // an admin sets limits
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(Plans::Table)
+                    .columns([Plans::Name])
+                    .values_panic(["free".into()])
+                    .values_panic(["pro".into()])
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserPlans::Table)
+                    .if_not_exists()
+                    .col(string(UserPlans::UserEmail).primary_key())
+                    .col(string(UserPlans::Plan))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(UserPlans::Table, UserPlans::Plan)
+                            .to(Plans::Table, Plans::Name),
+                    )
+                    .col(
+                        timestamp_with_time_zone(UserPlans::UpdatedAt)
+                            .default(now(manager)),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserPlans::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Plans::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Plans {
+    Table,
+    Name,
+    MaxLinks,
+    MaxCustomDomains,
+    AnalyticsRetentionDays,
+}
+
+#[derive(DeriveIden)]
+enum UserPlans {
+    Table,
+    UserEmail,
+    Plan,
+    UpdatedAt,
+}
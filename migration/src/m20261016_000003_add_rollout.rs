@@ -0,0 +1,46 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // one column per statement, as SQLite cannot alter several at once
+        for column in [
+            string_null(UrlRedirects::RolloutTarget),
+            small_integer_null(UrlRedirects::RolloutPercent),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(UrlRedirects::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [UrlRedirects::RolloutTarget, UrlRedirects::RolloutPercent] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(UrlRedirects::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    RolloutTarget,
+    RolloutPercent,
+}
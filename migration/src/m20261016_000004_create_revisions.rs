@@ -0,0 +1,69 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::now;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UrlRedirectRevisions::Table)
+                    .if_not_exists()
+                    .col(uuid(UrlRedirectRevisions::Id).primary_key())
+                    .col(uuid(UrlRedirectRevisions::UrlRedirectId))
+                    .col(integer(UrlRedirectRevisions::Revision))
+                    .col(string(UrlRedirectRevisions::Key))
+                    .col(string(UrlRedirectRevisions::Target))
+                    .col(string(UrlRedirectRevisions::ChangedBy))
+                    .col(
+                        timestamp_with_time_zone(UrlRedirectRevisions::ChangedAt)
+                            .default(now(manager)),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(
+                                UrlRedirectRevisions::Table,
+                                UrlRedirectRevisions::UrlRedirectId,
+                            )
+                            .to(UrlRedirects::Table, UrlRedirects::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .index(
+                        Index::create()
+                            .unique()
+                            .col(UrlRedirectRevisions::UrlRedirectId)
+                            .col(UrlRedirectRevisions::Revision),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UrlRedirectRevisions::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirectRevisions {
+    Table,
+    Id,
+    UrlRedirectId,
+    Revision,
+    Key,
+    Target,
+    ChangedBy,
+    ChangedAt,
+}
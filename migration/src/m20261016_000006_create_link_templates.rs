@@ -0,0 +1,56 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::now;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LinkTemplates::Table)
+                    .if_not_exists()
+                    .col(uuid(LinkTemplates::Id).primary_key())
+                    .col(string(LinkTemplates::UserEmail))
+                    .col(string(LinkTemplates::Name))
+                    .col(string(LinkTemplates::TargetPattern))
+                    .col(big_integer_null(LinkTemplates::DefaultTtlSecs))
+                    .col(
+                        timestamp_with_time_zone(LinkTemplates::CreatedAt)
+                            .default(now(manager)),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_link_templates_user_email")
+                    .table(LinkTemplates::Table)
+                    .col(LinkTemplates::UserEmail)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LinkTemplates::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum LinkTemplates {
+    Table,
+    Id,
+    UserEmail,
+    Name,
+    TargetPattern,
+    DefaultTtlSecs,
+    CreatedAt,
+}
@@ -0,0 +1,34 @@
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite has no sequences; an autoincrement key never reuses ids either
+        let statement = match manager.get_database_backend() {
+            DbBackend::Sqlite => {
+                "CREATE TABLE url_redirect_key_seq (id INTEGER PRIMARY KEY AUTOINCREMENT)"
+            }
+            _ => "CREATE SEQUENCE url_redirect_key_seq",
+        };
+        manager
+            .get_connection()
+            .execute_unprepared(statement)
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let statement = match manager.get_database_backend() {
+            DbBackend::Sqlite => "DROP TABLE url_redirect_key_seq",
+            _ => "DROP SEQUENCE url_redirect_key_seq",
+        };
+        manager
+            .get_connection()
+            .execute_unprepared(statement)
+            .await?;
+        Ok(())
+    }
+}
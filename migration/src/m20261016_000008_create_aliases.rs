@@ -0,0 +1,64 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::now;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UrlRedirectAliases::Table)
+                    .if_not_exists()
+                    .col(uuid(UrlRedirectAliases::Id).primary_key())
+                    .col(uuid(UrlRedirectAliases::UrlRedirectId))
+                    .col(string_uniq(UrlRedirectAliases::Key))
+                    .col(
+                        timestamp_with_time_zone(UrlRedirectAliases::CreatedAt)
+                            .default(now(manager)),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(UrlRedirectAliases::Table, UrlRedirectAliases::UrlRedirectId)
+                            .to(UrlRedirects::Table, UrlRedirects::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_url_redirect_aliases_url_redirect_id")
+                    .table(UrlRedirectAliases::Table)
+                    .col(UrlRedirectAliases::UrlRedirectId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UrlRedirectAliases::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirectAliases {
+    Table,
+    Id,
+    UrlRedirectId,
+    Key,
+    CreatedAt,
+}
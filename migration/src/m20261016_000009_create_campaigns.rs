@@ -0,0 +1,117 @@
+use sea_orm_migration::{prelude::*, schema::*, sea_orm::DbBackend};
+
+use crate::now;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Campaigns::Table)
+                    .if_not_exists()
+                    .col(uuid(Campaigns::Id).primary_key())
+                    .col(string(Campaigns::UserEmail))
+                    .col(string(Campaigns::Name))
+                    .col(
+                        timestamp_with_time_zone(Campaigns::CreatedAt)
+                            .default(now(manager)),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_campaigns_user_email")
+                    .table(Campaigns::Table)
+                    .col(Campaigns::UserEmail)
+                    .to_owned(),
+            )
+            .await?;
+
+        // SQLite cannot add a foreign key to an existing table, only a column
+        // referencing another one
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            manager
+                .get_connection()
+                .execute_unprepared(
+                    "ALTER TABLE url_redirects ADD COLUMN campaign_id uuid_text NULL \
+                     REFERENCES campaigns (id) ON DELETE SET NULL",
+                )
+                .await?;
+        } else {
+            manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirects::Table)
+                    .add_column(uuid_null(UrlRedirects::CampaignId))
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_url_redirects_campaign_id")
+                            .from_tbl(UrlRedirects::Table)
+                            .from_col(UrlRedirects::CampaignId)
+                            .to_tbl(Campaigns::Table)
+                            .to_col(Campaigns::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_url_redirects_campaign_id")
+                    .table(UrlRedirects::Table)
+                    .col(UrlRedirects::CampaignId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite refuses to drop indexed columns
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_url_redirects_campaign_id")
+                    .table(UrlRedirects::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirects::Table)
+                    .drop_column(UrlRedirects::CampaignId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Campaigns::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    CampaignId,
+}
+
+#[derive(DeriveIden)]
+enum Campaigns {
+    Table,
+    Id,
+    UserEmail,
+    Name,
+    CreatedAt,
+}
@@ -0,0 +1,52 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // one column per statement, as SQLite cannot alter several at once
+        for column in COLUMNS {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(UrlRedirects::Table)
+                        .add_column(string_null(column))
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in COLUMNS {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(UrlRedirects::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+const COLUMNS: [UrlRedirects; 4] = [
+    UrlRedirects::IosDeepLink,
+    UrlRedirects::IosFallbackUrl,
+    UrlRedirects::AndroidDeepLink,
+    UrlRedirects::AndroidFallbackUrl,
+];
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    IosDeepLink,
+    IosFallbackUrl,
+    AndroidDeepLink,
+    AndroidFallbackUrl,
+}
@@ -0,0 +1,102 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::now;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(BioPages::Table)
+                    .if_not_exists()
+                    .col(uuid(BioPages::Id).primary_key())
+                    .col(string_uniq(BioPages::UserEmail))
+                    .col(string_uniq(BioPages::Handle))
+                    .col(string(BioPages::Title))
+                    .col(
+                        timestamp_with_time_zone(BioPages::UpdatedAt)
+                            .default(now(manager)),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(BioPageLinks::Table)
+                    .if_not_exists()
+                    .col(uuid(BioPageLinks::Id).primary_key())
+                    .col(uuid(BioPageLinks::BioPageId))
+                    .col(uuid(BioPageLinks::UrlRedirectId))
+                    .col(integer(BioPageLinks::Position))
+                    .col(string(BioPageLinks::Title))
+                    .col(string_null(BioPageLinks::IconUrl))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(BioPageLinks::Table, BioPageLinks::BioPageId)
+                            .to(BioPages::Table, BioPages::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(BioPageLinks::Table, BioPageLinks::UrlRedirectId)
+                            .to(UrlRedirects::Table, UrlRedirects::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_bio_page_links_bio_page_id")
+                    .table(BioPageLinks::Table)
+                    .col(BioPageLinks::BioPageId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BioPageLinks::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(BioPages::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum BioPages {
+    Table,
+    Id,
+    UserEmail,
+    Handle,
+    Title,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum BioPageLinks {
+    Table,
+    Id,
+    BioPageId,
+    UrlRedirectId,
+    Position,
+    Title,
+    IconUrl,
+}
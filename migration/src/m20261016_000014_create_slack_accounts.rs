@@ -0,0 +1,57 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::now;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SlackAccounts::Table)
+                    .if_not_exists()
+                    .col(string(SlackAccounts::TeamId))
+                    .col(string(SlackAccounts::UserId))
+                    .col(string(SlackAccounts::UserEmail))
+                    .col(
+                        timestamp_with_time_zone(SlackAccounts::CreatedAt)
+                            .default(now(manager)),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(SlackAccounts::TeamId)
+                            .col(SlackAccounts::UserId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_slack_accounts_user_email")
+                    .table(SlackAccounts::Table)
+                    .col(SlackAccounts::UserEmail)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SlackAccounts::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum SlackAccounts {
+    Table,
+    TeamId,
+    UserId,
+    UserEmail,
+    CreatedAt,
+}
@@ -0,0 +1,98 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::now;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirects::Table)
+                    .add_column(boolean(UrlRedirects::ExpiryNotified).default(false))
+                    .to_owned(),
+            )
+            .await?;
+
+        // owners of links that expired before notifications existed are not
+        // told about them all at once
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(UrlRedirects::Table)
+                    .value(UrlRedirects::ExpiryNotified, true)
+                    .and_where(Expr::col(UrlRedirects::ExpiresAt).lte(now(manager)))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_url_redirects_expires_at")
+                    .table(UrlRedirects::Table)
+                    .col(UrlRedirects::ExpiresAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(NotificationPreferences::Table)
+                    .if_not_exists()
+                    .col(string(NotificationPreferences::UserEmail).primary_key())
+                    .col(boolean(NotificationPreferences::LinkExpired).default(true))
+                    .col(
+                        timestamp_with_time_zone(NotificationPreferences::UpdatedAt)
+                            .default(now(manager)),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(NotificationPreferences::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_url_redirects_expires_at")
+                    .table(UrlRedirects::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirects::Table)
+                    .drop_column(UrlRedirects::ExpiryNotified)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    ExpiresAt,
+    ExpiryNotified,
+}
+
+#[derive(DeriveIden)]
+enum NotificationPreferences {
+    Table,
+    UserEmail,
+    LinkExpired,
+    UpdatedAt,
+}
@@ -0,0 +1,52 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // no foreign key to the link: it would cost a lookup on every insert,
+        // and clicks of deleted links are still part of the history
+        manager
+            .create_table(
+                Table::create()
+                    .table(Clicks::Table)
+                    .if_not_exists()
+                    .col(
+                        big_integer(Clicks::Id)
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(uuid(Clicks::UrlRedirectId))
+                    .col(timestamp_with_time_zone(Clicks::ClickedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_clicks_url_redirect_id_clicked_at")
+                    .table(Clicks::Table)
+                    .col(Clicks::UrlRedirectId)
+                    .col(Clicks::ClickedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Clicks::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Clicks {
+    Table,
+    Id,
+    UrlRedirectId,
+    ClickedAt,
+}
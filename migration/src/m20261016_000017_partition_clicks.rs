@@ -0,0 +1,97 @@
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite has no partitioning; there, old clicks are deleted instead
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return Ok(());
+        }
+
+        // Postgres cannot partition an existing table, so the clicks move to a
+        // new one. Months from the oldest click to two ahead get a partition;
+        // the app creates later months as they approach, and the default
+        // partition catches anything it has not created yet.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                ALTER TABLE clicks RENAME TO clicks_unpartitioned;
+                ALTER INDEX clicks_pkey RENAME TO clicks_unpartitioned_pkey;
+                DROP INDEX idx_clicks_url_redirect_id_clicked_at;
+
+                CREATE TABLE clicks (
+                    id BIGINT NOT NULL DEFAULT nextval('clicks_id_seq'),
+                    url_redirect_id UUID NOT NULL,
+                    clicked_at TIMESTAMPTZ NOT NULL,
+                    PRIMARY KEY (id, clicked_at)
+                ) PARTITION BY RANGE (clicked_at);
+                ALTER SEQUENCE clicks_id_seq OWNED BY clicks.id;
+                CREATE TABLE clicks_default PARTITION OF clicks DEFAULT;
+
+                DO $$
+                DECLARE
+                    month TIMESTAMP;
+                BEGIN
+                    FOR month IN SELECT generate_series(
+                        date_trunc('month', LEAST(
+                            (SELECT min(clicked_at) FROM clicks_unpartitioned),
+                            now()
+                        ) AT TIME ZONE 'UTC'),
+                        date_trunc('month', now() AT TIME ZONE 'UTC') + interval '2 months',
+                        interval '1 month'
+                    ) LOOP
+                        EXECUTE format(
+                            'CREATE TABLE %I PARTITION OF clicks FOR VALUES FROM (%L) TO (%L)',
+                            'clicks_' || to_char(month, 'YYYY_MM'),
+                            month AT TIME ZONE 'UTC',
+                            (month + interval '1 month') AT TIME ZONE 'UTC'
+                        );
+                    END LOOP;
+                END $$;
+
+                INSERT INTO clicks (id, url_redirect_id, clicked_at)
+                    SELECT id, url_redirect_id, clicked_at FROM clicks_unpartitioned;
+                DROP TABLE clicks_unpartitioned;
+
+                CREATE INDEX idx_clicks_url_redirect_id_clicked_at
+                    ON clicks (url_redirect_id, clicked_at);
+                "#,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return Ok(());
+        }
+
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                CREATE TABLE clicks_unpartitioned (
+                    id BIGINT NOT NULL DEFAULT nextval('clicks_id_seq'),
+                    url_redirect_id UUID NOT NULL,
+                    clicked_at TIMESTAMPTZ NOT NULL,
+                    CONSTRAINT clicks_unpartitioned_pkey PRIMARY KEY (id)
+                );
+                INSERT INTO clicks_unpartitioned (id, url_redirect_id, clicked_at)
+                    SELECT id, url_redirect_id, clicked_at FROM clicks;
+                ALTER SEQUENCE clicks_id_seq OWNED BY clicks_unpartitioned.id;
+                DROP TABLE clicks;
+
+                ALTER TABLE clicks_unpartitioned RENAME TO clicks;
+                ALTER INDEX clicks_unpartitioned_pkey RENAME TO clicks_pkey;
+                CREATE INDEX idx_clicks_url_redirect_id_clicked_at
+                    ON clicks (url_redirect_id, clicked_at);
+                "#,
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite has no notifications, and the link cache requires Postgres
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return Ok(());
+        }
+
+        // Instances caching links LISTEN on `link_changes` and evict the link
+        // whose id is the payload. Only changes to existing rows are sent,
+        // since unknown keys are never cached. Triggers also cover writes made
+        // outside the app, like the CLI or a manual fix.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                CREATE FUNCTION notify_link_change() RETURNS trigger AS $$
+                BEGIN
+                    PERFORM pg_notify('link_changes', OLD.id::text);
+                    RETURN NULL;
+                END $$ LANGUAGE plpgsql;
+
+                CREATE FUNCTION notify_link_alias_change() RETURNS trigger AS $$
+                BEGIN
+                    PERFORM pg_notify('link_changes', OLD.url_redirect_id::text);
+                    RETURN NULL;
+                END $$ LANGUAGE plpgsql;
+
+                CREATE TRIGGER url_redirects_notify_change
+                    AFTER UPDATE OR DELETE ON url_redirects
+                    FOR EACH ROW EXECUTE FUNCTION notify_link_change();
+                CREATE TRIGGER url_redirect_aliases_notify_change
+                    AFTER UPDATE OR DELETE ON url_redirect_aliases
+                    FOR EACH ROW EXECUTE FUNCTION notify_link_alias_change();
+                "#,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return Ok(());
+        }
+
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                DROP TRIGGER url_redirect_aliases_notify_change ON url_redirect_aliases;
+                DROP TRIGGER url_redirects_notify_change ON url_redirects;
+                DROP FUNCTION notify_link_alias_change();
+                DROP FUNCTION notify_link_change();
+                "#,
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use sea_orm_migration::{prelude::*, schema::*, sea_orm::DbBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirects::Table)
+                    .add_column(timestamp_with_time_zone_null(UrlRedirects::LastAccessedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_url_redirects_user_email_last_accessed_at")
+                    .table(UrlRedirects::Table)
+                    .col(UrlRedirects::UserEmail)
+                    .col(UrlRedirects::LastAccessedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return Ok(());
+        }
+        // Redirects touch the access time of every clicked link; cached links
+        // stay valid through that, so it must not evict them.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                CREATE OR REPLACE FUNCTION notify_link_change() RETURNS trigger AS $$
+                BEGIN
+                    IF TG_OP = 'UPDATE'
+                        AND to_jsonb(OLD) - 'last_accessed_at' = to_jsonb(NEW) - 'last_accessed_at'
+                    THEN
+                        RETURN NULL;
+                    END IF;
+                    PERFORM pg_notify('link_changes', OLD.id::text);
+                    RETURN NULL;
+                END $$ LANGUAGE plpgsql;
+                "#,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::Sqlite {
+            manager
+                .get_connection()
+                .execute_unprepared(
+                    r#"
+                    CREATE OR REPLACE FUNCTION notify_link_change() RETURNS trigger AS $$
+                    BEGIN
+                        PERFORM pg_notify('link_changes', OLD.id::text);
+                        RETURN NULL;
+                    END $$ LANGUAGE plpgsql;
+                    "#,
+                )
+                .await?;
+        }
+
+        // SQLite refuses to drop indexed columns
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_url_redirects_user_email_last_accessed_at")
+                    .table(UrlRedirects::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirects::Table)
+                    .drop_column(UrlRedirects::LastAccessedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    UserEmail,
+    LastAccessedAt,
+}
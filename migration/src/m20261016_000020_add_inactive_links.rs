@@ -0,0 +1,61 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // one column per statement, as SQLite cannot alter several at once
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirects::Table)
+                    .add_column(timestamp_with_time_zone_null(UrlRedirects::InactiveSince))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirects::Table)
+                    .add_column(boolean(UrlRedirects::KeepWhenInactive).default(false))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Links clicked before access times were kept would otherwise look
+        // idle since their creation. Clicks past the retention are gone, so
+        // this is only a lower bound.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE url_redirects SET last_accessed_at = \
+                 (SELECT max(clicked_at) FROM clicks WHERE clicks.url_redirect_id = url_redirects.id) \
+                 WHERE last_accessed_at IS NULL",
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [UrlRedirects::KeepWhenInactive, UrlRedirects::InactiveSince] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(UrlRedirects::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    InactiveSince,
+    KeepWhenInactive,
+}
@@ -0,0 +1,67 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::now;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UrlRedirectTags::Table)
+                    .if_not_exists()
+                    .col(uuid(UrlRedirectTags::UrlRedirectId))
+                    .col(string(UrlRedirectTags::Tag))
+                    .col(
+                        timestamp_with_time_zone(UrlRedirectTags::CreatedAt)
+                            .default(now(manager)),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(UrlRedirectTags::UrlRedirectId)
+                            .col(UrlRedirectTags::Tag),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(UrlRedirectTags::Table, UrlRedirectTags::UrlRedirectId)
+                            .to(UrlRedirects::Table, UrlRedirects::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_url_redirect_tags_tag")
+                    .table(UrlRedirectTags::Table)
+                    .col(UrlRedirectTags::Tag)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UrlRedirectTags::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirectTags {
+    Table,
+    UrlRedirectId,
+    Tag,
+    CreatedAt,
+}
@@ -0,0 +1,39 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // owners list their most recent links first
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_url_redirects_user_email_created_at")
+                    .table(UrlRedirects::Table)
+                    .col(UrlRedirects::UserEmail)
+                    .col(UrlRedirects::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_url_redirects_user_email_created_at")
+                    .table(UrlRedirects::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    UserEmail,
+    CreatedAt,
+}
@@ -0,0 +1,59 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Filled in by the service after migrating, which knows how targets
+        // are normalized. A hash, as targets can outgrow an index entry.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirects::Table)
+                    .add_column(string_null(UrlRedirects::NormalizedTargetHash))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_url_redirects_user_email_normalized_target_hash")
+                    .table(UrlRedirects::Table)
+                    .col(UrlRedirects::UserEmail)
+                    .col(UrlRedirects::NormalizedTargetHash)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite refuses to drop indexed columns
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_url_redirects_user_email_normalized_target_hash")
+                    .table(UrlRedirects::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirects::Table)
+                    .drop_column(UrlRedirects::NormalizedTargetHash)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    UserEmail,
+    NormalizedTargetHash,
+}
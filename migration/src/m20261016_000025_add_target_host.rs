@@ -0,0 +1,67 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirects::Table)
+                    .add_column(string_null(UrlRedirects::TargetHost))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_url_redirects_target_host")
+                    .table(UrlRedirects::Table)
+                    .col(UrlRedirects::TargetHost)
+                    .to_owned(),
+            )
+            .await?;
+
+        // the service fills in the host of links whose target hash is
+        // missing, so clearing the hashes has it fill in every host
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(UrlRedirects::Table)
+                    .value(UrlRedirects::NormalizedTargetHash, Option::<String>::None)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite refuses to drop indexed columns
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_url_redirects_target_host")
+                    .table(UrlRedirects::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirects::Table)
+                    .drop_column(UrlRedirects::TargetHost)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    TargetHost,
+    NormalizedTargetHash,
+}
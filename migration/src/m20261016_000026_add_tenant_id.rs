@@ -0,0 +1,75 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// The tables of what users own, each added the tenant it belongs to. Rows
+/// made before tenancy, or by installations without it, keep the empty
+/// tenant.
+const TENANTED: [Tenanted; 4] = [
+    Tenanted::UrlRedirects,
+    Tenanted::Campaigns,
+    Tenanted::LinkTemplates,
+    Tenanted::BioPages,
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for table in TENANTED {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table)
+                        .add_column(string(Tenanted::TenantId).default(""))
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_url_redirects_tenant_id_user_email")
+                    .table(Tenanted::UrlRedirects)
+                    .col(Tenanted::TenantId)
+                    .col(Tenanted::UserEmail)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite refuses to drop indexed columns
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_url_redirects_tenant_id_user_email")
+                    .table(Tenanted::UrlRedirects)
+                    .to_owned(),
+            )
+            .await?;
+
+        for table in TENANTED {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table)
+                        .drop_column(Tenanted::TenantId)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden, Clone, Copy)]
+enum Tenanted {
+    UrlRedirects,
+    Campaigns,
+    LinkTemplates,
+    BioPages,
+    TenantId,
+    UserEmail,
+}
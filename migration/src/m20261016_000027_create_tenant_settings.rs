@@ -0,0 +1,44 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::now;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // a missing setting keeps the installation's
+        manager
+            .create_table(
+                Table::create()
+                    .table(TenantSettings::Table)
+                    .if_not_exists()
+                    .col(string(TenantSettings::TenantId).primary_key())
+                    .col(text_null(TenantSettings::AllowedOrigins))
+                    .col(big_integer_null(TenantSettings::MaxLinks))
+                    .col(small_integer_null(TenantSettings::RedirectStatus))
+                    .col(text_null(TenantSettings::NotFoundPage))
+                    .col(timestamp_with_time_zone(TenantSettings::UpdatedAt).default(now(manager)))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TenantSettings::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum TenantSettings {
+    Table,
+    TenantId,
+    AllowedOrigins,
+    MaxLinks,
+    RedirectStatus,
+    NotFoundPage,
+    UpdatedAt,
+}
@@ -0,0 +1,64 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::now;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // a link has at most one pending change; scheduling another replaces it
+        manager
+            .create_table(
+                Table::create()
+                    .table(ScheduledTargets::Table)
+                    .if_not_exists()
+                    .col(uuid(ScheduledTargets::UrlRedirectId).primary_key())
+                    .col(string(ScheduledTargets::Target))
+                    .col(timestamp_with_time_zone(ScheduledTargets::ApplyAt))
+                    .col(string(ScheduledTargets::ScheduledBy))
+                    .col(timestamp_with_time_zone(ScheduledTargets::CreatedAt).default(now(manager)))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ScheduledTargets::Table, ScheduledTargets::UrlRedirectId)
+                            .to(UrlRedirects::Table, UrlRedirects::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_scheduled_targets_apply_at")
+                    .table(ScheduledTargets::Table)
+                    .col(ScheduledTargets::ApplyAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ScheduledTargets::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum ScheduledTargets {
+    Table,
+    UrlRedirectId,
+    Target,
+    ApplyAt,
+    ScheduledBy,
+    CreatedAt,
+}
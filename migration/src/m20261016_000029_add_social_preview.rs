@@ -0,0 +1,50 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // one column per statement, as SQLite cannot alter several at once
+        for column in COLUMNS {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(UrlRedirects::Table)
+                        .add_column(text_null(column))
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in COLUMNS {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(UrlRedirects::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+const COLUMNS: [UrlRedirects; 3] = [
+    UrlRedirects::SocialTitle,
+    UrlRedirects::SocialDescription,
+    UrlRedirects::SocialImage,
+];
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    SocialTitle,
+    SocialDescription,
+    SocialImage,
+}
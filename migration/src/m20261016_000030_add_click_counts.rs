@@ -0,0 +1,63 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::now;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirects::Table)
+                    .add_column(big_integer(UrlRedirects::ClickCount).default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        // write-backs already applied, so one interrupted before its counts
+        // left Redis is not applied twice
+        manager
+            .create_table(
+                Table::create()
+                    .table(ClickCountWriteBacks::Table)
+                    .if_not_exists()
+                    .col(uuid(ClickCountWriteBacks::Id).primary_key())
+                    .col(
+                        timestamp_with_time_zone(ClickCountWriteBacks::AppliedAt)
+                            .default(now(manager)),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ClickCountWriteBacks::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirects::Table)
+                    .drop_column(UrlRedirects::ClickCount)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    ClickCount,
+}
+
+#[derive(DeriveIden)]
+enum ClickCountWriteBacks {
+    Table,
+    Id,
+    AppliedAt,
+}
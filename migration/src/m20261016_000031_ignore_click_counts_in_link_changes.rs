@@ -0,0 +1,58 @@
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return Ok(());
+        }
+        // Click counts are written back for every clicked link every few
+        // seconds; like the access time, they must not evict cached links.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                CREATE OR REPLACE FUNCTION notify_link_change() RETURNS trigger AS $$
+                BEGIN
+                    IF TG_OP = 'UPDATE'
+                        AND to_jsonb(OLD) - 'last_accessed_at' - 'click_count'
+                            = to_jsonb(NEW) - 'last_accessed_at' - 'click_count'
+                    THEN
+                        RETURN NULL;
+                    END IF;
+                    PERFORM pg_notify('link_changes', OLD.id::text);
+                    RETURN NULL;
+                END $$ LANGUAGE plpgsql;
+                "#,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return Ok(());
+        }
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                CREATE OR REPLACE FUNCTION notify_link_change() RETURNS trigger AS $$
+                BEGIN
+                    IF TG_OP = 'UPDATE'
+                        AND to_jsonb(OLD) - 'last_accessed_at' = to_jsonb(NEW) - 'last_accessed_at'
+                    THEN
+                        RETURN NULL;
+                    END IF;
+                    PERFORM pg_notify('link_changes', OLD.id::text);
+                    RETURN NULL;
+                END $$ LANGUAGE plpgsql;
+                "#,
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use sea_orm_migration::{prelude::*, schema::*, sea_orm::DbBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // aliases take the tenant of their link
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirectAliases::Table)
+                    .add_column(string(UrlRedirectAliases::TenantId).default(""))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE url_redirect_aliases SET tenant_id = \
+                 (SELECT tenant_id FROM url_redirects \
+                  WHERE url_redirects.id = url_redirect_aliases.url_redirect_id)",
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_url_redirects_tenant_id_key")
+                    .table(UrlRedirects::Table)
+                    .col(UrlRedirects::TenantId)
+                    .col(UrlRedirects::Key)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_url_redirect_aliases_tenant_id_key")
+                    .table(UrlRedirectAliases::Table)
+                    .col(UrlRedirectAliases::TenantId)
+                    .col(UrlRedirectAliases::Key)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // SQLite cannot drop a column's UNIQUE without rebuilding the table,
+        // so there keys stay unique across tenants; it only backs local
+        // development.
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return Ok(());
+        }
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "ALTER TABLE url_redirects DROP CONSTRAINT IF EXISTS url_redirects_key_key; \
+                 ALTER TABLE url_redirect_aliases \
+                 DROP CONSTRAINT IF EXISTS url_redirect_aliases_key_key;",
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::Sqlite {
+            // fails while two tenants share a key, which has to be resolved first
+            manager
+                .get_connection()
+                .execute_unprepared(
+                    "ALTER TABLE url_redirects \
+                     ADD CONSTRAINT url_redirects_key_key UNIQUE (key); \
+                     ALTER TABLE url_redirect_aliases \
+                     ADD CONSTRAINT url_redirect_aliases_key_key UNIQUE (key);",
+                )
+                .await?;
+        }
+
+        // SQLite refuses to drop indexed columns
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_url_redirect_aliases_tenant_id_key")
+                    .table(UrlRedirectAliases::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_url_redirects_tenant_id_key")
+                    .table(UrlRedirects::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UrlRedirectAliases::Table)
+                    .drop_column(UrlRedirectAliases::TenantId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirects {
+    Table,
+    TenantId,
+    Key,
+}
+
+#[derive(DeriveIden)]
+enum UrlRedirectAliases {
+    Table,
+    TenantId,
+    Key,
+}
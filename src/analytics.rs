@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use reqwest::StatusCode;
+
+use crate::{click_buffer::Click, config::ClickHouseConfig};
+
+const CLICKHOUSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, thiserror::Error)]
+pub enum AnalyticsError {
+    #[error("database error: {0}")]
+    Database(#[from] sea_orm::DbErr),
+    #[error("clickhouse request failed: {0}")]
+    ClickHouse(#[from] reqwest::Error),
+    #[error("clickhouse answered {0}: {1}")]
+    ClickHouseStatus(StatusCode, String),
+}
+
+/// Where clicks end up for analysis.
+#[async_trait]
+pub trait AnalyticsStore: Send + Sync {
+    async fn insert_clicks(&self, clicks: Vec<Click>) -> Result<(), AnalyticsError>;
+}
+
+/// Writes clicks to ClickHouse over its HTTP interface, keeping the bulk of
+/// analytics writes off Postgres.
+pub struct ClickHouse {
+    client: reqwest::Client,
+    config: ClickHouseConfig,
+}
+
+impl ClickHouse {
+    pub fn new(config: ClickHouseConfig) -> reqwest::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(CLICKHOUSE_TIMEOUT)
+            .build()?;
+
+        Ok(Self { client, config })
+    }
+
+    /// Creates the clicks table unless it exists. ClickHouse enforces the
+    /// retention itself through the table's TTL.
+    pub async fn ensure_table(&self, retention_months: u32) -> Result<(), AnalyticsError> {
+        self.execute(
+            format!(
+                "CREATE TABLE IF NOT EXISTS {}.clicks (\
+                 url_redirect_id UUID, \
+                 clicked_at DateTime64(6, 'UTC')\
+                 ) ENGINE = MergeTree \
+                 PARTITION BY toYYYYMM(clicked_at) \
+                 ORDER BY (url_redirect_id, clicked_at) \
+                 TTL toDateTime(clicked_at) + INTERVAL {retention_months} MONTH",
+                self.config.database
+            ),
+            String::new(),
+        )
+        .await
+    }
+
+    async fn execute(&self, query: String, body: String) -> Result<(), AnalyticsError> {
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .query(&[("query", query)])
+            .body(body);
+        if let Some(user) = &self.config.user {
+            request = request.header("X-ClickHouse-User", user);
+        }
+        if let Some(password) = &self.config.password {
+            request = request.header("X-ClickHouse-Key", password);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(AnalyticsError::ClickHouseStatus(status, message));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AnalyticsStore for ClickHouse {
+    async fn insert_clicks(&self, clicks: Vec<Click>) -> Result<(), AnalyticsError> {
+        let rows: String = clicks
+            .iter()
+            .map(|click| {
+                let row = serde_json::json!({
+                    "url_redirect_id": click.url_redirect_id,
+                    "clicked_at": click.clicked_at.format("%Y-%m-%d %H:%M:%S%.6f").to_string(),
+                });
+                format!("{row}\n")
+            })
+            .collect();
+
+        self.execute(
+            format!(
+                "INSERT INTO {}.clicks (url_redirect_id, clicked_at) FORMAT JSONEachRow",
+                self.config.database
+            ),
+            rows,
+        )
+        .await
+    }
+}
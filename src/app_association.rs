@@ -0,0 +1,67 @@
+use std::path::Path;
+
+use axum::response::{IntoResponse, Response};
+use http::{header::CONTENT_TYPE, StatusCode};
+
+use crate::config::AppAssociationConfig;
+
+/// The files that let mobile apps claim our links as universal links (iOS)
+/// or app links (Android).
+#[derive(Default)]
+pub struct AppAssociation {
+    apple_app_site_association: Option<String>,
+    android_asset_links: Option<String>,
+}
+
+impl AppAssociation {
+    /// Reads the configured files once at startup, rejecting any that is not
+    /// valid JSON, since the platforms silently ignore a broken one.
+    pub fn load(config: AppAssociationConfig) -> std::io::Result<Self> {
+        Ok(Self {
+            apple_app_site_association: config
+                .apple_app_site_association
+                .as_deref()
+                .map(read_json)
+                .transpose()?,
+            android_asset_links: config
+                .android_asset_links
+                .as_deref()
+                .map(read_json)
+                .transpose()?,
+        })
+    }
+
+    pub fn apple_app_site_association(&self) -> Response {
+        json_response(self.apple_app_site_association.as_deref())
+    }
+
+    pub fn android_asset_links(&self) -> Response {
+        json_response(self.android_asset_links.as_deref())
+    }
+}
+
+fn read_json(path: &Path) -> std::io::Result<String> {
+    let content = std::fs::read_to_string(path).map_err(|error| {
+        std::io::Error::new(
+            error.kind(),
+            format!("cannot read {}: {error}", path.display()),
+        )
+    })?;
+    serde_json::from_str::<serde_json::Value>(&content).map_err(|error| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} is not valid JSON: {error}", path.display()),
+        )
+    })?;
+
+    Ok(content)
+}
+
+fn json_response(content: Option<&str>) -> Response {
+    match content {
+        Some(content) => {
+            ([(CONTENT_TYPE, "application/json")], content.to_string()).into_response()
+        }
+        None => (StatusCode::NOT_FOUND, "not found").into_response(),
+    }
+}
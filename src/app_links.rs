@@ -0,0 +1,79 @@
+use axum::response::{Html, IntoResponse, Response};
+use http::{
+    header::{CACHE_CONTROL, LOCATION, USER_AGENT},
+    HeaderMap, HeaderValue, StatusCode,
+};
+
+use crate::{not_found::escape_html, responses::AppLinks};
+
+// Long enough for the app to take over when it is installed.
+const FALLBACK_DELAY_MS: u32 = 1500;
+
+/// The mobile platform a request comes from, going by its User-Agent.
+#[derive(Debug, Clone, Copy)]
+pub enum Platform {
+    Ios,
+    Android,
+}
+
+impl Platform {
+    pub fn detect(headers: &HeaderMap) -> Option<Self> {
+        let user_agent = headers.get(USER_AGENT)?.to_str().ok()?;
+        if ["iPhone", "iPad", "iPod"]
+            .iter()
+            .any(|device| user_agent.contains(device))
+        {
+            Some(Self::Ios)
+        } else if user_agent.contains("Android") {
+            Some(Self::Android)
+        } else {
+            None
+        }
+    }
+}
+
+/// Opens the app for `platform`, if the link has one, falling back to its
+/// store page or to `target` when the app is not installed. `None` when the
+/// request should get the plain redirect.
+pub fn response(app_links: &AppLinks, platform: Platform, target: &str) -> Option<Response> {
+    let app_link = match platform {
+        Platform::Ios => app_links.ios.as_ref(),
+        Platform::Android => app_links.android.as_ref(),
+    }?;
+    let fallback = app_link.fallback_url.as_deref().unwrap_or(target);
+
+    // intent URLs carry their own fallback and Android resolves them itself;
+    // one stored before deep links were normalized may not make a header,
+    // and the page opens it just as well
+    if app_link.deep_link.starts_with("intent:") {
+        if let Ok(location) = HeaderValue::try_from(&app_link.deep_link) {
+            return Some((StatusCode::TEMPORARY_REDIRECT, [(LOCATION, location)]).into_response());
+        }
+    }
+
+    let page = format!(
+        r#"<!doctype html>
+<html>
+<head><meta charset="utf-8"><title>Opening&hellip;</title></head>
+<body>
+<p><a href="{fallback_href}">Continue</a></p>
+<script>
+window.location.replace({deep_link});
+setTimeout(function () {{ window.location.replace({fallback}); }}, {FALLBACK_DELAY_MS});
+</script>
+</body>
+</html>
+"#,
+        fallback_href = escape_html(fallback),
+        deep_link = js_string(&app_link.deep_link),
+        fallback = js_string(fallback),
+    );
+    // whether the app opens depends on the device, so nothing may cache this
+    Some(([(CACHE_CONTROL, "no-store")], Html(page)).into_response())
+}
+
+fn js_string(value: &str) -> String {
+    serde_json::to_string(value)
+        .expect("strings always serialize")
+        .replace("</", "<\\/")
+}
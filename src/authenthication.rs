@@ -1,25 +1,44 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    net::IpAddr,
+    sync::Arc,
+    time::Duration,
+};
 
 use axum::{
     async_trait,
     extract::FromRequestParts,
     response::{IntoResponse, Response},
 };
-use http::StatusCode;
-use redis::{AsyncCommands, SetOptions};
+use http::{header::RETRY_AFTER, StatusCode};
+use redis::{AsyncCommands, Expiry, SetOptions};
+use sha2::{Digest, Sha256};
 
 use crate::{
+    client_ip,
+    config::{HttpClientConfig, ServiceAccountConfig, TokenCacheConfig},
+    error::{problem, ProblemType},
+    identity_provider::IdentityProvider,
+    jwt::{self, Claims},
     kvs::{KvsError, KvsPool, KvsPoolError},
+    lockout::AuthLockout,
+    request_id,
     responses::AuthResponse,
-    Services,
+    session::{self, SessionStore},
+    tenant, Services,
 };
 
 #[derive(Debug, thiserror::Error)]
 pub enum AuthenticationError {
     #[error("unauthorized")]
     Unauthorized,
+    #[error("forbidden")]
+    Forbidden,
+    #[error("too many failed attempts, retry in {}s", .0.as_secs())]
+    Blocked(Duration),
     #[error("internal error: {0}")]
-    Internal(Box<dyn std::error::Error>),
+    Internal(Box<dyn std::error::Error + Send + Sync>),
 }
 
 impl From<KvsPoolError> for AuthenticationError {
@@ -38,9 +57,18 @@ impl IntoResponse for AuthenticationError {
     fn into_response(self) -> Response {
         match self {
             Self::Unauthorized => (StatusCode::UNAUTHORIZED, "unauthorized"),
+            Self::Forbidden => (StatusCode::FORBIDDEN, "forbidden"),
+            Self::Blocked(retry_after) => {
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(RETRY_AFTER, retry_after.as_secs().to_string())],
+                    "too many failed authentication attempts",
+                )
+                    .into_response()
+            }
             Self::Internal(error) => {
                 tracing::error!(%error, "internal server error on authentication");
-                (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+                return problem(ProblemType::Internal, "Internal Server Error");
             }
         }
         .into_response()
@@ -53,9 +81,65 @@ impl From<AuthenticationError> for Response {
     }
 }
 
+/// Builds the HTTP client shared by every call to the identity providers, so
+/// connections are pooled and kept alive between requests.
+pub fn http_client(config: &HttpClientConfig) -> reqwest::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(config.timeout)
+        .connect_timeout(config.connect_timeout)
+        .pool_idle_timeout(Duration::from_secs(90))
+        .tcp_keepalive(Duration::from_secs(60));
+    if let Some(proxy_url) = &config.proxy_url {
+        builder = builder.proxy(reqwest::Proxy::all(proxy_url)?);
+    }
+
+    builder.build()
+}
+
+fn authorization_header(parts: &http::request::Parts) -> Result<&str, AuthenticationError> {
+    parts
+        .headers
+        .get(http::header::AUTHORIZATION)
+        .ok_or(AuthenticationError::Unauthorized)?
+        .to_str()
+        .map_err(|_| AuthenticationError::Unauthorized)
+}
+
+/// Names the provider that issued an opaque token. JWTs are routed by their
+/// issuer instead, and requests without it use the first provider.
+pub const IDENTITY_PROVIDER_HEADER: &str = "x-identity-provider";
+
+fn identity_provider(parts: &http::request::Parts) -> Option<&str> {
+    parts
+        .headers
+        .get(IDENTITY_PROVIDER_HEADER)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Lets an admin act as the given user.
+pub const IMPERSONATE_HEADER: &str = "x-impersonate";
+
+fn impersonated_user(parts: &http::request::Parts) -> Option<&str> {
+    parts
+        .headers
+        .get(IMPERSONATE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+}
+
 #[derive(Debug, Clone)]
 pub struct Requester {
+    /// The user the request acts as.
     pub email: String,
+    /// The admin behind an impersonated request.
+    pub impersonated_by: Option<String>,
+}
+
+impl Requester {
+    /// Who actually made the request, for recording changes.
+    pub fn actor(&self) -> &str {
+        self.impersonated_by.as_deref().unwrap_or(&self.email)
+    }
 }
 
 #[async_trait]
@@ -66,186 +150,529 @@ impl FromRequestParts<Arc<Services>> for Requester {
         parts: &mut http::request::Parts,
         state: &Arc<Services>,
     ) -> Result<Self, Self::Rejection> {
-        let header = parts
-            .headers
-            .get(http::header::AUTHORIZATION)
-            .ok_or(AuthenticationError::Unauthorized)?
-            .to_str()
-            .map_err(|_| AuthenticationError::Unauthorized)?;
+        let credentials = BearerToken::from_request_parts(parts, state).await?;
+
+        let request_id = request_id::request_id(&parts.extensions);
+        let client_ip = client_ip::client_ip(&parts.extensions);
+        let email = state
+            .auth
+            .guarded(
+                client_ip,
+                state.auth.introspect_token(
+                    &credentials.authorization,
+                    credentials.provider.as_deref(),
+                    request_id,
+                ),
+            )
+            .await?;
+        // admins run the installation, so they may act within any tenant
+        if !tenant::admits(&email) && !state.auth.is_admin(&email) {
+            tracing::warn!(requester = email, "requester outside the tenant refused");
+            return Err(AuthenticationError::Forbidden);
+        }
+
+        let Some(user) = impersonated_user(parts) else {
+            tracing::Span::current().record("requester", &email);
+            return Ok(Self {
+                email,
+                impersonated_by: None,
+            });
+        };
+        if !state.auth.is_admin(&email) || user.is_empty() || !tenant::admits(user) {
+            tracing::warn!(requester = email, user, "impersonation refused");
+            return Err(AuthenticationError::Forbidden);
+        }
+
+        tracing::info!(
+            target: "audit",
+            admin = email,
+            user,
+            method = %parts.method,
+            path = parts.uri.path(),
+            "impersonated request"
+        );
+        let span = tracing::Span::current();
+        span.record("requester", user);
+        span.record("impersonated_by", &email);
+
+        Ok(Self {
+            email: user.to_string(),
+            impersonated_by: Some(email),
+        })
+    }
+}
+
+/// A requester acting with a token of their own in the `Authorization`
+/// header, as scripts do: neither through a browser session nor as an admin
+/// impersonating them. The service has no API keys of its own, so user and
+/// service-account tokens stand in for them.
+#[derive(Debug, Clone)]
+pub struct TokenRequester {
+    pub email: String,
+}
 
-        let email = state.auth.introspect_token(header).await?;
+#[async_trait]
+impl FromRequestParts<Arc<Services>> for TokenRequester {
+    type Rejection = AuthenticationError;
 
-        Ok(Self { email })
+    async fn from_request_parts(
+        parts: &mut http::request::Parts,
+        state: &Arc<Services>,
+    ) -> Result<Self, Self::Rejection> {
+        authorization_header(parts)?;
+        let requester = Requester::from_request_parts(parts, state).await?;
+        if requester.impersonated_by.is_some() {
+            return Err(AuthenticationError::Forbidden);
+        }
+
+        Ok(Self {
+            email: requester.email,
+        })
+    }
+}
+
+/// A requester on the admin list. Impersonated requests never count as admin,
+/// even when the impersonated user is one.
+#[derive(Debug, Clone)]
+pub struct Admin {
+    pub email: String,
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<Services>> for Admin {
+    type Rejection = AuthenticationError;
+
+    async fn from_request_parts(
+        parts: &mut http::request::Parts,
+        state: &Arc<Services>,
+    ) -> Result<Self, Self::Rejection> {
+        let requester = Requester::from_request_parts(parts, state).await?;
+        if requester.impersonated_by.is_some() || !state.auth.is_admin(&requester.email) {
+            return Err(AuthenticationError::Forbidden);
+        }
+
+        Ok(Self {
+            email: requester.email,
+        })
+    }
+}
+
+/// The raw `Authorization` header, for endpoints that act on the token
+/// itself rather than on the user behind it. Browser clients without the
+/// header are resolved through their session cookie.
+#[derive(Debug, Clone)]
+pub struct BearerToken {
+    pub authorization: String,
+    pub provider: Option<String>,
+    pub session_id: Option<String>,
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<Services>> for BearerToken {
+    type Rejection = AuthenticationError;
+
+    async fn from_request_parts(
+        parts: &mut http::request::Parts,
+        state: &Arc<Services>,
+    ) -> Result<Self, Self::Rejection> {
+        if let Ok(authorization) = authorization_header(parts) {
+            return Ok(Self {
+                authorization: authorization.to_string(),
+                provider: identity_provider(parts).map(String::from),
+                session_id: None,
+            });
+        }
+
+        let (Some(sessions), Some(session_id)) =
+            (state.auth.sessions(), session::session_id(&parts.headers))
+        else {
+            return Err(AuthenticationError::Unauthorized);
+        };
+        let session = sessions
+            .get(session_id)
+            .await?
+            .ok_or(AuthenticationError::Unauthorized)?;
+
+        Ok(Self {
+            authorization: session.authorization,
+            provider: session.provider,
+            session_id: Some(session_id.to_string()),
+        })
     }
 }
 
 pub struct AuthenticationService {
-    host: String,
-    client_id: String,
-    client_secret: String,
-    redirect_uri: String,
+    providers: Vec<Box<dyn IdentityProvider>>,
     kvs_pool: Arc<KvsPool>,
+    token_cache: TokenCacheConfig,
+    sessions: Option<SessionStore>,
+    /// Owner namespace per OAuth client allowed to act as a service account.
+    service_accounts: HashMap<String, String>,
+    lockout: Option<AuthLockout>,
+    admins: HashSet<String>,
 }
 
 impl AuthenticationService {
+    /// `providers` must not be empty; the first one is the default.
     pub fn new(
-        host: String,
-        client_id: String,
-        client_secret: String,
-        redirect_uri: String,
+        providers: Vec<Box<dyn IdentityProvider>>,
         kvs_pool: Arc<KvsPool>,
+        token_cache: TokenCacheConfig,
     ) -> Self {
+        assert!(!providers.is_empty(), "no identity provider configured");
+
         Self {
-            host,
-            client_id,
-            client_secret,
-            redirect_uri,
+            providers,
             kvs_pool,
+            token_cache,
+            sessions: None,
+            service_accounts: HashMap::new(),
+            lockout: None,
+            admins: HashSet::new(),
         }
     }
 
-    async fn introspect_token(&self, header: &str) -> Result<String, AuthenticationError> {
-        if let Ok(Some(email)) = self
-            .get_cached_token(header)
+    /// Users allowed to impersonate others and use the admin API.
+    pub fn with_admins(mut self, admins: Vec<String>) -> Self {
+        self.admins = admins.into_iter().collect();
+        self
+    }
+
+    pub fn is_admin(&self, email: &str) -> bool {
+        self.admins.contains(email)
+    }
+
+    /// Blocks clients with too many failed authentication attempts.
+    pub fn with_lockout(mut self, lockout: AuthLockout) -> Self {
+        self.lockout = Some(lockout);
+        self
+    }
+
+    /// Runs an authentication attempt unless the client is blocked, counting
+    /// it against the client when it is rejected. Lockout bookkeeping
+    /// failures are logged and never fail the attempt itself.
+    pub async fn guarded<T>(
+        &self,
+        client_ip: Option<IpAddr>,
+        attempt: impl Future<Output = Result<T, AuthenticationError>>,
+    ) -> Result<T, AuthenticationError> {
+        let (Some(lockout), Some(ip)) = (&self.lockout, client_ip) else {
+            return attempt.await;
+        };
+
+        if let Ok(Some(retry_after)) = lockout
+            .blocked_for(ip)
             .await
-            .inspect_err(|error| tracing::error!(%error, "failed to get token from cache"))
+            .inspect_err(|error| tracing::error!(%error, "failed to check lockout"))
         {
-            tracing::debug!(email, "cache found, skipping profile call");
-            return Ok(email);
+            return Err(AuthenticationError::Blocked(retry_after));
         }
 
-        let client = reqwest::Client::new();
-        let result = client
-            .get(format!("{}/profile", self.host))
-            .header(http::header::AUTHORIZATION, header)
-            .send()
-            .await
-            .map_err(|error| AuthenticationError::Internal(Box::new(error)))?;
+        let result = attempt.await;
+        if let Err(AuthenticationError::Unauthorized) = &result {
+            lockout
+                .record_failure(ip)
+                .await
+                .inspect_err(|error| tracing::error!(%error, "failed to record auth failure"))
+                .ok();
+        }
+
+        result
+    }
+
+    /// Accepts client-credentials tokens from the given clients, acting as the
+    /// mapped owner namespace.
+    pub fn with_service_accounts(mut self, service_accounts: Vec<ServiceAccountConfig>) -> Self {
+        self.service_accounts = service_accounts
+            .into_iter()
+            .map(|account| (account.client_id, account.namespace))
+            .collect();
+        self
+    }
+
+    /// Lets browsers authenticate with a session cookie instead of the
+    /// `Authorization` header.
+    pub fn with_sessions(mut self, sessions: SessionStore) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
 
-        #[derive(Debug, serde::Deserialize)]
-        struct Profile {
-            email: String,
+    pub fn sessions(&self) -> Option<&SessionStore> {
+        self.sessions.as_ref()
+    }
+
+    fn provider(&self, name: Option<&str>) -> Result<&dyn IdentityProvider, AuthenticationError> {
+        let provider = match name {
+            None => self.providers.first(),
+            Some(name) => self
+                .providers
+                .iter()
+                .find(|provider| provider.name() == name),
+        };
+
+        provider
+            .map(AsRef::as_ref)
+            .ok_or(AuthenticationError::Unauthorized)
+    }
+
+    /// The provider that validates JWTs from the token's issuer, if any.
+    fn jwt_provider(&self, token: &str) -> Option<&dyn IdentityProvider> {
+        let issuer = jwt::unverified_issuer(token)?;
+        self.providers
+            .iter()
+            .find(|provider| provider.jwt_issuer() == Some(issuer.as_str()))
+            .map(AsRef::as_ref)
+    }
+
+    async fn introspect_token(
+        &self,
+        header: &str,
+        provider: Option<&str>,
+        request_id: Option<&str>,
+    ) -> Result<String, AuthenticationError> {
+        let token = bearer_token(header);
+        if let Some(jwt_provider) = self.jwt_provider(token) {
+            if let Some(claims) = jwt_provider.validate_jwt(token).await? {
+                if self
+                    .is_revoked(header)
+                    .await
+                    .inspect_err(|error| tracing::error!(%error, "failed to check revocation"))
+                    .unwrap_or(false)
+                {
+                    return Err(AuthenticationError::Unauthorized);
+                }
+                return self.identity(claims);
+            }
         }
 
-        let response = match result.status() {
-            StatusCode::UNAUTHORIZED => Err(AuthenticationError::Unauthorized),
-            StatusCode::BAD_REQUEST => Err(AuthenticationError::Unauthorized),
-            StatusCode::OK => result
-                .json::<Profile>()
-                .await
-                .map_err(|error| AuthenticationError::Internal(Box::new(error))),
-            _ => {
-                tracing::error!("unexpected status code: {:?}", result.status());
+        let provider = self.provider(provider)?;
+        match self
+            .get_cached_token(provider.name(), header)
+            .await
+            .inspect_err(|error| tracing::error!(%error, "failed to get token from cache"))
+        {
+            Ok(Some(CachedToken::Valid(email))) => {
+                tracing::debug!(email, "cache found, skipping profile call");
+                return Ok(email);
+            }
+            Ok(Some(CachedToken::Invalid)) => {
+                tracing::debug!("token cached as invalid, skipping profile call");
+                return Err(AuthenticationError::Unauthorized);
+            }
+            Ok(None) | Err(_) => {}
+        }
 
-                Err(AuthenticationError::Internal(Box::new(
-                    std::io::Error::other("unexpected status code"),
-                )))
+        match provider.introspect(header, request_id).await {
+            Ok(email) => {
+                self.cache_token_in_background(
+                    provider.name(),
+                    header,
+                    CachedToken::Valid(email.clone()),
+                );
+                Ok(email)
+            }
+            Err(AuthenticationError::Unauthorized) => {
+                self.cache_token_in_background(provider.name(), header, CachedToken::Invalid);
+                Err(AuthenticationError::Unauthorized)
             }
-        }?;
+            Err(error) => Err(error),
+        }
+    }
 
-        // cache the token in the background.
-        // if it fails, just log the error and continue.
-        let email = response.email.clone();
-        let token = header.to_string();
-        let kvs_pool = self.kvs_pool.clone();
-        tokio::spawn(async move {
-            cache_token(kvs_pool, &token, &email)
-                .await
-                .inspect_err(|error| {
-                    tracing::error!(%error, "failed to store token cache");
-                })
-                .ok();
-        });
+    /// Who the validated token acts as: its user, or the namespace of a
+    /// configured service account.
+    fn identity(&self, claims: Claims) -> Result<String, AuthenticationError> {
+        if let Some(client_id) = claims.service_client_id() {
+            return match self.service_accounts.get(client_id) {
+                Some(namespace) => {
+                    tracing::debug!(client_id, namespace, "service account token");
+                    Ok(namespace.clone())
+                }
+                None => {
+                    tracing::debug!(client_id, "client is not a configured service account");
+                    Err(AuthenticationError::Unauthorized)
+                }
+            };
+        }
 
-        Ok(response.email)
+        claims.email.ok_or(AuthenticationError::Unauthorized)
     }
 
     pub async fn exchange_token(
         &self,
+        provider: Option<&str>,
         authorization_code: &str,
+        code_verifier: Option<&str>,
+        request_id: Option<&str>,
     ) -> Result<AuthResponse, AuthenticationError> {
-        let client = reqwest::Client::new();
-
-        #[derive(Debug, serde::Serialize)]
-        struct TokenRequest<'a> {
-            grant_type: &'a str,
-            client_id: &'a str,
-            client_secret: &'a str,
-            redirect_uri: &'a str,
-            code: &'a str,
-        }
-        let result = client
-            .post(format!("{}/oauth2/token", self.host))
-            .form(&TokenRequest {
-                grant_type: "authorization_code",
-                client_id: &self.client_id,
-                client_secret: &self.client_secret,
-                redirect_uri: &self.redirect_uri,
-                code: authorization_code,
-            })
-            .send()
+        self.provider(provider)?
+            .exchange_token(authorization_code, code_verifier, request_id)
             .await
-            .map_err(|error| AuthenticationError::Internal(Box::new(error)))?;
+    }
 
-        #[derive(Debug, serde::Deserialize)]
-        struct TokenResponse {
-            access_token: String,
-            token_type: String,
+    /// Drops every trace of the token on our side and asks the provider that
+    /// issued it to revoke it.
+    pub async fn logout(
+        &self,
+        header: &str,
+        provider: Option<&str>,
+        request_id: Option<&str>,
+    ) -> Result<(), AuthenticationError> {
+        let token = bearer_token(header);
+
+        // locally validated JWTs never hit the cache, so they have to be
+        // remembered as revoked until they expire on their own
+        let jwt_provider = self.jwt_provider(token);
+        if let Some(jwt_provider) = jwt_provider {
+            if let Ok(Some(claims)) = jwt_provider.validate_jwt(token).await {
+                self.revoke_until(header, claims.exp).await?;
+            }
         }
-        let response = match result.status() {
-            StatusCode::BAD_REQUEST => Err(AuthenticationError::Unauthorized),
-            StatusCode::OK => result
-                .json::<TokenResponse>()
-                .await
-                .map_err(|error| AuthenticationError::Internal(Box::new(error))),
-            _ => {
-                tracing::error!("unexpected status code: {:?}", result.status());
 
-                Err(AuthenticationError::Internal(Box::new(
-                    std::io::Error::other("unexpected status code"),
-                )))
-            }
-        }?;
+        let provider = match jwt_provider {
+            Some(jwt_provider) => jwt_provider,
+            None => self.provider(provider)?,
+        };
 
-        Ok(AuthResponse::new(
-            response.access_token,
-            response.token_type,
-        ))
+        let mut conn = self.kvs_pool.get().await?;
+        conn.del::<_, ()>(token_key(provider.name(), header))
+            .await?;
+
+        provider.revoke(token, request_id).await
     }
 }
 
+fn bearer_token(header: &str) -> &str {
+    header
+        .strip_prefix("Bearer ")
+        .or_else(|| header.strip_prefix("bearer "))
+        .unwrap_or(header)
+}
+
+// Code below is for the revoked JWT list
+
+impl AuthenticationService {
+    async fn is_revoked(&self, token: &str) -> Result<bool, AuthenticationError> {
+        let mut conn = self.kvs_pool.get().await?;
+        conn.exists(revoked_key(token)).await.map_err(Into::into)
+    }
+
+    async fn revoke_until(&self, token: &str, expires_at: u64) -> Result<(), AuthenticationError> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if expires_at <= now {
+            return Ok(());
+        }
+
+        let mut conn = self.kvs_pool.get().await?;
+        conn.set_ex(revoked_key(token), "", expires_at - now)
+            .await
+            .map_err(Into::into)
+    }
+}
+
+fn revoked_key(token: &str) -> String {
+    format!("revoked-token-sha256:{:x}", Sha256::digest(token))
+}
+
 // Code below is for caching the token
 
+enum CachedToken {
+    Valid(String),
+    Invalid,
+}
+
+// Stored in place of an email for tokens the SSO rejected. Emails are never
+// empty, so the two cannot be confused.
+const INVALID_TOKEN_MARKER: &str = "";
+
 impl AuthenticationService {
     #[tracing::instrument(skip(self, token))]
-    async fn get_cached_token(&self, token: &str) -> Result<Option<String>, AuthenticationError> {
+    async fn get_cached_token(
+        &self,
+        provider: &str,
+        token: &str,
+    ) -> Result<Option<CachedToken>, AuthenticationError> {
         let mut conn = self.kvs_pool.get().await?;
-        let key = token_key(token);
+        let key = token_key(provider, token);
+
+        let value: Option<String> = if self.token_cache.refresh_on_hit {
+            let value: Option<String> = conn
+                .get_ex(&key, Expiry::EX(self.token_cache.ttl.as_secs()))
+                .await?;
+            // rejected tokens are kept for the negative TTL, if at all, which
+            // the refresh above just replaced
+            if value.as_deref() == Some(INVALID_TOKEN_MARKER) {
+                match self.token_cache.negative_ttl {
+                    Some(ttl) => conn.expire::<_, ()>(&key, ttl.as_secs() as i64).await?,
+                    None => conn.del::<_, ()>(&key).await?,
+                }
+            }
+            value
+        } else {
+            conn.get(key).await?
+        };
+
+        Ok(value.map(|value| match value.as_str() {
+            INVALID_TOKEN_MARKER => CachedToken::Invalid,
+            _ => CachedToken::Valid(value),
+        }))
+    }
 
-        conn.get(key).await.map_err(Into::into)
+    // If caching fails, just log the error and continue.
+    fn cache_token_in_background(&self, provider: &str, token: &str, value: CachedToken) {
+        let (value, ttl) = match value {
+            CachedToken::Valid(email) => (email, self.token_cache.ttl),
+            CachedToken::Invalid => match self.token_cache.negative_ttl {
+                Some(ttl) => (INVALID_TOKEN_MARKER.to_string(), ttl),
+                None => return,
+            },
+        };
+
+        let key = token_key(provider, token);
+        let kvs_pool = self.kvs_pool.clone();
+        tokio::spawn(async move {
+            cache_token(kvs_pool, key, &value, ttl)
+                .await
+                .inspect_err(|error| {
+                    tracing::error!(%error, "failed to store token cache");
+                })
+                .ok();
+        });
     }
 }
 
-#[tracing::instrument(skip(kvs_pool, token, value))]
+#[tracing::instrument(skip(kvs_pool, key, value))]
 async fn cache_token(
     kvs_pool: Arc<KvsPool>,
-    token: &str,
+    key: String,
     value: &str,
+    ttl: Duration,
 ) -> Result<(), AuthenticationError> {
     let mut conn = kvs_pool.get().await?;
-    let key = token_key(token);
 
     conn.set_options(
         key,
         value,
         SetOptions::default()
             .conditional_set(redis::ExistenceCheck::NX)
-            .with_expiration(redis::SetExpiry::EX(30)),
+            .with_expiration(redis::SetExpiry::EX(ttl.as_secs())),
     )
     .await
     .map_err(Into::into)
 }
 
-fn token_key(token: &str) -> String {
-    format!("token:{token}")
+// Keys use a new prefix so they never collide with entries written before
+// tokens were hashed; those legacy `token:<raw token>` entries expire on
+// their own TTL and are never read again. The provider is part of the hash so
+// a token rejected by the wrong provider is not cached as invalid for all.
+fn token_key(provider: &str, token: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(provider)
+        .chain_update([0])
+        .chain_update(token)
+        .finalize();
+    format!("token-sha256:{digest:x}")
 }
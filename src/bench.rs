@@ -0,0 +1,172 @@
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+
+use rand::{seq::SliceRandom, Rng};
+use serde::Serialize;
+
+pub struct BenchConfig {
+    pub url: url::Url,
+    /// The path redirects are served under, empty for the root.
+    pub redirect_prefix: String,
+    pub management_url: Option<url::Url>,
+    pub keys: Vec<String>,
+    pub authorization: Option<String>,
+    /// Share of requests sent to the management API, in percent.
+    pub api_percent: u8,
+    pub concurrency: usize,
+    pub duration: Duration,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Redirect,
+    Api,
+}
+
+#[derive(Default)]
+struct Samples {
+    latencies: Vec<Duration>,
+    errors: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    kind: &'static str,
+    requests: usize,
+    errors: u64,
+    requests_per_sec: f64,
+    p50_ms: f64,
+    p90_ms: f64,
+    p99_ms: f64,
+    max_ms: f64,
+}
+
+/// Sends redirect and API traffic to a running instance for the configured
+/// duration, then prints latency percentiles per kind, one JSON object per
+/// line. Requests failing or answered with a 5xx count as errors.
+pub async fn run(config: BenchConfig) -> Result<(), Box<dyn Error>> {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .pool_max_idle_per_host(config.concurrency)
+        .build()?;
+    let redirect_urls = config
+        .keys
+        .iter()
+        .map(|key| match config.redirect_prefix.trim_matches('/') {
+            "" => config.url.join(key),
+            prefix => config.url.join(&format!("{prefix}/{key}")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let api_url = config
+        .management_url
+        .as_ref()
+        .unwrap_or(&config.url)
+        .join("urls?limit=50")?;
+    // without credentials the API would only measure 401s
+    let api_percent = match config.authorization {
+        Some(_) => config.api_percent.min(100),
+        None => 0,
+    };
+
+    let started = Instant::now();
+    let deadline = started + config.duration;
+    let workers: Vec<_> = (0..config.concurrency.max(1))
+        .map(|_| {
+            let client = client.clone();
+            let redirect_urls = redirect_urls.clone();
+            let api_url = api_url.clone();
+            let authorization = config.authorization.clone();
+            tokio::spawn(async move {
+                let mut redirects = Samples::default();
+                let mut api = Samples::default();
+                while Instant::now() < deadline {
+                    let kind = if rand::thread_rng().gen_range(0..100) < api_percent {
+                        Kind::Api
+                    } else {
+                        Kind::Redirect
+                    };
+                    let request = match kind {
+                        Kind::Redirect => {
+                            let url = redirect_urls.choose(&mut rand::thread_rng()).unwrap();
+                            client.get(url.clone())
+                        }
+                        Kind::Api => client.get(api_url.clone()).header(
+                            http::header::AUTHORIZATION,
+                            authorization.as_deref().unwrap_or_default(),
+                        ),
+                    };
+
+                    let sent = Instant::now();
+                    let ok = match request.send().await {
+                        Ok(response) => {
+                            let ok = !response.status().is_server_error();
+                            // the body is part of the latency the client sees
+                            response.bytes().await.is_ok() && ok
+                        }
+                        Err(_) => false,
+                    };
+                    let samples = match kind {
+                        Kind::Redirect => &mut redirects,
+                        Kind::Api => &mut api,
+                    };
+                    samples.latencies.push(sent.elapsed());
+                    if !ok {
+                        samples.errors += 1;
+                    }
+                }
+                (redirects, api)
+            })
+        })
+        .collect();
+
+    let mut redirects = Samples::default();
+    let mut api = Samples::default();
+    for worker in workers {
+        let (worker_redirects, worker_api) = worker.await?;
+        redirects.merge(worker_redirects);
+        api.merge(worker_api);
+    }
+    let elapsed = started.elapsed();
+
+    for (kind, samples) in [("redirect", redirects), ("api", api)] {
+        if let Some(report) = samples.report(kind, elapsed) {
+            println!("{}", serde_json::to_string(&report)?);
+        }
+    }
+    Ok(())
+}
+
+impl Samples {
+    fn merge(&mut self, other: Samples) {
+        self.latencies.extend(other.latencies);
+        self.errors += other.errors;
+    }
+
+    fn report(mut self, kind: &'static str, elapsed: Duration) -> Option<Report> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        self.latencies.sort();
+
+        let percentile = |p: f64| {
+            let rank = (p * self.latencies.len() as f64).ceil() as usize;
+            millis(self.latencies[rank.clamp(1, self.latencies.len()) - 1])
+        };
+        Some(Report {
+            kind,
+            requests: self.latencies.len(),
+            errors: self.errors,
+            requests_per_sec: self.latencies.len() as f64 / elapsed.as_secs_f64(),
+            p50_ms: percentile(0.50),
+            p90_ms: percentile(0.90),
+            p99_ms: percentile(0.99),
+            max_ms: millis(*self.latencies.last().unwrap()),
+        })
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,80 @@
+use std::path::Path;
+
+use crate::{not_found::escape_html, responses::BioPage};
+
+const PAGE_FILE: &str = "bio.html";
+
+const DEFAULT_PAGE: &str = r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{{title}}</title>
+<style>
+body { font-family: sans-serif; max-width: 32rem; margin: 2rem auto; padding: 0 1rem; text-align: center; }
+ul { list-style: none; padding: 0; }
+li a { display: flex; align-items: center; justify-content: center; gap: 0.5rem; margin: 0.75rem 0; padding: 0.75rem; border: 1px solid #ccc; border-radius: 0.5rem; color: inherit; text-decoration: none; }
+li img { width: 1.5rem; height: 1.5rem; }
+</style>
+</head>
+<body>
+<h1>{{title}}</h1>
+<ul>
+{{links}}
+</ul>
+</body>
+</html>
+"#;
+
+/// The page behind `/u/:handle`. `{{title}}`, `{{handle}}` and `{{links}}`,
+/// a list of `<li>` items, are filled in for each page.
+pub struct BioTemplate(String);
+
+impl Default for BioTemplate {
+    fn default() -> Self {
+        Self(String::from(DEFAULT_PAGE))
+    }
+}
+
+impl BioTemplate {
+    /// Reads `bio.html` from `dir`, if one is configured, once at startup.
+    pub fn load(dir: Option<&Path>) -> std::io::Result<Self> {
+        let Some(dir) = dir else {
+            return Ok(Self::default());
+        };
+
+        let path = dir.join(PAGE_FILE);
+        std::fs::read_to_string(&path).map(Self).map_err(|error| {
+            std::io::Error::new(
+                error.kind(),
+                format!("cannot read {}: {error}", path.display()),
+            )
+        })
+    }
+
+    /// The page, its links going through the redirects under
+    /// `redirect_prefix`.
+    pub fn render(&self, page: &BioPage, redirect_prefix: &str) -> String {
+        let links: String = page
+            .links
+            .iter()
+            .map(|link| {
+                let icon = link.icon_url.as_deref().map_or(String::new(), |icon_url| {
+                    format!(r#"<img src="{}" alt="">"#, escape_html(icon_url))
+                });
+                // through the redirect, so visits from the page are counted
+                format!(
+                    "<li><a href=\"{}/{}\">{icon}{}</a></li>\n",
+                    escape_html(redirect_prefix),
+                    escape_html(&link.key),
+                    escape_html(&link.title),
+                )
+            })
+            .collect();
+
+        self.0
+            .replace("{{title}}", &escape_html(&page.title))
+            .replace("{{handle}}", &escape_html(&page.handle))
+            .replace("{{links}}", &links)
+    }
+}
@@ -0,0 +1,421 @@
+use std::{error::Error, path::PathBuf, sync::Arc, time::Duration};
+
+use chrono::{Datelike, Days, NaiveTime, TimeDelta, Utc};
+use clap::{Parser, Subcommand};
+use rand::{seq::SliceRandom, Rng};
+
+use tracing_subscriber::EnvFilter;
+
+use crate::{
+    analytics::{AnalyticsStore, ClickHouse},
+    bench::{self, BenchConfig},
+    click_buffer::{Click, MAX_CLICK_BATCH_SIZE},
+    config::{Config, LogFormat},
+    key_generator::KeyGenerator,
+    kvs::kvs_pool,
+    mock_sso,
+    reload::Reloadable,
+    requests::{LinkSort, LinkState, PageCursor},
+    service::{InsertError, LinkFilter, NewUrlRedirect, UrlService},
+    usage::RedirectCounter,
+};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "URL shortener server and management tool")]
+pub struct Cli {
+    /// Path to a TOML or YAML config file; environment variables override its values
+    #[arg(long, global = true, env = "CONFIG_PATH")]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (default)
+    Serve,
+    /// Apply all pending database migrations
+    Migrate,
+    /// Run a mock SSO that signs anyone in, for tests and local development;
+    /// point AGUS_DEV_SSO_HOST at it. Needs no other configuration
+    MockSso {
+        #[arg(long, default_value_t = 3010)]
+        port: u16,
+    },
+    /// Create a short URL owned by the given user
+    CreateUrl {
+        #[arg(long)]
+        email: String,
+        #[arg(long)]
+        key: String,
+        #[arg(long)]
+        target: String,
+    },
+    /// Delete a short URL owned by the given user
+    DeleteUrl {
+        #[arg(long)]
+        email: String,
+        #[arg(long)]
+        id: uuid::Uuid,
+    },
+    /// Delete expired anonymous links, which pile up as nobody owns them
+    PurgeExpired {
+        /// Also delete the expired links of signed-in users
+        #[arg(long)]
+        all: bool,
+    },
+    /// List short URLs owned by the given user, one JSON object per line
+    ListUrls {
+        #[arg(long)]
+        email: String,
+        /// List archived URLs instead of active ones
+        #[arg(long)]
+        archived: bool,
+        /// List the least recently accessed URLs first
+        #[arg(long)]
+        stale: bool,
+        #[arg(long)]
+        after: Option<String>,
+        #[arg(long, default_value_t = 50)]
+        limit: u64,
+    },
+    /// Send concurrent redirect and API traffic to a running instance and
+    /// report latency percentiles. Needs no configuration
+    Bench {
+        /// Where the instance serves redirects
+        #[arg(long, default_value = "http://localhost:3005/")]
+        url: url::Url,
+        /// The path the instance serves redirects under, empty for the root
+        #[arg(long, default_value = "/urls/redirect")]
+        redirect_prefix: String,
+        /// Where the instance serves the management API, if on its own port
+        #[arg(long)]
+        management_url: Option<url::Url>,
+        /// Keys to request redirects for, picked at random; repeatable
+        #[arg(long = "key", required = true)]
+        keys: Vec<String>,
+        /// `Authorization` header for API requests; none are sent without it
+        #[arg(long)]
+        authorization: Option<String>,
+        /// Share of requests sent to the API, in percent
+        #[arg(long, default_value_t = 10)]
+        api_percent: u8,
+        #[arg(long, default_value_t = 32)]
+        concurrency: usize,
+        #[arg(long, default_value_t = 30)]
+        duration_secs: u64,
+    },
+    /// Fill the database with fake users, links and click history, for
+    /// performance testing and frontend development
+    Seed {
+        #[arg(long, default_value_t = 10)]
+        users: u32,
+        /// Links in total, spread over the users
+        #[arg(long, default_value_t = 200)]
+        links: u32,
+        /// Days of click history, ending today
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+    },
+}
+
+/// Runs the command given on the command line, serving by default.
+pub async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    // the mock SSO runs next to the service, so it is started before the
+    // service's configuration is required
+    if let Some(Command::MockSso { port }) = cli.command {
+        init_tracing(LogFormat::default());
+        return mock_sso::serve(port).await;
+    }
+    // likewise for benchmarks, which run against any instance
+    if let Some(Command::Bench {
+        url,
+        redirect_prefix,
+        management_url,
+        keys,
+        authorization,
+        api_percent,
+        concurrency,
+        duration_secs,
+    }) = cli.command
+    {
+        return bench::run(BenchConfig {
+            url,
+            redirect_prefix,
+            management_url,
+            keys,
+            authorization,
+            api_percent,
+            concurrency,
+            duration: Duration::from_secs(duration_secs),
+        })
+        .await;
+    }
+    let config = match Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+    };
+    init_tracing(config.log_format);
+
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => crate::serve(config, cli.config).await,
+        Command::Migrate => migrate(&UrlService::new(&config.database).await?).await,
+        Command::MockSso { .. } | Command::Bench { .. } => {
+            unreachable!("handled before loading the config")
+        }
+        Command::CreateUrl { email, key, target } => {
+            let service = UrlService::new(&config.database)
+                .await?
+                .with_reloadable(Reloadable::new(&config));
+            create_url(&service, email, key, target).await
+        }
+        Command::DeleteUrl { email, id } => {
+            let service = UrlService::new(&config.database).await?;
+            delete_url(&service, email, id).await
+        }
+        Command::PurgeExpired { all } => {
+            let service = UrlService::new(&config.database).await?;
+            purge_expired(&service, all).await
+        }
+        Command::Seed { users, links, days } => {
+            let reloadable = Reloadable::new(&config);
+            let service = UrlService::new(&config.database)
+                .await?
+                .with_key_generator(KeyGenerator::new(
+                    &config.key_generation,
+                    reloadable.clone(),
+                ))
+                .with_reloadable(reloadable);
+            let redirects = RedirectCounter::new(Arc::new(kvs_pool(&config.kvs_url)?));
+            let clickhouse = match config.clickhouse {
+                Some(clickhouse) => {
+                    let clickhouse = ClickHouse::new(clickhouse)?;
+                    clickhouse
+                        .ensure_table(config.click_retention_months)
+                        .await?;
+                    Some(clickhouse)
+                }
+                None => None,
+            };
+            let analytics: &dyn AnalyticsStore = match &clickhouse {
+                Some(clickhouse) => clickhouse,
+                None => &service,
+            };
+            seed(&service, analytics, &redirects, users, links, days).await
+        }
+        Command::ListUrls {
+            email,
+            archived,
+            stale,
+            after,
+            limit,
+        } => {
+            let service = UrlService::new(&config.database).await?;
+            list_urls(&service, email, archived, stale, after, limit).await
+        }
+    }
+}
+
+fn init_tracing(format: LogFormat) {
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_file(true)
+        .with_line_number(true);
+
+    match format {
+        LogFormat::Pretty => subscriber.pretty().init(),
+        LogFormat::Json => subscriber.json().flatten_event(true).init(),
+    }
+}
+
+pub async fn migrate(service: &UrlService) -> Result<(), Box<dyn Error>> {
+    service.run_migrations().await?;
+    tracing::info!("Migrations applied");
+    Ok(())
+}
+
+pub async fn create_url(
+    service: &UrlService,
+    email: String,
+    key: String,
+    target: String,
+) -> Result<(), Box<dyn Error>> {
+    let url = service
+        .create(NewUrlRedirect::new(
+            email,
+            service.key_policy().parse(key)?,
+            target,
+        ))
+        .await?;
+
+    println!("{}", serde_json::to_string(&url)?);
+    Ok(())
+}
+
+pub async fn delete_url(
+    service: &UrlService,
+    email: String,
+    id: uuid::Uuid,
+) -> Result<(), Box<dyn Error>> {
+    let url = service
+        .delete(&email, id)
+        .await?
+        .ok_or_else(|| format!("url {id} not found for {email}"))?;
+
+    println!("{}", serde_json::to_string(&url)?);
+    Ok(())
+}
+
+pub async fn purge_expired(service: &UrlService, all: bool) -> Result<(), Box<dyn Error>> {
+    let purged = service.purge_expired(all).await?;
+    tracing::info!(purged, "Expired links purged");
+    Ok(())
+}
+
+pub async fn list_urls(
+    service: &UrlService,
+    email: String,
+    archived: bool,
+    stale: bool,
+    after: Option<String>,
+    limit: u64,
+) -> Result<(), Box<dyn Error>> {
+    let state = if archived {
+        LinkState::Archived
+    } else {
+        LinkState::Active
+    };
+    let sort = if stale {
+        LinkSort::LastAccessed
+    } else {
+        LinkSort::Key
+    };
+    for url in service
+        .list_by_email(
+            &email,
+            LinkFilter {
+                state,
+                ..Default::default()
+            },
+            sort,
+            after.map(PageCursor::After),
+            limit,
+        )
+        .await?
+        .items
+    {
+        println!("{}", serde_json::to_string(&url)?);
+    }
+
+    Ok(())
+}
+
+const SEED_HOSTS: &[&str] = &[
+    "https://www.example.com",
+    "https://blog.example.org",
+    "https://docs.example.net",
+    "https://shop.example.com",
+    "https://news.example.org",
+];
+
+const SEED_WORDS: &[&str] = &[
+    "launch", "pricing", "guide", "release", "notes", "summer", "sale", "webinar", "hiring",
+    "roadmap", "product", "update", "team", "story", "tips", "faq", "signup", "event",
+];
+
+// the busiest seeded link gets about this many clicks a day
+const SEED_MAX_DAILY_CLICKS: f64 = 500.0;
+
+pub async fn seed(
+    service: &UrlService,
+    analytics: &dyn AnalyticsStore,
+    redirects: &RedirectCounter,
+    users: u32,
+    links: u32,
+    days: u32,
+) -> Result<(), Box<dyn Error>> {
+    let emails: Vec<String> = (1..=users.max(1))
+        .map(|i| format!("seed-user-{i}@example.com"))
+        .collect();
+
+    let mut created = Vec::new();
+    for _ in 0..links {
+        let (email, target) = {
+            let mut rng = rand::thread_rng();
+            (
+                emails.choose(&mut rng).unwrap().clone(),
+                seed_target(&mut rng),
+            )
+        };
+        let suggested = service.suggest_keys(&url::Url::parse(&target)?).await?;
+        // some owners pick a readable key, the rest take a generated one
+        let result = match suggested.first() {
+            Some(key) if rand::random::<bool>() => {
+                service
+                    .create(NewUrlRedirect::new(
+                        email,
+                        service.key_policy().parse(key.clone())?,
+                        target,
+                    ))
+                    .await
+            }
+            _ => service.create_with_generated_key(email, target, None).await,
+        };
+        match result {
+            Ok(link) => created.push(link.id),
+            Err(InsertError::LinkLimitReached | InsertError::KeyAlreadyExists) => continue,
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    // a few links take most of the traffic, like real ones
+    let today = Utc::now().date_naive();
+    let mut clicks = 0;
+    for id in &created {
+        let daily_mean = rand::random::<f64>().powi(3) * SEED_MAX_DAILY_CLICKS;
+        for days_ago in 0..days {
+            let day = today - Days::new(days_ago.into());
+            let start = day.and_time(NaiveTime::MIN).and_utc();
+            // today only has clicks up to now
+            let seconds = (Utc::now() - start).num_seconds().clamp(1, 24 * 60 * 60);
+            let batch: Vec<Click> = {
+                let mut rng = rand::thread_rng();
+                let count = rng.gen_range(0..=(2.0 * daily_mean) as u64);
+                (0..count)
+                    .map(|_| Click {
+                        url_redirect_id: *id,
+                        clicked_at: start + TimeDelta::seconds(rng.gen_range(0..seconds)),
+                    })
+                    .collect()
+            };
+            if batch.is_empty() {
+                continue;
+            }
+            let count = batch.len() as u64;
+
+            for batch in batch.chunks(MAX_CLICK_BATCH_SIZE) {
+                analytics.insert_clicks(batch.to_vec()).await?;
+            }
+            redirects.record_on(*id, day, count).await?;
+            clicks += count;
+        }
+    }
+
+    println!(
+        "{}",
+        serde_json::json!({ "users": emails.len(), "links": created.len(), "clicks": clicks })
+    );
+    Ok(())
+}
+
+fn seed_target(rng: &mut impl Rng) -> String {
+    let host = SEED_HOSTS.choose(rng).unwrap();
+    let year = Utc::now().year() - rng.gen_range(0..3);
+    let count = rng.gen_range(1..=3);
+    let words: Vec<&str> = SEED_WORDS.choose_multiple(rng, count).copied().collect();
+    format!("{host}/{year}/{}", words.join("-"))
+}
@@ -0,0 +1,167 @@
+use std::{path::PathBuf, sync::Arc};
+
+use arrow_array::{Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::NaiveDate;
+use object_store::{aws::AmazonS3Builder, path::Path, ObjectStore, WriteMultipart};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use tokio::io::AsyncReadExt;
+
+use crate::{
+    click_partitions::{next_month, partition_name},
+    config::ClickArchiveConfig,
+    models::clicks,
+    service::{QueryError, UrlService},
+};
+
+// Rows read from the database, and written as one row group, at a time.
+const PAGE_SIZE: u64 = 10_000;
+const UPLOAD_CHUNK_BYTES: usize = 8 * 1024 * 1024;
+const UPLOAD_CONCURRENCY: usize = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("cannot read clicks: {0}")]
+    Query(#[from] QueryError),
+    #[error("cannot build parquet: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[error("cannot build parquet: {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+    #[error("cannot write temporary file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("cannot upload archive: {0}")]
+    Store(#[from] object_store::Error),
+}
+
+/// Copies a month of clicks to `<prefix>clicks_YYYY_MM.parquet` in an S3
+/// bucket, so it can still be analyzed after its partition is dropped.
+pub struct ClickArchive {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+}
+
+impl ClickArchive {
+    pub fn new(config: ClickArchiveConfig) -> object_store::Result<Self> {
+        // credentials and region not configured here come from the usual
+        // AWS_* variables
+        let mut builder = AmazonS3Builder::from_env().with_bucket_name(config.bucket);
+        if let Some(endpoint) = config.endpoint {
+            builder = builder
+                .with_allow_http(endpoint.starts_with("http://"))
+                .with_endpoint(endpoint);
+        }
+        if let Some(region) = config.region {
+            builder = builder.with_region(region);
+        }
+        if let Some(access_key_id) = config.access_key_id {
+            builder = builder.with_access_key_id(access_key_id);
+        }
+        if let Some(secret_access_key) = config.secret_access_key {
+            builder = builder.with_secret_access_key(secret_access_key);
+        }
+
+        Ok(Self {
+            store: Arc::new(builder.build()?),
+            prefix: config.prefix,
+        })
+    }
+
+    /// Uploads every click of `month`, returning how many there were. The
+    /// file is built on disk first, so a month of clicks never has to fit in
+    /// memory.
+    pub async fn archive(
+        &self,
+        service: &UrlService,
+        month: NaiveDate,
+    ) -> Result<u64, ArchiveError> {
+        let name = partition_name(month);
+        let file = std::env::temp_dir().join(format!("{name}-{}.parquet", uuid::Uuid::new_v4()));
+        let result = self.archive_via(&file, service, month).await;
+        if let Err(error) = tokio::fs::remove_file(&file).await {
+            tracing::warn!(%error, file = %file.display(), "failed to remove temporary archive");
+        }
+
+        result
+    }
+
+    async fn archive_via(
+        &self,
+        file: &PathBuf,
+        service: &UrlService,
+        month: NaiveDate,
+    ) -> Result<u64, ArchiveError> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("url_redirect_id", DataType::Utf8, false),
+            Field::new(
+                "clicked_at",
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+                false,
+            ),
+        ]));
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer = ArrowWriter::try_new(
+            std::fs::File::create(file)?,
+            schema.clone(),
+            Some(properties),
+        )?;
+
+        let from = month_start(month);
+        let to = month_start(next_month(month));
+        let mut after = None;
+        let mut count = 0;
+        loop {
+            let page = service.clicks_page(from, to, after, PAGE_SIZE).await?;
+            let Some(last) = page.last() else { break };
+            after = Some((last.clicked_at, last.id));
+            count += page.len() as u64;
+            writer.write(&record_batch(schema.clone(), &page)?)?;
+        }
+        writer.close()?;
+
+        let location = Path::from(format!("{}{}.parquet", self.prefix, partition_name(month)));
+        let mut upload = WriteMultipart::new(self.store.put_multipart(&location).await?);
+        let mut file = tokio::fs::File::open(file).await?;
+        let mut chunk = vec![0; UPLOAD_CHUNK_BYTES];
+        loop {
+            let read = file.read(&mut chunk).await?;
+            if read == 0 {
+                break;
+            }
+            upload.wait_for_capacity(UPLOAD_CONCURRENCY).await?;
+            upload.write(&chunk[..read]);
+        }
+        upload.finish().await?;
+
+        Ok(count)
+    }
+}
+
+fn month_start(month: NaiveDate) -> chrono::DateTime<chrono::FixedOffset> {
+    month
+        .and_hms_opt(0, 0, 0)
+        .expect("midnight exists")
+        .and_utc()
+        .fixed_offset()
+}
+
+fn record_batch(schema: Arc<Schema>, page: &[clicks::Model]) -> Result<RecordBatch, ArchiveError> {
+    let ids = Int64Array::from_iter_values(page.iter().map(|click| click.id));
+    let url_redirect_ids =
+        StringArray::from_iter_values(page.iter().map(|click| click.url_redirect_id.to_string()));
+    let clicked_at = TimestampMicrosecondArray::from_iter_values(
+        page.iter().map(|click| click.clicked_at.timestamp_micros()),
+    )
+    .with_timezone("UTC");
+
+    Ok(RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(ids),
+            Arc::new(url_redirect_ids),
+            Arc::new(clicked_at),
+        ],
+    )?)
+}
@@ -0,0 +1,127 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use uuid::Uuid;
+
+use crate::config::ClickBufferConfig;
+
+// Postgres takes at most 65535 parameters per statement, and each click
+// needs two.
+pub const MAX_CLICK_BATCH_SIZE: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct Click {
+    pub url_redirect_id: Uuid,
+    pub clicked_at: DateTime<Utc>,
+}
+
+/// Where redirects drop their clicks. Recording never waits: when the writer
+/// falls behind and the buffer is full, clicks are dropped and counted.
+pub struct ClickBuffer {
+    sender: mpsc::Sender<Click>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Drains a [`ClickBuffer`] in batches.
+pub struct ClickFlusher {
+    receiver: mpsc::Receiver<Click>,
+    dropped: Arc<AtomicU64>,
+    batch_size: usize,
+    flush_interval: Duration,
+}
+
+pub fn click_buffer(config: &ClickBufferConfig) -> (ClickBuffer, ClickFlusher) {
+    let (sender, receiver) = mpsc::channel(config.capacity);
+    let dropped = Arc::new(AtomicU64::new(0));
+
+    (
+        ClickBuffer {
+            sender,
+            dropped: dropped.clone(),
+        },
+        ClickFlusher {
+            receiver,
+            dropped,
+            batch_size: config.batch_size,
+            flush_interval: config.flush_interval,
+        },
+    )
+}
+
+impl ClickBuffer {
+    /// Clicks waiting to be written.
+    pub fn pending(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.sender.max_capacity()
+    }
+
+    pub fn record(&self, url_redirect_id: Uuid) {
+        let click = Click {
+            url_redirect_id,
+            clicked_at: Utc::now(),
+        };
+        match self.sender.try_send(click) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Closed(_)) => {
+                tracing::warn!("click writer stopped, click lost");
+            }
+        }
+    }
+}
+
+impl ClickFlusher {
+    /// Hands `write` a batch whenever `batch_size` clicks are waiting or
+    /// `flush_interval` has passed since the last one, whichever comes first.
+    /// A batch that fails to write is logged and dropped, so a database
+    /// outage cannot grow the backlog without bound.
+    pub async fn run<F, Fut, E>(mut self, write: F)
+    where
+        F: Fn(Vec<Click>) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+        E: std::fmt::Display,
+    {
+        let mut interval = tokio::time::interval(self.flush_interval);
+        let mut batch = Vec::with_capacity(self.batch_size);
+        loop {
+            let room = self.batch_size - batch.len();
+            let closed = tokio::select! {
+                received = self.receiver.recv_many(&mut batch, room) => {
+                    if batch.len() < self.batch_size && received > 0 {
+                        continue;
+                    }
+                    received == 0
+                }
+                _ = interval.tick() => false,
+            };
+
+            let dropped = self.dropped.swap(0, Ordering::Relaxed);
+            if dropped > 0 {
+                tracing::warn!(dropped, "click buffer full, clicks dropped");
+            }
+            if !batch.is_empty() {
+                let clicks = std::mem::replace(&mut batch, Vec::with_capacity(self.batch_size));
+                let count = clicks.len();
+                if let Err(error) = write(clicks).await {
+                    tracing::error!(%error, count, "failed to write clicks");
+                }
+            }
+            if closed {
+                return;
+            }
+        }
+    }
+}
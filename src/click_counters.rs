@@ -0,0 +1,199 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use uuid::Uuid;
+
+use crate::{
+    kvs::{KvsConnection, KvsError, KvsPool},
+    rate_limit::RateLimitError,
+};
+
+/// Clicks counted since the last write-back began, by link id.
+const PENDING_KEY: &str = "click-counts:pending";
+/// The counts of the write-back under way, or left unfinished.
+const WRITING_KEY: &str = "click-counts:writing";
+/// Held by the instance writing back, so only one does at a time.
+const LOCK_KEY: &str = "click-counts:lock";
+/// The field of [`WRITING_KEY`] holding the write-back's id; link ids are
+/// UUIDs, so it never clashes with one.
+const WRITE_BACK_ID_FIELD: &str = "write-back";
+
+// A write-back is a handful of updates; an instance stopped while holding the
+// lock only delays the next one by this much.
+const LOCK_TTL: Duration = Duration::from_secs(60);
+
+/// Counts the clicks of each link in Redis, where incrementing is cheap,
+/// until they are written back to the link's row in batches. However hot a
+/// link, its row is then updated once per write-back rather than per click.
+pub struct ClickCounters {
+    kvs_pool: Arc<KvsPool>,
+}
+
+/// Click counts being added to the links' rows, under an id that keeps them
+/// from being added twice.
+pub struct WriteBack {
+    pub id: Uuid,
+    pub counts: Vec<(Uuid, i64)>,
+    /// Marks the lock as this write-back's, so it never releases one another
+    /// instance took over once it outlived [`LOCK_TTL`].
+    lock_token: String,
+}
+
+impl ClickCounters {
+    pub fn new(kvs_pool: Arc<KvsPool>) -> Self {
+        Self { kvs_pool }
+    }
+
+    pub async fn record(&self, id: Uuid) -> Result<(), RateLimitError> {
+        let mut conn = self.kvs_pool.get().await?;
+        conn.hincr(PENDING_KEY, id.to_string(), 1)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Takes the counts to write back, unless another instance is already
+    /// at it: those of a write-back left unfinished, say by an instance that
+    /// stopped midway, or else every click counted since the last one.
+    pub async fn start_write_back(&self) -> Result<Option<WriteBack>, RateLimitError> {
+        let mut conn = self.kvs_pool.get().await?;
+        let lock_token = Uuid::new_v4().to_string();
+        let locked: Option<String> = conn
+            .set_options(
+                LOCK_KEY,
+                &lock_token,
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(LOCK_TTL.as_secs())),
+            )
+            .await?;
+        if locked.is_none() {
+            return Ok(None);
+        }
+
+        let mut writing: HashMap<String, String> = conn.hgetall(WRITING_KEY).await?;
+        if writing.is_empty() {
+            // redirects only ever add to the pending counts, so they are
+            // still there to be moved under the lock
+            let pending: bool = conn.exists(PENDING_KEY).await?;
+            if !pending {
+                unlock(&mut conn, &lock_token, false).await?;
+                return Ok(None);
+            }
+            (writing,) = redis::pipe()
+                .atomic()
+                .rename(PENDING_KEY, WRITING_KEY)
+                .ignore()
+                .hset(WRITING_KEY, WRITE_BACK_ID_FIELD, Uuid::new_v4().to_string())
+                .ignore()
+                .hgetall(WRITING_KEY)
+                .query_async(&mut conn)
+                .await?;
+        }
+
+        let id = writing
+            .remove(WRITE_BACK_ID_FIELD)
+            .and_then(|id| id.parse().ok())
+            .unwrap_or_else(Uuid::new_v4);
+        let counts = writing
+            .into_iter()
+            .filter_map(|(link, count)| Some((link.parse().ok()?, count.parse().ok()?)))
+            .collect();
+        Ok(Some(WriteBack {
+            id,
+            counts,
+            lock_token,
+        }))
+    }
+
+    /// Lets the next write-back start, dropping the counts of this one when
+    /// they made it into the database. Otherwise they are retried as they
+    /// are, under the same id.
+    pub async fn end_write_back(
+        &self,
+        write_back: &WriteBack,
+        written: bool,
+    ) -> Result<(), RateLimitError> {
+        let mut conn = self.kvs_pool.get().await?;
+        unlock(&mut conn, &write_back.lock_token, written)
+            .await
+            .map_err(Into::into)
+    }
+}
+
+/// Releases the lock, and drops the counts being written when `written`,
+/// but only while the lock is still `token`'s: past its TTL, the counts
+/// may already be another instance's to write.
+async fn unlock(conn: &mut KvsConnection, token: &str, written: bool) -> Result<(), KvsError> {
+    redis::cmd("WATCH")
+        .arg(LOCK_KEY)
+        .query_async::<()>(conn)
+        .await?;
+    let holder: Option<String> = conn.get(LOCK_KEY).await?;
+    if holder.as_deref() != Some(token) {
+        return redis::cmd("UNWATCH").query_async(conn).await;
+    }
+
+    // aborted, and so harmless, if the lock changes hands after all
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    if written {
+        pipe.del(WRITING_KEY).ignore();
+    }
+    pipe.del(LOCK_KEY).ignore().query_async(conn).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_kvs::MemoryKvs;
+
+    fn counters() -> ClickCounters {
+        ClickCounters::new(Arc::new(KvsPool::Memory(MemoryKvs::default())))
+    }
+
+    #[tokio::test]
+    async fn writes_back_the_clicks_counted_since_the_last_write_back() {
+        let counters = counters();
+        assert!(counters.start_write_back().await.unwrap().is_none());
+
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        for id in [first, first, second] {
+            counters.record(id).await.unwrap();
+        }
+        let write_back = counters.start_write_back().await.unwrap().unwrap();
+        let mut counts = write_back.counts.clone();
+        counts.sort_by_key(|(_, count)| *count);
+        assert_eq!(counts, vec![(second, 1), (first, 2)]);
+
+        // clicks during the write-back wait for the next one
+        counters.record(first).await.unwrap();
+        counters.end_write_back(&write_back, true).await.unwrap();
+        let next = counters.start_write_back().await.unwrap().unwrap();
+        assert_ne!(next.id, write_back.id);
+        assert_eq!(next.counts, vec![(first, 1)]);
+    }
+
+    #[tokio::test]
+    async fn lets_one_write_back_run_at_a_time() {
+        let counters = counters();
+        counters.record(Uuid::new_v4()).await.unwrap();
+        let write_back = counters.start_write_back().await.unwrap().unwrap();
+        assert!(counters.start_write_back().await.unwrap().is_none());
+
+        counters.end_write_back(&write_back, true).await.unwrap();
+        assert!(counters.start_write_back().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn retries_unwritten_counts_under_the_same_id() {
+        let counters = counters();
+        let link = Uuid::new_v4();
+        counters.record(link).await.unwrap();
+        let write_back = counters.start_write_back().await.unwrap().unwrap();
+        counters.end_write_back(&write_back, false).await.unwrap();
+
+        let retry = counters.start_write_back().await.unwrap().unwrap();
+        assert_eq!(retry.id, write_back.id);
+        assert_eq!(retry.counts, vec![(link, 1)]);
+    }
+}
@@ -0,0 +1,28 @@
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+
+/// Months ahead of the current one that always have a partition, so a late
+/// maintenance run never sends clicks to the default partition.
+pub const MONTHS_AHEAD: u32 = 2;
+
+/// The first day of `time`'s month, in UTC.
+pub fn month_of(time: DateTime<Utc>) -> NaiveDate {
+    time.date_naive()
+        .with_day(1)
+        .expect("every month has a first day")
+}
+
+pub fn next_month(month: NaiveDate) -> NaiveDate {
+    month + Months::new(1)
+}
+
+/// `clicks_2026_10` for October 2026.
+pub fn partition_name(month: NaiveDate) -> String {
+    format!("clicks_{}", month.format("%Y_%m"))
+}
+
+/// The month a partition created by [`partition_name`] holds. Other tables,
+/// such as the default partition, give `None`.
+pub fn partition_month(name: &str) -> Option<NaiveDate> {
+    let (year, month) = name.strip_prefix("clicks_")?.split_once('_')?;
+    NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)
+}
@@ -0,0 +1,201 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use http::{header::FORWARDED, request::Parts, Extensions, HeaderMap};
+use ipnet::IpNet;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// The client address worked out by [`resolve`].
+#[derive(Debug, Clone, Copy)]
+struct ResolvedClientIp(IpAddr);
+
+/// Proxies allowed to tell us who the client is.
+pub struct TrustedProxies(Vec<IpNet>);
+
+impl TrustedProxies {
+    pub fn new(cidrs: Vec<IpNet>) -> Self {
+        Self(cidrs)
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        self.0.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    /// The client behind `peer`. Forwarding hops are read from the right and
+    /// only believed while they come from a trusted proxy, so a client cannot
+    /// pass itself off as someone else by sending the headers itself.
+    fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let mut client = peer;
+        for hop in forwarded_hops(headers).into_iter().rev() {
+            if !self.contains(&client) {
+                break;
+            }
+            match hop {
+                Some(ip) => client = ip,
+                // obfuscated or unknown hops end the chain we can follow
+                None => break,
+            }
+        }
+
+        client
+    }
+}
+
+/// The hops listed by the standard `Forwarded` header, or by
+/// `X-Forwarded-For` when it is absent, oldest first.
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let values = |name| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+    };
+
+    if headers.contains_key(FORWARDED) {
+        values(FORWARDED.as_str())
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.trim().split_once('='))
+                    .find(|(name, _)| name.eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| parse_node(node))
+            })
+            .collect()
+    } else {
+        values(X_FORWARDED_FOR)
+            .map(|hop| hop.trim().parse().ok())
+            .collect()
+    }
+}
+
+/// Parses a `Forwarded` node such as `192.0.2.43`, `"192.0.2.43:47011"` or
+/// `"[2001:db8::1]:4711"`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    node.parse()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// Resolves the client IP once per request, so every later use of
+/// [`client_ip`] agrees on it.
+pub async fn resolve(
+    State(trusted_proxies): State<Arc<TrustedProxies>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(peer)| peer.ip());
+    if let Some(peer) = peer {
+        let client_ip = trusted_proxies.client_ip(peer, request.headers());
+        request.extensions_mut().insert(ResolvedClientIp(client_ip));
+    }
+
+    next.run(request).await
+}
+
+/// The address of the client, for per-client limits.
+pub fn client_ip(extensions: &Extensions) -> Option<IpAddr> {
+    extensions
+        .get::<ResolvedClientIp>()
+        .map(|ResolvedClientIp(ip)| *ip)
+        .or_else(|| {
+            extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(peer)| peer.ip())
+        })
+}
+
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(client_ip(&parts.extensions)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn parses_forwarded_nodes() {
+        assert_eq!(parse_node("192.0.2.43"), ip("192.0.2.43"));
+        assert_eq!(parse_node("\"192.0.2.43:47011\""), ip("192.0.2.43"));
+        assert_eq!(parse_node("\"[2001:db8::1]:4711\""), ip("2001:db8::1"));
+        assert_eq!(parse_node("\"[2001:db8::1]\""), ip("2001:db8::1"));
+        assert_eq!(parse_node(" 2001:db8::1 "), ip("2001:db8::1"));
+        assert_eq!(parse_node("unknown"), None);
+        assert_eq!(parse_node("_hidden"), None);
+        assert_eq!(parse_node("\"[2001:db8::1\""), None);
+    }
+
+    #[test]
+    fn lists_forwarded_hops_oldest_first() {
+        let hops = forwarded_hops(&headers(&[
+            (
+                "forwarded",
+                "for=192.0.2.60;proto=http, For=\"[2001:db8::1]:4711\"",
+            ),
+            ("forwarded", "by=203.0.113.1;for=unknown"),
+        ]));
+
+        assert_eq!(hops, vec![ip("192.0.2.60"), ip("2001:db8::1"), None]);
+    }
+
+    #[test]
+    fn prefers_forwarded_over_x_forwarded_for() {
+        let hops = forwarded_hops(&headers(&[
+            ("x-forwarded-for", "198.51.100.1"),
+            ("forwarded", "for=192.0.2.60"),
+        ]));
+
+        assert_eq!(hops, vec![ip("192.0.2.60")]);
+    }
+
+    #[test]
+    fn falls_back_to_x_forwarded_for() {
+        let hops = forwarded_hops(&headers(&[
+            ("x-forwarded-for", "198.51.100.1, garbage"),
+            ("x-forwarded-for", " 2001:db8::2"),
+        ]));
+
+        assert_eq!(hops, vec![ip("198.51.100.1"), None, ip("2001:db8::2")]);
+    }
+
+    #[test]
+    fn has_no_hops_without_forwarding_headers() {
+        assert!(forwarded_hops(&HeaderMap::new()).is_empty());
+    }
+}
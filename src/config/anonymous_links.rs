@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::{override_env, Setting, SettingError};
+
+/// Unauthenticated link creation, limited per client IP.
+pub struct AnonymousLinksConfig {
+    pub per_hour: u64,
+    /// Anonymous links always expire this long after creation.
+    pub ttl: Duration,
+}
+
+const ANONYMOUS_LINKS_ENABLED: Setting =
+    Setting::new("anonymous_links.enabled", "ANONYMOUS_LINKS_ENABLED");
+const ANONYMOUS_LINKS_PER_HOUR: Setting =
+    Setting::new("anonymous_links.per_hour", "ANONYMOUS_LINKS_PER_HOUR");
+const ANONYMOUS_LINKS_TTL: Setting =
+    Setting::new("anonymous_links.ttl_secs", "ANONYMOUS_LINKS_TTL_SECS");
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawAnonymousLinksConfig {
+    enabled: Option<bool>,
+    per_hour: Option<u64>,
+    ttl_secs: Option<u64>,
+}
+
+impl RawAnonymousLinksConfig {
+    pub(super) fn apply_env(&mut self, errors: &mut Vec<SettingError>) {
+        override_env(&mut self.enabled, ANONYMOUS_LINKS_ENABLED, errors);
+        override_env(&mut self.per_hour, ANONYMOUS_LINKS_PER_HOUR, errors);
+        override_env(&mut self.ttl_secs, ANONYMOUS_LINKS_TTL, errors);
+    }
+
+    pub(super) fn build(self, errors: &mut Vec<SettingError>) -> Option<AnonymousLinksConfig> {
+        if self.enabled != Some(true) {
+            return None;
+        }
+
+        let per_hour = self.per_hour.unwrap_or(5);
+        let ttl_secs = self.ttl_secs.unwrap_or(24 * 60 * 60);
+        for (value, setting) in [
+            (per_hour, ANONYMOUS_LINKS_PER_HOUR),
+            (ttl_secs, ANONYMOUS_LINKS_TTL),
+        ] {
+            if value == 0 {
+                errors.push(SettingError::Invalid {
+                    setting,
+                    reason: String::from("must be at least one"),
+                });
+            }
+        }
+        Some(AnonymousLinksConfig {
+            per_hour,
+            ttl: Duration::from_secs(ttl_secs),
+        })
+    }
+}
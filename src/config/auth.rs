@@ -0,0 +1,503 @@
+use std::{str::FromStr, time::Duration};
+
+use serde::Deserialize;
+
+use super::{override_env, required, url_error, validate_url, RawConfig, Setting, SettingError};
+use crate::service::ANONYMOUS_OWNER;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMode {
+    #[default]
+    Sso,
+    /// Trusts `Authorization: Dev <email>` headers as is. Only available in
+    /// debug builds.
+    Dev,
+}
+
+impl FromStr for AuthMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "sso" => Ok(Self::Sso),
+            "dev" => Ok(Self::Dev),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct HttpClientConfig {
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    pub proxy_url: Option<String>,
+}
+
+pub struct IdentityProviderConfig {
+    pub name: String,
+    pub host: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,
+    pub jwt: Option<JwtConfig>,
+}
+
+/// Local validation of JWT access tokens against the provider's JWKS.
+pub struct JwtConfig {
+    pub jwks_url: String,
+    pub issuer: String,
+    pub audience: Option<String>,
+}
+
+pub struct TokenCacheConfig {
+    pub ttl: Duration,
+    /// Pushes the expiry back on every cache hit instead of letting the
+    /// entry expire a fixed time after it was written.
+    pub refresh_on_hit: bool,
+    /// How long rejected tokens are remembered; `None` disables negative caching.
+    pub negative_ttl: Option<Duration>,
+}
+
+/// An OAuth client whose client-credentials tokens are accepted, owning
+/// links under `namespace` as if it were a user.
+pub struct ServiceAccountConfig {
+    pub client_id: String,
+    pub namespace: String,
+}
+
+/// Cookie sessions for browser clients, as an alternative to bearer tokens.
+pub struct SessionConfig {
+    pub ttl: Duration,
+}
+
+/// Temporary blocks for clients that keep failing authentication.
+pub struct AuthLockoutConfig {
+    /// Failures tolerated before the first block.
+    pub max_failures: u64,
+    pub base_block: Duration,
+    /// Also how long failures are remembered.
+    pub max_block: Duration,
+}
+
+/// Name of the provider configured through the top-level SSO settings.
+pub const DEFAULT_IDENTITY_PROVIDER: &str = "default";
+
+const AUTH_MODE: Setting = Setting::new("auth_mode", "AUTH_MODE");
+const SSO_HOST: Setting = Setting::new("agus_dev_sso_host", "AGUS_DEV_SSO_HOST");
+const SSO_TIMEOUT: Setting = Setting::new("sso_client.timeout_ms", "SSO_TIMEOUT_MS");
+const SSO_CONNECT_TIMEOUT: Setting =
+    Setting::new("sso_client.connect_timeout_ms", "SSO_CONNECT_TIMEOUT_MS");
+const SSO_PROXY_URL: Setting = Setting::new("sso_client.proxy_url", "SSO_PROXY_URL");
+const TOKEN_CACHE_TTL: Setting = Setting::new("token_cache.ttl_secs", "TOKEN_CACHE_TTL_SECS");
+const TOKEN_CACHE_REFRESH_ON_HIT: Setting =
+    Setting::new("token_cache.refresh_on_hit", "TOKEN_CACHE_REFRESH_ON_HIT");
+const TOKEN_CACHE_NEGATIVE_TTL: Setting = Setting::new(
+    "token_cache.negative_ttl_secs",
+    "TOKEN_CACHE_NEGATIVE_TTL_SECS",
+);
+const JWT_JWKS_URL: Setting = Setting::new("jwt.jwks_url", "JWT_JWKS_URL");
+const JWT_ISSUER: Setting = Setting::new("jwt.issuer", "JWT_ISSUER");
+const JWT_AUDIENCE: Setting = Setting::new("jwt.audience", "JWT_AUDIENCE");
+const CLIENT_ID: Setting = Setting::new("client_id", "CLIENT_ID");
+const CLIENT_SECRET: Setting = Setting::new("client_secret", "CLIENT_SECRET");
+const REDIRECT_URI: Setting = Setting::new("redirect_uri", "REDIRECT_URI");
+const SESSIONS_ENABLED: Setting = Setting::new("sessions.enabled", "SESSIONS_ENABLED");
+const SESSION_TTL: Setting = Setting::new("sessions.ttl_secs", "SESSION_TTL_SECS");
+const AUTH_LOCKOUT_ENABLED: Setting = Setting::new("auth_lockout.enabled", "AUTH_LOCKOUT_ENABLED");
+const AUTH_LOCKOUT_MAX_FAILURES: Setting =
+    Setting::new("auth_lockout.max_failures", "AUTH_LOCKOUT_MAX_FAILURES");
+const AUTH_LOCKOUT_BASE_SECS: Setting =
+    Setting::new("auth_lockout.base_secs", "AUTH_LOCKOUT_BASE_SECS");
+const AUTH_LOCKOUT_MAX_SECS: Setting =
+    Setting::new("auth_lockout.max_secs", "AUTH_LOCKOUT_MAX_SECS");
+const IDENTITY_PROVIDERS: Setting = Setting::file_only("identity_providers");
+const SERVICE_ACCOUNTS: Setting = Setting::file_only("service_accounts");
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawHttpClientConfig {
+    timeout_ms: Option<u64>,
+    connect_timeout_ms: Option<u64>,
+    proxy_url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawTokenCacheConfig {
+    ttl_secs: Option<u64>,
+    refresh_on_hit: Option<bool>,
+    negative_ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawJwtConfig {
+    jwks_url: Option<String>,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+/// Additional providers only come from the config file, so unlike the other
+/// raw sections their fields are required by the parser itself.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RawIdentityProviderConfig {
+    name: String,
+    host: String,
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    jwt: Option<RawProviderJwtConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawProviderJwtConfig {
+    jwks_url: String,
+    issuer: String,
+    audience: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RawServiceAccountConfig {
+    client_id: String,
+    namespace: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawSessionConfig {
+    enabled: Option<bool>,
+    ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawAuthLockoutConfig {
+    enabled: Option<bool>,
+    max_failures: Option<u64>,
+    base_secs: Option<u64>,
+    max_secs: Option<u64>,
+}
+
+/// How users and clients authenticate, once built.
+pub(super) struct Auth {
+    pub mode: AuthMode,
+    pub identity_providers: Vec<IdentityProviderConfig>,
+    pub sso_client: HttpClientConfig,
+    pub token_cache: TokenCacheConfig,
+    pub service_accounts: Vec<ServiceAccountConfig>,
+    pub sessions: Option<SessionConfig>,
+    pub lockout: Option<AuthLockoutConfig>,
+}
+
+impl RawConfig {
+    /// The environment overrides of the authentication settings, most of
+    /// which live at the top level of the config file.
+    pub(super) fn apply_auth_env(&mut self, errors: &mut Vec<SettingError>) {
+        override_env(&mut self.auth_mode, AUTH_MODE, errors);
+        override_env(&mut self.agus_dev_sso_host, SSO_HOST, errors);
+        override_env(&mut self.sso_client.timeout_ms, SSO_TIMEOUT, errors);
+        override_env(
+            &mut self.sso_client.connect_timeout_ms,
+            SSO_CONNECT_TIMEOUT,
+            errors,
+        );
+        override_env(&mut self.sso_client.proxy_url, SSO_PROXY_URL, errors);
+        override_env(&mut self.jwt.jwks_url, JWT_JWKS_URL, errors);
+        override_env(&mut self.jwt.issuer, JWT_ISSUER, errors);
+        override_env(&mut self.jwt.audience, JWT_AUDIENCE, errors);
+        override_env(&mut self.token_cache.ttl_secs, TOKEN_CACHE_TTL, errors);
+        override_env(
+            &mut self.token_cache.refresh_on_hit,
+            TOKEN_CACHE_REFRESH_ON_HIT,
+            errors,
+        );
+        override_env(
+            &mut self.token_cache.negative_ttl_secs,
+            TOKEN_CACHE_NEGATIVE_TTL,
+            errors,
+        );
+        override_env(&mut self.client_id, CLIENT_ID, errors);
+        override_env(&mut self.client_secret, CLIENT_SECRET, errors);
+        override_env(&mut self.redirect_uri, REDIRECT_URI, errors);
+        override_env(&mut self.sessions.enabled, SESSIONS_ENABLED, errors);
+        override_env(&mut self.sessions.ttl_secs, SESSION_TTL, errors);
+        override_env(&mut self.auth_lockout.enabled, AUTH_LOCKOUT_ENABLED, errors);
+        override_env(
+            &mut self.auth_lockout.max_failures,
+            AUTH_LOCKOUT_MAX_FAILURES,
+            errors,
+        );
+        override_env(
+            &mut self.auth_lockout.base_secs,
+            AUTH_LOCKOUT_BASE_SECS,
+            errors,
+        );
+        override_env(
+            &mut self.auth_lockout.max_secs,
+            AUTH_LOCKOUT_MAX_SECS,
+            errors,
+        );
+    }
+
+    /// Takes the authentication settings out of the raw configuration.
+    pub(super) fn build_auth(&mut self, errors: &mut Vec<SettingError>) -> Auth {
+        let mode = self.auth_mode.unwrap_or_default();
+        if mode == AuthMode::Dev && !cfg!(debug_assertions) {
+            errors.push(SettingError::Invalid {
+                setting: AUTH_MODE,
+                reason: String::from("`dev` is only available in debug builds"),
+            });
+        }
+
+        let sso_client = std::mem::take(&mut self.sso_client).build(errors);
+        let token_cache = std::mem::take(&mut self.token_cache).build(errors);
+        let default_provider = self.build_default_provider(mode, errors);
+        let extra_providers =
+            build_identity_providers(std::mem::take(&mut self.identity_providers), errors);
+        let service_accounts =
+            build_service_accounts(std::mem::take(&mut self.service_accounts), errors);
+
+        Auth {
+            mode,
+            identity_providers: default_provider
+                .into_iter()
+                .chain(extra_providers)
+                .collect(),
+            sso_client,
+            token_cache,
+            service_accounts,
+            sessions: std::mem::take(&mut self.sessions).build(errors),
+            lockout: std::mem::take(&mut self.auth_lockout).build(errors),
+        }
+    }
+
+    fn build_default_provider(
+        &mut self,
+        mode: AuthMode,
+        errors: &mut Vec<SettingError>,
+    ) -> Option<IdentityProviderConfig> {
+        // dev mode must work without any SSO around
+        let sso_required =
+            |value: Option<String>, setting, errors: &mut Vec<SettingError>| match mode {
+                AuthMode::Sso => required(value, setting, errors),
+                AuthMode::Dev => value,
+            };
+
+        let host = self
+            .agus_dev_sso_host
+            .take()
+            .unwrap_or(String::from("https://sso.v2.agus.dev"));
+        validate_url(&host, SSO_HOST, &["http", "https"], errors);
+
+        let raw_jwt = std::mem::take(&mut self.jwt);
+        let jwt = match raw_jwt.jwks_url {
+            Some(jwks_url) => {
+                validate_url(&jwks_url, JWT_JWKS_URL, &["http", "https"], errors);
+                required(raw_jwt.issuer, JWT_ISSUER, errors).map(|issuer| JwtConfig {
+                    jwks_url,
+                    issuer,
+                    audience: raw_jwt.audience,
+                })
+            }
+            None => None,
+        };
+
+        let client_id = sso_required(self.client_id.take(), CLIENT_ID, errors);
+        let client_secret = sso_required(self.client_secret.take(), CLIENT_SECRET, errors);
+
+        let redirect_uri = sso_required(self.redirect_uri.take(), REDIRECT_URI, errors);
+        if let Some(uri) = &redirect_uri {
+            validate_url(uri, REDIRECT_URI, &["http", "https"], errors);
+        }
+
+        match (client_id, client_secret, redirect_uri) {
+            (Some(client_id), Some(client_secret), Some(redirect_uri)) => {
+                Some(IdentityProviderConfig {
+                    name: String::from(DEFAULT_IDENTITY_PROVIDER),
+                    host,
+                    client_id,
+                    client_secret,
+                    redirect_uri,
+                    jwt,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl RawHttpClientConfig {
+    fn build(self, errors: &mut Vec<SettingError>) -> HttpClientConfig {
+        if let Some(proxy_url) = &self.proxy_url {
+            validate_url(proxy_url, SSO_PROXY_URL, &["http", "https"], errors);
+        }
+        HttpClientConfig {
+            timeout: Duration::from_millis(self.timeout_ms.unwrap_or(10_000)),
+            connect_timeout: Duration::from_millis(self.connect_timeout_ms.unwrap_or(3_000)),
+            proxy_url: self.proxy_url,
+        }
+    }
+}
+
+impl RawTokenCacheConfig {
+    fn build(self, errors: &mut Vec<SettingError>) -> TokenCacheConfig {
+        let ttl_secs = self.ttl_secs.unwrap_or(30);
+        if ttl_secs == 0 {
+            errors.push(SettingError::Invalid {
+                setting: TOKEN_CACHE_TTL,
+                reason: String::from("must be at least one second"),
+            });
+        }
+        TokenCacheConfig {
+            ttl: Duration::from_secs(ttl_secs),
+            refresh_on_hit: self.refresh_on_hit.unwrap_or(false),
+            negative_ttl: self
+                .negative_ttl_secs
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+        }
+    }
+}
+
+fn build_identity_providers(
+    raw: Vec<RawIdentityProviderConfig>,
+    errors: &mut Vec<SettingError>,
+) -> Vec<IdentityProviderConfig> {
+    let mut names = vec![DEFAULT_IDENTITY_PROVIDER];
+    for provider in &raw {
+        let name = provider.name.as_str();
+        let mut invalid = |reason: String| {
+            errors.push(SettingError::Invalid {
+                setting: IDENTITY_PROVIDERS,
+                reason: format!("provider `{name}`: {reason}"),
+            })
+        };
+
+        if name.is_empty() {
+            invalid(String::from("name must not be empty"));
+        } else if names.contains(&name) {
+            invalid(String::from("name is already taken"));
+        }
+        names.push(name);
+
+        let mut urls = vec![
+            ("host", &provider.host),
+            ("redirect_uri", &provider.redirect_uri),
+        ];
+        if let Some(jwt) = &provider.jwt {
+            urls.push(("jwt.jwks_url", &jwt.jwks_url));
+        }
+        for (field, url) in urls {
+            if let Some(reason) = url_error(url, &["http", "https"]) {
+                invalid(format!("{field} is invalid: {reason}"));
+            }
+        }
+    }
+
+    raw.into_iter()
+        .map(|provider| IdentityProviderConfig {
+            name: provider.name,
+            host: provider.host,
+            client_id: provider.client_id,
+            client_secret: provider.client_secret,
+            redirect_uri: provider.redirect_uri,
+            jwt: provider.jwt.map(|jwt| JwtConfig {
+                jwks_url: jwt.jwks_url,
+                issuer: jwt.issuer,
+                audience: jwt.audience,
+            }),
+        })
+        .collect()
+}
+
+fn build_service_accounts(
+    raw: Vec<RawServiceAccountConfig>,
+    errors: &mut Vec<SettingError>,
+) -> Vec<ServiceAccountConfig> {
+    let mut client_ids = Vec::new();
+    for account in &raw {
+        let client_id = account.client_id.as_str();
+        let mut invalid = |reason: &str| {
+            errors.push(SettingError::Invalid {
+                setting: SERVICE_ACCOUNTS,
+                reason: format!("client `{client_id}`: {reason}"),
+            })
+        };
+
+        if client_ids.contains(&client_id) {
+            invalid("configured more than once");
+        }
+        client_ids.push(client_id);
+
+        // user owners are emails, so a namespace without `@` can never be
+        // mistaken for a user
+        if account.namespace.is_empty() || account.namespace.contains('@') {
+            invalid("namespace must be non-empty and must not contain `@`");
+        } else if account.namespace == ANONYMOUS_OWNER {
+            invalid("namespace is reserved for anonymous links");
+        }
+    }
+
+    raw.into_iter()
+        .map(|account| ServiceAccountConfig {
+            client_id: account.client_id,
+            namespace: account.namespace,
+        })
+        .collect()
+}
+
+impl RawSessionConfig {
+    fn build(self, errors: &mut Vec<SettingError>) -> Option<SessionConfig> {
+        if self.enabled != Some(true) {
+            return None;
+        }
+
+        let ttl_secs = self.ttl_secs.unwrap_or(7 * 24 * 60 * 60);
+        if ttl_secs == 0 {
+            errors.push(SettingError::Invalid {
+                setting: SESSION_TTL,
+                reason: String::from("must be at least one second"),
+            });
+        }
+        Some(SessionConfig {
+            ttl: Duration::from_secs(ttl_secs),
+        })
+    }
+}
+
+impl RawAuthLockoutConfig {
+    fn build(self, errors: &mut Vec<SettingError>) -> Option<AuthLockoutConfig> {
+        if self.enabled != Some(true) {
+            return None;
+        }
+
+        let max_failures = self.max_failures.unwrap_or(10);
+        let base_secs = self.base_secs.unwrap_or(30);
+        let max_secs = self.max_secs.unwrap_or(60 * 60);
+        for (value, setting) in [
+            (max_failures, AUTH_LOCKOUT_MAX_FAILURES),
+            (base_secs, AUTH_LOCKOUT_BASE_SECS),
+        ] {
+            if value == 0 {
+                errors.push(SettingError::Invalid {
+                    setting,
+                    reason: String::from("must be at least one"),
+                });
+            }
+        }
+        if max_secs < base_secs {
+            errors.push(SettingError::Invalid {
+                setting: AUTH_LOCKOUT_MAX_SECS,
+                reason: format!("must be at least the base block of {base_secs}s"),
+            });
+        }
+        Some(AuthLockoutConfig {
+            max_failures,
+            base_block: Duration::from_secs(base_secs),
+            max_block: Duration::from_secs(max_secs),
+        })
+    }
+}
@@ -0,0 +1,184 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::{override_env, Setting, SettingError};
+use crate::click_buffer::MAX_CLICK_BATCH_SIZE;
+
+pub struct ClickHouseConfig {
+    /// The HTTP interface, e.g. `http://localhost:8123`.
+    pub url: String,
+    pub database: String,
+    pub user: Option<String>,
+    pub password: Option<String>,
+}
+
+/// An S3 bucket, or any S3-compatible store, receiving old clicks.
+pub struct ClickArchiveConfig {
+    pub bucket: String,
+    /// For stores other than AWS, e.g. `http://localhost:9000` for MinIO.
+    pub endpoint: Option<String>,
+    pub region: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    /// Prepended to object names, e.g. `archive/`.
+    pub prefix: String,
+}
+
+/// How clicks are batched on their way to the database.
+pub struct ClickBufferConfig {
+    /// Clicks waiting to be written; more are dropped.
+    pub capacity: usize,
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+}
+
+const CLICKHOUSE_URL: Setting = Setting::new("clickhouse.url", "CLICKHOUSE_URL");
+const CLICKHOUSE_DATABASE: Setting = Setting::new("clickhouse.database", "CLICKHOUSE_DATABASE");
+const CLICKHOUSE_USER: Setting = Setting::new("clickhouse.user", "CLICKHOUSE_USER");
+const CLICKHOUSE_PASSWORD: Setting = Setting::new("clickhouse.password", "CLICKHOUSE_PASSWORD");
+const CLICK_ARCHIVE_BUCKET: Setting = Setting::new("click_archive.bucket", "CLICK_ARCHIVE_BUCKET");
+const CLICK_ARCHIVE_ENDPOINT: Setting =
+    Setting::new("click_archive.endpoint", "CLICK_ARCHIVE_ENDPOINT");
+const CLICK_ARCHIVE_REGION: Setting = Setting::new("click_archive.region", "CLICK_ARCHIVE_REGION");
+const CLICK_ARCHIVE_ACCESS_KEY_ID: Setting =
+    Setting::new("click_archive.access_key_id", "CLICK_ARCHIVE_ACCESS_KEY_ID");
+const CLICK_ARCHIVE_SECRET_ACCESS_KEY: Setting = Setting::new(
+    "click_archive.secret_access_key",
+    "CLICK_ARCHIVE_SECRET_ACCESS_KEY",
+);
+const CLICK_ARCHIVE_PREFIX: Setting = Setting::new("click_archive.prefix", "CLICK_ARCHIVE_PREFIX");
+const CLICK_BUFFER_CAPACITY: Setting =
+    Setting::new("click_buffer.capacity", "CLICK_BUFFER_CAPACITY");
+const CLICK_BUFFER_BATCH_SIZE: Setting =
+    Setting::new("click_buffer.batch_size", "CLICK_BUFFER_BATCH_SIZE");
+const CLICK_BUFFER_FLUSH_INTERVAL: Setting = Setting::new(
+    "click_buffer.flush_interval_ms",
+    "CLICK_BUFFER_FLUSH_INTERVAL_MS",
+);
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawClickHouseConfig {
+    url: Option<String>,
+    database: Option<String>,
+    user: Option<String>,
+    password: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawClickArchiveConfig {
+    bucket: Option<String>,
+    endpoint: Option<String>,
+    region: Option<String>,
+    access_key_id: Option<String>,
+    secret_access_key: Option<String>,
+    prefix: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawClickBufferConfig {
+    capacity: Option<usize>,
+    batch_size: Option<usize>,
+    flush_interval_ms: Option<u64>,
+}
+
+impl RawClickHouseConfig {
+    pub(super) fn apply_env(&mut self, errors: &mut Vec<SettingError>) {
+        override_env(&mut self.url, CLICKHOUSE_URL, errors);
+        override_env(&mut self.database, CLICKHOUSE_DATABASE, errors);
+        override_env(&mut self.user, CLICKHOUSE_USER, errors);
+        override_env(&mut self.password, CLICKHOUSE_PASSWORD, errors);
+    }
+
+    pub(super) fn build(self, errors: &mut Vec<SettingError>) -> Option<ClickHouseConfig> {
+        let url = self.url?;
+        let database = self.database.unwrap_or_else(|| String::from("default"));
+        // spliced into queries, so only plain identifiers are allowed
+        if database.is_empty()
+            || !database
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            errors.push(SettingError::Invalid {
+                setting: CLICKHOUSE_DATABASE,
+                reason: String::from("must only contain letters, digits and underscores"),
+            });
+        }
+        Some(ClickHouseConfig {
+            url,
+            database,
+            user: self.user,
+            password: self.password,
+        })
+    }
+}
+
+impl RawClickArchiveConfig {
+    pub(super) fn apply_env(&mut self, errors: &mut Vec<SettingError>) {
+        override_env(&mut self.bucket, CLICK_ARCHIVE_BUCKET, errors);
+        override_env(&mut self.endpoint, CLICK_ARCHIVE_ENDPOINT, errors);
+        override_env(&mut self.region, CLICK_ARCHIVE_REGION, errors);
+        override_env(&mut self.access_key_id, CLICK_ARCHIVE_ACCESS_KEY_ID, errors);
+        override_env(
+            &mut self.secret_access_key,
+            CLICK_ARCHIVE_SECRET_ACCESS_KEY,
+            errors,
+        );
+        override_env(&mut self.prefix, CLICK_ARCHIVE_PREFIX, errors);
+    }
+
+    pub(super) fn build(self) -> Option<ClickArchiveConfig> {
+        Some(ClickArchiveConfig {
+            bucket: self.bucket?,
+            endpoint: self.endpoint,
+            region: self.region,
+            access_key_id: self.access_key_id,
+            secret_access_key: self.secret_access_key,
+            prefix: self.prefix.unwrap_or_default(),
+        })
+    }
+}
+
+impl RawClickBufferConfig {
+    pub(super) fn apply_env(&mut self, errors: &mut Vec<SettingError>) {
+        override_env(&mut self.capacity, CLICK_BUFFER_CAPACITY, errors);
+        override_env(&mut self.batch_size, CLICK_BUFFER_BATCH_SIZE, errors);
+        override_env(
+            &mut self.flush_interval_ms,
+            CLICK_BUFFER_FLUSH_INTERVAL,
+            errors,
+        );
+    }
+
+    pub(super) fn build(self, errors: &mut Vec<SettingError>) -> ClickBufferConfig {
+        let capacity = self.capacity.unwrap_or(10_000);
+        let batch_size = self.batch_size.unwrap_or(500);
+        let flush_interval_ms = self.flush_interval_ms.unwrap_or(1_000);
+        for (value, setting) in [
+            (capacity as u64, CLICK_BUFFER_CAPACITY),
+            (batch_size as u64, CLICK_BUFFER_BATCH_SIZE),
+            (flush_interval_ms, CLICK_BUFFER_FLUSH_INTERVAL),
+        ] {
+            if value == 0 {
+                errors.push(SettingError::Invalid {
+                    setting,
+                    reason: String::from("must be at least one"),
+                });
+            }
+        }
+        if batch_size > MAX_CLICK_BATCH_SIZE {
+            errors.push(SettingError::Invalid {
+                setting: CLICK_BUFFER_BATCH_SIZE,
+                reason: format!("must be at most {MAX_CLICK_BATCH_SIZE}"),
+            });
+        }
+        ClickBufferConfig {
+            capacity,
+            batch_size,
+            flush_interval: Duration::from_millis(flush_interval_ms),
+        }
+    }
+}
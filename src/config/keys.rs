@@ -0,0 +1,282 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use super::{env_value, override_env, Setting, SettingError};
+use crate::{
+    key_generator::{
+        self, DEFAULT_ALPHABET, DEFAULT_BLOCKED_WORDS, DEFAULT_CONFUSABLE_CHARS, DEFAULT_KEY_LENGTH,
+    },
+    service::KeyPolicy,
+};
+
+/// Characters besides letters and digits a key policy may allow: those that
+/// need no escaping in a URL path. Dots and tildes have their own switches.
+pub const KEY_PUNCTUATION: &str = "-_!$&'()*+,;=:@";
+
+/// The rules keys picked by users must follow. Generated keys only have to
+/// use the characters it allows and fit its maximum length.
+pub struct KeyPolicyConfig {
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Allowed besides letters and digits, out of [`KEY_PUNCTUATION`].
+    pub allowed_characters: String,
+    pub allow_dots: bool,
+    pub allow_tildes: bool,
+}
+
+impl Default for KeyPolicyConfig {
+    fn default() -> Self {
+        Self {
+            min_length: 1,
+            max_length: 100,
+            allowed_characters: String::from("-_"),
+            allow_dots: false,
+            allow_tildes: false,
+        }
+    }
+}
+
+/// How random keys are drawn.
+pub struct KeyGenerationConfig {
+    pub mode: KeyGenerationMode,
+    /// Number of random characters, not counting the prefix.
+    pub length: usize,
+    pub alphabet: String,
+    pub prefix: String,
+    /// Shuffles sequential keys, so they do not reveal how many links exist.
+    pub obfuscate: bool,
+    pub blocked_words: Vec<String>,
+    /// Left out of the alphabet.
+    pub confusable_chars: String,
+}
+
+impl Default for KeyGenerationConfig {
+    fn default() -> Self {
+        Self {
+            mode: KeyGenerationMode::default(),
+            length: DEFAULT_KEY_LENGTH,
+            alphabet: String::from(DEFAULT_ALPHABET),
+            prefix: String::new(),
+            obfuscate: false,
+            blocked_words: DEFAULT_BLOCKED_WORDS
+                .iter()
+                .map(|w| w.to_string())
+                .collect(),
+            confusable_chars: String::from(DEFAULT_CONFUSABLE_CHARS),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyGenerationMode {
+    #[default]
+    Random,
+    /// Counts up from a database sequence, for the shortest possible keys.
+    Sequential,
+}
+
+impl FromStr for KeyGenerationMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "random" => Ok(Self::Random),
+            "sequential" => Ok(Self::Sequential),
+            _ => Err(()),
+        }
+    }
+}
+
+const KEY_GENERATION_MODE: Setting = Setting::new("key_generation.mode", "KEY_GENERATION_MODE");
+const KEY_OBFUSCATE: Setting = Setting::new("key_generation.obfuscate", "KEY_OBFUSCATE");
+const KEY_LENGTH: Setting = Setting::new("key_generation.length", "KEY_LENGTH");
+const KEY_ALPHABET: Setting = Setting::new("key_generation.alphabet", "KEY_ALPHABET");
+const KEY_PREFIX: Setting = Setting::new("key_generation.prefix", "KEY_PREFIX");
+const KEY_BLOCKED_WORDS: Setting =
+    Setting::new("key_generation.blocked_words", "KEY_BLOCKED_WORDS");
+const KEY_CONFUSABLE_CHARS: Setting =
+    Setting::new("key_generation.confusable_chars", "KEY_CONFUSABLE_CHARS");
+const KEY_MIN_LENGTH: Setting = Setting::new("key_policy.min_length", "KEY_MIN_LENGTH");
+const KEY_MAX_LENGTH: Setting = Setting::new("key_policy.max_length", "KEY_MAX_LENGTH");
+const KEY_ALLOWED_CHARACTERS: Setting =
+    Setting::new("key_policy.allowed_characters", "KEY_ALLOWED_CHARACTERS");
+const KEY_ALLOW_DOTS: Setting = Setting::new("key_policy.allow_dots", "KEY_ALLOW_DOTS");
+const KEY_ALLOW_TILDES: Setting = Setting::new("key_policy.allow_tildes", "KEY_ALLOW_TILDES");
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawKeyGenerationConfig {
+    mode: Option<KeyGenerationMode>,
+    obfuscate: Option<bool>,
+    length: Option<usize>,
+    alphabet: Option<String>,
+    prefix: Option<String>,
+    blocked_words: Option<Vec<String>>,
+    confusable_chars: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawKeyPolicyConfig {
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    allowed_characters: Option<String>,
+    allow_dots: Option<bool>,
+    allow_tildes: Option<bool>,
+}
+
+impl RawKeyPolicyConfig {
+    pub(super) fn apply_env(&mut self, errors: &mut Vec<SettingError>) {
+        override_env(&mut self.min_length, KEY_MIN_LENGTH, errors);
+        override_env(&mut self.max_length, KEY_MAX_LENGTH, errors);
+        override_env(&mut self.allowed_characters, KEY_ALLOWED_CHARACTERS, errors);
+        override_env(&mut self.allow_dots, KEY_ALLOW_DOTS, errors);
+        override_env(&mut self.allow_tildes, KEY_ALLOW_TILDES, errors);
+    }
+
+    pub(super) fn build(self, errors: &mut Vec<SettingError>) -> KeyPolicyConfig {
+        let mut key_policy = KeyPolicyConfig::default();
+        if let Some(min_length) = self.min_length {
+            key_policy.min_length = min_length;
+        }
+        if let Some(max_length) = self.max_length {
+            key_policy.max_length = max_length;
+        }
+        if let Some(chars) = self.allowed_characters {
+            key_policy.allowed_characters = chars;
+        }
+        if let Some(allow_dots) = self.allow_dots {
+            key_policy.allow_dots = allow_dots;
+        }
+        if let Some(allow_tildes) = self.allow_tildes {
+            key_policy.allow_tildes = allow_tildes;
+        }
+        if let Some(c) = key_policy
+            .allowed_characters
+            .chars()
+            .find(|c| !c.is_ascii_alphanumeric() && !KEY_PUNCTUATION.contains(*c))
+        {
+            errors.push(SettingError::Invalid {
+                setting: KEY_ALLOWED_CHARACTERS,
+                reason: match c {
+                    '.' => format!("must not contain `.`, see {KEY_ALLOW_DOTS}"),
+                    '~' => format!("must not contain `~`, see {KEY_ALLOW_TILDES}"),
+                    c => format!("must not contain `{c}`, only `{KEY_PUNCTUATION}` are allowed"),
+                },
+            });
+        }
+        if key_policy.min_length == 0 {
+            errors.push(SettingError::Invalid {
+                setting: KEY_MIN_LENGTH,
+                reason: String::from("must be at least 1"),
+            });
+        }
+        if key_policy.max_length < key_policy.min_length {
+            errors.push(SettingError::Invalid {
+                setting: KEY_MAX_LENGTH,
+                reason: format!("must be at least {KEY_MIN_LENGTH}"),
+            });
+        }
+        key_policy
+    }
+}
+
+impl RawKeyGenerationConfig {
+    pub(super) fn apply_env(&mut self, errors: &mut Vec<SettingError>) {
+        override_env(&mut self.mode, KEY_GENERATION_MODE, errors);
+        override_env(&mut self.obfuscate, KEY_OBFUSCATE, errors);
+        override_env(&mut self.length, KEY_LENGTH, errors);
+        override_env(&mut self.alphabet, KEY_ALPHABET, errors);
+        override_env(&mut self.prefix, KEY_PREFIX, errors);
+        override_env(&mut self.confusable_chars, KEY_CONFUSABLE_CHARS, errors);
+        if let Some(words) = env_value(KEY_BLOCKED_WORDS, errors) {
+            self.blocked_words = Some(
+                words
+                    .split(',')
+                    .filter(|word| !word.is_empty())
+                    .map(String::from)
+                    .collect(),
+            );
+        }
+    }
+
+    /// Generated keys have to pass the same checks as the ones users pick,
+    /// so they are built against `key_policy`.
+    pub(super) fn build(
+        self,
+        key_policy: &KeyPolicyConfig,
+        errors: &mut Vec<SettingError>,
+    ) -> KeyGenerationConfig {
+        let policy = KeyPolicy::new(key_policy);
+
+        let mut key_generation = KeyGenerationConfig::default();
+        if let Some(mode) = self.mode {
+            key_generation.mode = mode;
+        }
+        if let Some(obfuscate) = self.obfuscate {
+            key_generation.obfuscate = obfuscate;
+        }
+        if let Some(length) = self.length {
+            key_generation.length = length;
+        }
+        if let Some(alphabet) = self.alphabet {
+            key_generation.alphabet = alphabet;
+        }
+        if let Some(prefix) = self.prefix {
+            key_generation.prefix = prefix;
+        }
+        if let Some(words) = self.blocked_words {
+            key_generation.blocked_words = words;
+        }
+        if let Some(chars) = self.confusable_chars {
+            key_generation.confusable_chars = chars;
+        }
+        for (value, setting) in [
+            (&key_generation.alphabet, KEY_ALPHABET),
+            (&key_generation.prefix, KEY_PREFIX),
+        ] {
+            if !value.chars().all(|c| policy.allows(c)) {
+                errors.push(SettingError::Invalid {
+                    setting,
+                    reason: String::from("may only contain characters the key policy allows"),
+                });
+            }
+        }
+        if key_generation.length == 0 {
+            errors.push(SettingError::Invalid {
+                setting: KEY_LENGTH,
+                reason: String::from("must be at least 1"),
+            });
+        } else if key_generation.prefix.chars().count() + key_generation.length
+            > key_policy.max_length
+        {
+            errors.push(SettingError::Invalid {
+                setting: KEY_LENGTH,
+                reason: format!("plus the prefix length must not exceed {KEY_MAX_LENGTH}"),
+            });
+        }
+        if key_generator::alphabet(&key_generation.alphabet, &key_generation.confusable_chars).len()
+            < 2
+        {
+            errors.push(SettingError::Invalid {
+                setting: KEY_ALPHABET,
+                reason: String::from(
+                    "must keep at least two characters once confusable ones are left out",
+                ),
+            });
+        }
+        if key_generation
+            .blocked_words
+            .iter()
+            .any(|word| word.is_empty())
+        {
+            errors.push(SettingError::Invalid {
+                setting: KEY_BLOCKED_WORDS,
+                reason: String::from("must not contain empty words"),
+            });
+        }
+        key_generation
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::{override_env, Setting, SettingError};
+
+/// Bounds on what a single request may cost.
+pub struct LimitsConfig {
+    pub max_body_bytes: usize,
+    pub redirect_timeout: Duration,
+    pub management_timeout: Duration,
+    /// Requests served at once across the whole server, beyond which more
+    /// get 503.
+    pub max_concurrent_requests: Option<usize>,
+    pub max_concurrent_redirects: Option<usize>,
+    pub max_concurrent_management: Option<usize>,
+}
+
+const LIMITS_MAX_BODY_BYTES: Setting =
+    Setting::new("limits.max_body_bytes", "LIMITS_MAX_BODY_BYTES");
+const LIMITS_REDIRECT_TIMEOUT: Setting =
+    Setting::new("limits.redirect_timeout_ms", "LIMITS_REDIRECT_TIMEOUT_MS");
+const LIMITS_MANAGEMENT_TIMEOUT: Setting = Setting::new(
+    "limits.management_timeout_ms",
+    "LIMITS_MANAGEMENT_TIMEOUT_MS",
+);
+const LIMITS_MAX_CONCURRENT_REQUESTS: Setting = Setting::new(
+    "limits.max_concurrent_requests",
+    "LIMITS_MAX_CONCURRENT_REQUESTS",
+);
+const LIMITS_MAX_CONCURRENT_REDIRECTS: Setting = Setting::new(
+    "limits.max_concurrent_redirects",
+    "LIMITS_MAX_CONCURRENT_REDIRECTS",
+);
+const LIMITS_MAX_CONCURRENT_MANAGEMENT: Setting = Setting::new(
+    "limits.max_concurrent_management",
+    "LIMITS_MAX_CONCURRENT_MANAGEMENT",
+);
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawLimitsConfig {
+    max_body_bytes: Option<usize>,
+    redirect_timeout_ms: Option<u64>,
+    management_timeout_ms: Option<u64>,
+    max_concurrent_requests: Option<usize>,
+    max_concurrent_redirects: Option<usize>,
+    max_concurrent_management: Option<usize>,
+}
+
+impl RawLimitsConfig {
+    pub(super) fn apply_env(&mut self, errors: &mut Vec<SettingError>) {
+        override_env(&mut self.max_body_bytes, LIMITS_MAX_BODY_BYTES, errors);
+        override_env(
+            &mut self.redirect_timeout_ms,
+            LIMITS_REDIRECT_TIMEOUT,
+            errors,
+        );
+        override_env(
+            &mut self.management_timeout_ms,
+            LIMITS_MANAGEMENT_TIMEOUT,
+            errors,
+        );
+        override_env(
+            &mut self.max_concurrent_requests,
+            LIMITS_MAX_CONCURRENT_REQUESTS,
+            errors,
+        );
+        override_env(
+            &mut self.max_concurrent_redirects,
+            LIMITS_MAX_CONCURRENT_REDIRECTS,
+            errors,
+        );
+        override_env(
+            &mut self.max_concurrent_management,
+            LIMITS_MAX_CONCURRENT_MANAGEMENT,
+            errors,
+        );
+    }
+
+    pub(super) fn build(self, errors: &mut Vec<SettingError>) -> LimitsConfig {
+        // axum's own default, so nothing changes unless asked
+        let max_body_bytes = self.max_body_bytes.unwrap_or(2 * 1024 * 1024);
+        let redirect_timeout_ms = self.redirect_timeout_ms.unwrap_or(5_000);
+        let management_timeout_ms = self.management_timeout_ms.unwrap_or(30_000);
+        for (value, setting) in [
+            (max_body_bytes as u64, LIMITS_MAX_BODY_BYTES),
+            (redirect_timeout_ms, LIMITS_REDIRECT_TIMEOUT),
+            (management_timeout_ms, LIMITS_MANAGEMENT_TIMEOUT),
+            // concurrency is unlimited when unset
+            (
+                self.max_concurrent_requests.unwrap_or(1) as u64,
+                LIMITS_MAX_CONCURRENT_REQUESTS,
+            ),
+            (
+                self.max_concurrent_redirects.unwrap_or(1) as u64,
+                LIMITS_MAX_CONCURRENT_REDIRECTS,
+            ),
+            (
+                self.max_concurrent_management.unwrap_or(1) as u64,
+                LIMITS_MAX_CONCURRENT_MANAGEMENT,
+            ),
+        ] {
+            if value == 0 {
+                errors.push(SettingError::Invalid {
+                    setting,
+                    reason: String::from("must be at least one"),
+                });
+            }
+        }
+        LimitsConfig {
+            max_body_bytes,
+            redirect_timeout: Duration::from_millis(redirect_timeout_ms),
+            management_timeout: Duration::from_millis(management_timeout_ms),
+            max_concurrent_requests: self.max_concurrent_requests,
+            max_concurrent_redirects: self.max_concurrent_redirects,
+            max_concurrent_management: self.max_concurrent_management,
+        }
+    }
+}
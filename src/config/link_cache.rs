@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::{override_env, Setting, SettingError};
+
+/// How many resolved links each instance keeps, and for how long.
+pub struct LinkCacheConfig {
+    pub capacity: usize,
+    pub ttl: Duration,
+}
+
+const LINK_CACHE_CAPACITY: Setting = Setting::new("link_cache.capacity", "LINK_CACHE_CAPACITY");
+const LINK_CACHE_TTL: Setting = Setting::new("link_cache.ttl_secs", "LINK_CACHE_TTL_SECS");
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawLinkCacheConfig {
+    capacity: Option<usize>,
+    ttl_secs: Option<u64>,
+}
+
+impl RawLinkCacheConfig {
+    pub(super) fn apply_env(&mut self, errors: &mut Vec<SettingError>) {
+        override_env(&mut self.capacity, LINK_CACHE_CAPACITY, errors);
+        override_env(&mut self.ttl_secs, LINK_CACHE_TTL, errors);
+    }
+
+    /// The cache, when given a capacity, in front of the database at
+    /// `database_url`.
+    pub(super) fn build(
+        self,
+        database_url: Option<&str>,
+        errors: &mut Vec<SettingError>,
+    ) -> Option<LinkCacheConfig> {
+        let capacity = self.capacity?;
+        // evictions arrive through Postgres notifications
+        if database_url.is_some_and(|url| url.starts_with("sqlite:")) {
+            errors.push(SettingError::Invalid {
+                setting: LINK_CACHE_CAPACITY,
+                reason: String::from("requires a Postgres database"),
+            });
+        }
+        // a backstop for changes made while notifications were off,
+        // e.g. with the triggers disabled during a restore
+        let ttl_secs = self.ttl_secs.unwrap_or(300);
+        for (value, setting) in [
+            (capacity as u64, LINK_CACHE_CAPACITY),
+            (ttl_secs, LINK_CACHE_TTL),
+        ] {
+            if value == 0 {
+                errors.push(SettingError::Invalid {
+                    setting,
+                    reason: String::from("must be at least one"),
+                });
+            }
+        }
+        Some(LinkCacheConfig {
+            capacity,
+            ttl: Duration::from_secs(ttl_secs),
+        })
+    }
+}
@@ -0,0 +1,955 @@
+use std::{
+    env, fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use ipnet::IpNet;
+use serde::Deserialize;
+
+mod anonymous_links;
+mod auth;
+mod clicks;
+mod keys;
+mod limits;
+mod link_cache;
+mod notifications;
+mod redirects;
+mod scan_guard;
+mod tenancy;
+
+pub use anonymous_links::AnonymousLinksConfig;
+pub use auth::{
+    AuthLockoutConfig, AuthMode, HttpClientConfig, IdentityProviderConfig, JwtConfig,
+    ServiceAccountConfig, SessionConfig, TokenCacheConfig, DEFAULT_IDENTITY_PROVIDER,
+};
+pub use clicks::{ClickArchiveConfig, ClickBufferConfig, ClickHouseConfig};
+pub use keys::{KeyGenerationConfig, KeyGenerationMode, KeyPolicyConfig, KEY_PUNCTUATION};
+pub use limits::LimitsConfig;
+pub use link_cache::LinkCacheConfig;
+pub use notifications::{InactiveLinksConfig, NotificationsConfig};
+pub use redirects::NotFoundConfig;
+pub use scan_guard::{ScanGuardAction, ScanGuardConfig};
+pub use tenancy::{TenancyConfig, TenantConfig};
+
+use self::{
+    anonymous_links::RawAnonymousLinksConfig,
+    auth::{
+        RawAuthLockoutConfig, RawHttpClientConfig, RawIdentityProviderConfig, RawJwtConfig,
+        RawServiceAccountConfig, RawSessionConfig, RawTokenCacheConfig,
+    },
+    clicks::{RawClickArchiveConfig, RawClickBufferConfig, RawClickHouseConfig},
+    keys::{RawKeyGenerationConfig, RawKeyPolicyConfig},
+    limits::RawLimitsConfig,
+    link_cache::RawLinkCacheConfig,
+    notifications::{RawInactiveLinksConfig, RawNotificationsConfig},
+    redirects::{build_redirect_prefix, RawNotFoundConfig, REDIRECT_PREFIX},
+    scan_guard::RawScanGuardConfig,
+    tenancy::RawTenancyConfig,
+};
+
+pub struct Config {
+    pub auth_mode: AuthMode,
+    /// Never empty in SSO mode; the first provider is the one used when a
+    /// request does not name one.
+    pub identity_providers: Vec<IdentityProviderConfig>,
+    pub sso_client: HttpClientConfig,
+    pub token_cache: TokenCacheConfig,
+    pub port: u16,
+    /// Serves the management API on its own port, leaving only redirects on `port`.
+    pub management_port: Option<u16>,
+    pub management_api_enabled: bool,
+    pub database: DatabaseConfig,
+    pub kvs_url: String,
+    pub allowed_origins: Vec<String>,
+    pub run_migrations: bool,
+    pub log_format: LogFormat,
+    pub slow_threshold: Option<Duration>,
+    /// Management API responses larger than this are compressed.
+    pub compression_min_bytes: u16,
+    pub limits: LimitsConfig,
+    pub click_buffer: ClickBufferConfig,
+    /// Keeps resolved links in memory, evicted through Postgres notifications.
+    pub link_cache: Option<LinkCacheConfig>,
+    /// Whole months of clicks kept before their partition is dropped.
+    pub click_retention_months: u32,
+    /// How often the clicks counted in the KVS are added to the links' rows.
+    pub click_count_write_back: Duration,
+    /// Where expired click partitions are copied before being dropped.
+    pub click_archive: Option<ClickArchiveConfig>,
+    /// Clicks go to ClickHouse instead of Postgres when set.
+    pub clickhouse: Option<ClickHouseConfig>,
+    pub tls: Option<TlsConfig>,
+    pub anonymous_links: Option<AnonymousLinksConfig>,
+    pub sessions: Option<SessionConfig>,
+    pub service_accounts: Vec<ServiceAccountConfig>,
+    pub auth_lockout: Option<AuthLockoutConfig>,
+    pub scan_guard: Option<ScanGuardConfig>,
+    /// Proxies whose `Forwarded` or `X-Forwarded-For` headers are believed
+    /// when resolving the client IP.
+    pub trusted_proxies: Vec<IpNet>,
+    pub admin_allowlist: Option<AdminAllowlistConfig>,
+    /// Emails of users allowed to impersonate others and use the admin API.
+    pub admins: Vec<String>,
+    /// What unknown keys get instead of a plain-text 404.
+    pub not_found: Option<NotFoundConfig>,
+    /// Answers `key+` with the preview of `key` rather than a 404.
+    pub plus_preview: bool,
+    /// The path redirects are served under, e.g. `/go`, without a trailing
+    /// slash; empty to serve them at the root. Other routes take precedence.
+    pub redirect_prefix: String,
+    pub app_association: AppAssociationConfig,
+    /// Served as `/robots.txt` instead of the built-in one.
+    pub robots_txt: Option<PathBuf>,
+    /// A directory of message catalogs, one `<language>.toml` each, for
+    /// errors in the client's language.
+    pub messages_dir: Option<PathBuf>,
+    /// Where redirects are served, e.g. `https://go.example.com/`, which
+    /// links' short URLs are spelled out under.
+    pub public_base_url: Option<url::Url>,
+    /// Signs tokens for sharing a link's stats; sharing is off without it.
+    pub stats_sharing_secret: Option<String>,
+    /// Signs the cursors of the link list; without it each instance signs
+    /// with a random secret, and cursors stop working on restart.
+    pub page_cursor_secret: Option<String>,
+    pub slack: Option<SlackConfig>,
+    pub notifications: Option<NotificationsConfig>,
+    pub inactive_links: Option<InactiveLinksConfig>,
+    /// A directory holding `bio.html`, replacing the built-in link-in-bio page.
+    pub bio_template_dir: Option<PathBuf>,
+    pub key_generation: KeyGenerationConfig,
+    pub key_policy: KeyPolicyConfig,
+    /// Serves several isolated organizations from one installation.
+    pub tenancy: Option<TenancyConfig>,
+}
+
+/// Files served under `/.well-known` for mobile app links.
+#[derive(Default)]
+pub struct AppAssociationConfig {
+    pub apple_app_site_association: Option<PathBuf>,
+    pub android_asset_links: Option<PathBuf>,
+}
+
+/// Client IP ranges allowed to reach `/admin` routes.
+pub struct AdminAllowlistConfig {
+    pub cidrs: Vec<IpNet>,
+    /// Applies the allowlist to the whole management API instead.
+    pub all_management: bool,
+}
+
+/// The `/shorten` slash command of a Slack app.
+pub struct SlackConfig {
+    /// From the app's "Basic Information" page; proves a request came from Slack.
+    pub signing_secret: String,
+}
+
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Pretty,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "pretty" => Ok(Self::Pretty),
+            "json" => Ok(Self::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: Option<u32>,
+    pub min_connections: Option<u32>,
+    pub acquire_timeout: Option<Duration>,
+    pub statement_timeout: Option<Duration>,
+    pub slow_statement_threshold: Option<Duration>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("failed to read config file {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to parse config file {path}: {message}")]
+    Parse { path: PathBuf, message: String },
+    #[error("invalid configuration:{}", .0.iter().map(|error| format!("\n  - {error}")).collect::<String>())]
+    Invalid(Vec<SettingError>),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettingError {
+    #[error("{0} must be set")]
+    Missing(Setting),
+    #[error("{setting} is invalid: {reason}")]
+    Invalid { setting: Setting, reason: String },
+}
+
+/// A configuration entry, known by its key in the config file and its
+/// environment variable.
+#[derive(Debug, Clone, Copy)]
+pub struct Setting {
+    key: &'static str,
+    env: &'static str,
+}
+
+impl Setting {
+    const fn new(key: &'static str, env: &'static str) -> Self {
+        Self { key, env }
+    }
+
+    /// A setting with no environment variable, only available in the config file.
+    const fn file_only(key: &'static str) -> Self {
+        Self { key, env: "" }
+    }
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.env {
+            "" => write!(f, "`{}` in config file", self.key),
+            env => write!(f, "{env} (`{}` in config file)", self.key),
+        }
+    }
+}
+
+const PORT: Setting = Setting::new("port", "SERVER_PORT");
+const MANAGEMENT_PORT: Setting = Setting::new("management_port", "MANAGEMENT_PORT");
+const MANAGEMENT_API_ENABLED: Setting =
+    Setting::new("management_api_enabled", "MANAGEMENT_API_ENABLED");
+const POSTGRES_URL: Setting = Setting::new("database.url", "POSTGRES_URL");
+const POSTGRES_MAX_CONNECTIONS: Setting =
+    Setting::new("database.max_connections", "POSTGRES_MAX_CONNECTIONS");
+const POSTGRES_MIN_CONNECTIONS: Setting =
+    Setting::new("database.min_connections", "POSTGRES_MIN_CONNECTIONS");
+const POSTGRES_ACQUIRE_TIMEOUT: Setting =
+    Setting::new("database.acquire_timeout_ms", "POSTGRES_ACQUIRE_TIMEOUT_MS");
+const POSTGRES_STATEMENT_TIMEOUT: Setting = Setting::new(
+    "database.statement_timeout_ms",
+    "POSTGRES_STATEMENT_TIMEOUT_MS",
+);
+const KVS_URL: Setting = Setting::new("kvs_url", "KVS_URL");
+const ALLOWED_ORIGINS: Setting = Setting::new("allowed_origins", "ALLOWED_ORIGINS");
+const RUN_MIGRATIONS: Setting = Setting::new("run_migrations", "RUN_MIGRATIONS");
+const LOG_FORMAT: Setting = Setting::new("log_format", "LOG_FORMAT");
+const TLS_CERT_PATH: Setting = Setting::new("tls.cert_path", "TLS_CERT_PATH");
+const TLS_KEY_PATH: Setting = Setting::new("tls.key_path", "TLS_KEY_PATH");
+const SLOW_THRESHOLD: Setting = Setting::new("slow_threshold_ms", "SLOW_THRESHOLD_MS");
+const CLICK_RETENTION_MONTHS: Setting =
+    Setting::new("click_retention_months", "CLICK_RETENTION_MONTHS");
+const CLICK_COUNT_WRITE_BACK: Setting =
+    Setting::new("click_count_write_back_secs", "CLICK_COUNT_WRITE_BACK_SECS");
+const COMPRESSION_MIN_BYTES: Setting =
+    Setting::new("compression_min_bytes", "COMPRESSION_MIN_BYTES");
+const TRUSTED_PROXIES: Setting = Setting::new("trusted_proxies", "TRUSTED_PROXIES");
+const ADMIN_ALLOWED_CIDRS: Setting = Setting::new("admin_allowlist.cidrs", "ADMIN_ALLOWED_CIDRS");
+const ADMIN_ALLOWLIST_ALL_MANAGEMENT: Setting = Setting::new(
+    "admin_allowlist.all_management",
+    "ADMIN_ALLOWLIST_ALL_MANAGEMENT",
+);
+const ADMINS: Setting = Setting::new("admins", "ADMINS");
+const APPLE_APP_SITE_ASSOCIATION_FILE: Setting = Setting::new(
+    "app_association.apple_app_site_association",
+    "APPLE_APP_SITE_ASSOCIATION_FILE",
+);
+const ANDROID_ASSET_LINKS_FILE: Setting = Setting::new(
+    "app_association.android_asset_links",
+    "ANDROID_ASSET_LINKS_FILE",
+);
+const BIO_PAGES_TEMPLATE_DIR: Setting =
+    Setting::new("bio_pages.template_dir", "BIO_PAGES_TEMPLATE_DIR");
+const SLACK_SIGNING_SECRET: Setting = Setting::new("slack.signing_secret", "SLACK_SIGNING_SECRET");
+const PUBLIC_BASE_URL: Setting = Setting::new("public_base_url", "PUBLIC_BASE_URL");
+const STATS_SHARING_SECRET: Setting = Setting::new("stats_sharing.secret", "STATS_SHARING_SECRET");
+const PAGE_CURSOR_SECRET: Setting = Setting::new("pagination.cursor_secret", "PAGE_CURSOR_SECRET");
+const PLUS_PREVIEW: Setting = Setting::new("plus_preview", "PLUS_PREVIEW");
+const ROBOTS_TXT_FILE: Setting = Setting::new("robots_txt", "ROBOTS_TXT_FILE");
+const MESSAGES_DIR: Setting = Setting::new("messages_dir", "MESSAGES_DIR");
+
+impl Config {
+    /// Loads the configuration from an optional TOML or YAML file, then lets
+    /// environment variables override any value found in it. Every missing or
+    /// invalid setting is reported at once rather than stopping at the first.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut raw = match path {
+            Some(path) => RawConfig::from_file(path)?,
+            None => RawConfig::default(),
+        };
+
+        let mut errors = Vec::new();
+        raw.apply_env(&mut errors);
+        raw.build(errors)
+    }
+
+    /// The settings every configuration needs plus `extra`, TOML read as
+    /// from a config file, without environment overrides.
+    #[cfg(test)]
+    pub(crate) fn for_tests(extra: &str) -> Self {
+        let raw: RawConfig = toml::from_str(&format!(
+            r#"
+port = 1
+kvs_url = "memory://"
+client_id = "client"
+client_secret = "secret"
+redirect_uri = "https://example.com"
+allowed_origins = ["http://localhost:3000"]
+{extra}
+
+[database]
+url = "sqlite::memory:"
+"#
+        ))
+        .expect("test configuration parses");
+        raw.build(Vec::new()).expect("test configuration is valid")
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawConfig {
+    auth_mode: Option<AuthMode>,
+    agus_dev_sso_host: Option<String>,
+    sso_client: RawHttpClientConfig,
+    token_cache: RawTokenCacheConfig,
+    jwt: RawJwtConfig,
+    port: Option<u16>,
+    management_port: Option<u16>,
+    management_api_enabled: Option<bool>,
+    database: RawDatabaseConfig,
+    kvs_url: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    redirect_uri: Option<String>,
+    allowed_origins: Option<Vec<String>>,
+    run_migrations: Option<bool>,
+    log_format: Option<LogFormat>,
+    slow_threshold_ms: Option<u64>,
+    compression_min_bytes: Option<u16>,
+    limits: RawLimitsConfig,
+    click_buffer: RawClickBufferConfig,
+    link_cache: RawLinkCacheConfig,
+    click_retention_months: Option<u32>,
+    click_count_write_back_secs: Option<u64>,
+    click_archive: RawClickArchiveConfig,
+    clickhouse: RawClickHouseConfig,
+    tls: RawTlsConfig,
+    anonymous_links: RawAnonymousLinksConfig,
+    sessions: RawSessionConfig,
+    auth_lockout: RawAuthLockoutConfig,
+    scan_guard: RawScanGuardConfig,
+    trusted_proxies: Option<Vec<String>>,
+    admin_allowlist: RawAdminAllowlistConfig,
+    admins: Option<Vec<String>>,
+    not_found: RawNotFoundConfig,
+    app_association: RawAppAssociationConfig,
+    plus_preview: Option<bool>,
+    redirect_prefix: Option<String>,
+    robots_txt: Option<PathBuf>,
+    messages_dir: Option<PathBuf>,
+    public_base_url: Option<String>,
+    bio_pages: RawBioPagesConfig,
+    stats_sharing: RawStatsSharingConfig,
+    pagination: RawPaginationConfig,
+    slack: RawSlackConfig,
+    notifications: RawNotificationsConfig,
+    inactive_links: RawInactiveLinksConfig,
+    key_generation: RawKeyGenerationConfig,
+    key_policy: RawKeyPolicyConfig,
+    identity_providers: Vec<RawIdentityProviderConfig>,
+    service_accounts: Vec<RawServiceAccountConfig>,
+    tenancy: RawTenancyConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawSlackConfig {
+    signing_secret: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawStatsSharingConfig {
+    secret: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawPaginationConfig {
+    cursor_secret: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawBioPagesConfig {
+    template_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawAppAssociationConfig {
+    apple_app_site_association: Option<PathBuf>,
+    android_asset_links: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawAdminAllowlistConfig {
+    cidrs: Option<Vec<String>>,
+    all_management: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawTlsConfig {
+    cert_path: Option<PathBuf>,
+    key_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawDatabaseConfig {
+    url: Option<String>,
+    max_connections: Option<u32>,
+    min_connections: Option<u32>,
+    acquire_timeout_ms: Option<u64>,
+    statement_timeout_ms: Option<u64>,
+}
+
+impl RawConfig {
+    fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let parse_error = |message: String| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message,
+        };
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => {
+                serde_yaml::from_str(&content).map_err(|error| parse_error(error.to_string()))
+            }
+            _ => toml::from_str(&content).map_err(|error| parse_error(error.to_string())),
+        }
+    }
+
+    fn apply_env(&mut self, errors: &mut Vec<SettingError>) {
+        self.apply_auth_env(errors);
+        override_env(&mut self.port, PORT, errors);
+        override_env(&mut self.management_port, MANAGEMENT_PORT, errors);
+        override_env(
+            &mut self.management_api_enabled,
+            MANAGEMENT_API_ENABLED,
+            errors,
+        );
+        override_env(&mut self.database.url, POSTGRES_URL, errors);
+        override_env(
+            &mut self.database.max_connections,
+            POSTGRES_MAX_CONNECTIONS,
+            errors,
+        );
+        override_env(
+            &mut self.database.min_connections,
+            POSTGRES_MIN_CONNECTIONS,
+            errors,
+        );
+        override_env(
+            &mut self.database.acquire_timeout_ms,
+            POSTGRES_ACQUIRE_TIMEOUT,
+            errors,
+        );
+        override_env(
+            &mut self.database.statement_timeout_ms,
+            POSTGRES_STATEMENT_TIMEOUT,
+            errors,
+        );
+        override_env(&mut self.kvs_url, KVS_URL, errors);
+        override_env(&mut self.run_migrations, RUN_MIGRATIONS, errors);
+        override_env(&mut self.log_format, LOG_FORMAT, errors);
+        override_env(&mut self.slow_threshold_ms, SLOW_THRESHOLD, errors);
+        override_env(
+            &mut self.compression_min_bytes,
+            COMPRESSION_MIN_BYTES,
+            errors,
+        );
+        override_env(
+            &mut self.click_retention_months,
+            CLICK_RETENTION_MONTHS,
+            errors,
+        );
+        override_env(
+            &mut self.click_count_write_back_secs,
+            CLICK_COUNT_WRITE_BACK,
+            errors,
+        );
+        self.clickhouse.apply_env(errors);
+        self.click_archive.apply_env(errors);
+        self.click_buffer.apply_env(errors);
+        self.link_cache.apply_env(errors);
+        self.limits.apply_env(errors);
+        override_env(&mut self.tls.cert_path, TLS_CERT_PATH, errors);
+        override_env(&mut self.tls.key_path, TLS_KEY_PATH, errors);
+        self.anonymous_links.apply_env(errors);
+        self.scan_guard.apply_env(errors);
+        self.key_generation.apply_env(errors);
+        self.key_policy.apply_env(errors);
+        self.not_found.apply_env(errors);
+        override_env(&mut self.plus_preview, PLUS_PREVIEW, errors);
+        override_env(&mut self.redirect_prefix, REDIRECT_PREFIX, errors);
+        override_env(&mut self.robots_txt, ROBOTS_TXT_FILE, errors);
+        override_env(&mut self.messages_dir, MESSAGES_DIR, errors);
+        override_env(&mut self.public_base_url, PUBLIC_BASE_URL, errors);
+        override_env(&mut self.stats_sharing.secret, STATS_SHARING_SECRET, errors);
+        override_env(
+            &mut self.pagination.cursor_secret,
+            PAGE_CURSOR_SECRET,
+            errors,
+        );
+        self.tenancy.apply_env(errors);
+        override_env(&mut self.slack.signing_secret, SLACK_SIGNING_SECRET, errors);
+        self.notifications.apply_env(errors);
+        self.inactive_links.apply_env(errors);
+        override_env(
+            &mut self.bio_pages.template_dir,
+            BIO_PAGES_TEMPLATE_DIR,
+            errors,
+        );
+        override_env(
+            &mut self.app_association.apple_app_site_association,
+            APPLE_APP_SITE_ASSOCIATION_FILE,
+            errors,
+        );
+        override_env(
+            &mut self.app_association.android_asset_links,
+            ANDROID_ASSET_LINKS_FILE,
+            errors,
+        );
+        override_env(
+            &mut self.admin_allowlist.all_management,
+            ADMIN_ALLOWLIST_ALL_MANAGEMENT,
+            errors,
+        );
+
+        if let Some(origins) = env_value(ALLOWED_ORIGINS, errors) {
+            self.allowed_origins = Some(origins.split(',').map(String::from).collect());
+        }
+        if let Some(proxies) = env_value(TRUSTED_PROXIES, errors) {
+            self.trusted_proxies = Some(proxies.split(',').map(String::from).collect());
+        }
+        if let Some(admins) = env_value(ADMINS, errors) {
+            self.admins = Some(admins.split(',').map(String::from).collect());
+        }
+        if let Some(cidrs) = env_value(ADMIN_ALLOWED_CIDRS, errors) {
+            self.admin_allowlist.cidrs = Some(cidrs.split(',').map(String::from).collect());
+        }
+    }
+
+    fn build(mut self, mut errors: Vec<SettingError>) -> Result<Config, ConfigError> {
+        let auth = self.build_auth(&mut errors);
+        let tenancy = self.tenancy.build(&auth.service_accounts, &mut errors);
+
+        let port = required(self.port, PORT, &mut errors);
+        if port.is_some() && port == self.management_port {
+            errors.push(SettingError::Invalid {
+                setting: MANAGEMENT_PORT,
+                reason: String::from("must differ from the server port"),
+            });
+        }
+
+        let database_url = required(self.database.url, POSTGRES_URL, &mut errors);
+        if let Some(url) = &database_url {
+            validate_url(
+                url,
+                POSTGRES_URL,
+                &["postgres", "postgresql", "sqlite"],
+                &mut errors,
+            );
+        }
+        if let (Some(min), Some(max)) =
+            (self.database.min_connections, self.database.max_connections)
+        {
+            if min > max {
+                errors.push(SettingError::Invalid {
+                    setting: POSTGRES_MIN_CONNECTIONS,
+                    reason: format!("must not exceed the maximum of {max} connections"),
+                });
+            }
+        }
+
+        let kvs_url = required(self.kvs_url, KVS_URL, &mut errors);
+        if let Some(url) = &kvs_url {
+            validate_url(url, KVS_URL, &["redis", "rediss", "memory"], &mut errors);
+        }
+
+        let allowed_origins = required(self.allowed_origins, ALLOWED_ORIGINS, &mut errors);
+        for origin in allowed_origins.iter().flatten() {
+            validate_origin(origin, &mut errors);
+        }
+
+        let slow_threshold = self.slow_threshold_ms.map(Duration::from_millis);
+
+        let tls = match (self.tls.cert_path, self.tls.key_path) {
+            (Some(cert_path), Some(key_path)) => Some(TlsConfig {
+                cert_path,
+                key_path,
+            }),
+            (Some(_), None) => {
+                errors.push(SettingError::Missing(TLS_KEY_PATH));
+                None
+            }
+            (None, Some(_)) => {
+                errors.push(SettingError::Missing(TLS_CERT_PATH));
+                None
+            }
+            (None, None) => None,
+        };
+
+        let anonymous_links = self.anonymous_links.build(&mut errors);
+
+        let trusted_proxies = parse_cidrs(
+            self.trusted_proxies.unwrap_or_default(),
+            TRUSTED_PROXIES,
+            &mut errors,
+        );
+
+        let admin_allowlist = match self.admin_allowlist.cidrs {
+            Some(cidrs) => Some(AdminAllowlistConfig {
+                cidrs: parse_cidrs(cidrs, ADMIN_ALLOWED_CIDRS, &mut errors),
+                all_management: self.admin_allowlist.all_management.unwrap_or(false),
+            }),
+            None => {
+                if self.admin_allowlist.all_management == Some(true) {
+                    errors.push(SettingError::Missing(ADMIN_ALLOWED_CIDRS));
+                }
+                None
+            }
+        };
+
+        let admins: Vec<String> = self
+            .admins
+            .unwrap_or_default()
+            .iter()
+            .map(|admin| admin.trim().to_string())
+            .collect();
+        for admin in &admins {
+            if !admin.contains('@') {
+                errors.push(SettingError::Invalid {
+                    setting: ADMINS,
+                    reason: format!("`{admin}` is not an email address"),
+                });
+            }
+        }
+
+        let not_found = self.not_found.build(&mut errors);
+        let redirect_prefix = build_redirect_prefix(self.redirect_prefix, &mut errors);
+
+        if self
+            .stats_sharing
+            .secret
+            .as_ref()
+            .is_some_and(|secret| secret.len() < 32)
+        {
+            errors.push(SettingError::Invalid {
+                setting: STATS_SHARING_SECRET,
+                reason: String::from("must be at least 32 bytes"),
+            });
+        }
+        if self
+            .pagination
+            .cursor_secret
+            .as_ref()
+            .is_some_and(|secret| secret.len() < 32)
+        {
+            errors.push(SettingError::Invalid {
+                setting: PAGE_CURSOR_SECRET,
+                reason: String::from("must be at least 32 bytes"),
+            });
+        }
+
+        let public_base_url = self.public_base_url.and_then(|base| match base_url(&base) {
+            Some(base) => Some(base),
+            None => {
+                errors.push(SettingError::Invalid {
+                    setting: PUBLIC_BASE_URL,
+                    reason: String::from("must be an http or https URL"),
+                });
+                None
+            }
+        });
+
+        // replies in Slack are useless without a full URL to click
+        let slack = self.slack.signing_secret.map(|signing_secret| {
+            required(public_base_url.as_ref(), PUBLIC_BASE_URL, &mut errors);
+            SlackConfig { signing_secret }
+        });
+
+        let limits = self.limits.build(&mut errors);
+        let scan_guard = self.scan_guard.build(limits.redirect_timeout, &mut errors);
+
+        let click_buffer = self.click_buffer.build(&mut errors);
+        let link_cache = self.link_cache.build(database_url.as_deref(), &mut errors);
+        // covers the longest report period, like the KVS counters
+        let click_retention_months = self.click_retention_months.unwrap_or(14);
+        if click_retention_months == 0 {
+            errors.push(SettingError::Invalid {
+                setting: CLICK_RETENTION_MONTHS,
+                reason: String::from("must be at least one"),
+            });
+        }
+        let click_count_write_back_secs = self.click_count_write_back_secs.unwrap_or(10);
+        if click_count_write_back_secs == 0 {
+            errors.push(SettingError::Invalid {
+                setting: CLICK_COUNT_WRITE_BACK,
+                reason: String::from("must be at least one"),
+            });
+        }
+        let clickhouse = self.clickhouse.build(&mut errors);
+        let click_archive = self.click_archive.build();
+
+        let notifications = self.notifications.build(&mut errors);
+        let inactive_links = self.inactive_links.build(&mut errors);
+
+        let app_association = AppAssociationConfig {
+            apple_app_site_association: self.app_association.apple_app_site_association,
+            android_asset_links: self.app_association.android_asset_links,
+        };
+
+        let key_policy = self.key_policy.build(&mut errors);
+        let key_generation = self.key_generation.build(&key_policy, &mut errors);
+
+        match (port, database_url, kvs_url, allowed_origins) {
+            (Some(port), Some(database_url), Some(kvs_url), Some(allowed_origins))
+                if errors.is_empty() =>
+            {
+                Ok(Config {
+                    auth_mode: auth.mode,
+                    identity_providers: auth.identity_providers,
+                    sso_client: auth.sso_client,
+                    token_cache: auth.token_cache,
+                    port,
+                    management_port: self.management_port,
+                    management_api_enabled: self.management_api_enabled.unwrap_or(true),
+                    database: DatabaseConfig {
+                        url: database_url,
+                        max_connections: self.database.max_connections,
+                        min_connections: self.database.min_connections,
+                        acquire_timeout: self
+                            .database
+                            .acquire_timeout_ms
+                            .map(Duration::from_millis),
+                        statement_timeout: self
+                            .database
+                            .statement_timeout_ms
+                            .map(Duration::from_millis),
+                        slow_statement_threshold: slow_threshold,
+                    },
+                    kvs_url,
+                    allowed_origins,
+                    run_migrations: self.run_migrations.unwrap_or(false),
+                    log_format: self.log_format.unwrap_or_default(),
+                    slow_threshold,
+                    // smaller bodies barely shrink, and gzip framing adds ~20 bytes
+                    compression_min_bytes: self.compression_min_bytes.unwrap_or(1024),
+                    limits,
+                    click_buffer,
+                    link_cache,
+                    click_retention_months,
+                    click_count_write_back: Duration::from_secs(click_count_write_back_secs),
+                    click_archive,
+                    clickhouse,
+                    tls,
+                    anonymous_links,
+                    sessions: auth.sessions,
+                    service_accounts: auth.service_accounts,
+                    auth_lockout: auth.lockout,
+                    scan_guard,
+                    trusted_proxies,
+                    admin_allowlist,
+                    admins,
+                    not_found,
+                    app_association,
+                    plus_preview: self.plus_preview.unwrap_or(false),
+                    redirect_prefix,
+                    robots_txt: self.robots_txt,
+                    messages_dir: self.messages_dir,
+                    public_base_url,
+                    stats_sharing_secret: self.stats_sharing.secret,
+                    page_cursor_secret: self.pagination.cursor_secret,
+                    slack,
+                    notifications,
+                    inactive_links,
+                    bio_template_dir: self.bio_pages.template_dir,
+                    key_generation,
+                    key_policy,
+                    tenancy,
+                })
+            }
+            _ => Err(ConfigError::Invalid(errors)),
+        }
+    }
+}
+
+fn override_env<T: FromStr>(
+    target: &mut Option<T>,
+    setting: Setting,
+    errors: &mut Vec<SettingError>,
+) {
+    let Some(value) = env_value(setting, errors) else {
+        return;
+    };
+
+    match value.parse() {
+        Ok(parsed) => *target = Some(parsed),
+        Err(_) => errors.push(SettingError::Invalid {
+            setting,
+            reason: format!("cannot parse `{value}`"),
+        }),
+    }
+}
+
+/// Reads a setting from its environment variable or, docker-secrets style,
+/// from the file named by the same variable with a `_FILE` suffix.
+fn env_value(setting: Setting, errors: &mut Vec<SettingError>) -> Option<String> {
+    let file_var = format!("{}_FILE", setting.env);
+    match (env::var(setting.env), env::var(&file_var)) {
+        (Ok(_), Ok(_)) => {
+            errors.push(SettingError::Invalid {
+                setting,
+                reason: format!("both {} and {file_var} are set", setting.env),
+            });
+            None
+        }
+        (Ok(value), Err(_)) => Some(value),
+        (Err(_), Ok(path)) => match std::fs::read_to_string(&path) {
+            Ok(value) => Some(value.trim_end_matches(['\r', '\n']).to_string()),
+            Err(error) => {
+                errors.push(SettingError::Invalid {
+                    setting,
+                    reason: format!("cannot read {file_var} at {path}: {error}"),
+                });
+                None
+            }
+        },
+        (Err(_), Err(_)) => None,
+    }
+}
+
+/// `base` as a URL keys can be joined to, unless it is not an http or https
+/// URL.
+fn base_url(base: &str) -> Option<url::Url> {
+    // without the trailing slash, joining a key would replace the last path
+    // segment instead of appending to it
+    let base = if base.ends_with('/') {
+        base.to_owned()
+    } else {
+        format!("{base}/")
+    };
+    url::Url::parse(&base)
+        .ok()
+        .filter(|base| matches!(base.scheme(), "http" | "https"))
+}
+
+fn required<T>(value: Option<T>, setting: Setting, errors: &mut Vec<SettingError>) -> Option<T> {
+    let already_invalid = errors.iter().any(|error| {
+        matches!(error, SettingError::Invalid { setting: invalid, .. } if invalid.env == setting.env)
+    });
+    if value.is_none() && !already_invalid {
+        errors.push(SettingError::Missing(setting));
+    }
+    value
+}
+
+/// Parses CIDR ranges, taking a bare address as a range of its own.
+fn parse_cidrs(
+    values: Vec<String>,
+    setting: Setting,
+    errors: &mut Vec<SettingError>,
+) -> Vec<IpNet> {
+    values
+        .iter()
+        .map(|value| value.trim())
+        .filter_map(|value| {
+            match value
+                .parse::<IpNet>()
+                .or_else(|_| value.parse::<std::net::IpAddr>().map(IpNet::from))
+            {
+                Ok(cidr) => Some(cidr),
+                Err(_) => {
+                    errors.push(SettingError::Invalid {
+                        setting,
+                        reason: format!("`{value}` is not an IP address or CIDR range"),
+                    });
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn validate_url(value: &str, setting: Setting, schemes: &[&str], errors: &mut Vec<SettingError>) {
+    if let Some(reason) = url_error(value, schemes) {
+        errors.push(SettingError::Invalid { setting, reason });
+    }
+}
+
+// The value itself is left out of the reason, since URLs may carry credentials.
+fn url_error(value: &str, schemes: &[&str]) -> Option<String> {
+    match url::Url::parse(value) {
+        Ok(url) if schemes.contains(&url.scheme()) => None,
+        Ok(url) => Some(format!(
+            "unsupported scheme `{}`, expected one of: {}",
+            url.scheme(),
+            schemes.join(", ")
+        )),
+        Err(error) => Some(format!("not a valid URL ({error})")),
+    }
+}
+
+fn validate_origin(origin: &str, errors: &mut Vec<SettingError>) {
+    let reason = match url::Url::parse(origin) {
+        Ok(url) if !matches!(url.scheme(), "http" | "https") => {
+            format!("origin `{origin}` must use http or https")
+        }
+        Ok(url) if url.path() != "/" || url.query().is_some() || origin.ends_with('/') => {
+            format!("origin `{origin}` must not contain a path, query or trailing slash")
+        }
+        Ok(_) => return,
+        Err(error) => format!("origin `{origin}` is not a valid URL ({error})"),
+    };
+
+    errors.push(SettingError::Invalid {
+        setting: ALLOWED_ORIGINS,
+        reason,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_public_base_url_as_a_directory() {
+        let config = Config::for_tests(r#"public_base_url = "https://go.example.com/s""#);
+
+        assert_eq!(
+            config.public_base_url.map(String::from),
+            Some(String::from("https://go.example.com/s/"))
+        );
+        assert_eq!(base_url("ftp://go.example.com"), None);
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use super::{override_env, validate_url, Setting, SettingError};
+
+/// What unknown keys get instead of a plain-text 404.
+pub enum NotFoundConfig {
+    Redirect(String),
+    /// A directory holding `not_found.html`, where `{{key}}` stands for the
+    /// requested key.
+    Page(PathBuf),
+}
+
+/// Where redirects are served unless configured otherwise.
+const DEFAULT_REDIRECT_PREFIX: &str = "/urls/redirect";
+
+pub(super) const REDIRECT_PREFIX: Setting = Setting::new("redirect_prefix", "REDIRECT_PREFIX");
+const NOT_FOUND_REDIRECT_URL: Setting =
+    Setting::new("not_found.redirect_url", "NOT_FOUND_REDIRECT_URL");
+const NOT_FOUND_TEMPLATE_DIR: Setting =
+    Setting::new("not_found.template_dir", "NOT_FOUND_TEMPLATE_DIR");
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawNotFoundConfig {
+    redirect_url: Option<String>,
+    template_dir: Option<PathBuf>,
+}
+
+impl RawNotFoundConfig {
+    pub(super) fn apply_env(&mut self, errors: &mut Vec<SettingError>) {
+        override_env(&mut self.redirect_url, NOT_FOUND_REDIRECT_URL, errors);
+        override_env(&mut self.template_dir, NOT_FOUND_TEMPLATE_DIR, errors);
+    }
+
+    pub(super) fn build(self, errors: &mut Vec<SettingError>) -> Option<NotFoundConfig> {
+        match (self.redirect_url, self.template_dir) {
+            (Some(_), Some(_)) => {
+                errors.push(SettingError::Invalid {
+                    setting: NOT_FOUND_TEMPLATE_DIR,
+                    reason: format!("cannot be combined with {NOT_FOUND_REDIRECT_URL}"),
+                });
+                None
+            }
+            (Some(url), None) => {
+                validate_url(&url, NOT_FOUND_REDIRECT_URL, &["http", "https"], errors);
+                Some(NotFoundConfig::Redirect(url))
+            }
+            (None, Some(dir)) => Some(NotFoundConfig::Page(dir)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// The prefix as routed: with a leading slash and without a trailing one, or
+/// empty for the root.
+pub(super) fn build_redirect_prefix(raw: Option<String>, errors: &mut Vec<SettingError>) -> String {
+    let Some(raw) = raw else {
+        return String::from(DEFAULT_REDIRECT_PREFIX);
+    };
+    let trimmed = raw.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        return String::new();
+    }
+
+    // segments are matched literally, so they must need no escaping
+    let valid = trimmed.split('/').all(|segment| {
+        !segment.is_empty()
+            && !segment.chars().all(|c| c == '.')
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.~".contains(c))
+    });
+    if !valid {
+        errors.push(SettingError::Invalid {
+            setting: REDIRECT_PREFIX,
+            reason: format!(
+                "`{raw}` must be path segments of letters, digits, `-`, `_`, `.` or `~`"
+            ),
+        });
+    }
+    format!("/{trimmed}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redirect_prefix(raw: Option<&str>) -> (String, Vec<SettingError>) {
+        let mut errors = Vec::new();
+        let prefix = build_redirect_prefix(raw.map(String::from), &mut errors);
+        (prefix, errors)
+    }
+
+    #[test]
+    fn defaults_the_redirect_prefix() {
+        let (prefix, errors) = redirect_prefix(None);
+
+        assert_eq!(prefix, DEFAULT_REDIRECT_PREFIX);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn normalizes_the_redirect_prefix_slashes() {
+        for raw in ["go", "/go", "go/", " /go/ "] {
+            let (prefix, errors) = redirect_prefix(Some(raw));
+            assert_eq!(prefix, "/go");
+            assert!(errors.is_empty());
+        }
+        assert_eq!(redirect_prefix(Some("/a/b.c/")).0, "/a/b.c");
+    }
+
+    #[test]
+    fn serves_redirects_at_the_root_for_an_empty_prefix() {
+        for raw in ["", "/", " / "] {
+            let (prefix, errors) = redirect_prefix(Some(raw));
+            assert_eq!(prefix, "");
+            assert!(errors.is_empty());
+        }
+    }
+
+    #[test]
+    fn rejects_redirect_prefixes_needing_escaping() {
+        for raw in ["/a b", "/a//b", "/a/../b", "/caf\u{e9}", "/a?b"] {
+            let (_, errors) = redirect_prefix(Some(raw));
+            assert!(
+                matches!(
+                    errors.as_slice(),
+                    [SettingError::Invalid { setting, .. }] if setting.env == REDIRECT_PREFIX.env
+                ),
+                "{raw:?} was accepted"
+            );
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::{str::FromStr, time::Duration};
+
+use serde::Deserialize;
+
+use super::{env_value, override_env, Setting, SettingError};
+
+/// Flags clients asking for many keys that do not exist, as those scanning
+/// for private links do.
+pub struct ScanGuardConfig {
+    /// Distinct unknown keys tolerated within `window`.
+    pub max_misses: u64,
+    pub window: Duration,
+    /// How long a flagged client stays flagged.
+    pub block: Duration,
+    pub action: ScanGuardAction,
+    pub tarpit_delay: Duration,
+    /// Keys no link may take, which only a scanner would ask for; one
+    /// request for any of them flags the client.
+    pub honeypot_keys: Vec<String>,
+}
+
+/// What flagged clients get on redirects.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanGuardAction {
+    /// 429 until the block ends.
+    #[default]
+    Throttle,
+    /// The usual answer, but only after the tarpit delay.
+    Tarpit,
+}
+
+impl FromStr for ScanGuardAction {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "throttle" => Ok(Self::Throttle),
+            "tarpit" => Ok(Self::Tarpit),
+            _ => Err(()),
+        }
+    }
+}
+
+const SCAN_GUARD_ENABLED: Setting = Setting::new("scan_guard.enabled", "SCAN_GUARD_ENABLED");
+const SCAN_GUARD_MAX_MISSES: Setting =
+    Setting::new("scan_guard.max_misses", "SCAN_GUARD_MAX_MISSES");
+const SCAN_GUARD_WINDOW_SECS: Setting =
+    Setting::new("scan_guard.window_secs", "SCAN_GUARD_WINDOW_SECS");
+const SCAN_GUARD_BLOCK_SECS: Setting =
+    Setting::new("scan_guard.block_secs", "SCAN_GUARD_BLOCK_SECS");
+const SCAN_GUARD_ACTION: Setting = Setting::new("scan_guard.action", "SCAN_GUARD_ACTION");
+const SCAN_GUARD_TARPIT_MS: Setting = Setting::new("scan_guard.tarpit_ms", "SCAN_GUARD_TARPIT_MS");
+const SCAN_GUARD_HONEYPOT_KEYS: Setting =
+    Setting::new("scan_guard.honeypot_keys", "SCAN_GUARD_HONEYPOT_KEYS");
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawScanGuardConfig {
+    enabled: Option<bool>,
+    max_misses: Option<u64>,
+    window_secs: Option<u64>,
+    block_secs: Option<u64>,
+    action: Option<ScanGuardAction>,
+    tarpit_ms: Option<u64>,
+    honeypot_keys: Option<Vec<String>>,
+}
+
+impl RawScanGuardConfig {
+    pub(super) fn apply_env(&mut self, errors: &mut Vec<SettingError>) {
+        override_env(&mut self.enabled, SCAN_GUARD_ENABLED, errors);
+        override_env(&mut self.max_misses, SCAN_GUARD_MAX_MISSES, errors);
+        override_env(&mut self.window_secs, SCAN_GUARD_WINDOW_SECS, errors);
+        override_env(&mut self.block_secs, SCAN_GUARD_BLOCK_SECS, errors);
+        override_env(&mut self.action, SCAN_GUARD_ACTION, errors);
+        override_env(&mut self.tarpit_ms, SCAN_GUARD_TARPIT_MS, errors);
+        if let Some(keys) = env_value(SCAN_GUARD_HONEYPOT_KEYS, errors) {
+            self.honeypot_keys = Some(
+                keys.split(',')
+                    .filter(|key| !key.is_empty())
+                    .map(String::from)
+                    .collect(),
+            );
+        }
+    }
+
+    /// The guard, when enabled, for redirects answered within
+    /// `redirect_timeout`.
+    pub(super) fn build(
+        self,
+        redirect_timeout: Duration,
+        errors: &mut Vec<SettingError>,
+    ) -> Option<ScanGuardConfig> {
+        if self.enabled != Some(true) {
+            return None;
+        }
+
+        let max_misses = self.max_misses.unwrap_or(20);
+        let window_secs = self.window_secs.unwrap_or(60);
+        let block_secs = self.block_secs.unwrap_or(15 * 60);
+        let tarpit_ms = self.tarpit_ms.unwrap_or(3_000);
+        for (value, setting) in [
+            (max_misses, SCAN_GUARD_MAX_MISSES),
+            (window_secs, SCAN_GUARD_WINDOW_SECS),
+            (block_secs, SCAN_GUARD_BLOCK_SECS),
+        ] {
+            if value == 0 {
+                errors.push(SettingError::Invalid {
+                    setting,
+                    reason: String::from("must be at least one"),
+                });
+            }
+        }
+        // past the timeout, tarpitted clients would get errors instead
+        let redirect_timeout_ms = redirect_timeout.as_millis();
+        if u128::from(tarpit_ms) >= redirect_timeout_ms {
+            errors.push(SettingError::Invalid {
+                setting: SCAN_GUARD_TARPIT_MS,
+                reason: format!("must be below the redirect timeout of {redirect_timeout_ms}ms"),
+            });
+        }
+        Some(ScanGuardConfig {
+            max_misses,
+            window: Duration::from_secs(window_secs),
+            block: Duration::from_secs(block_secs),
+            action: self.action.unwrap_or_default(),
+            tarpit_delay: Duration::from_millis(tarpit_ms),
+            honeypot_keys: self
+                .honeypot_keys
+                .unwrap_or_default()
+                .into_iter()
+                .map(|key| key.trim().to_owned())
+                .filter(|key| !key.is_empty())
+                .collect(),
+        })
+    }
+}
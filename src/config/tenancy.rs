@@ -0,0 +1,165 @@
+use serde::Deserialize;
+
+use super::{base_url, override_env, ServiceAccountConfig, Setting, SettingError};
+
+/// The organizations an installation serves, each told apart by the host
+/// requests come to, or by a header set by a trusted proxy.
+pub struct TenancyConfig {
+    /// Names the tenant by its id. Only believed from trusted proxies.
+    pub header: Option<String>,
+    pub tenants: Vec<TenantConfig>,
+}
+
+pub struct TenantConfig {
+    pub id: String,
+    /// Lowercase and without a port.
+    pub hosts: Vec<String>,
+    /// Users must have an email in one of these, lowercase; any user may
+    /// sign in when empty.
+    pub email_domains: Vec<String>,
+    /// Where the tenant's redirects are served, when not under the
+    /// installation's public base URL.
+    pub public_base_url: Option<url::Url>,
+    /// Namespaces of the service accounts that may act within the tenant,
+    /// which no others may.
+    pub service_accounts: Vec<String>,
+}
+
+const TENANT_HEADER: Setting = Setting::new("tenancy.header", "TENANT_HEADER");
+const TENANTS: Setting = Setting::file_only("tenancy.tenants");
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct RawTenancyConfig {
+    header: Option<String>,
+    tenants: Vec<RawTenantConfig>,
+}
+
+/// Tenants only come from the config file, so like identity providers their
+/// fields are required by the parser itself.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTenantConfig {
+    id: String,
+    hosts: Vec<String>,
+    #[serde(default)]
+    email_domains: Vec<String>,
+    public_base_url: Option<String>,
+    #[serde(default)]
+    service_accounts: Vec<String>,
+}
+
+impl RawTenancyConfig {
+    pub(super) fn apply_env(&mut self, errors: &mut Vec<SettingError>) {
+        override_env(&mut self.header, TENANT_HEADER, errors);
+    }
+
+    pub(super) fn build(
+        self,
+        service_accounts: &[ServiceAccountConfig],
+        errors: &mut Vec<SettingError>,
+    ) -> Option<TenancyConfig> {
+        if self.tenants.is_empty() {
+            if self.header.is_some() {
+                errors.push(SettingError::Invalid {
+                    setting: TENANT_HEADER,
+                    reason: String::from("requires tenants to be configured"),
+                });
+            }
+            return None;
+        }
+        if let Some(header) = &self.header {
+            if http::HeaderName::from_bytes(header.as_bytes()).is_err() {
+                errors.push(SettingError::Invalid {
+                    setting: TENANT_HEADER,
+                    reason: format!("`{header}` is not a valid header name"),
+                });
+            }
+        }
+
+        let mut ids = Vec::new();
+        let mut hosts = Vec::new();
+        let tenants = self
+            .tenants
+            .into_iter()
+            .map(|tenant| {
+                let id = tenant.id;
+                let mut invalid = |reason: String| {
+                    errors.push(SettingError::Invalid {
+                        setting: TENANTS,
+                        reason: format!("tenant `{id}`: {reason}"),
+                    })
+                };
+
+                // ids end up in cache keys and logs, so they are kept plain
+                if id.is_empty()
+                    || !id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                {
+                    invalid(String::from(
+                        "id must be non-empty letters, digits, `-` or `_`",
+                    ));
+                } else if ids.contains(&id) {
+                    invalid(String::from("configured more than once"));
+                }
+                ids.push(id.clone());
+
+                let tenant_hosts: Vec<String> = tenant
+                    .hosts
+                    .iter()
+                    .map(|host| host.trim().to_ascii_lowercase())
+                    .collect();
+                if tenant_hosts.is_empty() {
+                    invalid(String::from("needs at least one host"));
+                }
+                for host in &tenant_hosts {
+                    if host.is_empty() || host.contains([':', '/']) {
+                        invalid(format!("host `{host}` must be a bare host name"));
+                    } else if hosts.contains(host) {
+                        invalid(format!("host `{host}` belongs to another tenant"));
+                    }
+                    hosts.push(host.clone());
+                }
+
+                let public_base_url = tenant.public_base_url.and_then(|base| {
+                    let parsed = base_url(&base);
+                    if parsed.is_none() {
+                        invalid(format!(
+                            "public_base_url `{base}` must be an http or https URL"
+                        ));
+                    }
+                    parsed
+                });
+
+                for namespace in &tenant.service_accounts {
+                    if !service_accounts
+                        .iter()
+                        .any(|account| &account.namespace == namespace)
+                    {
+                        invalid(format!(
+                            "service account `{namespace}` is not one of service_accounts"
+                        ));
+                    }
+                }
+
+                TenantConfig {
+                    id,
+                    hosts: tenant_hosts,
+                    public_base_url,
+                    service_accounts: tenant.service_accounts,
+                    email_domains: tenant
+                        .email_domains
+                        .iter()
+                        .map(|domain| domain.trim().trim_start_matches('@').to_ascii_lowercase())
+                        .collect(),
+                }
+            })
+            .collect();
+
+        Some(TenancyConfig {
+            header: self.header,
+            tenants,
+        })
+    }
+}
@@ -0,0 +1,139 @@
+use axum::{
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{header::AUTHORIZATION, HeaderValue, Method, StatusCode};
+
+use crate::session;
+
+/// Readable by the page's JavaScript, which echoes it back in [`CSRF_HEADER`].
+pub const CSRF_COOKIE: &str = "csrf";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+pub fn cookie(token: &str, max_age: u64) -> HeaderValue {
+    // tokens are alphanumeric, so the value is always a valid header
+    HeaderValue::from_str(&format!(
+        "{CSRF_COOKIE}={token}; Path=/; Max-Age={max_age}; Secure; SameSite=Lax"
+    ))
+    .expect("csrf cookie is a valid header value")
+}
+
+/// Double-submit check for requests riding on a session cookie: a mutating
+/// request must repeat the CSRF cookie in the `X-CSRF-Token` header, which
+/// another site cannot do since it cannot read our cookies. Requests with an
+/// `Authorization` header are left alone, as browsers never add one on their own.
+pub async fn protect(request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    let exempt = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) || headers.contains_key(AUTHORIZATION)
+        || session::session_id(headers).is_none();
+    if exempt {
+        return next.run(request).await;
+    }
+
+    let expected = session::cookie(headers, CSRF_COOKIE);
+    let submitted = headers
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok());
+    match (expected, submitted) {
+        (Some(expected), Some(submitted)) if constant_time_eq(expected, submitted) => {
+            next.run(request).await
+        }
+        _ => (StatusCode::FORBIDDEN, "missing or invalid CSRF token").into_response(),
+    }
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, middleware, routing::post, Router};
+    use http::{header::COOKIE, Request};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn status(request: Request<Body>) -> StatusCode {
+        Router::new()
+            .route("/", post(|| async { "done" }).get(|| async { "done" }))
+            .route_layer(middleware::from_fn(protect))
+            .oneshot(request)
+            .await
+            .unwrap()
+            .status()
+    }
+
+    fn request(method: Method) -> http::request::Builder {
+        Request::builder().method(method).uri("/")
+    }
+
+    #[tokio::test]
+    async fn lets_through_requests_without_a_session() {
+        let request = request(Method::POST).body(Body::empty()).unwrap();
+
+        assert_eq!(status(request).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn lets_through_safe_methods_with_a_session() {
+        let request = request(Method::GET)
+            .header(COOKIE, "session=s")
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!(status(request).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn lets_through_requests_with_an_authorization_header() {
+        let request = request(Method::POST)
+            .header(COOKIE, "session=s")
+            .header(AUTHORIZATION, "Bearer t")
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!(status(request).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn lets_through_session_requests_echoing_the_cookie() {
+        let request = request(Method::POST)
+            .header(COOKIE, "session=s; csrf=token")
+            .header(CSRF_HEADER, "token")
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!(status(request).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn forbids_session_requests_without_the_token() {
+        let missing = request(Method::POST)
+            .header(COOKIE, "session=s; csrf=token")
+            .body(Body::empty())
+            .unwrap();
+        let wrong = request(Method::POST)
+            .header(COOKIE, "session=s; csrf=token")
+            .header(CSRF_HEADER, "tokem")
+            .body(Body::empty())
+            .unwrap();
+        let no_cookie = request(Method::POST)
+            .header(COOKIE, "session=s")
+            .header(CSRF_HEADER, "token")
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!(status(missing).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(wrong).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(no_cookie).await, StatusCode::FORBIDDEN);
+    }
+}
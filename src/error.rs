@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    body::Body,
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{
+    header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE},
+    HeaderMap, HeaderValue, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{i18n::Message, request_id::request_id};
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+// Error bodies are short messages; anything bigger is passed through untouched.
+const MAX_ERROR_BODY_SIZE: usize = 64 * 1024;
+
+/// Errors clients can tell apart by their problem type, which stays the same
+/// whatever the wording of the message. Other errors are told apart by their
+/// status alone, with the `about:blank` type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemType {
+    ValidationFailed,
+    KeyAlreadyExists,
+    HandleTaken,
+    LinkLimitReached,
+    UnrestorableRevision,
+    Internal,
+}
+
+impl ProblemType {
+    fn uri(self) -> &'static str {
+        match self {
+            Self::ValidationFailed => "urn:url-shortener:problem:validation-failed",
+            Self::KeyAlreadyExists => "urn:url-shortener:problem:key-already-exists",
+            Self::HandleTaken => "urn:url-shortener:problem:handle-taken",
+            Self::LinkLimitReached => "urn:url-shortener:problem:link-limit-reached",
+            Self::UnrestorableRevision => "urn:url-shortener:problem:unrestorable-revision",
+            Self::Internal => "urn:url-shortener:problem:internal",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::ValidationFailed => "Invalid request",
+            Self::KeyAlreadyExists => "Key already exists",
+            Self::HandleTaken => "Handle already taken",
+            Self::LinkLimitReached => "Link limit reached",
+            Self::UnrestorableRevision => "Revision cannot be restored",
+            Self::Internal => "Internal server error",
+        }
+    }
+
+    fn status(self) -> StatusCode {
+        match self {
+            Self::ValidationFailed => StatusCode::BAD_REQUEST,
+            Self::KeyAlreadyExists | Self::HandleTaken => StatusCode::CONFLICT,
+            Self::LinkLimitReached => StatusCode::FORBIDDEN,
+            Self::UnrestorableRevision => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// An error answered with `detail` as plain text, marked with its problem
+/// type for clients that ask for `application/problem+json`.
+pub fn problem(problem_type: ProblemType, detail: impl Into<String>) -> Response {
+    let mut response = (problem_type.status(), detail.into()).into_response();
+    response.extensions_mut().insert(problem_type);
+    response
+}
+
+/// Like [`problem`], with the detail translated for the client when there
+/// is a catalog for its language.
+pub fn localized_problem(problem_type: ProblemType, message: Message) -> Response {
+    let mut response = problem(problem_type, message.english());
+    response.extensions_mut().insert(message);
+    response
+}
+
+/// The problems of each invalid field, as worded for the client.
+#[derive(Debug, Clone, Serialize)]
+pub struct InvalidFields(pub BTreeMap<&'static str, Vec<String>>);
+
+impl InvalidFields {
+    /// All the problems on one line, for plain-text answers.
+    pub fn summary(&self) -> String {
+        self.0
+            .iter()
+            .map(|(field, problems)| format!("{field}: {}", problems.join(", ")))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// RFC 7807 problem details, with the request id and the invalid fields as
+/// extension members.
+#[derive(Debug, Serialize)]
+struct ProblemDetails<'a> {
+    #[serde(rename = "type")]
+    problem_type: &'a str,
+    title: &'a str,
+    status: u16,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    detail: &'a str,
+    instance: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errors: Option<&'a InvalidFields>,
+}
+
+/// The `{"error": ...}` bodies some layers answer with.
+#[derive(Deserialize)]
+struct JsonError {
+    error: String,
+}
+
+/// Rewrites plain-text and JSON error answers as problem details for clients
+/// whose `Accept` lists `application/problem+json`; everyone else gets them
+/// unchanged. Other errors, like custom not-found pages, are left alone.
+pub async fn negotiate(request: Request, next: Next) -> Response {
+    if !accepts_problem(request.headers()) {
+        return next.run(request).await;
+    }
+    let instance = request.uri().path().to_owned();
+    let id = request_id(request.extensions()).map(String::from);
+    let response = next.run(request).await;
+
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let is_plain_text = content_type.starts_with("text/plain");
+    if !is_plain_text && !content_type.starts_with("application/json") {
+        return response;
+    }
+
+    let problem_type = response.extensions().get::<ProblemType>().copied();
+    let invalid_fields = response.extensions().get::<InvalidFields>().cloned();
+    let (mut parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_ERROR_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(error) => {
+            tracing::warn!(%error, "failed to read error body");
+            Default::default()
+        }
+    };
+    let detail = if is_plain_text {
+        String::from_utf8_lossy(&body).trim_end().to_owned()
+    } else {
+        match serde_json::from_slice::<JsonError>(&body) {
+            Ok(body) => body.error,
+            // some other JSON answer; it is passed on as it was
+            Err(_) => return Response::from_parts(parts, Body::from(body)),
+        }
+    };
+
+    let problem = ProblemDetails {
+        problem_type: problem_type.map_or("about:blank", ProblemType::uri),
+        title: problem_type.map_or_else(
+            || status.canonical_reason().unwrap_or_default(),
+            ProblemType::title,
+        ),
+        status: status.as_u16(),
+        detail: &detail,
+        instance: &instance,
+        request_id: id.as_deref(),
+        errors: invalid_fields.as_ref(),
+    };
+    let body = serde_json::to_vec(&problem).expect("problem details serialize");
+
+    parts.headers.remove(CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Whether a media range of `Accept` names problem details, without `q=0`.
+fn accepts_problem(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|range| {
+            let mut params = range.split(';').map(str::trim);
+            let media_type = params.next().unwrap_or_default();
+            media_type.eq_ignore_ascii_case(PROBLEM_JSON)
+                && !params.any(|param| {
+                    param
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok())
+                        .is_some_and(|q| q == 0.0)
+                })
+        })
+}
@@ -0,0 +1,258 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use http::{
+    header::{ACCEPT_LANGUAGE, CONTENT_LANGUAGE, CONTENT_LENGTH},
+    HeaderMap, HeaderValue,
+};
+
+use crate::validation::FieldErrors;
+
+/// The language of the built-in messages.
+const DEFAULT_LANGUAGE: &str = "en";
+
+/// User-facing messages that can be translated. Their keys name them in
+/// catalogs, and `{name}` in a message is replaced by the argument `name`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageId {
+    KeyTooShort,
+    KeyTooLong,
+    KeyInvalidCharacters,
+    KeyOnlyDots,
+    KeyReserved,
+    KeyAlreadyExists,
+    HandleTaken,
+    LinkLimitReached,
+    TenantLinkLimitReached,
+    NotHttpUrl,
+    NotInFuture,
+}
+
+impl MessageId {
+    const ALL: [Self; 11] = [
+        Self::KeyTooShort,
+        Self::KeyTooLong,
+        Self::KeyInvalidCharacters,
+        Self::KeyOnlyDots,
+        Self::KeyReserved,
+        Self::KeyAlreadyExists,
+        Self::HandleTaken,
+        Self::LinkLimitReached,
+        Self::TenantLinkLimitReached,
+        Self::NotHttpUrl,
+        Self::NotInFuture,
+    ];
+
+    fn key(self) -> &'static str {
+        match self {
+            Self::KeyTooShort => "key_too_short",
+            Self::KeyTooLong => "key_too_long",
+            Self::KeyInvalidCharacters => "key_invalid_characters",
+            Self::KeyOnlyDots => "key_only_dots",
+            Self::KeyReserved => "key_reserved",
+            Self::KeyAlreadyExists => "key_already_exists",
+            Self::HandleTaken => "handle_taken",
+            Self::LinkLimitReached => "link_limit_reached",
+            Self::TenantLinkLimitReached => "tenant_link_limit_reached",
+            Self::NotHttpUrl => "not_http_url",
+            Self::NotInFuture => "not_in_future",
+        }
+    }
+
+    fn english(self) -> &'static str {
+        match self {
+            Self::KeyTooShort => "too short, minimum length of a key is {min}",
+            Self::KeyTooLong => "too long, maximum length of a key is {max}",
+            Self::KeyInvalidCharacters => "invalid characters: {characters}",
+            Self::KeyOnlyDots => "must not be only dots",
+            Self::KeyReserved => "taken by a route of the service",
+            Self::KeyAlreadyExists => "key already exists",
+            Self::HandleTaken => "handle already taken",
+            Self::LinkLimitReached => "link limit of your plan reached",
+            Self::TenantLinkLimitReached => "link limit of your organization reached",
+            Self::NotHttpUrl => "must be an http or https URL",
+            Self::NotInFuture => "must be in the future",
+        }
+    }
+}
+
+/// A message with its arguments, rendered in English unless the client
+/// prefers a language there is a catalog for.
+#[derive(Debug, Clone)]
+pub struct Message {
+    id: MessageId,
+    args: Vec<(&'static str, String)>,
+}
+
+impl Message {
+    pub fn new(id: MessageId) -> Self {
+        Self {
+            id,
+            args: Vec::new(),
+        }
+    }
+
+    pub fn with_arg(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.args.push((name, value.to_string()));
+        self
+    }
+
+    pub fn english(&self) -> String {
+        self.render(self.id.english())
+    }
+
+    fn render(&self, template: &str) -> String {
+        self.args
+            .iter()
+            .fold(template.to_owned(), |text, (name, value)| {
+                text.replace(&format!("{{{name}}}"), value)
+            })
+    }
+}
+
+/// Translations of the messages, by lowercase language tag.
+#[derive(Default)]
+pub struct Catalogs {
+    by_language: HashMap<String, HashMap<String, String>>,
+}
+
+impl Catalogs {
+    /// Reads every `<language>.toml` in `dir`, each a table from message
+    /// keys to translations. Messages a catalog leaves out stay in English.
+    pub fn load(dir: Option<&Path>) -> std::io::Result<Self> {
+        let Some(dir) = dir else {
+            return Ok(Self::default());
+        };
+        let invalid = |path: &Path, reason: String| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid message catalog {}: {reason}", path.display()),
+            )
+        };
+
+        let mut by_language = HashMap::new();
+        for entry in std::fs::read_dir(dir).map_err(|error| {
+            std::io::Error::new(
+                error.kind(),
+                format!("cannot read {}: {error}", dir.display()),
+            )
+        })? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "toml") {
+                continue;
+            }
+            let Some(language) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let messages: HashMap<String, String> =
+                toml::from_str(&std::fs::read_to_string(&path)?)
+                    .map_err(|error| invalid(&path, error.to_string()))?;
+            if let Some(unknown) = messages
+                .keys()
+                .find(|key| MessageId::ALL.iter().all(|id| id.key() != key.as_str()))
+            {
+                return Err(invalid(&path, format!("unknown message `{unknown}`")));
+            }
+            by_language.insert(language.to_ascii_lowercase(), messages);
+        }
+
+        Ok(Self { by_language })
+    }
+
+    /// The most preferred language of `Accept-Language` there is a catalog
+    /// for, trying each range and then its primary language. `None` keeps
+    /// English.
+    fn language(&self, headers: &HeaderMap) -> Option<String> {
+        for language in preferred_languages(headers) {
+            let candidates = [
+                Some(language.as_str()),
+                language.split_once('-').map(|(primary, _)| primary),
+            ];
+            for candidate in candidates.into_iter().flatten() {
+                if candidate == DEFAULT_LANGUAGE {
+                    return None;
+                }
+                if self.by_language.contains_key(candidate) {
+                    return Some(candidate.to_owned());
+                }
+            }
+        }
+        None
+    }
+
+    /// `message` in `language`, unless its catalog leaves it out.
+    fn translate(&self, language: &str, message: &Message) -> Option<String> {
+        self.by_language
+            .get(language)
+            .and_then(|messages| messages.get(message.id.key()))
+            .map(|translation| message.render(translation))
+    }
+}
+
+/// The lowercase language ranges of `Accept-Language`, most preferred
+/// first, leaving out `*` and those with `q=0`.
+fn preferred_languages(headers: &HeaderMap) -> Vec<String> {
+    let mut ranges: Vec<(f32, String)> = headers
+        .get_all(ACCEPT_LANGUAGE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|range| {
+            let mut params = range.split(';').map(str::trim);
+            let language = params.next()?.to_ascii_lowercase();
+            let quality = params
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+            (!language.is_empty() && language != "*" && quality > 0.0)
+                .then_some((quality, language))
+        })
+        .collect();
+    // stable, so equal preferences keep the client's order
+    ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+    ranges.into_iter().map(|(_, language)| language).collect()
+}
+
+/// Replaces the body of answers carrying a [`Message`] or [`FieldErrors`]
+/// with their translation in the client's preferred language, when there
+/// is a catalog for it.
+pub async fn localize(
+    State(catalogs): State<Arc<Catalogs>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(language) = catalogs.language(request.headers()) else {
+        return next.run(request).await;
+    };
+    let mut response = next.run(request).await;
+
+    let text = if let Some(message) = response.extensions().get::<Message>() {
+        match catalogs.translate(&language, message) {
+            Some(text) => text,
+            None => return response,
+        }
+    } else if let Some(errors) = response.extensions().get::<FieldErrors>() {
+        let fields = errors.render(|message| {
+            catalogs
+                .translate(&language, message)
+                .unwrap_or_else(|| message.english())
+        });
+        let text = fields.summary();
+        response.extensions_mut().insert(fields);
+        text
+    } else {
+        return response;
+    };
+
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(CONTENT_LENGTH);
+    if let Ok(language) = HeaderValue::from_str(&language) {
+        parts.headers.insert(CONTENT_LANGUAGE, language);
+    }
+    Response::from_parts(parts, Body::from(text))
+}
@@ -0,0 +1,344 @@
+use axum::async_trait;
+use http::StatusCode;
+
+use crate::{
+    authenthication::AuthenticationError,
+    config::IdentityProviderConfig,
+    jwt::{Claims, JwtValidator},
+    request_id::REQUEST_ID_HEADER,
+    responses::AuthResponse,
+};
+
+/// An identity provider users can sign in with.
+#[async_trait]
+pub trait IdentityProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// The `iss` claim of JWTs this provider issues, if they can be
+    /// validated locally.
+    fn jwt_issuer(&self) -> Option<&str> {
+        None
+    }
+
+    /// Validates a JWT locally. `None` means the token could not be checked
+    /// here and has to be introspected instead.
+    async fn validate_jwt(&self, _token: &str) -> Result<Option<Claims>, AuthenticationError> {
+        Ok(None)
+    }
+
+    /// Resolves the email of the user behind an `Authorization` header value.
+    async fn introspect(
+        &self,
+        authorization: &str,
+        request_id: Option<&str>,
+    ) -> Result<String, AuthenticationError>;
+
+    async fn exchange_token(
+        &self,
+        authorization_code: &str,
+        code_verifier: Option<&str>,
+        request_id: Option<&str>,
+    ) -> Result<AuthResponse, AuthenticationError>;
+
+    /// Revokes an access token. Providers that cannot revoke tokens succeed
+    /// without doing anything.
+    async fn revoke(
+        &self,
+        token: &str,
+        request_id: Option<&str>,
+    ) -> Result<(), AuthenticationError>;
+}
+
+struct Endpoints {
+    userinfo: String,
+    token: String,
+    revocation: Option<String>,
+}
+
+impl Endpoints {
+    /// The agus.dev SSO, which predates discovery support.
+    fn legacy(host: &str) -> Self {
+        Self {
+            userinfo: format!("{host}/profile"),
+            token: format!("{host}/oauth2/token"),
+            revocation: Some(format!("{host}/oauth2/revoke")),
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DiscoveryDocument {
+    userinfo_endpoint: String,
+    token_endpoint: String,
+    revocation_endpoint: Option<String>,
+}
+
+/// An OAuth2 / OIDC provider whose endpoints are discovered at startup.
+pub struct OidcProvider {
+    client: reqwest::Client,
+    name: String,
+    endpoints: Endpoints,
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    jwt_validator: Option<JwtValidator>,
+}
+
+impl OidcProvider {
+    /// Reads the endpoints from the provider's
+    /// `/.well-known/openid-configuration`, falling back to the agus.dev SSO
+    /// paths when the provider does not publish one.
+    pub async fn discover(
+        client: reqwest::Client,
+        config: IdentityProviderConfig,
+    ) -> Result<Self, reqwest::Error> {
+        let host = config.host.trim_end_matches('/');
+        let response = client
+            .get(format!("{host}/.well-known/openid-configuration"))
+            .send()
+            .await?;
+
+        let endpoints = if response.status() == StatusCode::NOT_FOUND {
+            tracing::info!(
+                provider = config.name,
+                "no OIDC discovery document, using the agus.dev SSO endpoints"
+            );
+            Endpoints::legacy(host)
+        } else {
+            let document = response
+                .error_for_status()?
+                .json::<DiscoveryDocument>()
+                .await?;
+            tracing::info!(
+                provider = config.name,
+                ?document,
+                "discovered OIDC endpoints"
+            );
+            Endpoints {
+                userinfo: document.userinfo_endpoint,
+                token: document.token_endpoint,
+                revocation: document.revocation_endpoint,
+            }
+        };
+
+        Ok(Self::new(client, config, endpoints))
+    }
+
+    fn new(client: reqwest::Client, config: IdentityProviderConfig, endpoints: Endpoints) -> Self {
+        Self {
+            jwt_validator: config.jwt.map(|jwt| JwtValidator::new(client.clone(), jwt)),
+            client,
+            name: config.name,
+            endpoints,
+            client_id: config.client_id,
+            client_secret: config.client_secret,
+            redirect_uri: config.redirect_uri,
+        }
+    }
+
+    fn with_request_id(
+        request: reqwest::RequestBuilder,
+        request_id: Option<&str>,
+    ) -> reqwest::RequestBuilder {
+        match request_id {
+            Some(request_id) => request.header(REQUEST_ID_HEADER, request_id),
+            None => request,
+        }
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for OidcProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn jwt_issuer(&self) -> Option<&str> {
+        self.jwt_validator.as_ref().map(JwtValidator::issuer)
+    }
+
+    async fn validate_jwt(&self, token: &str) -> Result<Option<Claims>, AuthenticationError> {
+        match &self.jwt_validator {
+            Some(jwt_validator) => jwt_validator.validate(token).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn introspect(
+        &self,
+        authorization: &str,
+        request_id: Option<&str>,
+    ) -> Result<String, AuthenticationError> {
+        let request = self
+            .client
+            .get(&self.endpoints.userinfo)
+            .header(http::header::AUTHORIZATION, authorization);
+        let result = Self::with_request_id(request, request_id)
+            .send()
+            .await
+            .map_err(|error| AuthenticationError::Internal(Box::new(error)))?;
+
+        #[derive(Debug, serde::Deserialize)]
+        struct Profile {
+            email: String,
+        }
+
+        let response = match result.status() {
+            StatusCode::UNAUTHORIZED => Err(AuthenticationError::Unauthorized),
+            StatusCode::BAD_REQUEST => Err(AuthenticationError::Unauthorized),
+            StatusCode::OK => result
+                .json::<Profile>()
+                .await
+                .map_err(|error| AuthenticationError::Internal(Box::new(error))),
+            _ => {
+                tracing::error!("unexpected status code: {:?}", result.status());
+
+                Err(AuthenticationError::Internal(Box::new(
+                    std::io::Error::other("unexpected status code"),
+                )))
+            }
+        }?;
+
+        Ok(response.email)
+    }
+
+    async fn exchange_token(
+        &self,
+        authorization_code: &str,
+        code_verifier: Option<&str>,
+        request_id: Option<&str>,
+    ) -> Result<AuthResponse, AuthenticationError> {
+        #[derive(Debug, serde::Serialize)]
+        struct TokenRequest<'a> {
+            grant_type: &'a str,
+            client_id: &'a str,
+            client_secret: &'a str,
+            redirect_uri: &'a str,
+            code: &'a str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            code_verifier: Option<&'a str>,
+        }
+        let request = self.client.post(&self.endpoints.token).form(&TokenRequest {
+            grant_type: "authorization_code",
+            client_id: &self.client_id,
+            client_secret: &self.client_secret,
+            redirect_uri: &self.redirect_uri,
+            code: authorization_code,
+            code_verifier,
+        });
+        let result = Self::with_request_id(request, request_id)
+            .send()
+            .await
+            .map_err(|error| AuthenticationError::Internal(Box::new(error)))?;
+
+        #[derive(Debug, serde::Deserialize)]
+        struct TokenResponse {
+            access_token: String,
+            token_type: String,
+        }
+        let response = match result.status() {
+            StatusCode::BAD_REQUEST => Err(AuthenticationError::Unauthorized),
+            StatusCode::OK => result
+                .json::<TokenResponse>()
+                .await
+                .map_err(|error| AuthenticationError::Internal(Box::new(error))),
+            _ => {
+                tracing::error!("unexpected status code: {:?}", result.status());
+
+                Err(AuthenticationError::Internal(Box::new(
+                    std::io::Error::other("unexpected status code"),
+                )))
+            }
+        }?;
+
+        Ok(AuthResponse::new(
+            response.access_token,
+            response.token_type,
+        ))
+    }
+
+    async fn revoke(
+        &self,
+        token: &str,
+        request_id: Option<&str>,
+    ) -> Result<(), AuthenticationError> {
+        let Some(revocation_endpoint) = &self.endpoints.revocation else {
+            tracing::debug!(provider = self.name, "no revocation endpoint, skipping");
+            return Ok(());
+        };
+
+        #[derive(Debug, serde::Serialize)]
+        struct RevokeRequest<'a> {
+            token: &'a str,
+            token_type_hint: &'a str,
+            client_id: &'a str,
+            client_secret: &'a str,
+        }
+        let request = self.client.post(revocation_endpoint).form(&RevokeRequest {
+            token,
+            token_type_hint: "access_token",
+            client_id: &self.client_id,
+            client_secret: &self.client_secret,
+        });
+        let result = Self::with_request_id(request, request_id)
+            .send()
+            .await
+            .map_err(|error| AuthenticationError::Internal(Box::new(error)))?;
+
+        match result.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::NOT_FOUND
+            | StatusCode::METHOD_NOT_ALLOWED
+            | StatusCode::NOT_IMPLEMENTED => {
+                tracing::debug!(provider = self.name, "no revocation endpoint, skipping");
+                Ok(())
+            }
+            status => {
+                tracing::error!("unexpected status code: {:?}", status);
+
+                Err(AuthenticationError::Internal(Box::new(
+                    std::io::Error::other("unexpected status code"),
+                )))
+            }
+        }
+    }
+}
+
+/// Accepts `Authorization: Dev <email>` as that user, for local development
+/// and integration tests without an SSO.
+pub struct DevProvider;
+
+#[async_trait]
+impl IdentityProvider for DevProvider {
+    fn name(&self) -> &str {
+        "dev"
+    }
+
+    async fn introspect(
+        &self,
+        authorization: &str,
+        _request_id: Option<&str>,
+    ) -> Result<String, AuthenticationError> {
+        match authorization.strip_prefix("Dev ").map(str::trim) {
+            Some(email) if !email.is_empty() => Ok(email.to_string()),
+            _ => Err(AuthenticationError::Unauthorized),
+        }
+    }
+
+    async fn exchange_token(
+        &self,
+        _authorization_code: &str,
+        _code_verifier: Option<&str>,
+        _request_id: Option<&str>,
+    ) -> Result<AuthResponse, AuthenticationError> {
+        Err(AuthenticationError::Unauthorized)
+    }
+
+    async fn revoke(
+        &self,
+        _token: &str,
+        _request_id: Option<&str>,
+    ) -> Result<(), AuthenticationError> {
+        Ok(())
+    }
+}
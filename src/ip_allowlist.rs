@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::StatusCode;
+use ipnet::IpNet;
+
+use crate::{client_ip::client_ip, config::AdminAllowlistConfig};
+
+pub const ADMIN_PATH_PREFIX: &str = "/admin";
+
+/// Client IP ranges allowed to reach the admin routes, or every route the
+/// middleware is layered on.
+pub struct IpAllowlist {
+    cidrs: Vec<IpNet>,
+    all_routes: bool,
+}
+
+impl IpAllowlist {
+    pub fn new(config: AdminAllowlistConfig) -> Self {
+        Self {
+            cidrs: config.cidrs,
+            all_routes: config.all_management,
+        }
+    }
+
+    fn applies_to(&self, path: &str) -> bool {
+        self.all_routes
+            || path
+                .strip_prefix(ADMIN_PATH_PREFIX)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
+
+pub async fn restrict(
+    State(allowlist): State<Arc<IpAllowlist>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !allowlist.applies_to(request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    match client_ip(request.extensions()) {
+        Some(ip) if allowlist.cidrs.iter().any(|cidr| cidr.contains(&ip)) => {
+            next.run(request).await
+        }
+        client_ip => {
+            tracing::warn!(
+                ?client_ip,
+                path = request.uri().path(),
+                "client IP not allowed"
+            );
+            (StatusCode::FORBIDDEN, "forbidden").into_response()
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use tokio::sync::RwLock;
+
+use crate::{authenthication::AuthenticationError, config::JwtConfig};
+
+// Keys are refetched at most this often when a token names an unknown key id,
+// so a stream of forged tokens cannot hammer the JWKS endpoint.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_KEYS_AGE: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Claims {
+    /// Absent from client-credentials tokens, which act for no user.
+    pub email: Option<String>,
+    pub sub: Option<String>,
+    /// The OAuth client the token was issued to (RFC 9068).
+    pub client_id: Option<String>,
+    /// Expiry as a unix timestamp in seconds.
+    pub exp: u64,
+}
+
+impl Claims {
+    /// The client id of a client-credentials token, whose subject is the
+    /// client itself.
+    pub fn service_client_id(&self) -> Option<&str> {
+        match (&self.sub, &self.client_id) {
+            (Some(sub), Some(client_id)) if sub == client_id => Some(client_id),
+            _ => None,
+        }
+    }
+}
+
+struct CachedKeys {
+    keys: JwkSet,
+    fetched_at: Instant,
+}
+
+/// Validates JWT access tokens locally against the SSO's published keys.
+pub struct JwtValidator {
+    client: reqwest::Client,
+    config: JwtConfig,
+    keys: RwLock<Option<CachedKeys>>,
+}
+
+impl JwtValidator {
+    pub fn new(client: reqwest::Client, config: JwtConfig) -> Self {
+        Self {
+            client,
+            config,
+            keys: RwLock::new(None),
+        }
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.config.issuer
+    }
+
+    /// Returns the token's claims if it is a valid JWT, or `None` if the token
+    /// is not a JWT at all and has to be introspected instead.
+    pub async fn validate(&self, token: &str) -> Result<Option<Claims>, AuthenticationError> {
+        if token.split('.').count() != 3 {
+            return Ok(None);
+        }
+        let Ok(header) = jsonwebtoken::decode_header(token) else {
+            return Ok(None);
+        };
+
+        // JWKS only carry public keys, so a symmetric algorithm can never be
+        // legitimate here.
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(AuthenticationError::Unauthorized);
+        }
+        let kid = header.kid.ok_or(AuthenticationError::Unauthorized)?;
+        let key = self.decoding_key(&kid).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.config.issuer]);
+        match &self.config.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        let data = jsonwebtoken::decode::<Claims>(token, &key, &validation).map_err(|error| {
+            tracing::debug!(%error, "rejected JWT");
+            AuthenticationError::Unauthorized
+        })?;
+
+        Ok(Some(data.claims))
+    }
+
+    async fn decoding_key(&self, kid: &str) -> Result<DecodingKey, AuthenticationError> {
+        {
+            let keys = self.keys.read().await;
+            if let Some(cached) = keys.as_ref() {
+                let fresh = cached.fetched_at.elapsed() < MAX_KEYS_AGE;
+                match cached.keys.find(kid) {
+                    Some(jwk) if fresh => return key_from_jwk(jwk),
+                    None if cached.fetched_at.elapsed() < MIN_REFRESH_INTERVAL => {
+                        return Err(AuthenticationError::Unauthorized)
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut keys = self.keys.write().await;
+        // another request may have refreshed the keys while we waited for the lock
+        let refreshed_recently = matches!(
+            keys.as_ref(),
+            Some(cached) if cached.fetched_at.elapsed() < MIN_REFRESH_INTERVAL
+        );
+        if !refreshed_recently {
+            *keys = Some(CachedKeys {
+                keys: self.fetch_keys().await?,
+                fetched_at: Instant::now(),
+            });
+        }
+
+        keys.as_ref()
+            .and_then(|cached| cached.keys.find(kid))
+            .ok_or(AuthenticationError::Unauthorized)
+            .and_then(key_from_jwk)
+    }
+
+    async fn fetch_keys(&self) -> Result<JwkSet, AuthenticationError> {
+        tracing::info!(url = self.config.jwks_url, "fetching JWKS");
+        let response = self
+            .client
+            .get(&self.config.jwks_url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|error| AuthenticationError::Internal(Box::new(error)))?;
+
+        response
+            .json::<JwkSet>()
+            .await
+            .map_err(|error| AuthenticationError::Internal(Box::new(error)))
+    }
+}
+
+/// Reads the `iss` claim without checking the signature, only to pick which
+/// provider should validate the token.
+pub fn unverified_issuer(token: &str) -> Option<String> {
+    #[derive(serde::Deserialize)]
+    struct Issuer {
+        iss: String,
+    }
+
+    let header = jsonwebtoken::decode_header(token).ok()?;
+    let mut validation = Validation::new(header.alg);
+    validation.insecure_disable_signature_validation();
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    validation.required_spec_claims.clear();
+
+    jsonwebtoken::decode::<Issuer>(token, &DecodingKey::from_secret(&[]), &validation)
+        .ok()
+        .map(|data| data.claims.iss)
+}
+
+fn key_from_jwk(jwk: &jsonwebtoken::jwk::Jwk) -> Result<DecodingKey, AuthenticationError> {
+    DecodingKey::from_jwk(jwk).map_err(|error| AuthenticationError::Internal(Box::new(error)))
+}
@@ -0,0 +1,265 @@
+use rand::seq::SliceRandom;
+
+use crate::{
+    config::{KeyGenerationConfig, KeyGenerationMode},
+    reload::Reloadable,
+};
+
+/// Words generated keys must not contain, matched case-insensitively.
+pub const DEFAULT_BLOCKED_WORDS: &[&str] = &[
+    "anal", "anus", "ass", "bitch", "cock", "cum", "cunt", "dick", "fag", "fuck", "kkk", "nazi",
+    "nigg", "penis", "piss", "porn", "rape", "sex", "shit", "slut", "tit", "twat", "whore",
+];
+
+/// Characters easily mistaken for one another when a key is read aloud or
+/// copied by hand.
+pub const DEFAULT_CONFUSABLE_CHARS: &str = "0Oo1lI";
+
+pub const DEFAULT_ALPHABET: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+pub const DEFAULT_KEY_LENGTH: usize = 8;
+
+// A key containing a blocked word is drawn again; past this many draws the
+// blocked words are probably too broad, and the last draw is used anyway.
+const MAX_DRAWS: usize = 100;
+
+const SUGGESTION_SUFFIX_LENGTH: usize = 4;
+
+// Long enough to recognise the target, short enough to stay a short link.
+const MAX_SUGGESTION_BASE_LENGTH: usize = 24;
+
+// A prime, so it is coprime with every alphabet size and multiplying by it
+// shuffles the keys of each length without two ids ever sharing one.
+const OBFUSCATION_MULTIPLIER: u128 = 2_147_483_647;
+
+/// Draws random keys from a curated alphabet, skipping any that spell out a
+/// blocked word.
+pub struct KeyGenerator {
+    mode: KeyGenerationMode,
+    alphabet: Vec<char>,
+    length: usize,
+    prefix: String,
+    obfuscate: bool,
+    /// Holds the blocked words, which can change while running.
+    reloadable: Reloadable,
+}
+
+impl KeyGenerator {
+    pub fn new(config: &KeyGenerationConfig, reloadable: Reloadable) -> Self {
+        Self {
+            mode: config.mode,
+            alphabet: alphabet(&config.alphabet, &config.confusable_chars),
+            length: config.length,
+            prefix: config.prefix.clone(),
+            obfuscate: config.obfuscate,
+            reloadable,
+        }
+    }
+
+    pub fn mode(&self) -> KeyGenerationMode {
+        self.mode
+    }
+
+    /// The key for the `id`th value of the key sequence: the id written in
+    /// the alphabet's base with as few digits as possible, or `None` when
+    /// that spells out a blocked word. With obfuscation the digits are
+    /// shuffled so consecutive ids do not give away how many links exist.
+    pub fn sequential(&self, id: u64) -> Option<String> {
+        let base = self.alphabet.len() as u128;
+        let mut digits = 1;
+        let mut capacity = base;
+        while capacity <= id as u128 {
+            digits += 1;
+            capacity *= base;
+        }
+
+        let mut value = if self.obfuscate {
+            id as u128 * OBFUSCATION_MULTIPLIER % capacity
+        } else {
+            id as u128
+        };
+        let mut encoded = vec![self.alphabet[0]; digits];
+        for digit in encoded.iter_mut().rev() {
+            *digit = self.alphabet[(value % base) as usize];
+            value /= base;
+        }
+
+        let encoded: String = encoded.into_iter().collect();
+        if self.is_blocked(&encoded) {
+            return None;
+        }
+        Some(format!("{}{encoded}", self.prefix))
+    }
+
+    pub fn generate(&self) -> String {
+        let mut rng = rand::thread_rng();
+        let mut random = String::new();
+        for _ in 0..MAX_DRAWS {
+            random = (0..self.length)
+                .map(|_| {
+                    *self
+                        .alphabet
+                        .choose(&mut rng)
+                        .expect("alphabet is not empty")
+                })
+                .collect();
+            if !self.is_blocked(&random) {
+                break;
+            }
+        }
+
+        if self.is_blocked(&random) {
+            tracing::warn!("every generated key contained a blocked word, using the last one");
+        }
+        // the prefix is the operator's choice, so only the random part is screened
+        format!("{}{random}", self.prefix)
+    }
+
+    /// Keys hinting at `target`: its host without the top-level domain,
+    /// alone and followed by the last path segment, each also with a random
+    /// suffix in case the plain one is taken.
+    pub fn suggestions(&self, target: &url::Url) -> Vec<String> {
+        let host = target
+            .host_str()
+            .unwrap_or_default()
+            .trim_start_matches("www.");
+        let host = host.rsplit_once('.').map_or(host, |(name, _)| name);
+        let path = target
+            .path_segments()
+            .and_then(|mut segments| segments.rfind(|segment| !segment.is_empty()))
+            .map(|segment| segment.split_once('.').map_or(segment, |(name, _)| name));
+
+        let mut bases = vec![slug(host)];
+        if let Some(path) = path {
+            bases.push(slug(&format!("{host}-{path}")));
+        }
+        bases.retain(|base| !base.is_empty() && !self.is_blocked(base));
+
+        let mut rng = rand::thread_rng();
+        let mut suggestions = Vec::new();
+        for base in bases {
+            let suffix: String = (0..SUGGESTION_SUFFIX_LENGTH)
+                .map(|_| {
+                    *self
+                        .alphabet
+                        .choose(&mut rng)
+                        .expect("alphabet is not empty")
+                })
+                .collect();
+            if !self.is_blocked(&suffix) {
+                suggestions.push(format!("{base}-{suffix}"));
+            }
+            suggestions.push(base);
+        }
+        suggestions.sort_by_key(String::len);
+        suggestions.dedup();
+        suggestions
+    }
+
+    fn is_blocked(&self, key: &str) -> bool {
+        self.reloadable.is_blocked(key)
+    }
+}
+
+/// `value` lowercased, with runs of anything not allowed in a key turned into
+/// a single `-`.
+fn slug(value: &str) -> String {
+    let mut slug = String::new();
+    for c in value.chars().map(|c| c.to_ascii_lowercase()) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(MAX_SUGGESTION_BASE_LENGTH);
+    slug.trim_end_matches('-').to_string()
+}
+
+/// The distinct characters of `alphabet`, minus the confusable ones.
+pub fn alphabet(alphabet: &str, confusable_chars: &str) -> Vec<char> {
+    let mut chars: Vec<char> = alphabet
+        .chars()
+        .filter(|c| !confusable_chars.contains(*c))
+        .collect();
+    chars.sort_unstable();
+    chars.dedup();
+    chars
+}
+
+impl Default for KeyGenerator {
+    fn default() -> Self {
+        Self::new(&KeyGenerationConfig::default(), Reloadable::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generator(alphabet: &str, obfuscate: bool) -> KeyGenerator {
+        KeyGenerator::new(
+            &KeyGenerationConfig {
+                alphabet: String::from(alphabet),
+                length: 6,
+                prefix: String::from("x-"),
+                obfuscate,
+                ..KeyGenerationConfig::default()
+            },
+            Reloadable::default(),
+        )
+    }
+
+    #[test]
+    fn leaves_confusable_and_repeated_characters_out() {
+        assert_eq!(
+            alphabet("ba0O1lIab", DEFAULT_CONFUSABLE_CHARS),
+            vec!['a', 'b']
+        );
+    }
+
+    #[test]
+    fn generates_prefixed_keys_without_blocked_words() {
+        let generator = generator("as", false);
+        for _ in 0..50 {
+            let key = generator.generate();
+            let random = key.strip_prefix("x-").unwrap();
+            assert_eq!(random.len(), 6);
+            assert!(!random.contains("ass"), "{key}");
+        }
+    }
+
+    #[test]
+    fn writes_sequential_keys_in_the_alphabet_base() {
+        let generator = generator("as", false);
+        assert_eq!(generator.sequential(0).as_deref(), Some("x-a"));
+        assert_eq!(generator.sequential(2).as_deref(), Some("x-sa"));
+        assert_eq!(generator.sequential(4).as_deref(), Some("x-saa"));
+        // 1011, "sass"
+        assert_eq!(generator.sequential(11), None);
+    }
+
+    #[test]
+    fn shuffles_sequential_keys_without_sharing_one() {
+        let generator = generator("abcdef", true);
+        let mut keys: Vec<String> = (6..36)
+            .map(|id| generator.sequential(id).unwrap())
+            .collect();
+        assert_ne!(keys[1], "x-bb");
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), 30);
+    }
+
+    #[test]
+    fn suggests_keys_from_the_target() {
+        let target = url::Url::parse("https://www.Example.com/docs/Getting_Started.html").unwrap();
+        let suggestions = KeyGenerator::default().suggestions(&target);
+        assert!(suggestions.contains(&String::from("example")));
+        assert!(suggestions.contains(&String::from("example-getting-started")));
+        assert_eq!(suggestions.len(), 4);
+        assert!(suggestions
+            .windows(2)
+            .all(|pair| pair[0].len() <= pair[1].len()));
+    }
+}
@@ -1,11 +1,96 @@
-use std::error::Error;
+use std::{error::Error, time::Duration};
+
+use redis::{aio::ConnectionLike, AsyncCommands, Cmd, Pipeline, RedisFuture, Value};
+
+use crate::memory_kvs::MemoryKvs;
 
-pub type KvsPool = deadpool_redis::Pool;
 pub type KvsPoolError = deadpool_redis::PoolError;
 pub type KvsError = redis::RedisError;
 
+/// Redis, or with `memory://` an in-process store so the service runs
+/// locally with just Postgres.
+pub enum KvsPool {
+    Redis(deadpool_redis::Pool),
+    Memory(MemoryKvs),
+}
+
+pub enum KvsConnection {
+    Redis(deadpool_redis::Connection),
+    Memory(MemoryKvs),
+}
+
+impl KvsPool {
+    pub async fn get(&self) -> Result<KvsConnection, KvsPoolError> {
+        match self {
+            Self::Redis(pool) => pool.get().await.map(KvsConnection::Redis),
+            Self::Memory(kvs) => Ok(KvsConnection::Memory(kvs.clone())),
+        }
+    }
+}
+
+impl ConnectionLike for KvsConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            Self::Redis(conn) => conn.req_packed_command(cmd),
+            Self::Memory(kvs) => Box::pin(std::future::ready(kvs.query(cmd))),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            Self::Redis(conn) => conn.req_packed_commands(cmd, offset, count),
+            Self::Memory(kvs) => {
+                let values = kvs.query_all(cmd.cmd_iter()).map(|values| {
+                    // a transaction is asked for just the reply to EXEC,
+                    // past those to MULTI and each queued command
+                    if offset > 0 {
+                        vec![Value::Array(values)]
+                    } else {
+                        values
+                    }
+                });
+                Box::pin(std::future::ready(values))
+            }
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            Self::Redis(conn) => conn.get_db(),
+            Self::Memory(_) => 0,
+        }
+    }
+}
+
 pub fn kvs_pool(host: &str) -> Result<KvsPool, Box<dyn Error>> {
+    if host.starts_with("memory:") {
+        tracing::warn!("using the in-memory KVS, which is not shared between instances");
+        return Ok(KvsPool::Memory(MemoryKvs::default()));
+    }
+
     let cfg = deadpool_redis::Config::from_url(host);
     cfg.create_pool(Some(deadpool_redis::Runtime::Tokio1))
+        .map(KvsPool::Redis)
         .map_err(Into::into)
 }
+
+/// How long `key` has left to live, or `None` when it is missing or never
+/// expires.
+pub async fn ttl_remaining(
+    conn: &mut KvsConnection,
+    key: &str,
+) -> Result<Option<Duration>, KvsError> {
+    // TTL answers -2 for a missing key and -1 for one without an expiry, so
+    // only positive values count
+    let ttl: i64 = conn.ttl(key).await?;
+
+    Ok(u64::try_from(ttl)
+        .ok()
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs))
+}
@@ -0,0 +1,74 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use http::{
+    header::{CONTENT_LENGTH, RETRY_AFTER},
+    HeaderValue, StatusCode,
+};
+use tokio::sync::Semaphore;
+
+fn json_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+/// Answers 408 when a request, reading its body included, takes longer than
+/// `duration`, so a client trickling its body cannot hold a worker forever.
+pub async fn timeout(State(duration): State<Duration>, request: Request, next: Next) -> Response {
+    match tokio::time::timeout(duration, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => json_error(StatusCode::REQUEST_TIMEOUT, "request timed out"),
+    }
+}
+
+/// Serves at most as many requests at once as `permits` has, and answers 503
+/// straight away beyond that instead of queueing, so a spike cannot pile up
+/// waiting for database connections.
+pub async fn shed_load(
+    State(permits): State<Arc<Semaphore>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Ok(_permit) = permits.try_acquire() else {
+        let mut response = json_error(StatusCode::SERVICE_UNAVAILABLE, "server is busy");
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from_static("1"));
+        return response;
+    };
+
+    next.run(request).await
+}
+
+/// Refuses bodies over `max_bytes` with 413. A declared `Content-Length` is
+/// refused before reading anything; chunked bodies are cut off by the
+/// extractors' `DefaultBodyLimit`, whose plain-text rejection is replaced
+/// here so both cases look the same.
+pub async fn limit_body(State(max_bytes): State<usize>, request: Request, next: Next) -> Response {
+    let declared = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if declared.is_some_and(|declared| declared > max_bytes) {
+        return too_large(max_bytes);
+    }
+
+    let response = next.run(request).await;
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        return too_large(max_bytes);
+    }
+
+    response
+}
+
+fn too_large(max_bytes: usize) -> Response {
+    json_error(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        &format!("request body larger than {max_bytes} bytes"),
+    )
+}
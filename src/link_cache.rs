@@ -0,0 +1,125 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use uuid::Uuid;
+
+use crate::{
+    config::LinkCacheConfig,
+    responses::{CacheStats, UrlRedirect},
+};
+
+/// Channel the database notifies with the id of every changed link.
+pub const LINK_CHANGES_CHANNEL: &str = "link_changes";
+
+struct Entry {
+    link: UrlRedirect,
+    valid_until: Instant,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<String, Entry>,
+    /// Bumped on every eviction, so a lookup that raced with a change does
+    /// not cache what it read from before the change.
+    generation: u64,
+}
+
+/// Resolved links by the key they were looked up with, so a link reached
+/// through aliases may be cached several times. Unknown keys are not cached.
+pub struct LinkCache {
+    entries: Mutex<Entries>,
+    capacity: usize,
+    ttl: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl LinkCache {
+    pub fn new(config: &LinkCacheConfig) -> Self {
+        Self {
+            entries: Mutex::default(),
+            capacity: config.capacity,
+            ttl: config.ttl,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<UrlRedirect> {
+        let mut entries = self.entries.lock().unwrap();
+        let link = match entries.by_key.get(key) {
+            Some(entry) if entry.valid_until > Instant::now() => Some(entry.link.clone()),
+            Some(_) => {
+                entries.by_key.remove(key);
+                None
+            }
+            None => None,
+        };
+        let counter = match link {
+            Some(_) => &self.hits,
+            None => &self.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        link
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats::new(
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Taken before looking a link up, to be handed back to [`Self::insert`].
+    pub fn generation(&self) -> u64 {
+        self.entries.lock().unwrap().generation
+    }
+
+    pub fn insert(&self, key: String, link: UrlRedirect, generation: u64) {
+        let now = Instant::now();
+        // an expiring link must not outlive its expiry in here
+        let until_expiry = link.expires_at().map(|expires_at| {
+            (expires_at.to_utc() - chrono::Utc::now())
+                .to_std()
+                .unwrap_or_default()
+        });
+        let ttl = until_expiry.map_or(self.ttl, |until_expiry| until_expiry.min(self.ttl));
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.generation != generation {
+            return;
+        }
+        if entries.by_key.len() >= self.capacity {
+            entries.by_key.retain(|_, entry| entry.valid_until > now);
+        }
+        // when still full, the link is simply looked up again next time
+        if entries.by_key.len() < self.capacity {
+            entries.by_key.insert(
+                key,
+                Entry {
+                    link,
+                    valid_until: now + ttl,
+                },
+            );
+        }
+    }
+
+    /// Drops every key resolving to the link.
+    pub fn evict(&self, id: Uuid) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.generation += 1;
+        entries.by_key.retain(|_, entry| entry.link.id != id);
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.generation += 1;
+        entries.by_key.clear();
+    }
+}
@@ -0,0 +1,448 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use http::header::{CONTENT_TYPE, LOCATION};
+use redis::AsyncCommands;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+
+use crate::{kvs::KvsPool, rate_limit::RateLimitError, responses::CacheStats};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REDIRECTS: usize = 3;
+// Open Graph tags live in the head, well within this.
+const MAX_BODY_BYTES: usize = 512 * 1024;
+const CACHE_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// What a target page says about itself in its Open Graph tags.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Preview {
+    pub title: Option<String>,
+    pub site_name: Option<String>,
+    pub image: Option<String>,
+}
+
+/// Fetches previews of link targets, caching them in the KVS so a popular
+/// link does not send every unfurl to its target.
+pub struct LinkPreviews {
+    client: reqwest::Client,
+    kvs_pool: Arc<KvsPool>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl LinkPreviews {
+    pub fn new(kvs_pool: Arc<KvsPool>) -> reqwest::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            // followed by hand, so every hop's host is checked
+            .redirect(reqwest::redirect::Policy::none())
+            // a proxy would be resolved and connected to in place of the target
+            .no_proxy()
+            .dns_resolver(Arc::new(PublicResolver))
+            .build()?;
+
+        Ok(Self {
+            client,
+            kvs_pool,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats::new(
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    /// The preview of `target`. A target that cannot be fetched gets an empty
+    /// preview, which is cached like any other.
+    pub async fn get(&self, target: &str) -> Result<Preview, RateLimitError> {
+        let key = cache_key(target);
+        let mut conn = self.kvs_pool.get().await?;
+        let cached: Option<String> = conn.get(&key).await?;
+        if let Some(preview) = cached.and_then(|cached| serde_json::from_str(&cached).ok()) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(preview);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let preview = match self.fetch(target).await {
+            Ok(preview) => preview,
+            Err(error) => {
+                tracing::debug!(%error, target, "cannot fetch link preview");
+                Preview::default()
+            }
+        };
+        let value = serde_json::to_string(&preview).expect("previews always serialize");
+        conn.set_ex::<_, _, ()>(&key, value, CACHE_TTL_SECS).await?;
+
+        Ok(preview)
+    }
+
+    async fn fetch(&self, target: &str) -> Result<Preview, Box<dyn std::error::Error>> {
+        let mut url = url::Url::parse(target)?;
+        for _ in 0..=MAX_REDIRECTS {
+            // hosts that are names are checked by `PublicResolver` as they
+            // are connected to
+            if !matches!(url.scheme(), "http" | "https") || !is_public_host(&url) {
+                return Err(format!("refusing to fetch {url}").into());
+            }
+
+            let mut response = self.client.get(url.clone()).send().await?;
+            if response.status().is_redirection() {
+                let location = response
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .ok_or("redirect without a location")?;
+                url = url.join(location)?;
+                continue;
+            }
+
+            let is_html = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|content_type| content_type.to_str().ok())
+                .is_some_and(|content_type| content_type.starts_with("text/html"));
+            if !response.status().is_success() || !is_html {
+                return Ok(Preview::default());
+            }
+
+            let mut body = Vec::new();
+            while let Some(chunk) = response.chunk().await? {
+                body.extend_from_slice(&chunk);
+                if body.len() >= MAX_BODY_BYTES {
+                    break;
+                }
+            }
+
+            let mut preview = parse(&String::from_utf8_lossy(&body));
+            preview.image = preview
+                .image
+                .and_then(|image| url.join(&image).ok())
+                .map(String::from);
+            return Ok(preview);
+        }
+
+        Err("too many redirects".into())
+    }
+}
+
+fn cache_key(target: &str) -> String {
+    format!("link-preview:{target}")
+}
+
+/// Whether the host of `url`, when it is an address, is on the public
+/// internet. Names are left to [`PublicResolver`].
+fn is_public_host(url: &url::Url) -> bool {
+    match url.host() {
+        Some(url::Host::Ipv4(ip)) => is_global_v4(ip),
+        Some(url::Host::Ipv6(ip)) => is_global_v6(ip),
+        Some(url::Host::Domain(_)) => true,
+        None => false,
+    }
+}
+
+/// Resolves names only to addresses on the public internet, failing for
+/// names with any other, so link owners cannot use previews to reach our
+/// internal network. As the resolver of the client, the addresses it checks
+/// are the ones connected to, which a second lookup could not guarantee
+/// against a name that resolves differently each time.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if addresses.is_empty() || !addresses.iter().all(|address| is_global(address.ip())) {
+                return Err(format!("{} is not on the public internet", name.as_str()).into());
+            }
+            let addresses: Addrs = Box::new(addresses.into_iter());
+            Ok(addresses)
+        })
+    }
+}
+
+fn is_global(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_global_v4(ip),
+        IpAddr::V6(ip) => is_global_v6(ip),
+    }
+}
+
+fn is_global_v4(ip: Ipv4Addr) -> bool {
+    let [first, second, third, _] = ip.octets();
+    // 0.0.0.0/8, "this network"
+    let this_network = first == 0;
+    // 100.64.0.0/10, carrier-grade NAT
+    let shared = first == 100 && (64..128).contains(&second);
+    // 192.0.0.0/24, protocol assignments
+    let protocol_assignments = first == 192 && second == 0 && third == 0;
+    // 198.18.0.0/15, benchmarking
+    let benchmarking = first == 198 && (18..20).contains(&second);
+    // 240.0.0.0/4, reserved, broadcast included
+    let reserved = first >= 240;
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || this_network
+        || shared
+        || protocol_assignments
+        || benchmarking
+        || reserved)
+}
+
+fn is_global_v6(ip: Ipv6Addr) -> bool {
+    // ::ffff:0:0/96, IPv4 addresses as seen by IPv6 sockets
+    if let Some(ip) = ip.to_ipv4_mapped() {
+        return is_global_v4(ip);
+    }
+    let segments = ip.segments();
+    // 2002::/16, 6to4, reaching the IPv4 address it embeds
+    if segments[0] == 0x2002 {
+        let [a, b] = segments[1].to_be_bytes();
+        let [c, d] = segments[2].to_be_bytes();
+        return is_global_v4(Ipv4Addr::new(a, b, c, d));
+    }
+    // ::/96, the deprecated IPv4-compatible addresses, and the rest of ::/8
+    let reserved = segments[0] == 0;
+    // 64:ff9b::/96 and 64:ff9b:1::/48, NAT64 to any IPv4 address
+    let nat64 = segments[0] == 0x64 && (segments[1] == 0xff9b || segments[1] == 0xff9c);
+    // 100::/64, discard
+    let discard = segments[..4] == [0x100, 0, 0, 0];
+    // 2001::/32 Teredo, and 2001:db8::/32 documentation
+    let teredo = segments[0] == 0x2001 && segments[1] == 0;
+    let documentation = segments[0] == 0x2001 && segments[1] == 0xdb8;
+    let unique_local = segments[0] & 0xfe00 == 0xfc00;
+    let link_local = segments[0] & 0xffc0 == 0xfe80;
+    !(ip.is_multicast()
+        || reserved
+        || nat64
+        || discard
+        || teredo
+        || documentation
+        || unique_local
+        || link_local)
+}
+
+/// Reads the Open Graph tags, and the `<title>` for pages without `og:title`.
+fn parse(html: &str) -> Preview {
+    let head = html.find("</head>").map_or(html, |end| &html[..end]);
+    // lowercasing ASCII keeps byte offsets, so positions carry over to `head`
+    let lower = head.to_ascii_lowercase();
+
+    let mut preview = Preview::default();
+    let mut position = 0;
+    while let Some(start) = lower[position..]
+        .find("<meta")
+        .map(|start| position + start)
+    {
+        let end = lower[start..]
+            .find('>')
+            .map_or(lower.len(), |end| start + end);
+        let tag = &head[start..end];
+        let lower_tag = &lower[start..end];
+        position = end;
+
+        let property = attribute(tag, lower_tag, "property")
+            .or_else(|| attribute(tag, lower_tag, "name"))
+            .map(str::to_ascii_lowercase);
+        let Some(content) = attribute(tag, lower_tag, "content").map(decode_entities) else {
+            continue;
+        };
+        let field = match property.as_deref() {
+            Some("og:title") => &mut preview.title,
+            Some("og:site_name") => &mut preview.site_name,
+            Some("og:image") => &mut preview.image,
+            _ => continue,
+        };
+        field.get_or_insert(content);
+    }
+
+    if preview.title.is_none() {
+        preview.title = lower.find("<title").and_then(|start| {
+            let start = start + lower[start..].find('>')? + 1;
+            let end = start + lower[start..].find("</title")?;
+            Some(decode_entities(head[start..end].trim()))
+        });
+    }
+    preview
+}
+
+fn attribute<'a>(tag: &'a str, lower_tag: &str, name: &str) -> Option<&'a str> {
+    for quote in ['"', '\''] {
+        let pattern = format!(" {name}={quote}");
+        if let Some(start) = lower_tag.find(&pattern) {
+            let start = start + pattern.len();
+            let end = start + tag[start..].find(quote)?;
+            return Some(&tag[start..end]);
+        }
+    }
+    None
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v4(address: &str) -> bool {
+        is_global(IpAddr::V4(address.parse().unwrap()))
+    }
+
+    fn v6(address: &str) -> bool {
+        is_global(IpAddr::V6(address.parse().unwrap()))
+    }
+
+    #[test]
+    fn accepts_public_addresses() {
+        assert!(v4("93.184.215.14"));
+        assert!(v4("8.8.8.8"));
+        assert!(v6("2606:2800:21f:cb07:6820:80da:af6b:8b2c"));
+        assert!(v6("::ffff:8.8.8.8"));
+        assert!(v6("2002:808:808::1"));
+    }
+
+    #[test]
+    fn refuses_private_and_local_v4() {
+        assert!(!v4("10.1.2.3"));
+        assert!(!v4("172.16.0.1"));
+        assert!(!v4("192.168.1.1"));
+        assert!(!v4("127.0.0.1"));
+        assert!(!v4("169.254.169.254"));
+    }
+
+    #[test]
+    fn refuses_this_network() {
+        assert!(!v4("0.0.0.0"));
+        assert!(!v4("0.1.2.3"));
+    }
+
+    #[test]
+    fn refuses_carrier_grade_nat() {
+        assert!(!v4("100.64.0.1"));
+        assert!(!v4("100.127.255.254"));
+        assert!(v4("100.128.0.1"));
+    }
+
+    #[test]
+    fn refuses_multicast() {
+        assert!(!v4("224.0.0.1"));
+        assert!(!v4("239.255.255.250"));
+        assert!(!v6("ff02::1"));
+    }
+
+    #[test]
+    fn refuses_reserved_and_broadcast() {
+        assert!(!v4("240.0.0.1"));
+        assert!(!v4("255.255.255.255"));
+    }
+
+    #[test]
+    fn refuses_special_purpose_v4() {
+        assert!(!v4("192.0.0.8"));
+        assert!(!v4("198.18.0.1"));
+        assert!(!v4("192.0.2.1"));
+    }
+
+    #[test]
+    fn refuses_local_v6() {
+        assert!(!v6("::1"));
+        assert!(!v6("::"));
+        assert!(!v6("fd00::1"));
+        assert!(!v6("fe80::1"));
+        assert!(!v6("2001:db8::1"));
+    }
+
+    #[test]
+    fn checks_mapped_v6_as_v4() {
+        assert!(!v6("::ffff:127.0.0.1"));
+        assert!(!v6("::ffff:10.0.0.1"));
+        assert!(!v6("::ffff:169.254.169.254"));
+    }
+
+    #[test]
+    fn refuses_v6_reaching_any_v4() {
+        assert!(!v6("64:ff9b::7f00:1"));
+        assert!(!v6("64:ff9b::808:808"));
+        assert!(!v6("64:ff9b:1::a00:1"));
+        assert!(!v6("::127.0.0.1"));
+        assert!(!v6("2001:0:4136:e378:8000:63bf:3fff:fdd2"));
+        assert!(!v6("2002:7f00:1::1"));
+    }
+
+    #[test]
+    fn checks_address_hosts_before_connecting() {
+        let host = |url: &str| is_public_host(&url::Url::parse(url).unwrap());
+        assert!(host("https://example.com/"));
+        assert!(host("https://93.184.215.14/"));
+        assert!(!host("http://127.0.0.1:8080/"));
+        assert!(!host("http://[::ffff:7f00:1]/"));
+    }
+
+    #[tokio::test]
+    async fn resolves_only_to_public_addresses() {
+        let resolved = PublicResolver.resolve("localhost".parse().unwrap()).await;
+        assert!(resolved.is_err());
+    }
+
+    #[test]
+    fn reads_open_graph_tags() {
+        let preview = parse(
+            r#"<html><head>
+            <META Property="og:title" content="Tom &amp; Jerry">
+            <meta property='og:site_name' content='Cartoons'>
+            <meta property="og:image" content="https://example.com/a.png" />
+            <title>Ignored</title>
+            </head><body><meta property="og:image" content="https://example.com/b.png"></body></html>"#,
+        );
+        assert_eq!(preview.title.as_deref(), Some("Tom & Jerry"));
+        assert_eq!(preview.site_name.as_deref(), Some("Cartoons"));
+        assert_eq!(preview.image.as_deref(), Some("https://example.com/a.png"));
+    }
+
+    #[test]
+    fn keeps_the_first_of_repeated_tags() {
+        let preview = parse(
+            r#"<meta property="og:title" content="First"><meta property="og:title" content="Second">"#,
+        );
+        assert_eq!(preview.title.as_deref(), Some("First"));
+    }
+
+    #[test]
+    fn falls_back_to_the_title() {
+        let preview = parse("<head><TITLE> Docs &lt;v2&gt; </TITLE></head>");
+        assert_eq!(preview.title.as_deref(), Some("Docs <v2>"));
+        assert!(preview.site_name.is_none());
+        assert!(preview.image.is_none());
+    }
+
+    #[test]
+    fn decodes_entities_once() {
+        assert_eq!(
+            decode_entities("&amp;lt; &quot;a&#39;s&#x27;"),
+            "&lt; \"a's'"
+        );
+    }
+}
@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+
+/// Fills the `{name}` placeholders of a target pattern. Values are inserted
+/// as given; the names of placeholders without a value are returned instead.
+pub fn render(pattern: &str, values: &HashMap<String, String>) -> Result<String, Vec<String>> {
+    let mut rendered = String::with_capacity(pattern.len());
+    let mut missing = Vec::new();
+
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}').map(|end| (&after[..end], end)) {
+            Some((name, end)) if is_placeholder_name(name) => {
+                match values.get(name) {
+                    Some(value) => rendered.push_str(value),
+                    None if !missing.iter().any(|m| m == name) => missing.push(name.to_string()),
+                    None => {}
+                }
+                rest = &after[end + 1..];
+            }
+            // not a placeholder, keep the brace as is
+            _ => {
+                rendered.push('{');
+                rest = after;
+            }
+        }
+    }
+    rendered.push_str(rest);
+
+    if missing.is_empty() {
+        Ok(rendered)
+    } else {
+        Err(missing)
+    }
+}
+
+fn is_placeholder_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,134 @@
+use std::{net::IpAddr, sync::Arc, time::Duration};
+
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+
+use crate::{
+    config::AuthLockoutConfig,
+    kvs::{ttl_remaining, KvsPool},
+    rate_limit::RateLimitError,
+};
+
+// Doubling stops here; the configured maximum caps the block well before.
+const MAX_BLOCK_EXPONENT: u64 = 20;
+
+/// Blocks clients that keep failing authentication, for a period that doubles
+/// with every failure past the threshold.
+pub struct AuthLockout {
+    kvs_pool: Arc<KvsPool>,
+    config: AuthLockoutConfig,
+}
+
+impl AuthLockout {
+    pub fn new(kvs_pool: Arc<KvsPool>, config: AuthLockoutConfig) -> Self {
+        Self { kvs_pool, config }
+    }
+
+    /// How long the client remains blocked, if it is.
+    pub async fn blocked_for(&self, ip: IpAddr) -> Result<Option<Duration>, RateLimitError> {
+        let mut conn = self.kvs_pool.get().await?;
+        ttl_remaining(&mut conn, &blocked_key(ip))
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn record_failure(&self, ip: IpAddr) -> Result<(), RateLimitError> {
+        let key = failures_key(ip);
+        let mut conn = self.kvs_pool.get().await?;
+
+        let (failures,): (u64,) = redis::pipe()
+            .atomic()
+            .set_options(
+                &key,
+                0,
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(self.config.max_block.as_secs())),
+            )
+            .ignore()
+            .incr(&key, 1)
+            .query_async(&mut conn)
+            .await?;
+
+        if failures < self.config.max_failures {
+            return Ok(());
+        }
+
+        let exponent = (failures - self.config.max_failures).min(MAX_BLOCK_EXPONENT);
+        let block = (self.config.base_block * (1 << exponent)).min(self.config.max_block);
+        tracing::warn!(
+            %ip,
+            failures,
+            block_secs = block.as_secs(),
+            "blocking client after repeated authentication failures"
+        );
+
+        conn.set_ex(blocked_key(ip), "", block.as_secs())
+            .await
+            .map_err(Into::into)
+    }
+}
+
+fn failures_key(ip: IpAddr) -> String {
+    format!("auth-failures:{ip}")
+}
+
+fn blocked_key(ip: IpAddr) -> String {
+    format!("auth-blocked:{ip}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_kvs::MemoryKvs;
+
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(203, 0, 113, 7));
+
+    fn lockout() -> AuthLockout {
+        AuthLockout::new(
+            Arc::new(KvsPool::Memory(MemoryKvs::default())),
+            AuthLockoutConfig {
+                max_failures: 3,
+                base_block: Duration::from_secs(10),
+                max_block: Duration::from_secs(60),
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn blocks_clients_once_failures_reach_the_threshold() {
+        let lockout = lockout();
+        for _ in 0..2 {
+            lockout.record_failure(CLIENT).await.unwrap();
+        }
+        assert_eq!(lockout.blocked_for(CLIENT).await.unwrap(), None);
+
+        lockout.record_failure(CLIENT).await.unwrap();
+        assert_eq!(
+            lockout.blocked_for(CLIENT).await.unwrap(),
+            Some(Duration::from_secs(10))
+        );
+
+        let other = IpAddr::V4(std::net::Ipv4Addr::new(203, 0, 113, 8));
+        assert_eq!(lockout.blocked_for(other).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn doubles_blocks_up_to_the_maximum() {
+        let lockout = lockout();
+        let mut blocks = Vec::new();
+        for _ in 0..7 {
+            lockout.record_failure(CLIENT).await.unwrap();
+            blocks.push(
+                lockout
+                    .blocked_for(CLIENT)
+                    .await
+                    .unwrap()
+                    .map(|block| block.as_secs()),
+            );
+        }
+        assert_eq!(
+            blocks,
+            [None, None, Some(10), Some(20), Some(40), Some(60), Some(60)]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{header::RETRY_AFTER, Method, StatusCode};
+use redis::AsyncCommands;
+
+use crate::{ip_allowlist::ADMIN_PATH_PREFIX, kvs::KvsPool, rate_limit::RateLimitError};
+
+const MAINTENANCE_KEY: &str = "maintenance";
+// How stale an instance's view of the mode may get.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceMode {
+    #[default]
+    Off,
+    /// Reads keep working, changes are refused.
+    ReadOnly,
+    Unavailable,
+}
+
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub struct MaintenanceState {
+    pub mode: MaintenanceMode,
+    /// Sent as `Retry-After` while the management API is restricted.
+    pub retry_after_secs: Option<u64>,
+}
+
+/// The maintenance mode shared by every instance through the KVS. Redirects
+/// never go through it, so they keep serving during maintenance.
+#[derive(Clone)]
+pub struct Maintenance {
+    kvs_pool: Arc<KvsPool>,
+    current: Arc<RwLock<MaintenanceState>>,
+}
+
+impl Maintenance {
+    pub fn new(kvs_pool: Arc<KvsPool>) -> Self {
+        Self {
+            kvs_pool,
+            current: Arc::default(),
+        }
+    }
+
+    pub fn current(&self) -> MaintenanceState {
+        *self.current.read().expect("maintenance lock poisoned")
+    }
+
+    pub async fn set(&self, state: MaintenanceState) -> Result<(), RateLimitError> {
+        let value = serde_json::to_string(&state).expect("maintenance state serializes");
+        let mut conn = self.kvs_pool.get().await?;
+        conn.set::<_, _, ()>(MAINTENANCE_KEY, value).await?;
+
+        *self.current.write().expect("maintenance lock poisoned") = state;
+        Ok(())
+    }
+
+    async fn fetch(&self) -> Result<MaintenanceState, RateLimitError> {
+        let mut conn = self.kvs_pool.get().await?;
+        let value: Option<String> = conn.get(MAINTENANCE_KEY).await?;
+
+        Ok(value
+            .and_then(|value| serde_json::from_str(&value).ok())
+            .unwrap_or_default())
+    }
+
+    /// Keeps this instance's view of the mode in sync with the KVS. When the
+    /// KVS cannot be reached the last known mode stays in effect.
+    pub fn poll(&self) {
+        let maintenance = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                match maintenance.fetch().await {
+                    Ok(state) => {
+                        let mut current = maintenance
+                            .current
+                            .write()
+                            .expect("maintenance lock poisoned");
+                        if current.mode != state.mode {
+                            tracing::info!(mode = ?state.mode, "maintenance mode changed");
+                        }
+                        *current = state;
+                    }
+                    Err(error) => tracing::warn!(%error, "failed to read maintenance mode"),
+                }
+            }
+        });
+    }
+}
+
+/// Answers 503 for what the current mode does not allow. Admin routes stay
+/// reachable, so maintenance can always be turned off again, and signing in
+/// or out only touches the KVS.
+pub async fn enforce(
+    State(maintenance): State<Maintenance>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    let state = maintenance.current();
+    let refused = !path.starts_with(ADMIN_PATH_PREFIX)
+        && match state.mode {
+            MaintenanceMode::Off => false,
+            MaintenanceMode::ReadOnly => {
+                !matches!(
+                    *request.method(),
+                    Method::GET | Method::HEAD | Method::OPTIONS
+                ) && !path.starts_with("/auth/")
+            }
+            MaintenanceMode::Unavailable => true,
+        };
+    if !refused {
+        return next.run(request).await;
+    }
+
+    let message = match state.mode {
+        MaintenanceMode::ReadOnly => "read-only during maintenance",
+        _ => "unavailable during maintenance",
+    };
+    match state.retry_after_secs {
+        Some(secs) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(RETRY_AFTER, secs.to_string())],
+            message,
+        )
+            .into_response(),
+        None => (StatusCode::SERVICE_UNAVAILABLE, message).into_response(),
+    }
+}
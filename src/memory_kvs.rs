@@ -0,0 +1,422 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use redis::{Arg, Cmd, ErrorKind, RedisError, RedisResult, Value};
+
+use crate::kvs::KvsError;
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+enum Data {
+    String(Vec<u8>),
+    Hash(HashMap<Vec<u8>, Vec<u8>>),
+    SortedSet(HashMap<Vec<u8>, f64>),
+}
+
+struct Entry {
+    data: Data,
+    expires_at: Option<Instant>,
+}
+
+struct Entries {
+    by_key: HashMap<Vec<u8>, Entry>,
+    last_sweep: Instant,
+}
+
+/// An in-process stand-in for Redis, answering the commands this service
+/// sends. Meant for local development only: nothing is persisted, and each
+/// instance has its own.
+#[derive(Clone)]
+pub struct MemoryKvs {
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl Default for MemoryKvs {
+    fn default() -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(Entries {
+                by_key: HashMap::new(),
+                last_sweep: Instant::now(),
+            })),
+        }
+    }
+}
+
+impl MemoryKvs {
+    pub fn query(&self, cmd: &Cmd) -> RedisResult<Value> {
+        self.entries.lock().unwrap().execute(cmd)
+    }
+
+    /// Runs the commands under one lock, so transactions are atomic.
+    pub fn query_all<'a>(&self, cmds: impl Iterator<Item = &'a Cmd>) -> RedisResult<Vec<Value>> {
+        let mut entries = self.entries.lock().unwrap();
+        cmds.map(|cmd| entries.execute(cmd)).collect()
+    }
+}
+
+impl Entries {
+    fn execute(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        let now = Instant::now();
+        if now.duration_since(self.last_sweep) > SWEEP_INTERVAL {
+            self.by_key.retain(|_, entry| !entry.expired(now));
+            self.last_sweep = now;
+        }
+
+        let mut args = cmd.args_iter().map(|arg| match arg {
+            Arg::Simple(arg) => arg.to_vec(),
+            Arg::Cursor => Vec::new(),
+        });
+        let name = args
+            .next()
+            .map(|name| String::from_utf8_lossy(&name).to_ascii_uppercase())
+            .unwrap_or_default();
+        let args: Vec<Vec<u8>> = args.collect();
+
+        match (name.as_str(), args.as_slice()) {
+            ("PING", []) => Ok(Value::SimpleString(String::from("PONG"))),
+            // one process has nobody else to race, so nothing needs watching
+            ("WATCH", keys) if !keys.is_empty() => Ok(Value::Okay),
+            ("UNWATCH", []) => Ok(Value::Okay),
+            ("GET", [key]) => Ok(self.get_string(key, now)?.map_or(Value::Nil, bulk)),
+            // like Redis, keys holding anything but a string read as missing
+            ("MGET", keys) if !keys.is_empty() => Ok(Value::Array(
+                keys.iter()
+                    .map(|key| {
+                        let value = self.get_string(key, now).ok().flatten();
+                        value.map_or(Value::Nil, bulk)
+                    })
+                    .collect(),
+            )),
+            ("SET", [key, value, options @ ..]) => self.set(key, value, options, now),
+            ("SETEX", [key, seconds, value]) => {
+                let ttl = Duration::from_secs(integer(seconds)?);
+                self.insert(key, Data::String(value.clone()), Some(now + ttl));
+                Ok(Value::Okay)
+            }
+            ("GETEX", [key, options @ ..]) => {
+                let value = self.get_string(key, now)?;
+                if let Some(entry) = self.by_key.get_mut(key.as_slice()) {
+                    match options {
+                        [] => {}
+                        [option] if option.eq_ignore_ascii_case(b"PERSIST") => {
+                            entry.expires_at = None
+                        }
+                        [option, ttl] => entry.expires_at = Some(now + expiry(option, ttl)?),
+                        _ => return Err(syntax_error(&name)),
+                    }
+                }
+                Ok(value.map_or(Value::Nil, bulk))
+            }
+            ("GETDEL", [key]) => {
+                let value = self.get_string(key, now)?;
+                self.by_key.remove(key.as_slice());
+                Ok(value.map_or(Value::Nil, bulk))
+            }
+            ("DEL", keys) if !keys.is_empty() => Ok(Value::Int(
+                keys.iter()
+                    .filter(|key| {
+                        let live = self.live(key, now).is_some();
+                        self.by_key.remove(key.as_slice());
+                        live
+                    })
+                    .count() as i64,
+            )),
+            ("EXISTS", keys) if !keys.is_empty() => Ok(Value::Int(
+                keys.iter()
+                    .filter(|key| self.live(key, now).is_some())
+                    .count() as i64,
+            )),
+            ("TTL", [key]) => Ok(Value::Int(match self.live(key, now) {
+                None => -2,
+                Some(Entry {
+                    expires_at: None, ..
+                }) => -1,
+                Some(Entry {
+                    expires_at: Some(expires_at),
+                    ..
+                }) => ((expires_at.duration_since(now).as_millis() + 500) / 1000) as i64,
+            })),
+            ("EXPIRE", [key, seconds]) => {
+                let ttl = Duration::from_secs(integer(seconds)?);
+                Ok(Value::Int(match self.live(key, now) {
+                    Some(entry) => {
+                        entry.expires_at = Some(now + ttl);
+                        1
+                    }
+                    None => 0,
+                }))
+            }
+            ("RENAME", [key, new_key]) => {
+                if self.live(key, now).is_none() {
+                    return Err(RedisError::from((ErrorKind::ResponseError, "no such key")));
+                }
+                let entry = self.by_key.remove(key.as_slice()).unwrap();
+                self.by_key.insert(new_key.clone(), entry);
+                Ok(Value::Okay)
+            }
+            ("INCR", [key]) => self.incr_by(key, 1, now),
+            ("INCRBY", [key, by]) => self.incr_by(key, integer(by)?, now),
+            ("HINCRBY", [key, field, by]) => {
+                let by: i64 = integer(by)?;
+                if self.live(key, now).is_none() {
+                    self.insert(key, Data::Hash(HashMap::new()), None);
+                }
+                let entry = self.by_key.get_mut(key.as_slice()).unwrap();
+                let Data::Hash(hash) = &mut entry.data else {
+                    return Err(wrong_type());
+                };
+                let current: i64 = hash
+                    .get(field)
+                    .map(|value| integer(value))
+                    .transpose()?
+                    .unwrap_or(0);
+                let value = current.checked_add(by).ok_or_else(overflow)?;
+                hash.insert(field.clone(), value.to_string().into_bytes());
+                Ok(Value::Int(value))
+            }
+            ("HSET", [key, pairs @ ..]) if !pairs.is_empty() && pairs.len() % 2 == 0 => {
+                if self.live(key, now).is_none() {
+                    self.insert(key, Data::Hash(HashMap::new()), None);
+                }
+                let entry = self.by_key.get_mut(key.as_slice()).unwrap();
+                let Data::Hash(hash) = &mut entry.data else {
+                    return Err(wrong_type());
+                };
+                Ok(Value::Int(
+                    pairs
+                        .chunks(2)
+                        .filter(|pair| hash.insert(pair[0].clone(), pair[1].clone()).is_none())
+                        .count() as i64,
+                ))
+            }
+            ("HGETALL", [key]) => Ok(Value::Array(match self.live(key, now) {
+                Some(Entry {
+                    data: Data::Hash(hash),
+                    ..
+                }) => hash
+                    .iter()
+                    .flat_map(|(field, value)| [bulk(field.clone()), bulk(value.clone())])
+                    .collect(),
+                Some(_) => return Err(wrong_type()),
+                None => Vec::new(),
+            })),
+            ("HMGET", [key, fields @ ..]) if !fields.is_empty() => {
+                let hash = match self.live(key, now) {
+                    Some(Entry {
+                        data: Data::Hash(hash),
+                        ..
+                    }) => Some(&*hash),
+                    Some(_) => return Err(wrong_type()),
+                    None => None,
+                };
+                Ok(Value::Array(
+                    fields
+                        .iter()
+                        .map(|field| {
+                            hash.and_then(|hash| hash.get(field))
+                                .map_or(Value::Nil, |value| bulk(value.clone()))
+                        })
+                        .collect(),
+                ))
+            }
+            ("ZADD", [key, pairs @ ..]) if !pairs.is_empty() && pairs.len() % 2 == 0 => {
+                let members = pairs
+                    .chunks(2)
+                    .map(|pair| Ok((pair[1].clone(), score(&pair[0])?)))
+                    .collect::<RedisResult<Vec<_>>>()?;
+                if self.live(key, now).is_none() {
+                    self.insert(key, Data::SortedSet(HashMap::new()), None);
+                }
+                let set = self.sorted_set(key, now)?.unwrap();
+                Ok(Value::Int(
+                    members
+                        .into_iter()
+                        .filter(|(member, score)| set.insert(member.clone(), *score).is_none())
+                        .count() as i64,
+                ))
+            }
+            ("ZREMRANGEBYSCORE", [key, min, max]) => {
+                let (min, max) = (score(min)?, score(max)?);
+                let Some(set) = self.sorted_set(key, now)? else {
+                    return Ok(Value::Int(0));
+                };
+                let before = set.len();
+                set.retain(|_, score| *score < min || *score > max);
+                let removed = before - set.len();
+                // like Redis, an emptied set is no longer there
+                if set.is_empty() {
+                    self.by_key.remove(key.as_slice());
+                }
+                Ok(Value::Int(removed as i64))
+            }
+            ("ZCARD", [key]) => Ok(Value::Int(
+                self.sorted_set(key, now)?.map_or(0, |set| set.len()) as i64,
+            )),
+            _ => Err(RedisError::from((
+                ErrorKind::ResponseError,
+                "unsupported command for the in-memory KVS",
+                name,
+            ))),
+        }
+    }
+
+    /// The entry under `key`, dropping it first when it has expired.
+    fn live(&mut self, key: &[u8], now: Instant) -> Option<&mut Entry> {
+        if self.by_key.get(key).is_some_and(|entry| entry.expired(now)) {
+            self.by_key.remove(key);
+        }
+        self.by_key.get_mut(key)
+    }
+
+    fn get_string(&mut self, key: &[u8], now: Instant) -> RedisResult<Option<Vec<u8>>> {
+        match self.live(key, now) {
+            Some(Entry {
+                data: Data::String(value),
+                ..
+            }) => Ok(Some(value.clone())),
+            Some(_) => Err(wrong_type()),
+            None => Ok(None),
+        }
+    }
+
+    fn sorted_set(
+        &mut self,
+        key: &[u8],
+        now: Instant,
+    ) -> RedisResult<Option<&mut HashMap<Vec<u8>, f64>>> {
+        match self.live(key, now) {
+            Some(Entry {
+                data: Data::SortedSet(set),
+                ..
+            }) => Ok(Some(set)),
+            Some(_) => Err(wrong_type()),
+            None => Ok(None),
+        }
+    }
+
+    fn insert(&mut self, key: &[u8], data: Data, expires_at: Option<Instant>) {
+        self.by_key.insert(key.to_vec(), Entry { data, expires_at });
+    }
+
+    fn set(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+        options: &[Vec<u8>],
+        now: Instant,
+    ) -> RedisResult<Value> {
+        let mut only_if_missing = false;
+        let mut only_if_present = false;
+        let mut expires_at = None;
+        let mut options = options.iter();
+        while let Some(option) = options.next() {
+            match option.to_ascii_uppercase().as_slice() {
+                b"NX" => only_if_missing = true,
+                b"XX" => only_if_present = true,
+                b"EX" | b"PX" => {
+                    let ttl = options.next().ok_or_else(|| syntax_error("SET"))?;
+                    expires_at = Some(now + expiry(option, ttl)?);
+                }
+                _ => return Err(syntax_error("SET")),
+            }
+        }
+
+        let exists = self.live(key, now).is_some();
+        if (only_if_missing && exists) || (only_if_present && !exists) {
+            return Ok(Value::Nil);
+        }
+        self.insert(key, Data::String(value.to_vec()), expires_at);
+        Ok(Value::Okay)
+    }
+
+    fn incr_by(&mut self, key: &[u8], by: i64, now: Instant) -> RedisResult<Value> {
+        let value = match self.live(key, now) {
+            Some(entry) => {
+                let Data::String(current) = &mut entry.data else {
+                    return Err(wrong_type());
+                };
+                let value = integer::<i64>(current)?
+                    .checked_add(by)
+                    .ok_or_else(overflow)?;
+                *current = value.to_string().into_bytes();
+                value
+            }
+            None => {
+                self.insert(key, Data::String(by.to_string().into_bytes()), None);
+                by
+            }
+        };
+        Ok(Value::Int(value))
+    }
+}
+
+impl Entry {
+    fn expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+fn bulk(value: Vec<u8>) -> Value {
+    Value::BulkString(value)
+}
+
+fn integer<T: std::str::FromStr>(value: &[u8]) -> RedisResult<T> {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| {
+            RedisError::from((
+                ErrorKind::ResponseError,
+                "value is not an integer or out of range",
+            ))
+        })
+}
+
+/// A score or score bound, which may be `-inf` or `+inf`.
+fn score(value: &[u8]) -> RedisResult<f64> {
+    match value.to_ascii_lowercase().as_slice() {
+        b"-inf" => Ok(f64::NEG_INFINITY),
+        b"+inf" | b"inf" => Ok(f64::INFINITY),
+        _ => std::str::from_utf8(value)
+            .ok()
+            .and_then(|value| value.parse::<f64>().ok())
+            .filter(|score| !score.is_nan())
+            .ok_or_else(|| {
+                RedisError::from((ErrorKind::ResponseError, "value is not a valid float"))
+            }),
+    }
+}
+
+fn expiry(unit: &[u8], ttl: &[u8]) -> RedisResult<Duration> {
+    let ttl = integer(ttl)?;
+    match unit.to_ascii_uppercase().as_slice() {
+        b"EX" => Ok(Duration::from_secs(ttl)),
+        b"PX" => Ok(Duration::from_millis(ttl)),
+        _ => Err(syntax_error("expiry")),
+    }
+}
+
+fn syntax_error(command: &str) -> KvsError {
+    RedisError::from((
+        ErrorKind::ResponseError,
+        "syntax error",
+        command.to_string(),
+    ))
+}
+
+fn wrong_type() -> KvsError {
+    RedisError::from((
+        ErrorKind::TypeError,
+        "operation against a key holding the wrong kind of value",
+    ))
+}
+
+fn overflow() -> KvsError {
+    RedisError::from((
+        ErrorKind::ResponseError,
+        "increment or decrement would overflow",
+    ))
+}
@@ -0,0 +1,73 @@
+use std::error::Error;
+
+use axum::{
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Form, Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+/// Prefixed to the email to make a token, so tokens are recognisably fake.
+const TOKEN_PREFIX: &str = "mock.";
+
+/// A stand-in for the agus.dev SSO, for tests and local development. The
+/// authorization code is the email of the user signing in, and tokens are
+/// accepted for whoever they were issued to. Nothing is checked beyond
+/// that, so it must never be what a real deployment points at.
+pub fn router() -> Router {
+    Router::new()
+        .route("/oauth2/token", post(token))
+        .route("/oauth2/revoke", post(revoke))
+        .route("/profile", get(profile))
+}
+
+pub async fn serve(port: u16) -> Result<(), Box<dyn Error>> {
+    tracing::warn!("Mock SSO listening on 0.0.0.0:{port}; it signs anyone in as anyone");
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port)).await?;
+    axum::serve(listener, router()).await?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenRequest {
+    grant_type: String,
+    code: String,
+}
+
+async fn token(Form(request): Form<TokenRequest>) -> Response {
+    if request.grant_type != "authorization_code" {
+        return oauth_error("unsupported_grant_type");
+    }
+    if !request.code.contains('@') {
+        return oauth_error("invalid_grant");
+    }
+
+    Json(json!({
+        "access_token": format!("{TOKEN_PREFIX}{}", request.code),
+        "token_type": "Bearer",
+    }))
+    .into_response()
+}
+
+async fn revoke() -> StatusCode {
+    StatusCode::OK
+}
+
+async fn profile(headers: HeaderMap) -> Response {
+    let email = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| token.strip_prefix(TOKEN_PREFIX));
+
+    match email {
+        Some(email) if email.contains('@') => Json(json!({ "email": email })).into_response(),
+        _ => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+fn oauth_error(error: &str) -> Response {
+    (StatusCode::BAD_REQUEST, Json(json!({ "error": error }))).into_response()
+}
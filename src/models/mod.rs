@@ -2,4 +2,19 @@
 
 pub mod prelude;
 
+pub mod bio_page_links;
+pub mod bio_pages;
+pub mod campaigns;
+pub mod click_count_write_backs;
+pub mod clicks;
+pub mod link_templates;
+pub mod notification_preferences;
+pub mod plans;
+pub mod scheduled_targets;
+pub mod slack_accounts;
+pub mod tenant_settings;
+pub mod url_redirect_aliases;
+pub mod url_redirect_revisions;
+pub mod url_redirect_tags;
 pub mod url_redirects;
+pub mod user_plans;
@@ -1,3 +1,18 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.0.0
 
+pub use super::bio_page_links::Entity as BioPageLinks;
+pub use super::bio_pages::Entity as BioPages;
+pub use super::campaigns::Entity as Campaigns;
+pub use super::click_count_write_backs::Entity as ClickCountWriteBacks;
+pub use super::clicks::Entity as Clicks;
+pub use super::link_templates::Entity as LinkTemplates;
+pub use super::notification_preferences::Entity as NotificationPreferences;
+pub use super::plans::Entity as Plans;
+pub use super::scheduled_targets::Entity as ScheduledTargets;
+pub use super::slack_accounts::Entity as SlackAccounts;
+pub use super::tenant_settings::Entity as TenantSettings;
+pub use super::url_redirect_aliases::Entity as UrlRedirectAliases;
+pub use super::url_redirect_revisions::Entity as UrlRedirectRevisions;
+pub use super::url_redirect_tags::Entity as UrlRedirectTags;
 pub use super::url_redirects::Entity as UrlRedirects;
+pub use super::user_plans::Entity as UserPlans;
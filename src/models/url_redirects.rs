@@ -8,11 +8,33 @@ pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: Uuid,
     pub user_email: String,
-    #[sea_orm(unique)]
     pub key: String,
     pub target: String,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
+    pub expires_at: Option<DateTimeWithTimeZone>,
+    pub rollout_target: Option<String>,
+    pub rollout_percent: Option<i16>,
+    pub archived_at: Option<DateTimeWithTimeZone>,
+    pub campaign_id: Option<Uuid>,
+    pub ios_deep_link: Option<String>,
+    pub ios_fallback_url: Option<String>,
+    pub android_deep_link: Option<String>,
+    pub android_fallback_url: Option<String>,
+    pub allow_indexing: bool,
+    pub public: bool,
+    pub expiry_notified: bool,
+    pub last_accessed_at: Option<DateTimeWithTimeZone>,
+    pub inactive_since: Option<DateTimeWithTimeZone>,
+    pub keep_when_inactive: bool,
+    pub pinned: bool,
+    pub normalized_target_hash: Option<String>,
+    pub target_host: Option<String>,
+    pub tenant_id: String,
+    pub social_title: Option<String>,
+    pub social_description: Option<String>,
+    pub social_image: Option<String>,
+    pub click_count: i64,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,67 @@
+use std::path::Path;
+
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use http::StatusCode;
+
+use crate::{config::NotFoundConfig, tenant};
+
+const PAGE_FILE: &str = "not_found.html";
+
+/// The answer for keys that do not resolve to a link.
+pub enum NotFound {
+    Plain,
+    Redirect(String),
+    Page(String),
+}
+
+impl NotFound {
+    /// Reads the not-found page, if one is configured, once at startup.
+    pub fn load(config: Option<NotFoundConfig>) -> std::io::Result<Self> {
+        match config {
+            None => Ok(Self::Plain),
+            Some(NotFoundConfig::Redirect(url)) => Ok(Self::Redirect(url)),
+            Some(NotFoundConfig::Page(dir)) => {
+                let path = Path::new(&dir).join(PAGE_FILE);
+                std::fs::read_to_string(&path)
+                    .map(Self::Page)
+                    .map_err(|error| {
+                        std::io::Error::new(
+                            error.kind(),
+                            format!("cannot read {}: {error}", path.display()),
+                        )
+                    })
+            }
+        }
+    }
+
+    /// The answer for `key`, on the current tenant's own page when it has one.
+    pub fn response(&self, key: &str) -> Response {
+        if let Some(page) = tenant::settings().and_then(|settings| settings.not_found_page.clone())
+        {
+            return page_response(&page, key);
+        }
+
+        match self {
+            Self::Plain => (StatusCode::NOT_FOUND, "not found").into_response(),
+            Self::Redirect(url) => Redirect::temporary(url).into_response(),
+            Self::Page(page) => page_response(page, key),
+        }
+    }
+}
+
+fn page_response(page: &str, key: &str) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Html(page.replace("{{key}}", &escape_html(key))),
+    )
+        .into_response()
+}
+
+pub fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
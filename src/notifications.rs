@@ -0,0 +1,95 @@
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use crate::config::NotificationsConfig;
+
+#[derive(Debug, thiserror::Error)]
+pub enum NotifyError {
+    #[error("invalid recipient: {0}")]
+    Address(#[from] lettre::address::AddressError),
+    #[error("cannot build email: {0}")]
+    Message(#[from] lettre::error::Error),
+    #[error("smtp error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+}
+
+/// Something that happened to one of a user's links.
+#[derive(Debug, Clone)]
+pub enum Notification {
+    LinkExpired {
+        key: String,
+        target: String,
+    },
+    InactiveLink {
+        key: String,
+        target: String,
+        archived_on: chrono::NaiveDate,
+    },
+    InactiveLinkArchived {
+        key: String,
+        target: String,
+    },
+}
+
+impl Notification {
+    fn subject(&self) -> String {
+        match self {
+            Self::LinkExpired { key, .. } => format!("Your link {key} has expired"),
+            Self::InactiveLink { key, .. } => format!("Your link {key} will be archived"),
+            Self::InactiveLinkArchived { key, .. } => {
+                format!("Your link {key} has been archived")
+            }
+        }
+    }
+
+    fn body(&self) -> String {
+        match self {
+            Self::LinkExpired { key, target } => format!(
+                "Your short link {key}, which pointed to {target}, has expired and no longer \
+                 redirects.\n\nYou can turn these emails off under /me/notifications.\n"
+            ),
+            Self::InactiveLink {
+                key,
+                target,
+                archived_on,
+            } => format!(
+                "Nobody has used your short link {key}, which points to {target}, in a long \
+                 time. It will be archived on {archived_on} unless it is used before then.\n\n\
+                 To keep it regardless, mark it with PUT /urls/:id/keep.\n"
+            ),
+            Self::InactiveLinkArchived { key, target } => format!(
+                "Your short link {key}, which pointed to {target}, went unused for too long \
+                 and has been archived. It no longer redirects until you unarchive it.\n"
+            ),
+        }
+    }
+}
+
+/// Emails users about their links over SMTP.
+pub struct Notifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl Notifier {
+    pub fn new(config: NotificationsConfig) -> Result<Self, lettre::transport::smtp::Error> {
+        Ok(Self {
+            transport: AsyncSmtpTransport::<Tokio1Executor>::from_url(&config.smtp_url)?.build(),
+            from: config.from,
+        })
+    }
+
+    pub async fn send(&self, to: &str, notification: &Notification) -> Result<(), NotifyError> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(Mailbox::new(None, to.parse()?))
+            .subject(notification.subject())
+            .header(ContentType::TEXT_PLAIN)
+            .body(notification.body())?;
+        self.transport.send(message).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::requests::{LinkSort, PageCursor};
+
+const SIGNATURE_LEN: usize = 32;
+
+/// What a cursor says: the order it was handed out for, and where the page
+/// it leads to starts.
+#[derive(Debug, Serialize, Deserialize)]
+struct Payload {
+    sort: LinkSort,
+    position: PageCursor,
+}
+
+/// Signs and opens the cursors of the link list. They are opaque to clients,
+/// who can only hand back cursors they were given, and carry the order they
+/// were handed out for so a page is never continued in another.
+pub struct PageCursors {
+    secret: Vec<u8>,
+}
+
+impl PageCursors {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            secret: secret.as_bytes().to_vec(),
+        }
+    }
+
+    /// Signs with a secret of its own, so cursors stop working on restart
+    /// and are only good on the instance that handed them out.
+    pub fn random() -> Self {
+        let mut secret = vec![0; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        Self { secret }
+    }
+
+    pub fn sign(&self, sort: LinkSort, position: PageCursor) -> String {
+        let mut cursor =
+            serde_json::to_vec(&Payload { sort, position }).expect("cursors serialize");
+        let signature = self.mac(&cursor).finalize().into_bytes();
+        cursor.extend_from_slice(&signature);
+        URL_SAFE_NO_PAD.encode(cursor)
+    }
+
+    /// The order and position `cursor` was signed with, if it is genuine.
+    pub fn open(&self, cursor: &str) -> Option<(LinkSort, PageCursor)> {
+        let cursor = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        let split = cursor.len().checked_sub(SIGNATURE_LEN)?;
+        let (payload, signature) = cursor.split_at(split);
+        self.mac(payload).verify_slice(signature).ok()?;
+
+        let payload: Payload = serde_json::from_slice(payload).ok()?;
+        Some((payload.sort, payload.position))
+    }
+
+    /// The order and position a list request asks for, from the `after` or
+    /// `before` cursor it gives and the `sort` it names, which must agree
+    /// with the cursor's. Explains what is wrong when they are unusable.
+    pub fn resolve(
+        &self,
+        after: Option<&str>,
+        before: Option<&str>,
+        sort: Option<LinkSort>,
+    ) -> Result<(LinkSort, Option<PageCursor>), &'static str> {
+        let (cursor, forward) = match (after, before) {
+            (Some(_), Some(_)) => return Err("give either after or before, not both"),
+            (Some(after), None) => (after, true),
+            (None, Some(before)) => (before, false),
+            (None, None) => return Ok((sort.unwrap_or_default(), None)),
+        };
+        let (cursor_sort, position) = self.open(cursor).ok_or("invalid cursor")?;
+        if matches!(position, PageCursor::After(_)) != forward {
+            return Err("cursor is for paging the other way");
+        }
+        if sort.is_some_and(|sort| sort != cursor_sort) {
+            return Err("cursor is for another sort");
+        }
+        Ok((cursor_sort, Some(position)))
+    }
+
+    fn mac(&self, payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("hmac accepts keys of any length");
+        mac.update(payload);
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "a secret of at least thirty-two bytes";
+
+    fn after(key: &str) -> PageCursor {
+        PageCursor::After(key.to_owned())
+    }
+
+    #[test]
+    fn opens_the_cursors_it_signs() {
+        let cursors = PageCursors::new(SECRET);
+        let cursor = cursors.sign(LinkSort::Newest, after("abc"));
+
+        assert_eq!(
+            cursors.open(&cursor),
+            Some((LinkSort::Newest, after("abc")))
+        );
+    }
+
+    #[test]
+    fn rejects_cursors_signed_with_another_secret() {
+        let cursor = PageCursors::new(SECRET).sign(LinkSort::Key, after("abc"));
+
+        assert_eq!(PageCursors::random().open(&cursor), None);
+    }
+
+    #[test]
+    fn rejects_tampered_cursors() {
+        let cursors = PageCursors::new(SECRET);
+        let mut cursor = URL_SAFE_NO_PAD
+            .decode(cursors.sign(LinkSort::Key, after("abc")))
+            .unwrap();
+        cursor[0] ^= 1;
+
+        assert_eq!(cursors.open(&URL_SAFE_NO_PAD.encode(cursor)), None);
+        assert_eq!(cursors.open("not base64!"), None);
+        assert_eq!(cursors.open(""), None);
+    }
+
+    #[test]
+    fn resolves_the_sort_without_a_cursor() {
+        let cursors = PageCursors::new(SECRET);
+
+        assert_eq!(cursors.resolve(None, None, None), Ok((LinkSort::Key, None)));
+        assert_eq!(
+            cursors.resolve(None, None, Some(LinkSort::Pinned)),
+            Ok((LinkSort::Pinned, None))
+        );
+    }
+
+    #[test]
+    fn resolves_cursors_in_their_direction_and_sort() {
+        let cursors = PageCursors::new(SECRET);
+        let next = cursors.sign(LinkSort::Newest, after("abc"));
+        let prev = cursors.sign(LinkSort::Newest, PageCursor::Before("abc".to_owned()));
+
+        assert_eq!(
+            cursors.resolve(Some(&next), None, None),
+            Ok((LinkSort::Newest, Some(after("abc"))))
+        );
+        assert_eq!(
+            cursors.resolve(None, Some(&prev), Some(LinkSort::Newest)),
+            Ok((LinkSort::Newest, Some(PageCursor::Before("abc".to_owned()))))
+        );
+    }
+
+    #[test]
+    fn explains_unusable_cursors() {
+        let cursors = PageCursors::new(SECRET);
+        let next = cursors.sign(LinkSort::Newest, after("abc"));
+
+        assert_eq!(
+            cursors.resolve(Some(&next), Some(&next), None),
+            Err("give either after or before, not both")
+        );
+        assert_eq!(
+            cursors.resolve(Some("forged"), None, None),
+            Err("invalid cursor")
+        );
+        assert_eq!(
+            cursors.resolve(None, Some(&next), None),
+            Err("cursor is for paging the other way")
+        );
+        assert_eq!(
+            cursors.resolve(Some(&next), None, Some(LinkSort::Key)),
+            Err("cursor is for another sort")
+        );
+    }
+}
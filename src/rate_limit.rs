@@ -0,0 +1,175 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::response::{IntoResponseParts, Response, ResponseParts};
+use http::{header::RETRY_AFTER, HeaderName, HeaderValue};
+use redis::{ExistenceCheck, SetExpiry, SetOptions};
+
+use crate::{
+    error::{problem, ProblemType},
+    kvs::{KvsError, KvsPool, KvsPoolError},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RateLimitError {
+    #[error("kvs pool error: {0}")]
+    Pool(#[from] KvsPoolError),
+    #[error("kvs error: {0}")]
+    Kvs(#[from] KvsError),
+}
+
+impl From<RateLimitError> for Response {
+    fn from(value: RateLimitError) -> Self {
+        tracing::error!(error = %value, "failed to check rate limit");
+        problem(ProblemType::Internal, "internal server error")
+    }
+}
+
+pub const RATE_LIMIT_LIMIT: &str = "ratelimit-limit";
+pub const RATE_LIMIT_REMAINING: &str = "ratelimit-remaining";
+pub const RATE_LIMIT_RESET: &str = "ratelimit-reset";
+
+/// Where a subject stands in the current window, answered as `RateLimit-*`
+/// headers so clients can pace themselves, with `Retry-After` once refused.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub limit: u64,
+    pub remaining: u64,
+    /// Until the window ends and the count starts over.
+    pub reset: Duration,
+    /// Whether the hit just counted was within the limit.
+    pub allowed: bool,
+}
+
+impl IntoResponseParts for RateLimit {
+    type Error = std::convert::Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        let headers = res.headers_mut();
+        let reset = HeaderValue::from(self.reset.as_secs());
+        headers.insert(
+            HeaderName::from_static(RATE_LIMIT_LIMIT),
+            HeaderValue::from(self.limit),
+        );
+        headers.insert(
+            HeaderName::from_static(RATE_LIMIT_REMAINING),
+            HeaderValue::from(self.remaining),
+        );
+        headers.insert(HeaderName::from_static(RATE_LIMIT_RESET), reset.clone());
+        if !self.allowed {
+            headers.insert(RETRY_AFTER, reset);
+        }
+        Ok(res)
+    }
+}
+
+/// Fixed-window rate limiter backed by the KVS, so the limit holds across
+/// every instance of the service.
+pub struct RateLimiter {
+    kvs_pool: Arc<KvsPool>,
+    name: &'static str,
+    window: Duration,
+}
+
+impl RateLimiter {
+    pub fn new(kvs_pool: Arc<KvsPool>, name: &'static str, window: Duration) -> Self {
+        Self {
+            kvs_pool,
+            name,
+            window,
+        }
+    }
+
+    /// Counts one hit for `subject` and tells whether it is still within
+    /// `limit`, and how much of it is left. The limit is given on each hit,
+    /// so a reloaded one applies to the window under way.
+    pub async fn hit(&self, subject: &str, limit: u64) -> Result<RateLimit, RateLimitError> {
+        let key = format!("rate-limit:{}:{subject}", self.name);
+        let mut conn = self.kvs_pool.get().await?;
+
+        // creating the counter with its expiry in the same transaction as the
+        // increment means a window can never be left without a TTL
+        let (count, ttl): (u64, i64) = redis::pipe()
+            .atomic()
+            .set_options(
+                &key,
+                0,
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(self.window.as_secs())),
+            )
+            .ignore()
+            .incr(&key, 1)
+            .ttl(&key)
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(RateLimit {
+            limit,
+            remaining: limit.saturating_sub(count),
+            reset: u64::try_from(ttl).map_or(self.window, Duration::from_secs),
+            allowed: count <= limit,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::response::IntoResponse;
+
+    use super::*;
+    use crate::memory_kvs::MemoryKvs;
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(
+            Arc::new(KvsPool::Memory(MemoryKvs::default())),
+            "test",
+            Duration::from_secs(3600),
+        )
+    }
+
+    #[tokio::test]
+    async fn allows_hits_up_to_the_limit_within_a_window() {
+        let limiter = limiter();
+        let mut remaining = Vec::new();
+        for _ in 0..3 {
+            let hit = limiter.hit("203.0.113.7", 2).await.unwrap();
+            remaining.push((hit.allowed, hit.remaining));
+            assert_eq!(hit.reset, Duration::from_secs(3600));
+        }
+        assert_eq!(remaining, [(true, 1), (true, 0), (false, 0)]);
+
+        assert!(limiter.hit("203.0.113.8", 2).await.unwrap().allowed);
+    }
+
+    #[tokio::test]
+    async fn applies_a_changed_limit_to_the_window_under_way() {
+        let limiter = limiter();
+        for _ in 0..2 {
+            limiter.hit("203.0.113.7", 2).await.unwrap();
+        }
+        assert!(!limiter.hit("203.0.113.7", 2).await.unwrap().allowed);
+        assert!(limiter.hit("203.0.113.7", 5).await.unwrap().allowed);
+    }
+
+    #[test]
+    fn answers_retry_after_only_once_refused() {
+        let limit = RateLimit {
+            limit: 2,
+            remaining: 0,
+            reset: Duration::from_secs(30),
+            allowed: true,
+        };
+        let response = (limit, "").into_response();
+        assert_eq!(response.headers()[RATE_LIMIT_LIMIT], "2");
+        assert_eq!(response.headers()[RATE_LIMIT_REMAINING], "0");
+        assert_eq!(response.headers()[RATE_LIMIT_RESET], "30");
+        assert!(response.headers().get(RETRY_AFTER).is_none());
+
+        let refused = RateLimit {
+            allowed: false,
+            ..limit
+        };
+        let response = (refused, "").into_response();
+        assert_eq!(response.headers()[RETRY_AFTER], "30");
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, RwLock, RwLockReadGuard},
+};
+
+use axum_server::tls_rustls::RustlsConfig;
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::{
+    config::{Config, KeyGenerationConfig},
+    routes,
+    service::KeyPolicy,
+};
+
+/// Settings that can change while the server is running.
+struct ReloadableSettings {
+    allowed_origins: Vec<String>,
+    key_policy: KeyPolicy,
+    /// Lowercased, as keys are matched against them case-insensitively.
+    blocked_words: Vec<String>,
+    anonymous_links_per_hour: u64,
+}
+
+impl ReloadableSettings {
+    /// The settings of `config`, reserving the keys routes take under
+    /// `redirect_prefix`, the prefix the routes were built with.
+    fn new(config: &Config, redirect_prefix: &str) -> Self {
+        Self {
+            allowed_origins: config.allowed_origins.clone(),
+            key_policy: KeyPolicy::new(&config.key_policy)
+                .with_reserved_keys(routes::shadowed_keys(redirect_prefix)),
+            blocked_words: blocked_words(&config.key_generation),
+            anonymous_links_per_hour: config
+                .anonymous_links
+                .as_ref()
+                .map_or(0, |anonymous_links| anonymous_links.per_hour),
+        }
+    }
+}
+
+impl Default for ReloadableSettings {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            key_policy: KeyPolicy::default(),
+            blocked_words: blocked_words(&KeyGenerationConfig::default()),
+            anonymous_links_per_hour: 0,
+        }
+    }
+}
+
+fn blocked_words(config: &KeyGenerationConfig) -> Vec<String> {
+    config
+        .blocked_words
+        .iter()
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+#[derive(Clone, Default)]
+pub struct Reloadable {
+    settings: Arc<RwLock<ReloadableSettings>>,
+    /// The prefix redirects are routed under, which only a restart changes.
+    redirect_prefix: Arc<String>,
+}
+
+impl Reloadable {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            settings: Arc::new(RwLock::new(ReloadableSettings::new(
+                config,
+                &config.redirect_prefix,
+            ))),
+            redirect_prefix: Arc::new(config.redirect_prefix.clone()),
+        }
+    }
+
+    pub fn is_allowed_origin(&self, origin: &[u8]) -> bool {
+        self.settings()
+            .allowed_origins
+            .iter()
+            .any(|allowed| allowed.as_bytes() == origin)
+    }
+
+    /// The rules keys picked by users are checked against.
+    pub fn key_policy(&self) -> KeyPolicy {
+        self.settings().key_policy.clone()
+    }
+
+    /// Whether `key` contains a word generated keys must not.
+    pub fn is_blocked(&self, key: &str) -> bool {
+        let key = key.to_lowercase();
+        self.settings()
+            .blocked_words
+            .iter()
+            .any(|word| key.contains(word))
+    }
+
+    /// How many anonymous links each client may create in an hour.
+    pub fn anonymous_links_per_hour(&self) -> u64 {
+        self.settings().anonymous_links_per_hour
+    }
+
+    fn settings(&self) -> RwLockReadGuard<'_, ReloadableSettings> {
+        self.settings
+            .read()
+            .expect("reloadable settings lock poisoned")
+    }
+
+    fn apply(&self, config: &Config) {
+        if config.redirect_prefix != *self.redirect_prefix {
+            tracing::warn!(
+                redirect_prefix = %self.redirect_prefix,
+                "redirect_prefix changed, which needs a restart; keeping the current one"
+            );
+        }
+        *self
+            .settings
+            .write()
+            .expect("reloadable settings lock poisoned") =
+            ReloadableSettings::new(config, &self.redirect_prefix);
+    }
+}
+
+/// Reloads the configuration on every SIGHUP and swaps in the reloadable
+/// settings, re-reading the TLS certificate when serving TLS. An invalid
+/// configuration or certificate is logged and the current one is kept.
+pub fn reload_on_sighup(
+    reloadable: Reloadable,
+    tls: Option<RustlsConfig>,
+    config_path: Option<PathBuf>,
+) -> std::io::Result<()> {
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            match Config::load(config_path.as_deref()) {
+                Ok(config) => {
+                    reloadable.apply(&config);
+                    tracing::info!("configuration reloaded");
+
+                    if let (Some(tls), Some(paths)) = (&tls, &config.tls) {
+                        match tls
+                            .reload_from_pem_file(&paths.cert_path, &paths.key_path)
+                            .await
+                        {
+                            Ok(()) => tracing::info!("TLS certificate reloaded"),
+                            Err(error) => {
+                                tracing::error!(%error, "failed to reload TLS certificate")
+                            }
+                        }
+                    }
+                }
+                Err(error) => {
+                    tracing::error!(%error, "failed to reload configuration, keeping current one");
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reloads_the_key_policy() {
+        let reloadable = Reloadable::new(&Config::for_tests(""));
+        assert!(reloadable.key_policy().parse(String::from("a")).is_ok());
+
+        reloadable.apply(&Config::for_tests("[key_policy]\nmin_length = 2"));
+        assert!(reloadable.key_policy().parse(String::from("a")).is_err());
+    }
+
+    #[test]
+    fn reserves_keys_under_the_running_redirect_prefix() {
+        let reloadable = Reloadable::new(&Config::for_tests(r#"redirect_prefix = """#));
+        assert!(reloadable.key_policy().is_reserved("shorten"));
+
+        reloadable.apply(&Config::for_tests(r#"redirect_prefix = "/go""#));
+        assert!(reloadable.key_policy().is_reserved("shorten"));
+
+        let reloadable = Reloadable::new(&Config::for_tests(r#"redirect_prefix = "/go""#));
+        reloadable.apply(&Config::for_tests(r#"redirect_prefix = """#));
+        assert!(!reloadable.key_policy().is_reserved("shorten"));
+    }
+
+    #[test]
+    fn matches_blocked_words_case_insensitively() {
+        let reloadable = Reloadable::new(&Config::for_tests(
+            "[key_generation]\nblocked_words = [\"Bad\"]",
+        ));
+
+        assert!(reloadable.is_blocked("xBADx"));
+        assert!(!reloadable.is_blocked("good"));
+    }
+}
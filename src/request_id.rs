@@ -0,0 +1,96 @@
+use std::{convert::Infallible, future::ready};
+
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::{FromRequestParts, Request},
+    middleware::Next,
+    response::Response,
+};
+use futures_util::{stream, StreamExt};
+use http::{header::CONTENT_TYPE, request::Parts, Extensions, HeaderValue};
+use tower_http::request_id::RequestId;
+use tracing::Span;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// Error bodies are short messages; anything bigger is passed through untouched.
+const MAX_ERROR_BODY_SIZE: usize = 64 * 1024;
+
+pub fn request_id(extensions: &Extensions) -> Option<&str> {
+    extensions
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+}
+
+/// The id of the current request, for handlers that forward it on outbound calls.
+pub struct CurrentRequestId(pub Option<String>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for CurrentRequestId {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(request_id(&parts.extensions).map(String::from)))
+    }
+}
+
+pub fn make_span(request: &Request) -> Span {
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        request_id = request_id(request.extensions()).unwrap_or_default(),
+        requester = tracing::field::Empty,
+        impersonated_by = tracing::field::Empty,
+    )
+}
+
+/// Appends the request id to plain-text error bodies, so a failure reported
+/// by a client can be matched to the server logs.
+pub async fn append_to_error_body(request: Request, next: Next) -> Response {
+    let id = request_id(request.extensions()).map(String::from);
+    let response = next.run(request).await;
+
+    let Some(id) = id else { return response };
+    let is_plain_text = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/plain"));
+    if !(response.status().is_client_error() || response.status().is_server_error())
+        || !is_plain_text
+    {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let mut chunks = body.into_data_stream();
+    let mut body = Vec::new();
+    while let Some(chunk) = chunks.next().await {
+        match chunk {
+            Ok(chunk) => body.extend_from_slice(&chunk),
+            Err(error) => {
+                tracing::warn!(%error, "failed to read error body");
+                break;
+            }
+        }
+        if body.len() > MAX_ERROR_BODY_SIZE {
+            // what was read goes out first, then the rest as it comes
+            let body = stream::once(ready(Ok(Bytes::from(body)))).chain(chunks);
+            return Response::from_parts(parts, Body::from_stream(body));
+        }
+    }
+    let body = format!(
+        "{} (request id: {id})",
+        String::from_utf8_lossy(&body).trim_end()
+    );
+
+    parts.headers.remove(http::header::CONTENT_LENGTH);
+    parts.headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    Response::from_parts(parts, Body::from(body))
+}
@@ -1,14 +1,76 @@
-use serde::Deserialize;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct AuthRequest {
     pub authorization_code: String,
+    /// PKCE verifier for the challenge sent with the authorization request.
+    pub code_verifier: Option<String>,
+    /// Name of the identity provider the code comes from; the default
+    /// provider when absent.
+    pub provider: Option<String>,
+    /// Keeps the token server side and answers with a session cookie instead.
+    #[serde(default)]
+    pub session: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OEmbedQuery {
+    pub url: String,
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct KeySuggestionQuery {
+    pub target: String,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ListUrl {
+    /// The `next` cursor of a page, continuing past it.
     pub after: Option<String>,
+    /// The `prev` cursor of a page, paging back. Exclusive with `after`.
+    pub before: Option<String>,
     pub limit: Option<u64>,
+    pub state: Option<LinkState>,
+    pub sort: Option<LinkSort>,
+    /// Only links with this tag.
+    pub tag: Option<String>,
+    /// Only links to this URL, as given or once normalized.
+    pub target: Option<String>,
+}
+
+/// Where a page of links starts, named by the key of a link in the order
+/// they are listed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageCursor {
+    After(String),
+    Before(String),
+}
+
+/// The order links are listed in, which the cursors of a list carry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkSort {
+    #[default]
+    Key,
+    /// Least recently accessed first, starting with links never accessed,
+    /// to find stale ones.
+    LastAccessed,
+    /// Pinned links first, each group by key.
+    Pinned,
+    /// Most recently created first.
+    Newest,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkState {
+    #[default]
+    Active,
+    Archived,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -22,6 +84,292 @@ pub struct NewUrl {
     pub target: String,
 }
 
+/// Checks a create or update as if it were made, without making it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DryRunQuery {
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewTemplate {
+    pub name: String,
+    /// The target, with `{name}` placeholders filled in per link.
+    pub target_pattern: String,
+    /// Links created from the template expire this long after creation.
+    pub default_ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewUrlFromTemplate {
+    /// A generated key when absent.
+    pub key: Option<String>,
+    #[serde(default)]
+    pub values: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UtmParams {
+    pub utm_source: String,
+    pub utm_medium: String,
+    pub utm_campaign: String,
+    pub utm_term: Option<String>,
+    pub utm_content: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BuildUtm {
+    pub url: String,
+    #[serde(flatten)]
+    pub params: UtmParams,
+    /// Also creates a short link to the composed target.
+    #[serde(default)]
+    pub shorten: bool,
+    /// A generated key when absent.
+    pub key: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewBioPage {
+    pub handle: String,
+    pub title: String,
+    /// Shown in this order.
+    #[serde(default)]
+    pub links: Vec<NewBioLink>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewBioLink {
+    pub url_id: uuid::Uuid,
+    pub title: String,
+    pub icon_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HandlePathParam {
+    pub handle: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SetNotificationPreferences {
+    pub link_expired: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinkSlackAccount {
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ShareStats {
+    pub ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SharedStatsPathParam {
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TagPathParam {
+    pub tag: String,
+}
+
+/// Links to tag or untag, all of which must belong to the requester.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TagLinks {
+    pub ids: Vec<uuid::Uuid>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RenameTag {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MergeTag {
+    pub into: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SetKeep {
+    pub keep_when_inactive: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SetVisibility {
+    pub public: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListPublicUrl {
+    pub after: Option<String>,
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SetIndexing {
+    pub allow_indexing: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewCampaign {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CampaignPathParam {
+    pub campaign_id: uuid::Uuid,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CampaignStatsQuery {
+    pub period: Option<ReportPeriod>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SetCampaign {
+    /// Takes the link out of its campaign when absent.
+    pub campaign_id: Option<uuid::Uuid>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplatePathParam {
+    pub template_id: uuid::Uuid,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CloneUrl {
+    /// A generated key when absent.
+    pub key: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewRollout {
+    pub target: String,
+    /// Share of the traffic sent to `target`, from 0 to 100.
+    pub percent: u8,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleTarget {
+    pub target: String,
+    /// When the link starts pointing at `target`; must be in the future.
+    pub at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewAnonymousUrl {
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportPeriod {
+    Day,
+    Week,
+    #[default]
+    Month,
+}
+
+impl ReportPeriod {
+    pub fn days(self) -> u64 {
+        match self {
+            Self::Day => 1,
+            Self::Week => 7,
+            Self::Month => 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+/// Filters of the admin link listing, which pages like the owner's.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AdminListUrl {
+    pub owner: Option<String>,
+    /// Links to this domain or its subdomains.
+    pub domain: Option<String>,
+    pub created_after: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_before: Option<chrono::DateTime<chrono::Utc>>,
+    /// Links flagged as inactive, or only those not.
+    pub flagged: Option<bool>,
+    pub state: Option<LinkState>,
+    pub sort: Option<LinkSort>,
+    pub after: Option<String>,
+    pub before: Option<String>,
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UsageReportQuery {
+    pub period: Option<ReportPeriod>,
+    pub format: Option<ReportFormat>,
+}
+
+/// Limits of a plan; a missing limit means unlimited.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlanLimits {
+    pub max_links: Option<u32>,
+    pub max_custom_domains: Option<u32>,
+    /// Clicks on the owner's links are deleted once this many days old.
+    pub analytics_retention_days: Option<u32>,
+}
+
+/// What a tenant overrides of the installation's settings; a missing
+/// setting keeps the installation's.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TenantOverrides {
+    pub allowed_origins: Option<Vec<String>>,
+    /// Links the whole tenant may have, on top of each user's plan.
+    pub max_links: Option<u32>,
+    /// The status of redirects, other than those of links with a rollout.
+    pub redirect_status: Option<u16>,
+    /// Answers unknown keys, with `{{key}}` standing for the key.
+    pub not_found_page: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlanPathParam {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserPathParam {
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AssignPlan {
+    pub plan: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewAlias {
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AliasPathParam {
+    pub id: uuid::Uuid,
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RevisionPathParam {
+    pub id: uuid::Uuid,
+    pub revision: i32,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct RedirectUrlIdPathParam {
     pub id: uuid::Uuid,
@@ -1,6 +1,19 @@
-use serde::Serialize;
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::{
+    link_preview::Preview,
+    models::{
+        bio_page_links, bio_pages, campaigns, link_templates, notification_preferences, plans,
+        scheduled_targets, tenant_settings, url_redirect_aliases, url_redirect_revisions,
+        url_redirects,
+    },
+    requests::{PageCursor, ReportPeriod},
+    rollout::Variant,
+};
+
 #[derive(Debug, Clone, Serialize)]
 pub struct AuthResponse {
     access_token: String,
@@ -14,19 +27,306 @@ impl AuthResponse {
             token_type,
         }
     }
+
+    /// The `Authorization` header value that presents this token.
+    pub fn authorization(&self) -> String {
+        format!("{} {}", self.token_type, self.access_token)
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct MeResponse {
     pub email: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub impersonated_by: Option<String>,
 }
 
 impl MeResponse {
-    pub fn new(email: String) -> Self {
-        Self { email }
+    pub fn new(email: String, impersonated_by: Option<String>) -> Self {
+        Self {
+            email,
+            impersonated_by,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+    period: ReportPeriod,
+    since: DateTime<Utc>,
+    links_created: u64,
+    redirects_served: u64,
+    top_users: Vec<UserUsage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserUsage {
+    email: String,
+    links_created: i64,
+}
+
+impl UsageReport {
+    pub fn new(
+        period: ReportPeriod,
+        since: DateTime<Utc>,
+        links_created: u64,
+        redirects_served: u64,
+        top_users: Vec<(String, i64)>,
+    ) -> Self {
+        Self {
+            period,
+            since,
+            links_created,
+            redirects_served,
+            top_users: top_users
+                .into_iter()
+                .map(|(email, links_created)| UserUsage {
+                    email,
+                    links_created,
+                })
+                .collect(),
+        }
+    }
+
+    /// One `metric,subject,value` row per figure, so the whole report fits a
+    /// single table.
+    pub fn to_csv(&self) -> String {
+        let since = self.since.to_rfc3339();
+        let mut rows = vec![
+            [
+                String::from("links_created"),
+                since.clone(),
+                self.links_created.to_string(),
+            ],
+            [
+                String::from("redirects_served"),
+                since,
+                self.redirects_served.to_string(),
+            ],
+        ];
+        rows.extend(self.top_users.iter().map(|user| {
+            [
+                String::from("top_user_links_created"),
+                user.email.clone(),
+                user.links_created.to_string(),
+            ]
+        }));
+
+        let mut csv = String::from("metric,subject,value\n");
+        for row in rows {
+            let row: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    name: String,
+    max_links: Option<i64>,
+    max_custom_domains: Option<i64>,
+    analytics_retention_days: Option<i64>,
+}
+
+impl From<plans::Model> for Plan {
+    fn from(value: plans::Model) -> Self {
+        Self {
+            name: value.name,
+            max_links: value.max_links,
+            max_custom_domains: value.max_custom_domains,
+            analytics_retention_days: value.analytics_retention_days,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkTemplate {
+    pub id: Uuid,
+    name: String,
+    pub target_pattern: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_ttl_secs: Option<i64>,
+    created_at: DateTime<FixedOffset>,
+}
+
+impl From<link_templates::Model> for LinkTemplate {
+    fn from(value: link_templates::Model) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            target_pattern: value.target_pattern,
+            default_ttl_secs: value.default_ttl_secs,
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UtmResponse {
+    target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    link: Option<UrlRedirect>,
+}
+
+impl UtmResponse {
+    pub fn new(target: String, link: Option<UrlRedirect>) -> Self {
+        Self { target, link }
+    }
+}
+
+/// Links grouped together, say for a coordinated launch.
+#[derive(Debug, Clone, Serialize)]
+pub struct Campaign {
+    pub id: Uuid,
+    name: String,
+    created_at: DateTime<FixedOffset>,
+}
+
+impl From<campaigns::Model> for Campaign {
+    fn from(value: campaigns::Model) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            created_at: value.created_at,
+        }
     }
 }
 
+/// Redirects to any of a campaign's links, per UTC day.
+#[derive(Debug, Clone, Serialize)]
+pub struct CampaignStats {
+    campaign: Campaign,
+    period: ReportPeriod,
+    links: usize,
+    clicks: u64,
+    daily: Vec<DailyClicks>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyClicks {
+    date: NaiveDate,
+    clicks: u64,
+}
+
+impl CampaignStats {
+    pub fn new(
+        campaign: Campaign,
+        period: ReportPeriod,
+        links: usize,
+        daily: Vec<(NaiveDate, u64)>,
+    ) -> Self {
+        Self {
+            campaign,
+            period,
+            links,
+            clicks: daily.iter().map(|(_, clicks)| clicks).sum(),
+            daily: daily
+                .into_iter()
+                .map(|(date, clicks)| DailyClicks { date, clicks })
+                .collect(),
+        }
+    }
+}
+
+/// What a link pointed to before a change, and who changed it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Revision {
+    revision: i32,
+    key: String,
+    target: String,
+    changed_by: String,
+    changed_at: DateTime<FixedOffset>,
+}
+
+impl From<url_redirect_revisions::Model> for Revision {
+    fn from(value: url_redirect_revisions::Model) -> Self {
+        Self {
+            revision: value.revision,
+            key: value.key,
+            target: value.target,
+            changed_by: value.changed_by,
+            changed_at: value.changed_at,
+        }
+    }
+}
+
+/// Another key leading to the same link.
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkAlias {
+    key: String,
+    created_at: DateTime<FixedOffset>,
+}
+
+impl From<url_redirect_aliases::Model> for LinkAlias {
+    fn from(value: url_redirect_aliases::Model) -> Self {
+        Self {
+            key: value.key,
+            created_at: value.created_at,
+        }
+    }
+}
+
+/// How often a cache had what was asked of it, on this instance since it
+/// started.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CacheStats {
+    hits: u64,
+    misses: u64,
+    /// Hits out of all lookups; absent before the first.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hit_rate: Option<f64>,
+}
+
+impl CacheStats {
+    pub fn new(hits: u64, misses: u64) -> Self {
+        let lookups = hits + misses;
+        Self {
+            hits,
+            misses,
+            hit_rate: (lookups > 0).then(|| hits as f64 / lookups as f64),
+        }
+    }
+}
+
+/// Clients caught scanning for keys, on this instance since it started.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ScanGuardStats {
+    pub flagged_clients: u64,
+    pub honeypot_hits: u64,
+    /// Requests throttled or tarpitted because their client was flagged.
+    pub slowed_requests: u64,
+}
+
+/// Totals across all users, and how this instance's caches and queues fare.
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemOverview {
+    pub links: u64,
+    pub archived_links: u64,
+    /// Owners of at least one link.
+    pub users: u64,
+    pub redirects_last_24h: u64,
+    /// Absent when links are not cached.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link_cache: Option<CacheStats>,
+    pub link_preview_cache: CacheStats,
+    /// Clicks waiting to be written, out of how many the buffer holds.
+    pub pending_clicks: usize,
+    pub click_buffer_capacity: usize,
+    /// Absent when scanning clients are not looked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan_guard: Option<ScanGuardStats>,
+}
+
 pub trait CursorDefault {
     fn id(&self) -> String;
 }
@@ -35,20 +335,443 @@ pub trait CursorDefault {
 pub struct PagedResponse<T> {
     data: Vec<T>,
     last: Option<String>,
+    /// The `before` cursor of the previous page, if there is one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prev: Option<String>,
+    /// The `after` cursor of the next page, if there is one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next: Option<String>,
 }
 
 impl<T: CursorDefault> PagedResponse<T> {
     pub fn new(data: Vec<T>) -> Self {
         let last = data.last().map(CursorDefault::id);
-        Self { data, last }
+        Self {
+            data,
+            last,
+            prev: None,
+            next: None,
+        }
+    }
+
+    /// A page of a list that can be paged both ways, with the cursors
+    /// `cursor` makes for the pages around it; `last` continues past it as
+    /// `next` does, whether or not there is more.
+    pub fn with_cursors(
+        data: Vec<T>,
+        has_prev: bool,
+        has_next: bool,
+        cursor: impl Fn(PageCursor) -> String,
+    ) -> Self {
+        let last = data.last().map(|item| cursor(PageCursor::After(item.id())));
+        let prev = data
+            .first()
+            .filter(|_| has_prev)
+            .map(|item| cursor(PageCursor::Before(item.id())));
+        Self {
+            data,
+            next: last.clone().filter(|_| has_next),
+            last,
+            prev,
+        }
     }
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct UrlRedirect {
-    id: Uuid,
+    pub id: Uuid,
+    pub key: String,
+    pub target: String,
+    /// The link to share: the key's redirect path under the public base URL,
+    /// or just that path when it is not configured.
+    pub short_url: String,
+    /// Who the link belongs to; links made without signing in have no one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owner: Option<String>,
+    created_at: DateTime<FixedOffset>,
+    updated_at: DateTime<FixedOffset>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<FixedOffset>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rollout: Option<Rollout>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archived_at: Option<DateTime<FixedOffset>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    campaign_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_links: Option<AppLinks>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub social_preview: Option<SocialPreview>,
+    /// Lets search engines index the link, which redirects forbid by
+    /// default. Only public links may allow it.
+    pub allow_indexing: bool,
+    /// Listed in the public directory.
+    pub public: bool,
+    /// When the link last redirected someone, as of the last click flush.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_accessed_at: Option<DateTime<FixedOffset>>,
+    /// Redirects served over the link's life, as of the last write-back of
+    /// the click counters.
+    pub clicks: u64,
+    /// Since when the link counts as inactive; it is archived once the grace
+    /// period runs out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inactive_since: Option<DateTime<FixedOffset>>,
+    /// Exempts the link from being archived for inactivity.
+    pub keep_when_inactive: bool,
+    /// Listed before the owner's other links when sorting by pins.
+    pub pinned: bool,
+}
+
+/// What a create or update would do, answered by dry runs instead of doing it.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DryRunAction {
+    Create,
+    /// The key or target changes, which records a revision.
+    Update,
+    Unchanged,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DryRun {
+    action: DryRunAction,
+    key: String,
+    target: String,
+}
+
+impl DryRun {
+    pub fn new(action: DryRunAction, key: String, target: String) -> Self {
+        Self {
+            action,
+            key,
+            target,
+        }
+    }
+}
+
+/// One of the owner's tags and how many of their links carry it.
+#[derive(Debug, Clone, Serialize)]
+pub struct TagSummary {
+    tag: String,
+    links: u64,
+}
+
+impl TagSummary {
+    pub fn new(tag: String, links: u64) -> Self {
+        Self { tag, links }
+    }
+}
+
+/// The outcome of a bulk tag operation: the tag, and how many links it
+/// changed.
+#[derive(Debug, Clone, Serialize)]
+pub struct TagChange {
+    tag: String,
+    changed: u64,
+}
+
+impl TagChange {
+    pub fn new(tag: String, changed: u64) -> Self {
+        Self { tag, changed }
+    }
+}
+
+/// Which emails a user gets; everything is on until they say otherwise.
+#[derive(Debug, Clone, Serialize)]
+pub struct NotificationPreferences {
+    pub link_expired: bool,
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self { link_expired: true }
+    }
+}
+
+impl From<notification_preferences::Model> for NotificationPreferences {
+    fn from(value: notification_preferences::Model) -> Self {
+        Self {
+            link_expired: value.link_expired,
+        }
+    }
+}
+
+/// The settings a tenant overrides; those left out are the installation's.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TenantSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_origins: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_links: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_found_page: Option<String>,
+}
+
+impl From<tenant_settings::Model> for TenantSettings {
+    fn from(value: tenant_settings::Model) -> Self {
+        Self {
+            allowed_origins: value
+                .allowed_origins
+                .map(|origins| origins.lines().map(String::from).collect()),
+            max_links: value.max_links,
+            redirect_status: value
+                .redirect_status
+                .and_then(|status| u16::try_from(status).ok()),
+            not_found_page: value.not_found_page,
+        }
+    }
+}
+
+/// A slash-command reply; ephemeral ones are shown to the invoking user only.
+#[derive(Debug, Clone, Serialize)]
+pub struct SlackReply {
+    response_type: &'static str,
+    text: String,
+}
+
+impl SlackReply {
+    pub fn ephemeral(text: impl Into<String>) -> Self {
+        Self {
+            response_type: "ephemeral",
+            text: text.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SharedStats {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+impl SharedStats {
+    pub fn new(token: String, expires_at: DateTime<Utc>) -> Self {
+        Self { token, expires_at }
+    }
+}
+
+/// Redirects to one link per UTC day.
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkStats {
+    key: String,
+    target: String,
+    period: ReportPeriod,
+    clicks: u64,
+    daily: Vec<DailyClicks>,
+}
+
+impl LinkStats {
+    pub fn new(link: UrlRedirect, period: ReportPeriod, daily: Vec<(NaiveDate, u64)>) -> Self {
+        Self {
+            key: link.key,
+            target: link.target,
+            period,
+            clicks: daily.iter().map(|(_, clicks)| clicks).sum(),
+            daily: daily
+                .into_iter()
+                .map(|(date, clicks)| DailyClicks { date, clicks })
+                .collect(),
+        }
+    }
+}
+
+/// An oEmbed `link` response, see <https://oembed.com>.
+#[derive(Debug, Clone, Serialize)]
+pub struct OEmbed {
+    version: &'static str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    provider_name: String,
+    provider_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumbnail_url: Option<String>,
+}
+
+impl OEmbed {
+    pub fn new(target: &url::Url, preview: Preview) -> Self {
+        let origin = target.origin().ascii_serialization();
+        Self {
+            version: "1.0",
+            kind: "link",
+            title: preview.title,
+            provider_name: preview
+                .site_name
+                .or_else(|| target.host_str().map(String::from))
+                .unwrap_or_else(|| origin.clone()),
+            provider_url: origin,
+            thumbnail_url: preview.image,
+        }
+    }
+}
+
+/// Where a link goes, for checking before following it.
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkPreview {
+    key: String,
+    target: String,
+    title: Option<String>,
+    site_name: Option<String>,
+    image: Option<String>,
+}
+
+impl LinkPreview {
+    pub fn new(key: String, target: String, preview: Preview) -> Self {
+        Self {
+            key,
+            target,
+            title: preview.title,
+            site_name: preview.site_name,
+            image: preview.image,
+        }
+    }
+}
+
+/// A user's hosted page of selected links.
+#[derive(Debug, Clone, Serialize)]
+pub struct BioPage {
+    pub handle: String,
+    pub title: String,
+    pub links: Vec<BioLink>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BioLink {
+    url_id: Uuid,
+    pub key: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_url: Option<String>,
+}
+
+impl BioPage {
+    pub fn new(page: bio_pages::Model, links: Vec<BioLink>) -> Self {
+        Self {
+            handle: page.handle,
+            title: page.title,
+            links,
+        }
+    }
+}
+
+impl BioLink {
+    pub fn new(link: bio_page_links::Model, key: String) -> Self {
+        Self {
+            url_id: link.url_redirect_id,
+            key,
+            title: link.title,
+            icon_url: link.icon_url,
+        }
+    }
+}
+
+/// A link as shown in the public directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct PublicLink {
     key: String,
+    target: String,
+}
+
+impl From<url_redirects::Model> for PublicLink {
+    fn from(value: url_redirects::Model) -> Self {
+        Self {
+            key: value.key,
+            target: value.target,
+        }
+    }
+}
+
+impl CursorDefault for PublicLink {
+    fn id(&self) -> String {
+        self.key.clone()
+    }
+}
+
+/// Apps to open instead of the target on mobile devices.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AppLinks {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ios: Option<AppLink>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub android: Option<AppLink>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppLink {
+    /// A universal link, custom scheme URL or Android intent URL.
+    pub deep_link: String,
+    /// Where to go when the app is not installed, usually its store page;
+    /// the link's target when absent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_url: Option<String>,
+}
+
+/// What social platforms show when the link is shared, instead of what the
+/// target page says about itself.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SocialPreview {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// An http or https URL of the image.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+impl SocialPreview {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.description.is_none() && self.image.is_none()
+    }
+}
+
+/// A share of the traffic going to a new target while the link migrates.
+#[derive(Debug, Clone, Serialize)]
+pub struct Rollout {
     pub target: String,
+    pub percent: u8,
+}
+
+/// A target the link switches to at a set time, and who set it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduledTarget {
+    target: String,
+    apply_at: DateTime<FixedOffset>,
+    scheduled_by: String,
+    scheduled_at: DateTime<FixedOffset>,
+}
+
+impl From<scheduled_targets::Model> for ScheduledTarget {
+    fn from(value: scheduled_targets::Model) -> Self {
+        Self {
+            target: value.target,
+            apply_at: value.apply_at,
+            scheduled_by: value.scheduled_by,
+            scheduled_at: value.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RolloutStatus {
+    #[serde(flatten)]
+    rollout: Rollout,
+    current_target_clicks: u64,
+    rollout_target_clicks: u64,
+}
+
+impl RolloutStatus {
+    pub fn new(rollout: Rollout, (current, rollout_clicks): (u64, u64)) -> Self {
+        Self {
+            rollout,
+            current_target_clicks: current,
+            rollout_target_clicks: rollout_clicks,
+        }
+    }
 }
 
 impl CursorDefault for UrlRedirect {
@@ -58,7 +781,118 @@ impl CursorDefault for UrlRedirect {
 }
 
 impl UrlRedirect {
-    pub fn new(id: Uuid, key: String, target: String) -> Self {
-        Self { id, key, target }
+    pub fn new(
+        id: Uuid,
+        key: String,
+        short_url: String,
+        target: String,
+        created_at: DateTime<FixedOffset>,
+        updated_at: DateTime<FixedOffset>,
+        expires_at: Option<DateTime<FixedOffset>>,
+    ) -> Self {
+        Self {
+            id,
+            key,
+            short_url,
+            target,
+            owner: None,
+            created_at,
+            updated_at,
+            expires_at,
+            rollout: None,
+            archived_at: None,
+            campaign_id: None,
+            app_links: None,
+            social_preview: None,
+            allow_indexing: false,
+            public: false,
+            last_accessed_at: None,
+            clicks: 0,
+            inactive_since: None,
+            keep_when_inactive: false,
+            pinned: false,
+        }
+    }
+
+    pub fn with_clicks(mut self, clicks: u64) -> Self {
+        self.clicks = clicks;
+        self
+    }
+
+    pub fn with_last_accessed_at(
+        mut self,
+        last_accessed_at: Option<DateTime<FixedOffset>>,
+    ) -> Self {
+        self.last_accessed_at = last_accessed_at;
+        self
+    }
+
+    pub fn with_owner(mut self, owner: Option<String>) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    pub fn with_archived_at(mut self, archived_at: Option<DateTime<FixedOffset>>) -> Self {
+        self.archived_at = archived_at;
+        self
+    }
+
+    pub fn with_campaign_id(mut self, campaign_id: Option<Uuid>) -> Self {
+        self.campaign_id = campaign_id;
+        self
+    }
+
+    pub fn with_public(mut self, public: bool) -> Self {
+        self.public = public;
+        self
+    }
+
+    pub fn with_allow_indexing(mut self, allow_indexing: bool) -> Self {
+        self.allow_indexing = allow_indexing;
+        self
+    }
+
+    pub fn with_inactivity(
+        mut self,
+        inactive_since: Option<DateTime<FixedOffset>>,
+        keep_when_inactive: bool,
+    ) -> Self {
+        self.inactive_since = inactive_since;
+        self.keep_when_inactive = keep_when_inactive;
+        self
+    }
+
+    pub fn with_pinned(mut self, pinned: bool) -> Self {
+        self.pinned = pinned;
+        self
+    }
+
+    pub fn with_app_links(mut self, app_links: Option<AppLinks>) -> Self {
+        self.app_links = app_links;
+        self
+    }
+
+    pub fn with_social_preview(mut self, social_preview: Option<SocialPreview>) -> Self {
+        self.social_preview = social_preview;
+        self
+    }
+
+    pub fn with_rollout(mut self, rollout: Option<Rollout>) -> Self {
+        self.rollout = rollout;
+        self
+    }
+
+    pub fn expires_at(&self) -> Option<DateTime<FixedOffset>> {
+        self.expires_at
+    }
+
+    /// The target for one redirect, drawn according to the rollout share.
+    pub fn pick_target(&self) -> (&str, Variant) {
+        match &self.rollout {
+            Some(rollout) if rand::thread_rng().gen_range(0..100) < rollout.percent => {
+                (&rollout.target, Variant::Rollout)
+            }
+            _ => (&self.target, Variant::Current),
+        }
     }
 }
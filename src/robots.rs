@@ -0,0 +1,32 @@
+use std::path::Path;
+
+use axum::response::Response;
+use http::{HeaderName, HeaderValue};
+
+pub const X_ROBOTS_TAG: HeaderName = HeaderName::from_static("x-robots-tag");
+
+// Crawlers have to fetch a link to see its `X-Robots-Tag`, and a link they
+// may not fetch can still be indexed from the pages linking to it, so
+// nothing is disallowed by default.
+const DEFAULT_ROBOTS_TXT: &str = "User-agent: *\nDisallow:\n";
+
+/// The `/robots.txt` to serve, read once at startup.
+pub fn load(path: Option<&Path>) -> std::io::Result<String> {
+    let Some(path) = path else {
+        return Ok(String::from(DEFAULT_ROBOTS_TXT));
+    };
+
+    std::fs::read_to_string(path).map_err(|error| {
+        std::io::Error::new(
+            error.kind(),
+            format!("cannot read {}: {error}", path.display()),
+        )
+    })
+}
+
+/// Asks search engines to keep `response` out of their index.
+pub fn noindex(response: &mut Response) {
+    response
+        .headers_mut()
+        .insert(X_ROBOTS_TAG, HeaderValue::from_static("noindex"));
+}
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use redis::AsyncCommands;
+use uuid::Uuid;
+
+use crate::{kvs::KvsPool, rate_limit::RateLimitError};
+
+/// Which target of a link under rollout a redirect went to.
+#[derive(Debug, Clone, Copy)]
+pub enum Variant {
+    Current,
+    Rollout,
+}
+
+impl Variant {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Current => "current",
+            Self::Rollout => "rollout",
+        }
+    }
+}
+
+/// Per-variant redirect counts of links under rollout.
+pub struct RolloutClicks {
+    kvs_pool: Arc<KvsPool>,
+}
+
+impl RolloutClicks {
+    pub fn new(kvs_pool: Arc<KvsPool>) -> Self {
+        Self { kvs_pool }
+    }
+
+    pub async fn record(&self, id: Uuid, variant: Variant) -> Result<(), RateLimitError> {
+        let mut conn = self.kvs_pool.get().await?;
+        conn.hincr(clicks_key(id), variant.as_str(), 1)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Redirects to the current and the rollout target since the rollout started.
+    pub async fn counts(&self, id: Uuid) -> Result<(u64, u64), RateLimitError> {
+        let mut conn = self.kvs_pool.get().await?;
+        let (current, rollout): (Option<u64>, Option<u64>) = redis::cmd("HMGET")
+            .arg(clicks_key(id))
+            .arg(Variant::Current.as_str())
+            .arg(Variant::Rollout.as_str())
+            .query_async(&mut conn)
+            .await?;
+
+        Ok((current.unwrap_or(0), rollout.unwrap_or(0)))
+    }
+
+    /// Starts counting from zero, for a new rollout.
+    pub async fn reset(&self, id: Uuid) -> Result<(), RateLimitError> {
+        let mut conn = self.kvs_pool.get().await?;
+        conn.del(clicks_key(id)).await.map_err(Into::into)
+    }
+}
+
+fn clicks_key(id: Uuid) -> String {
+    format!("rollout-clicks:{id}")
+}
@@ -0,0 +1,265 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    routing::{get, put},
+    Json, Router,
+};
+use http::{header::CONTENT_TYPE, StatusCode};
+
+use crate::{
+    authenthication::Admin,
+    error::{problem, ProblemType},
+    maintenance::MaintenanceState,
+    requests::{
+        AdminListUrl, AssignPlan, PlanLimits, PlanPathParam, ReportFormat, TenantOverrides,
+        UsageReportQuery, UserPathParam,
+    },
+    responses::{PagedResponse, Plan, SystemOverview, TenantSettings, UrlRedirect, UsageReport},
+    service::LinkFilter,
+    tenant, usage, Services,
+};
+
+/// Statuses a tenant may have its redirects answered with.
+const REDIRECT_STATUSES: [u16; 4] = [301, 302, 307, 308];
+
+/// Usage reports, everyone's links, plans, tenant settings and maintenance
+/// mode, for admins only.
+pub fn router() -> Router<Arc<Services>> {
+    Router::new()
+        .route("/admin/reports/usage", get(usage_report))
+        .route("/admin/overview", get(overview))
+        .route("/admin/urls", get(list_urls))
+        .route("/admin/plans", get(list_plans))
+        .route("/admin/plans/:name", put(save_plan))
+        .route("/admin/users/:email/plan", get(user_plan).put(assign_plan))
+        .route(
+            "/admin/tenant/settings",
+            get(tenant_settings).put(save_tenant_settings),
+        )
+        .route(
+            "/admin/maintenance",
+            get(get_maintenance).put(set_maintenance),
+        )
+}
+
+async fn usage_report(
+    admin: Admin,
+    service: State<Arc<Services>>,
+    Query(query): Query<UsageReportQuery>,
+) -> Result<Response, Response> {
+    let period = query.period.unwrap_or_default();
+    tracing::info!(target: "audit", admin = admin.email, ?period, "usage report");
+    let since = usage::period_start(period.days());
+
+    let links_created = service.url.count_created_since(since).await?;
+    let top_users = service.url.top_creators_since(since, 10).await?;
+    let redirects_served = service.redirects.served_since(since).await?;
+    let report = UsageReport::new(period, since, links_created, redirects_served, top_users);
+
+    match query.format.unwrap_or_default() {
+        ReportFormat::Json => Ok(Json(report).into_response()),
+        ReportFormat::Csv => Ok(([(CONTENT_TYPE, "text/csv")], report.to_csv()).into_response()),
+    }
+}
+
+async fn overview(
+    _admin: Admin,
+    service: State<Arc<Services>>,
+) -> Result<Json<SystemOverview>, Response> {
+    let (links, archived_links, users) = service.url.link_totals().await?;
+    let redirects_last_24h = service.redirects.served_last_day().await?;
+
+    Ok(Json(SystemOverview {
+        links,
+        archived_links,
+        users,
+        redirects_last_24h,
+        link_cache: service.link_cache.as_ref().map(|cache| cache.stats()),
+        link_preview_cache: service.link_previews.stats(),
+        pending_clicks: service.clicks.pending(),
+        click_buffer_capacity: service.clicks.capacity(),
+        scan_guard: service.scan_guard.as_ref().map(|guard| guard.stats()),
+    }))
+}
+
+async fn list_urls(
+    admin: Admin,
+    service: State<Arc<Services>>,
+    Query(query): Query<AdminListUrl>,
+) -> Result<Json<PagedResponse<UrlRedirect>>, Response> {
+    let (sort, cursor) = service
+        .page_cursors
+        .resolve(query.after.as_deref(), query.before.as_deref(), query.sort)
+        .map_err(|reason| problem(ProblemType::ValidationFailed, reason))?;
+    let domain = query
+        .domain
+        .map(|domain| domain.trim().to_ascii_lowercase());
+    if domain.as_ref().is_some_and(|domain| {
+        domain.is_empty()
+            || !domain
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+    }) {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "domains are letters, digits, `-` and `.`",
+        ));
+    }
+    tracing::info!(target: "audit", admin = admin.email, owner = query.owner, domain, "links listed");
+
+    let page = service
+        .url
+        .list_all(
+            query.owner.as_deref(),
+            LinkFilter {
+                state: query.state.unwrap_or_default(),
+                domain,
+                created_after: query.created_after,
+                created_before: query.created_before,
+                flagged: query.flagged,
+                ..Default::default()
+            },
+            sort,
+            cursor,
+            query.limit.unwrap_or(50),
+        )
+        .await?;
+
+    Ok(Json(PagedResponse::with_cursors(
+        page.items,
+        page.has_prev,
+        page.has_next,
+        |cursor| service.page_cursors.sign(sort, cursor),
+    )))
+}
+
+async fn list_plans(
+    _admin: Admin,
+    service: State<Arc<Services>>,
+) -> Result<Json<Vec<Plan>>, Response> {
+    Ok(Json(service.url.list_plans().await?))
+}
+
+async fn save_plan(
+    admin: Admin,
+    service: State<Arc<Services>>,
+    Path(PlanPathParam { name }): Path<PlanPathParam>,
+    Json(limits): Json<PlanLimits>,
+) -> Result<Json<Plan>, Response> {
+    let valid_name = !name.is_empty()
+        && name.len() <= 50
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid_name {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "plan names are 1 to 50 letters, digits, `-` or `_`",
+        ));
+    }
+
+    tracing::info!(target: "audit", admin = admin.email, plan = name, ?limits, "plan saved");
+    Ok(Json(service.url.save_plan(name, limits).await?))
+}
+
+async fn user_plan(
+    _admin: Admin,
+    service: State<Arc<Services>>,
+    Path(UserPathParam { email }): Path<UserPathParam>,
+) -> Result<Json<Plan>, Response> {
+    Ok(Json(service.url.user_plan(&email).await?))
+}
+
+async fn assign_plan(
+    admin: Admin,
+    service: State<Arc<Services>>,
+    Path(UserPathParam { email }): Path<UserPathParam>,
+    Json(AssignPlan { plan }): Json<AssignPlan>,
+) -> Result<Json<Plan>, Response> {
+    tracing::info!(target: "audit", admin = admin.email, user = email, plan, "plan assigned");
+    service
+        .url
+        .assign_plan(email, plan)
+        .await?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "plan not found").into_response())
+        .map(Json)
+}
+
+fn not_a_tenant() -> Response {
+    (StatusCode::NOT_FOUND, "not serving a tenant").into_response()
+}
+
+/// What the tenant the request is served for overrides.
+async fn tenant_settings(
+    _admin: Admin,
+    service: State<Arc<Services>>,
+) -> Result<Json<TenantSettings>, Response> {
+    let tenant = tenant::current().ok_or_else(not_a_tenant)?;
+    Ok(Json(service.url.tenant_settings(&tenant.id).await?))
+}
+
+async fn save_tenant_settings(
+    admin: Admin,
+    service: State<Arc<Services>>,
+    Json(overrides): Json<TenantOverrides>,
+) -> Result<Json<TenantSettings>, Response> {
+    let (Some(tenants), Some(tenant)) = (&service.tenants, tenant::current()) else {
+        return Err(not_a_tenant());
+    };
+
+    // an origin is exactly what browsers send: scheme, host and port only
+    let invalid_origin = overrides.allowed_origins.iter().flatten().find(|origin| {
+        url::Url::parse(origin).map_or(true, |url| {
+            !matches!(url.scheme(), "http" | "https")
+                || url.origin().ascii_serialization() != origin.as_str()
+        })
+    });
+    if let Some(origin) = invalid_origin {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            format!("`{origin}` is not an http or https origin"),
+        ));
+    }
+    if overrides
+        .redirect_status
+        .is_some_and(|status| !REDIRECT_STATUSES.contains(&status))
+    {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "redirect_status must be 301, 302, 307 or 308",
+        ));
+    }
+
+    tracing::info!(
+        target: "audit",
+        admin = admin.email,
+        tenant = tenant.id,
+        allowed_origins = ?overrides.allowed_origins,
+        max_links = overrides.max_links,
+        redirect_status = overrides.redirect_status,
+        not_found_page = overrides.not_found_page.is_some(),
+        "tenant settings saved"
+    );
+    let settings = service
+        .url
+        .save_tenant_settings(tenant.id.clone(), overrides)
+        .await?;
+    tenants.forget_settings(&tenant.id);
+    Ok(Json(settings))
+}
+
+async fn get_maintenance(_admin: Admin, service: State<Arc<Services>>) -> Json<MaintenanceState> {
+    Json(service.maintenance.current())
+}
+
+async fn set_maintenance(
+    admin: Admin,
+    service: State<Arc<Services>>,
+    Json(state): Json<MaintenanceState>,
+) -> Result<Json<MaintenanceState>, Response> {
+    tracing::info!(target: "audit", admin = admin.email, ?state, "maintenance mode set");
+    service.maintenance.set(state).await?;
+    Ok(Json(state))
+}
@@ -0,0 +1,146 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use http::{header::SET_COOKIE, StatusCode};
+
+use crate::{
+    authenthication::{BearerToken, Requester},
+    client_ip::ClientIp,
+    csrf,
+    request_id::CurrentRequestId,
+    requests::{AuthRequest, SetNotificationPreferences},
+    responses::{MeResponse, NotificationPreferences},
+    session::{self, Session},
+    Services,
+};
+
+/// Signing in and out, and what the signed-in user can see of themselves.
+pub fn router() -> Router<Arc<Services>> {
+    Router::new()
+        .route("/auth/callback", post(auth_callback))
+        .route("/auth/logout", post(logout))
+        .route("/me", get(me_handler))
+        .route(
+            "/me/notifications",
+            get(get_notification_preferences).put(set_notification_preferences),
+        )
+}
+
+async fn auth_callback(
+    service: State<Arc<Services>>,
+    CurrentRequestId(request_id): CurrentRequestId,
+    ClientIp(client_ip): ClientIp,
+    Json(AuthRequest {
+        authorization_code,
+        code_verifier,
+        provider,
+        session,
+    }): Json<AuthRequest>,
+) -> Result<Response, Response> {
+    let sessions = match (session, service.auth.sessions()) {
+        (false, _) => None,
+        (true, Some(sessions)) => Some(sessions),
+        (true, None) => {
+            return Err((StatusCode::BAD_REQUEST, "cookie sessions are not enabled").into_response())
+        }
+    };
+
+    let access_token = service
+        .auth
+        .guarded(
+            client_ip,
+            service.auth.exchange_token(
+                provider.as_deref(),
+                &authorization_code,
+                code_verifier.as_deref(),
+                request_id.as_deref(),
+            ),
+        )
+        .await?;
+
+    let Some(sessions) = sessions else {
+        return Ok(Json(access_token).into_response());
+    };
+    let session_id = sessions
+        .create(&Session {
+            authorization: access_token.authorization(),
+            provider,
+        })
+        .await?;
+
+    Ok((
+        StatusCode::NO_CONTENT,
+        [
+            (SET_COOKIE, sessions.cookie(&session_id)),
+            (SET_COOKIE, sessions.csrf_cookie()),
+        ],
+    )
+        .into_response())
+}
+
+async fn logout(
+    BearerToken {
+        authorization,
+        provider,
+        session_id,
+    }: BearerToken,
+    service: State<Arc<Services>>,
+    CurrentRequestId(request_id): CurrentRequestId,
+) -> Result<Response, Response> {
+    service
+        .auth
+        .logout(&authorization, provider.as_deref(), request_id.as_deref())
+        .await?;
+
+    match (session_id, service.auth.sessions()) {
+        (Some(session_id), Some(sessions)) => {
+            sessions.delete(&session_id).await?;
+            Ok((
+                StatusCode::NO_CONTENT,
+                [
+                    (SET_COOKIE, session::expired_cookie()),
+                    (SET_COOKIE, csrf::cookie("", 0)),
+                ],
+            )
+                .into_response())
+        }
+        _ => Ok(StatusCode::NO_CONTENT.into_response()),
+    }
+}
+
+async fn me_handler(requester: Requester) -> Result<Json<MeResponse>, Response> {
+    Ok(Json(MeResponse::new(
+        requester.email,
+        requester.impersonated_by,
+    )))
+}
+
+async fn get_notification_preferences(
+    requester: Requester,
+    service: State<Arc<Services>>,
+) -> Result<Json<NotificationPreferences>, Response> {
+    service
+        .url
+        .notification_preferences(&requester.email)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+async fn set_notification_preferences(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Json(preferences): Json<SetNotificationPreferences>,
+) -> Result<Json<NotificationPreferences>, Response> {
+    service
+        .url
+        .set_notification_preferences(requester.email, preferences.link_expired)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
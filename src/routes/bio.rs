@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use http::StatusCode;
+
+use crate::{
+    authenthication::Requester,
+    error::{problem, ProblemType},
+    requests::NewBioPage,
+    responses::BioPage,
+    routes::found,
+    service::is_key_char,
+    Services,
+};
+
+/// Editing the bio page of whoever is signed in.
+pub fn router() -> Router<Arc<Services>> {
+    Router::new().route(
+        "/bio",
+        get(get_bio_page).put(save_bio_page).delete(delete_bio_page),
+    )
+}
+
+async fn get_bio_page(
+    requester: Requester,
+    service: State<Arc<Services>>,
+) -> Result<Json<BioPage>, Response> {
+    service
+        .url
+        .get_bio_page(&requester.email)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+const BIO_HANDLE_LENGTH: std::ops::RangeInclusive<usize> = 3..=32;
+
+async fn save_bio_page(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Json(page): Json<NewBioPage>,
+) -> Result<Json<BioPage>, Response> {
+    if !BIO_HANDLE_LENGTH.contains(&page.handle.len()) || !page.handle.chars().all(is_key_char) {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "handle must be 3 to 32 letters, digits, `-` or `_`",
+        ));
+    }
+    let valid_icons = page.links.iter().all(|link| {
+        link.icon_url.as_ref().is_none_or(|icon_url| {
+            url::Url::parse(icon_url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+        })
+    });
+    if !valid_icons {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "icon_url must be an http or https URL",
+        ));
+    }
+
+    service
+        .url
+        .save_bio_page(&requester.email, page)
+        .await?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "link not found").into_response())
+        .map(Json)
+}
+
+async fn delete_bio_page(
+    requester: Requester,
+    service: State<Arc<Services>>,
+) -> Result<Json<BioPage>, Response> {
+    service
+        .url
+        .delete_bio_page(&requester.email)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use http::StatusCode;
+
+use crate::{
+    authenthication::Requester,
+    error::{problem, ProblemType},
+    requests::{BuildUtm, CampaignPathParam, CampaignStatsQuery, NewCampaign},
+    responses::{Campaign, CampaignStats, UtmResponse},
+    routes::found,
+    service::NewUrlRedirect,
+    usage, utm, Services,
+};
+
+/// Campaigns, their stats and the UTM builder.
+pub fn router() -> Router<Arc<Services>> {
+    Router::new()
+        .route("/campaigns", get(get_campaigns).post(new_campaign))
+        .route("/campaigns/:campaign_id", delete(delete_campaign))
+        .route("/campaigns/:campaign_id/stats", get(campaign_stats))
+        .route("/tools/utm", post(build_utm))
+}
+
+async fn get_campaigns(
+    requester: Requester,
+    service: State<Arc<Services>>,
+) -> Result<Json<Vec<Campaign>>, Response> {
+    Ok(Json(service.url.list_campaigns(&requester.email).await?))
+}
+
+async fn new_campaign(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Json(NewCampaign { name }): Json<NewCampaign>,
+) -> Result<(StatusCode, Json<Campaign>), Response> {
+    let campaign = service.url.create_campaign(requester.email, name).await?;
+    Ok((StatusCode::CREATED, Json(campaign)))
+}
+
+async fn delete_campaign(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(CampaignPathParam { campaign_id }): Path<CampaignPathParam>,
+) -> Result<Json<Campaign>, Response> {
+    service
+        .url
+        .delete_campaign(&requester.email, campaign_id)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn campaign_stats(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(CampaignPathParam { campaign_id }): Path<CampaignPathParam>,
+    Query(query): Query<CampaignStatsQuery>,
+) -> Result<Json<CampaignStats>, Response> {
+    let campaign = service
+        .url
+        .get_campaign(&requester.email, campaign_id)
+        .await?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())?;
+
+    let period = query.period.unwrap_or_default();
+    let links = service.url.campaign_link_ids(campaign.id).await?;
+    let daily = service
+        .redirects
+        .served_daily(&links, usage::period_start(period.days()))
+        .await?;
+
+    Ok(Json(CampaignStats::new(
+        campaign,
+        period,
+        links.len(),
+        daily,
+    )))
+}
+
+async fn build_utm(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Json(request): Json<BuildUtm>,
+) -> Result<Json<UtmResponse>, Response> {
+    let Some(base) = url::Url::parse(&request.url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+    else {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "url must be an http or https URL",
+        ));
+    };
+    let required = [
+        &request.params.utm_source,
+        &request.params.utm_medium,
+        &request.params.utm_campaign,
+    ];
+    if required.iter().any(|value| value.trim().is_empty()) {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "utm_source, utm_medium and utm_campaign must not be empty",
+        ));
+    }
+
+    let target = utm::compose(&base, &request.params).to_string();
+    if !request.shorten {
+        return Ok(Json(UtmResponse::new(target, None)));
+    }
+
+    let url = match request.key {
+        Some(key) => {
+            service
+                .url
+                .create(NewUrlRedirect::new(
+                    requester.email,
+                    service.url.key_policy().parse(key)?,
+                    target.clone(),
+                ))
+                .await?
+        }
+        None => {
+            service
+                .url
+                .create_with_generated_key(requester.email, target.clone(), None)
+                .await?
+        }
+    };
+    Ok(Json(UtmResponse::new(target, Some(url))))
+}
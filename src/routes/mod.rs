@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use axum::{
+    response::{IntoResponse, Response},
+    Router,
+};
+use http::StatusCode;
+
+use crate::Services;
+
+mod admin;
+mod auth;
+mod bio;
+mod campaigns;
+mod redirect;
+mod slack;
+mod stats;
+mod tags;
+mod templates;
+mod urls;
+
+/// The routes of a single path segment, which a key would have to be to
+/// clash with one: keys never contain a slash.
+const SINGLE_SEGMENT_ROUTES: [&str; 9] = [
+    "bio",
+    "campaigns",
+    "me",
+    "oembed",
+    "robots.txt",
+    "shorten",
+    "tags",
+    "templates",
+    "urls",
+];
+
+/// Keys whose redirect path under `redirect_prefix` is taken by a route,
+/// which wins over redirects, so links must not be given them. Only redirects
+/// served at the root share their paths with routes; a prefix is meant to
+/// keep them apart, as the default does.
+pub fn shadowed_keys(redirect_prefix: &str) -> Vec<String> {
+    if !redirect_prefix.is_empty() {
+        return Vec::new();
+    }
+    SINGLE_SEGMENT_ROUTES.map(String::from).to_vec()
+}
+
+/// The thing looked up, or a 404 when there is none.
+// handlers fail with the `Response` to send, whatever its size
+#[allow(clippy::result_large_err)]
+fn found<T>(value: Option<T>) -> Result<T, Response> {
+    value.ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())
+}
+
+/// What the redirect port serves.
+pub fn redirects() -> Router<Arc<Services>> {
+    redirect::router()
+}
+
+/// The management API, one router per domain.
+pub fn management() -> Router<Arc<Services>> {
+    Router::new()
+        .merge(auth::router())
+        .merge(urls::router())
+        .merge(templates::router())
+        .merge(campaigns::router())
+        .merge(stats::router())
+        .merge(tags::router())
+        .merge(bio::router())
+        .merge(slack::router())
+        .merge(admin::router())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, fs, path::Path};
+
+    use super::*;
+
+    /// The paths of every `.route(...)` call in the route modules.
+    fn registered_paths() -> Vec<String> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/routes");
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let source = fs::read_to_string(entry.unwrap().path()).unwrap();
+            for call in source.split(".route(").skip(1) {
+                if let Some((path, _)) = call
+                    .trim_start()
+                    .strip_prefix('"')
+                    .and_then(|path| path.split_once('"'))
+                {
+                    paths.push(path.to_owned());
+                }
+            }
+        }
+        paths
+    }
+
+    #[test]
+    fn lists_every_single_segment_route() {
+        let paths = registered_paths();
+        assert!(paths.iter().any(|path| path == "/urls/:id/clone"));
+
+        let single_segment: BTreeSet<&str> = paths
+            .iter()
+            .filter_map(|path| path.strip_prefix('/'))
+            .filter(|segment| !segment.contains('/'))
+            .collect();
+        assert_eq!(
+            single_segment,
+            BTreeSet::from(SINGLE_SEGMENT_ROUTES),
+            "SINGLE_SEGMENT_ROUTES must list the routes of one segment"
+        );
+    }
+
+    #[test]
+    fn shadows_keys_only_at_the_root() {
+        assert!(shadowed_keys("/urls/redirect").is_empty());
+        assert!(shadowed_keys("").contains(&String::from("shorten")));
+    }
+}
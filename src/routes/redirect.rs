@@ -0,0 +1,324 @@
+use std::{net::IpAddr, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use http::{
+    header::{LOCATION, RETRY_AFTER},
+    HeaderMap, Method, StatusCode, Uri,
+};
+use percent_encoding::percent_decode_str;
+
+use crate::{
+    app_links::{self, Platform},
+    client_ip::ClientIp,
+    config::ScanGuardAction,
+    requests::{HandlePathParam, OEmbedQuery, RedirectUrlPathParam},
+    responses::{LinkPreview, OEmbed, UrlRedirect},
+    robots, social_preview, tenant, Services,
+};
+
+/// Redirects, and the public pages and files served next to them. Redirects
+/// are served for paths no route takes, so they never shadow one.
+pub fn router() -> Router<Arc<Services>> {
+    Router::new()
+        .fallback(redirect_handler)
+        .route("/urls/preview/:key", get(preview_handler))
+        .route(
+            "/.well-known/apple-app-site-association",
+            get(apple_app_site_association),
+        )
+        .route("/.well-known/assetlinks.json", get(android_asset_links))
+        .route("/robots.txt", get(robots_txt))
+        .route("/u/:handle", get(bio_page))
+        .route("/oembed", get(oembed))
+}
+
+async fn redirect_handler(
+    service: State<Arc<Services>>,
+    ClientIp(client_ip): ClientIp,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response, Response> {
+    let Some(key) = redirect_key(&service.redirect_prefix, uri.path()) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    if method != Method::GET && method != Method::HEAD {
+        return Ok(StatusCode::METHOD_NOT_ALLOWED.into_response());
+    }
+    // keys never contain whitespace, but copied links often carry some
+    let key = key.trim();
+    if let Some(response) = slow_down(&service, client_ip).await {
+        return Ok(response);
+    }
+    let result = service.link(key).await?;
+
+    let (mut response, allow_indexing) = match result {
+        None => match key.strip_suffix('+').filter(|_| service.plus_preview) {
+            Some(key) => return preview(&service, key, client_ip).await,
+            None => {
+                record_miss(&service, client_ip, key).await;
+                (service.not_found.response(key), false)
+            }
+        },
+        Some(redirect) => {
+            // an unfurl is not a visit, so it goes uncounted
+            if let Some(preview) = &redirect.social_preview {
+                if social_preview::is_crawler(&headers) {
+                    let mut response = social_preview::response(preview, &redirect.target);
+                    if !(redirect.public && redirect.allow_indexing) {
+                        robots::noindex(&mut response);
+                    }
+                    return Ok(response);
+                }
+            }
+
+            let (target, variant) = redirect.pick_target();
+
+            // counting must not hold up the redirect
+            let redirects = service.redirects.clone();
+            let click_counters = service.click_counters.clone();
+            let rollout_clicks = redirect
+                .rollout
+                .is_some()
+                .then(|| service.rollout_clicks.clone());
+            let id = redirect.id;
+            service.clicks.record(id);
+            tokio::spawn(async move {
+                if let Err(error) = redirects.record(id).await {
+                    tracing::warn!(%error, "failed to count redirect");
+                }
+                if let Err(error) = click_counters.record(id).await {
+                    tracing::warn!(%error, "failed to count link click");
+                }
+                if let Some(rollout_clicks) = rollout_clicks {
+                    if let Err(error) = rollout_clicks.record(id, variant).await {
+                        tracing::warn!(%error, "failed to count rollout redirect");
+                    }
+                }
+            });
+
+            let mut response = link_response(&redirect, target, &headers);
+            if redirect.social_preview.is_some() {
+                social_preview::vary_by_user_agent(&mut response);
+            }
+            (response, redirect.public && redirect.allow_indexing)
+        }
+    };
+
+    if !allow_indexing {
+        robots::noindex(&mut response);
+    }
+    Ok(response)
+}
+
+/// The key `path` asks to be redirected by, when it is a single segment
+/// under `prefix`.
+fn redirect_key(prefix: &str, path: &str) -> Option<String> {
+    let key = path.strip_prefix(prefix)?.strip_prefix('/')?;
+    // pasted links often pick up a trailing slash
+    let key = key.strip_suffix('/').unwrap_or(key);
+    if key.is_empty() || key.contains('/') {
+        return None;
+    }
+    percent_decode_str(key).decode_utf8().ok().map(String::from)
+}
+
+async fn preview_handler(
+    Path(RedirectUrlPathParam { key }): Path<RedirectUrlPathParam>,
+    service: State<Arc<Services>>,
+    ClientIp(client_ip): ClientIp,
+) -> Result<Response, Response> {
+    if let Some(response) = slow_down(&service, client_ip).await {
+        return Ok(response);
+    }
+    preview(&service, key.trim(), client_ip).await
+}
+
+/// Where the link of `key` goes, without following it or counting a click.
+async fn preview(
+    service: &Services,
+    key: &str,
+    client_ip: Option<IpAddr>,
+) -> Result<Response, Response> {
+    let Some(redirect) = service.link(key).await? else {
+        record_miss(service, client_ip, key).await;
+        return Ok(service.not_found.response(key));
+    };
+    let preview = service.link_previews.get(&redirect.target).await?;
+
+    let mut response = Json(LinkPreview::new(
+        redirect.key.clone(),
+        redirect.target.clone(),
+        preview,
+    ))
+    .into_response();
+    if !(redirect.public && redirect.allow_indexing) {
+        robots::noindex(&mut response);
+    }
+    Ok(response)
+}
+
+/// Refuses or holds up clients flagged for scanning keys, answering in
+/// their place when refused. Failing to check lets the request through.
+async fn slow_down(service: &Services, client_ip: Option<IpAddr>) -> Option<Response> {
+    let (Some(scan_guard), Some(ip)) = (&service.scan_guard, client_ip) else {
+        return None;
+    };
+    let flagged_for = scan_guard
+        .flagged_for(ip)
+        .await
+        .inspect_err(|error| tracing::warn!(%error, "failed to check scan guard"))
+        .ok()
+        .flatten()?;
+
+    scan_guard.record_slowed();
+    match scan_guard.action() {
+        ScanGuardAction::Throttle => Some(
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, flagged_for.as_secs().to_string())],
+                "too many requests",
+            )
+                .into_response(),
+        ),
+        ScanGuardAction::Tarpit => {
+            tokio::time::sleep(scan_guard.tarpit_delay()).await;
+            None
+        }
+    }
+}
+
+/// Counts a request for a key no link has against the client.
+async fn record_miss(service: &Services, client_ip: Option<IpAddr>, key: &str) {
+    let (Some(scan_guard), Some(ip)) = (&service.scan_guard, client_ip) else {
+        return;
+    };
+    if let Err(error) = scan_guard.record_miss(ip, key).await {
+        tracing::warn!(%error, "failed to record unknown key");
+    }
+}
+
+fn link_response(redirect: &UrlRedirect, target: &str, headers: &HeaderMap) -> Response {
+    let app_response = redirect
+        .app_links
+        .as_ref()
+        .zip(Platform::detect(headers))
+        .and_then(|(app_links, platform)| app_links::response(app_links, platform, target));
+    if let Some(response) = app_response {
+        return response;
+    }
+
+    // browsers cache permanent redirects, which would pin each of
+    // them to one side of the rollout
+    if redirect.rollout.is_some() {
+        return axum::response::Redirect::temporary(target).into_response();
+    }
+    let status = tenant::settings()
+        .and_then(|settings| settings.redirect_status)
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .unwrap_or(StatusCode::PERMANENT_REDIRECT);
+    (status, [(LOCATION, target)]).into_response()
+}
+
+async fn oembed(
+    service: State<Arc<Services>>,
+    Query(OEmbedQuery { url, format }): Query<OEmbedQuery>,
+) -> Result<Json<OEmbed>, Response> {
+    if format.is_some_and(|format| format != "json") {
+        return Err((StatusCode::NOT_IMPLEMENTED, "only json is supported").into_response());
+    }
+
+    // the key is the last path segment, whichever host the link was shared on
+    let key = url::Url::parse(&url).ok().and_then(|url| {
+        url.path_segments()?
+            .rfind(|segment| !segment.is_empty())
+            .map(String::from)
+    });
+    let redirect = match key {
+        Some(key) => service.link(&key).await?,
+        None => None,
+    };
+    let Some(redirect) = redirect else {
+        return Err((StatusCode::NOT_FOUND, "not found").into_response());
+    };
+    let target = url::Url::parse(&redirect.target)
+        .map_err(|_| (StatusCode::NOT_FOUND, "not found").into_response())?;
+
+    let preview = service.link_previews.get(&redirect.target).await?;
+    Ok(Json(OEmbed::new(&target, preview)))
+}
+
+async fn bio_page(
+    Path(HandlePathParam { handle }): Path<HandlePathParam>,
+    service: State<Arc<Services>>,
+) -> Result<Response, Response> {
+    let page = service.url.public_bio_page(&handle).await?;
+
+    Ok(match page {
+        Some(page) => {
+            axum::response::Html(service.bio_template.render(&page, &service.redirect_prefix))
+                .into_response()
+        }
+        None => (StatusCode::NOT_FOUND, "not found").into_response(),
+    })
+}
+
+async fn robots_txt(service: State<Arc<Services>>) -> String {
+    service.robots_txt.clone()
+}
+
+async fn apple_app_site_association(service: State<Arc<Services>>) -> Response {
+    service.app_association.apple_app_site_association()
+}
+
+async fn android_asset_links(service: State<Arc<Services>>) -> Response {
+    service.app_association.android_asset_links()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_the_key_under_the_prefix() {
+        assert_eq!(
+            redirect_key("/urls/redirect", "/urls/redirect/abc"),
+            Some(String::from("abc"))
+        );
+        assert_eq!(redirect_key("", "/abc"), Some(String::from("abc")));
+    }
+
+    #[test]
+    fn ignores_a_trailing_slash() {
+        assert_eq!(
+            redirect_key("/urls/redirect", "/urls/redirect/abc/"),
+            Some(String::from("abc"))
+        );
+        assert_eq!(redirect_key("", "/abc/"), Some(String::from("abc")));
+    }
+
+    #[test]
+    fn decodes_percent_encoded_keys() {
+        assert_eq!(
+            redirect_key("/go", "/go/caf%C3%A9"),
+            Some(String::from("café"))
+        );
+        assert_eq!(redirect_key("/go", "/go/%FF"), None);
+    }
+
+    #[test]
+    fn answers_none_outside_a_single_segment_under_the_prefix() {
+        assert_eq!(redirect_key("/urls/redirect", "/urls/redirect"), None);
+        assert_eq!(redirect_key("/urls/redirect", "/urls/redirect/"), None);
+        assert_eq!(redirect_key("/urls/redirect", "/urls/redirectabc"), None);
+        assert_eq!(redirect_key("/urls/redirect", "/urls/redirect/a/b"), None);
+        assert_eq!(redirect_key("/urls/redirect", "/other/abc"), None);
+        assert_eq!(redirect_key("", "/"), None);
+        assert_eq!(redirect_key("", "/a/b"), None);
+    }
+}
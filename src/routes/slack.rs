@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use http::{HeaderMap, StatusCode};
+
+use crate::{
+    authenthication::Requester,
+    error::{problem, ProblemType},
+    requests::LinkSlackAccount,
+    responses::SlackReply,
+    service::{InsertError, NewUrlRedirect},
+    slack::{self, SlashCommand},
+    Services,
+};
+
+/// The Slack slash command and linking Slack accounts to users.
+pub fn router() -> Router<Arc<Services>> {
+    Router::new()
+        .route("/integrations/slack", post(slack_command))
+        .route(
+            "/integrations/slack/link",
+            post(link_slack_account).delete(unlink_slack_accounts),
+        )
+}
+
+const SLACK_USAGE: &str =
+    "Usage: `/shorten <url> [key]`, or `/shorten link` to connect your account.";
+
+/// Slack posts slash commands here, signed with the app's secret instead of
+/// carrying our credentials. Mistakes are answered with a reply, since Slack
+/// shows the user nothing useful for an error status.
+async fn slack_command(
+    service: State<Arc<Services>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<SlackReply>, Response> {
+    let Some(slack) = &service.slack else {
+        return Err((StatusCode::NOT_FOUND, "not found").into_response());
+    };
+    if !slack.verify(&headers, &body) {
+        return Err((StatusCode::UNAUTHORIZED, "invalid signature").into_response());
+    }
+
+    let command = SlashCommand::parse(&body);
+    let mut args = command.text.split_whitespace();
+    let (target, key) = match (args.next(), args.next(), args.next()) {
+        (Some("link"), None, _) => {
+            let code = slack.issue_link_code(&command).await?;
+            return Ok(Json(SlackReply::ephemeral(format!(
+                "To connect your account, send `POST /integrations/slack/link` with \
+                 `{{\"code\": \"{code}\"}}` while signed in. The code expires in {} minutes.",
+                slack::LINK_CODE_TTL_SECS / 60
+            ))));
+        }
+        (Some(target), key, None) => (target, key),
+        _ => return Ok(Json(SlackReply::ephemeral(SLACK_USAGE))),
+    };
+
+    let Some(owner) = service
+        .url
+        .slack_account_owner(&command.team_id, &command.user_id)
+        .await?
+    else {
+        return Ok(Json(SlackReply::ephemeral(
+            "Your Slack account is not connected yet. Run `/shorten link` to connect it.",
+        )));
+    };
+
+    // Slack wraps the URLs it recognizes as `<url>` or `<url|label>`
+    let target = target.trim_start_matches('<').trim_end_matches('>');
+    let target = target.split_once('|').map_or(target, |(url, _)| url);
+    let valid_target =
+        url::Url::parse(target).is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+    if !valid_target {
+        return Ok(Json(SlackReply::ephemeral(
+            "The target must be an http or https URL.",
+        )));
+    }
+
+    let created = match key {
+        Some(key) => match service.url.key_policy().parse(key.to_string()) {
+            Ok(key) => {
+                service
+                    .url
+                    .create(NewUrlRedirect::new(owner.clone(), key, target.to_string()))
+                    .await
+            }
+            Err(error) => {
+                return Ok(Json(SlackReply::ephemeral(format!(
+                    "Invalid key: {error}."
+                ))));
+            }
+        },
+        None => {
+            service
+                .url
+                .create_with_generated_key(owner.clone(), target.to_string(), None)
+                .await
+        }
+    };
+
+    let text = match created {
+        Ok(url) => {
+            tracing::info!(target: "audit", owner, key = url.key, "link created from slack");
+            format!("Created {} → {target}", url.short_url)
+        }
+        Err(InsertError::KeyAlreadyExists) => String::from("That key is already taken."),
+        Err(InsertError::LinkLimitReached) => String::from("Your plan's link limit is reached."),
+        Err(InsertError::TenantLinkLimitReached) => {
+            String::from("Your organization's link limit is reached.")
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    Ok(Json(SlackReply::ephemeral(text)))
+}
+
+async fn link_slack_account(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Json(LinkSlackAccount { code }): Json<LinkSlackAccount>,
+) -> Result<StatusCode, Response> {
+    let Some(slack) = &service.slack else {
+        return Err((StatusCode::NOT_FOUND, "not found").into_response());
+    };
+    let Some((team_id, user_id)) = slack.redeem_link_code(&code).await? else {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "invalid or expired code",
+        ));
+    };
+
+    service
+        .url
+        .link_slack_account(team_id.clone(), user_id.clone(), requester.email.clone())
+        .await?;
+    tracing::info!(target: "audit", owner = requester.email, team_id, user_id, "slack account linked");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn unlink_slack_accounts(
+    requester: Requester,
+    service: State<Arc<Services>>,
+) -> Result<StatusCode, Response> {
+    service.url.unlink_slack_accounts(&requester.email).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
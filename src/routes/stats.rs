@@ -0,0 +1,90 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use http::StatusCode;
+
+use crate::{
+    authenthication::Requester,
+    error::{problem, ProblemType},
+    requests::{CampaignStatsQuery, RedirectUrlIdPathParam, ShareStats, SharedStatsPathParam},
+    responses::{LinkStats, SharedStats},
+    usage, Services,
+};
+
+/// Sharing a link's stats through signed tokens.
+pub fn router() -> Router<Arc<Services>> {
+    Router::new()
+        .route("/urls/:id/share", post(share_stats))
+        .route("/stats/shared/:token", get(shared_stats))
+}
+
+const DEFAULT_SHARE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const MAX_SHARE_TTL: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+async fn share_stats(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+    body: Option<Json<ShareStats>>,
+) -> Result<(StatusCode, Json<SharedStats>), Response> {
+    let Some(stats_sharing) = &service.stats_sharing else {
+        return Err((StatusCode::NOT_FOUND, "not found").into_response());
+    };
+    let ttl = body
+        .and_then(|Json(body)| body.ttl_secs)
+        .map_or(DEFAULT_SHARE_TTL, Duration::from_secs);
+    if ttl.is_zero() || ttl > MAX_SHARE_TTL {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "ttl_secs must be between 1 second and 90 days",
+        ));
+    }
+
+    service
+        .url
+        .get_by_id_and_email(id, &requester.email)
+        .await?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())?;
+    let expires_at = chrono::Utc::now() + ttl;
+    let token = stats_sharing.issue(id, expires_at).map_err(|error| {
+        tracing::error!(%error, "failed to sign shared stats token");
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+    tracing::info!(target: "audit", owner = requester.email, %id, %expires_at, "stats shared");
+
+    Ok((
+        StatusCode::CREATED,
+        Json(SharedStats::new(token, expires_at)),
+    ))
+}
+
+/// Needs no sign-in: holding an unexpired token is the permission.
+async fn shared_stats(
+    service: State<Arc<Services>>,
+    Path(SharedStatsPathParam { token }): Path<SharedStatsPathParam>,
+    Query(query): Query<CampaignStatsQuery>,
+) -> Result<Json<LinkStats>, Response> {
+    let id = service
+        .stats_sharing
+        .as_ref()
+        .and_then(|stats_sharing| stats_sharing.verify(&token))
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())?;
+    let link = service
+        .url
+        .get_by_id(id)
+        .await?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())?;
+
+    let period = query.period.unwrap_or_default();
+    let daily = service
+        .redirects
+        .served_daily(&[id], usage::period_start(period.days()))
+        .await?;
+
+    Ok(Json(LinkStats::new(link, period, daily)))
+}
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use http::StatusCode;
+
+use crate::{
+    authenthication::Requester,
+    error::{problem, ProblemType},
+    requests::{MergeTag, RenameTag, TagLinks, TagPathParam},
+    responses::{TagChange, TagSummary},
+    service::TagRename,
+    Services,
+};
+
+const MAX_TAG_LENGTH: usize = 50;
+
+/// How many links one bulk request may tag or untag.
+const MAX_TAGGED_LINKS: usize = 1000;
+
+/// Tags on links, managed in bulk across the requester's links.
+pub fn router() -> Router<Arc<Services>> {
+    Router::new()
+        .route("/tags", get(get_tags))
+        .route("/tags/:tag", put(rename_tag))
+        .route("/tags/:tag/links", post(tag_links).delete(untag_links))
+        .route("/tags/:tag/merge", post(merge_tag))
+}
+
+/// `tag` without surrounding whitespace, if it is a tag name at all.
+// fails with the handler's answer, like the handlers themselves
+#[allow(clippy::result_large_err)]
+fn tag_name(tag: &str) -> Result<String, Response> {
+    let tag = tag.trim();
+    if tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            format!("tags are 1 to {MAX_TAG_LENGTH} characters long"),
+        ));
+    }
+    if tag.chars().any(char::is_control) {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "tags must not contain control characters",
+        ));
+    }
+    Ok(tag.to_owned())
+}
+
+#[allow(clippy::result_large_err)]
+fn check_links(ids: &[uuid::Uuid]) -> Result<(), Response> {
+    match ids.len() {
+        0 => Err(problem(ProblemType::ValidationFailed, "no links given")),
+        len if len > MAX_TAGGED_LINKS => Err(problem(
+            ProblemType::ValidationFailed,
+            format!("at most {MAX_TAGGED_LINKS} links at a time"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+async fn get_tags(
+    requester: Requester,
+    service: State<Arc<Services>>,
+) -> Result<Json<Vec<TagSummary>>, Response> {
+    Ok(Json(service.url.list_tags(&requester.email).await?))
+}
+
+async fn tag_links(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(TagPathParam { tag }): Path<TagPathParam>,
+    Json(TagLinks { ids }): Json<TagLinks>,
+) -> Result<Json<TagChange>, Response> {
+    let tag = tag_name(&tag)?;
+    check_links(&ids)?;
+
+    let tagged = service
+        .url
+        .tag_links(&requester.email, &tag, ids)
+        .await?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())?;
+    Ok(Json(TagChange::new(tag, tagged)))
+}
+
+async fn untag_links(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(TagPathParam { tag }): Path<TagPathParam>,
+    Json(TagLinks { ids }): Json<TagLinks>,
+) -> Result<Json<TagChange>, Response> {
+    let tag = tag_name(&tag)?;
+    check_links(&ids)?;
+
+    let untagged = service.url.untag_links(&requester.email, &tag, ids).await?;
+    Ok(Json(TagChange::new(tag, untagged)))
+}
+
+async fn rename_tag(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(TagPathParam { tag }): Path<TagPathParam>,
+    Json(RenameTag { name }): Json<RenameTag>,
+) -> Result<Json<TagChange>, Response> {
+    let tag = tag_name(&tag)?;
+    let name = tag_name(&name)?;
+    if name == tag {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "the tag already has that name",
+        ));
+    }
+
+    match service
+        .url
+        .rename_tag(&requester.email, &tag, &name)
+        .await?
+    {
+        TagRename::Renamed(renamed) => Ok(Json(TagChange::new(name, renamed))),
+        TagRename::NotFound => Err((StatusCode::NOT_FOUND, "not found").into_response()),
+        TagRename::Taken => Err((
+            StatusCode::CONFLICT,
+            "tag already exists; merge into it instead",
+        )
+            .into_response()),
+    }
+}
+
+async fn merge_tag(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(TagPathParam { tag }): Path<TagPathParam>,
+    Json(MergeTag { into }): Json<MergeTag>,
+) -> Result<Json<TagChange>, Response> {
+    let tag = tag_name(&tag)?;
+    let into = tag_name(&into)?;
+    if into == tag {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "a tag cannot be merged into itself",
+        ));
+    }
+
+    let merged = service
+        .url
+        .merge_tag(&requester.email, &tag, &into)
+        .await?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())?;
+    Ok(Json(TagChange::new(into, merged)))
+}
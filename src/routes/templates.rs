@@ -0,0 +1,122 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use http::StatusCode;
+
+use crate::{
+    authenthication::Requester,
+    error::{problem, ProblemType},
+    link_template,
+    requests::{NewTemplate, NewUrlFromTemplate, TemplatePathParam},
+    responses::{LinkTemplate, UrlRedirect},
+    routes::found,
+    service::NewUrlRedirect,
+    Services,
+};
+
+/// Link templates and the links made from them.
+pub fn router() -> Router<Arc<Services>> {
+    Router::new()
+        .route("/templates", get(get_templates).post(new_template))
+        .route("/templates/:template_id", delete(delete_template))
+        .route(
+            "/urls/from-template/:template_id",
+            post(new_url_from_template),
+        )
+}
+
+async fn get_templates(
+    requester: Requester,
+    service: State<Arc<Services>>,
+) -> Result<Json<Vec<LinkTemplate>>, Response> {
+    Ok(Json(service.url.list_templates(&requester.email).await?))
+}
+
+async fn new_template(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Json(template): Json<NewTemplate>,
+) -> Result<(StatusCode, Json<LinkTemplate>), Response> {
+    if template.name.trim().is_empty() {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "name must not be empty",
+        ));
+    }
+    if template.default_ttl_secs == Some(0) {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "default_ttl_secs must be at least one second",
+        ));
+    }
+
+    let template = service
+        .url
+        .create_template(requester.email, template)
+        .await?;
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+async fn delete_template(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(TemplatePathParam { template_id }): Path<TemplatePathParam>,
+) -> Result<Json<LinkTemplate>, Response> {
+    service
+        .url
+        .delete_template(&requester.email, template_id)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn new_url_from_template(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(TemplatePathParam { template_id }): Path<TemplatePathParam>,
+    Json(NewUrlFromTemplate { key, values }): Json<NewUrlFromTemplate>,
+) -> Result<(StatusCode, Json<UrlRedirect>), Response> {
+    let template = service
+        .url
+        .get_template(&requester.email, template_id)
+        .await?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "template not found").into_response())?;
+
+    let target = link_template::render(&template.target_pattern, &values).map_err(|missing| {
+        problem(
+            ProblemType::ValidationFailed,
+            format!("missing values for: {}", missing.join(", ")),
+        )
+    })?;
+    let expires_at = template
+        .default_ttl_secs
+        .and_then(|secs| u64::try_from(secs).ok())
+        .map(|secs| chrono::Utc::now() + Duration::from_secs(secs));
+
+    let url = match key {
+        Some(key) => {
+            let mut new_url = NewUrlRedirect::new(
+                requester.email,
+                service.url.key_policy().parse(key)?,
+                target,
+            );
+            if let Some(expires_at) = expires_at {
+                new_url = new_url.expiring_at(expires_at);
+            }
+            service.url.create(new_url).await?
+        }
+        None => {
+            service
+                .url
+                .create_with_generated_key(requester.email, target, expires_at)
+                .await?
+        }
+    };
+    Ok((StatusCode::CREATED, Json(url)))
+}
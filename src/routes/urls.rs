@@ -0,0 +1,685 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use http::{header::CONTENT_TYPE, HeaderName, StatusCode};
+
+use crate::{
+    authenthication::{Requester, TokenRequester},
+    client_ip::ClientIp,
+    error::{problem, ProblemType},
+    rate_limit::RateLimit,
+    requests::{
+        AliasPathParam, CloneUrl, DryRunQuery, KeySuggestionQuery, ListPublicUrl, ListUrl,
+        NewAlias, NewAnonymousUrl, NewRollout, NewUrl, RedirectUrlIdPathParam,
+        RedirectUrlPathParam, RevisionPathParam, ScheduleTarget, SetCampaign, SetIndexing, SetKeep,
+        SetVisibility, UserPathParam,
+    },
+    responses::{
+        AppLinks, LinkAlias, PagedResponse, PublicLink, Revision, RolloutStatus, ScheduledTarget,
+        SocialPreview, UrlRedirect,
+    },
+    routes::found,
+    service::{LinkFilter, ANONYMOUS_OWNER},
+    validation, Services,
+};
+
+/// Creating and managing short links, and listing the public ones.
+pub fn router() -> Router<Arc<Services>> {
+    Router::new()
+        .route("/urls", get(get_urls).post(new_url))
+        .route("/urls/anonymous", post(new_anonymous_url))
+        .route("/shorten", post(quick_shorten))
+        .route("/urls/suggestions", get(suggest_keys))
+        .route(
+            "/urls/:id",
+            get(get_url).delete(delete_url).patch(update_url),
+        )
+        .route("/urls/by-key/:key", get(get_url_by_key))
+        .route("/urls/:id/history", get(get_history))
+        .route("/urls/:id/aliases", get(get_aliases).post(new_alias))
+        .route("/urls/:id/aliases/:key", delete(delete_alias))
+        .route("/urls/:id/clone", post(clone_url))
+        .route("/urls/:id/campaign", put(set_campaign))
+        .route("/urls/:id/indexing", put(set_indexing))
+        .route("/urls/:id/visibility", put(set_visibility))
+        .route("/urls/:id/keep", put(set_keep))
+        .route("/urls/:id/pin", post(pin_url))
+        .route("/urls/:id/unpin", post(unpin_url))
+        .route(
+            "/urls/:id/app-links",
+            put(set_app_links).delete(remove_app_links),
+        )
+        .route(
+            "/urls/:id/social-preview",
+            put(set_social_preview).delete(remove_social_preview),
+        )
+        .route("/urls/:id/archive", post(archive_url))
+        .route("/urls/:id/unarchive", post(unarchive_url))
+        .route("/urls/:id/revert/:revision", post(revert_url))
+        .route(
+            "/urls/:id/rollout",
+            get(get_rollout).put(set_rollout).delete(cancel_rollout),
+        )
+        .route(
+            "/urls/:id/scheduled-target",
+            get(get_scheduled_target)
+                .put(schedule_target)
+                .delete(cancel_scheduled_target),
+        )
+        .route("/public/urls", get(public_urls))
+        .route("/public/users/:email/urls", get(public_urls_of_user))
+}
+
+async fn get_urls(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Query(query): Query<ListUrl>,
+) -> Result<Json<PagedResponse<UrlRedirect>>, Response> {
+    let (sort, cursor) = service
+        .page_cursors
+        .resolve(query.after.as_deref(), query.before.as_deref(), query.sort)
+        .map_err(|reason| problem(ProblemType::ValidationFailed, reason))?;
+
+    let page = service
+        .url
+        .list_by_email(
+            &requester.email,
+            LinkFilter {
+                state: query.state.unwrap_or_default(),
+                tag: query.tag.map(|tag| tag.trim().to_owned()),
+                target: query.target,
+                ..Default::default()
+            },
+            sort,
+            cursor,
+            query.limit.unwrap_or(50),
+        )
+        .await?;
+
+    Ok(Json(PagedResponse::with_cursors(
+        page.items,
+        page.has_prev,
+        page.has_next,
+        |cursor| service.page_cursors.sign(sort, cursor),
+    )))
+}
+
+async fn new_url(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Query(DryRunQuery { dry_run }): Query<DryRunQuery>,
+    Json(new_url): Json<NewUrl>,
+) -> Result<Response, Response> {
+    let new_url = validation::new_url(&service.url.key_policy(), requester.email, new_url)?;
+    if dry_run {
+        return Ok(Json(service.url.check_create(&new_url).await?).into_response());
+    }
+
+    Ok(Json(service.url.create(new_url).await?).into_response())
+}
+
+async fn get_url(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+) -> Result<Json<UrlRedirect>, Response> {
+    service
+        .url
+        .get_by_id_and_email(id, &requester.email)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn get_url_by_key(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlPathParam { key }): Path<RedirectUrlPathParam>,
+) -> Result<Json<UrlRedirect>, Response> {
+    service
+        .url
+        .get_by_key_and_email(key.trim(), &requester.email)
+        .await?
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())
+}
+
+async fn update_url(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+    Query(DryRunQuery { dry_run }): Query<DryRunQuery>,
+    Json(new_url): Json<NewUrl>,
+) -> Result<Response, Response> {
+    let new_url = validation::new_url(&service.url.key_policy(), requester.email.clone(), new_url)?;
+    let not_found = || (StatusCode::NOT_FOUND, "not found").into_response();
+    if dry_run {
+        let dry_run = service.url.check_update(id, &new_url).await?;
+        return Ok(Json(dry_run.ok_or_else(not_found)?).into_response());
+    }
+
+    let url = service.url.update(id, new_url, requester.actor()).await?;
+    Ok(Json(url.ok_or_else(not_found)?).into_response())
+}
+
+async fn delete_url(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+) -> Result<Json<UrlRedirect>, Response> {
+    service
+        .url
+        .delete(&requester.email, id)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+/// For `curl --data-binary <url>`: the body is the bare target, whatever its
+/// content type, and the answer is the bare short URL. Only a token in the
+/// `Authorization` header is accepted, not a session cookie or impersonation.
+async fn quick_shorten(
+    requester: TokenRequester,
+    service: State<Arc<Services>>,
+    target: String,
+) -> Result<(StatusCode, [(HeaderName, &'static str); 1], String), Response> {
+    let target = target.trim();
+    validation::target(target)?;
+
+    let url = service
+        .url
+        .create_with_generated_key(requester.email, target.to_string(), None)
+        .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        [(CONTENT_TYPE, "text/plain; charset=utf-8")],
+        format!("{}\n", url.short_url),
+    ))
+}
+
+/// Public quick-shorten: random key, forced expiry and a per-IP limit, since
+/// nobody can be held responsible for these links.
+async fn new_anonymous_url(
+    ClientIp(client_ip): ClientIp,
+    service: State<Arc<Services>>,
+    Json(new_url): Json<NewAnonymousUrl>,
+) -> Result<(StatusCode, RateLimit, Json<UrlRedirect>), Response> {
+    let Some(anonymous_links) = &service.anonymous_links else {
+        return Err((StatusCode::NOT_FOUND, "not found").into_response());
+    };
+
+    let Some(client_ip) = client_ip else {
+        return Err((StatusCode::BAD_REQUEST, "unknown client address").into_response());
+    };
+    let rate_limit = anonymous_links
+        .rate_limiter
+        .hit(
+            &client_ip.to_string(),
+            service.http.reloadable.anonymous_links_per_hour(),
+        )
+        .await?;
+    if !rate_limit.allowed {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            rate_limit,
+            "too many requests",
+        )
+            .into_response());
+    }
+
+    validation::target(&new_url.target)?;
+
+    let expires_at = chrono::Utc::now() + anonymous_links.ttl;
+    let url = service
+        .url
+        .create_with_generated_key(
+            ANONYMOUS_OWNER.to_string(),
+            new_url.target,
+            Some(expires_at),
+        )
+        .await?;
+
+    Ok((StatusCode::CREATED, rate_limit, Json(url)))
+}
+
+async fn suggest_keys(
+    _requester: Requester,
+    service: State<Arc<Services>>,
+    Query(KeySuggestionQuery { target }): Query<KeySuggestionQuery>,
+) -> Result<Json<Vec<String>>, Response> {
+    let target = validation::target(&target)?;
+
+    Ok(Json(service.url.suggest_keys(&target).await?))
+}
+
+async fn clone_url(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+    Json(CloneUrl { key }): Json<CloneUrl>,
+) -> Result<(StatusCode, Json<UrlRedirect>), Response> {
+    let key = key
+        .map(|key| service.url.key_policy().parse(key))
+        .transpose()?;
+    service
+        .url
+        .clone_url(id, &requester.email, key)
+        .await?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())
+        .map(|url| (StatusCode::CREATED, Json(url)))
+}
+
+async fn set_app_links(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+    Json(mut app_links): Json<AppLinks>,
+) -> Result<Json<UrlRedirect>, Response> {
+    for app_link in [&mut app_links.ios, &mut app_links.android]
+        .into_iter()
+        .flatten()
+    {
+        // javascript: and data: URLs would run in the page opening the app
+        let deep_link = url::Url::parse(&app_link.deep_link)
+            .ok()
+            .filter(|url| !matches!(url.scheme(), "javascript" | "data" | "vbscript"))
+            .ok_or_else(|| problem(ProblemType::ValidationFailed, "invalid deep_link"))?;
+        // parsing drops tabs and newlines, which must not reach a Location
+        // header, so the parsed URLs are the ones stored
+        app_link.deep_link = deep_link.into();
+
+        if let Some(fallback) = &mut app_link.fallback_url {
+            let parsed = url::Url::parse(fallback)
+                .ok()
+                .filter(|url| matches!(url.scheme(), "http" | "https"))
+                .ok_or_else(|| {
+                    problem(
+                        ProblemType::ValidationFailed,
+                        "fallback_url must be an http or https URL",
+                    )
+                })?;
+            *fallback = parsed.into();
+        }
+    }
+
+    service
+        .url
+        .set_app_links(&requester.email, id, app_links)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn remove_app_links(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+) -> Result<Json<UrlRedirect>, Response> {
+    service
+        .url
+        .set_app_links(&requester.email, id, AppLinks::default())
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn set_social_preview(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+    Json(social_preview): Json<SocialPreview>,
+) -> Result<Json<UrlRedirect>, Response> {
+    validation::social_preview(&social_preview)?;
+
+    service
+        .url
+        .set_social_preview(&requester.email, id, social_preview)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn remove_social_preview(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+) -> Result<Json<UrlRedirect>, Response> {
+    service
+        .url
+        .set_social_preview(&requester.email, id, SocialPreview::default())
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn set_indexing(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+    Json(SetIndexing { allow_indexing }): Json<SetIndexing>,
+) -> Result<Json<UrlRedirect>, Response> {
+    let url = service
+        .url
+        .set_allow_indexing(&requester.email, id, allow_indexing)
+        .await?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())?;
+    if url.allow_indexing != allow_indexing {
+        return Err((StatusCode::CONFLICT, "only public links can be indexed").into_response());
+    }
+
+    Ok(Json(url))
+}
+
+async fn pin_url(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+) -> Result<Json<UrlRedirect>, Response> {
+    set_pinned(requester, service, id, true).await
+}
+
+async fn unpin_url(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+) -> Result<Json<UrlRedirect>, Response> {
+    set_pinned(requester, service, id, false).await
+}
+
+async fn set_pinned(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    id: uuid::Uuid,
+    pinned: bool,
+) -> Result<Json<UrlRedirect>, Response> {
+    service
+        .url
+        .set_pinned(&requester.email, id, pinned)
+        .await?
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())
+}
+
+async fn set_keep(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+    Json(SetKeep { keep_when_inactive }): Json<SetKeep>,
+) -> Result<Json<UrlRedirect>, Response> {
+    service
+        .url
+        .set_keep_when_inactive(&requester.email, id, keep_when_inactive)
+        .await?
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())
+}
+
+async fn set_visibility(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+    Json(SetVisibility { public }): Json<SetVisibility>,
+) -> Result<Json<UrlRedirect>, Response> {
+    service
+        .url
+        .set_public(&requester.email, id, public)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+// The directory needs no sign-in, so pages are kept small.
+const MAX_PUBLIC_PAGE_SIZE: u64 = 100;
+
+async fn public_urls(
+    service: State<Arc<Services>>,
+    Query(query): Query<ListPublicUrl>,
+) -> Result<Json<PagedResponse<PublicLink>>, Response> {
+    let limit = query.limit.unwrap_or(50).min(MAX_PUBLIC_PAGE_SIZE);
+    let result = service.url.list_public(None, query.after, limit).await?;
+
+    Ok(Json(PagedResponse::new(result)))
+}
+
+async fn public_urls_of_user(
+    service: State<Arc<Services>>,
+    Path(UserPathParam { email }): Path<UserPathParam>,
+    Query(query): Query<ListPublicUrl>,
+) -> Result<Json<PagedResponse<PublicLink>>, Response> {
+    let limit = query.limit.unwrap_or(50).min(MAX_PUBLIC_PAGE_SIZE);
+    let result = service
+        .url
+        .list_public(Some(&email), query.after, limit)
+        .await?;
+
+    Ok(Json(PagedResponse::new(result)))
+}
+
+async fn set_campaign(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+    Json(SetCampaign { campaign_id }): Json<SetCampaign>,
+) -> Result<Json<UrlRedirect>, Response> {
+    service
+        .url
+        .set_campaign(&requester.email, id, campaign_id)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn archive_url(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+) -> Result<Json<UrlRedirect>, Response> {
+    set_archived(requester, service, id, true).await
+}
+
+async fn unarchive_url(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+) -> Result<Json<UrlRedirect>, Response> {
+    set_archived(requester, service, id, false).await
+}
+
+async fn set_archived(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    id: uuid::Uuid,
+    archived: bool,
+) -> Result<Json<UrlRedirect>, Response> {
+    service
+        .url
+        .set_archived(&requester.email, id, archived)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn get_history(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+) -> Result<Json<Vec<Revision>>, Response> {
+    service
+        .url
+        .history(id, &requester.email)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn get_aliases(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+) -> Result<Json<Vec<LinkAlias>>, Response> {
+    service
+        .url
+        .list_aliases(id, &requester.email)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn new_alias(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+    Json(NewAlias { key }): Json<NewAlias>,
+) -> Result<(StatusCode, Json<LinkAlias>), Response> {
+    service
+        .url
+        .add_alias(id, &requester.email, service.url.key_policy().parse(key)?)
+        .await?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())
+        .map(|alias| (StatusCode::CREATED, Json(alias)))
+}
+
+async fn delete_alias(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(AliasPathParam { id, key }): Path<AliasPathParam>,
+) -> Result<Json<LinkAlias>, Response> {
+    service
+        .url
+        .delete_alias(id, &requester.email, &key)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn revert_url(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RevisionPathParam { id, revision }): Path<RevisionPathParam>,
+) -> Result<Json<UrlRedirect>, Response> {
+    service
+        .url
+        .revert(id, requester.email.clone(), revision, requester.actor())
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn get_rollout(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+) -> Result<Json<RolloutStatus>, Response> {
+    let rollout = service
+        .url
+        .get_by_id_and_email(id, &requester.email)
+        .await?
+        .and_then(|url| url.rollout)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())?;
+    let clicks = service.rollout_clicks.counts(id).await?;
+
+    Ok(Json(RolloutStatus::new(rollout, clicks)))
+}
+
+async fn set_rollout(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+    Json(rollout): Json<NewRollout>,
+) -> Result<Json<UrlRedirect>, Response> {
+    if rollout.percent > 100 {
+        return Err(problem(
+            ProblemType::ValidationFailed,
+            "percent must be between 0 and 100",
+        ));
+    }
+    let rollout = validation::rollout(rollout)?;
+
+    let url = service
+        .url
+        .set_rollout(&requester.email, id, Some(rollout))
+        .await?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "not found").into_response())?;
+    service.rollout_clicks.reset(id).await?;
+
+    Ok(Json(url))
+}
+
+async fn cancel_rollout(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+) -> Result<Json<UrlRedirect>, Response> {
+    service
+        .url
+        .set_rollout(&requester.email, id, None)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn get_scheduled_target(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+) -> Result<Json<ScheduledTarget>, Response> {
+    service
+        .url
+        .scheduled_target(&requester.email, id)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn schedule_target(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+    Json(schedule): Json<ScheduleTarget>,
+) -> Result<Json<ScheduledTarget>, Response> {
+    validation::schedule_target(&schedule)?;
+
+    let ScheduleTarget { target, at } = schedule;
+    service
+        .url
+        .schedule_target(&requester.email, id, target, at, requester.actor())
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
+
+async fn cancel_scheduled_target(
+    requester: Requester,
+    service: State<Arc<Services>>,
+    Path(RedirectUrlIdPathParam { id }): Path<RedirectUrlIdPathParam>,
+) -> Result<Json<ScheduledTarget>, Response> {
+    service
+        .url
+        .cancel_scheduled_target(&requester.email, id)
+        .await
+        .map_err(Into::into)
+        .and_then(found)
+        .map(Json)
+}
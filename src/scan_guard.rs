@@ -0,0 +1,197 @@
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use redis::AsyncCommands;
+
+use crate::{
+    config::{ScanGuardAction, ScanGuardConfig},
+    kvs::{ttl_remaining, KvsPool},
+    rate_limit::RateLimitError,
+    responses::ScanGuardStats,
+};
+
+/// Flags clients that ask for many keys no link has within a sliding window,
+/// or for a honeypot key, so private links cannot be found by enumeration.
+pub struct ScanGuard {
+    kvs_pool: Arc<KvsPool>,
+    config: ScanGuardConfig,
+    flagged_clients: AtomicU64,
+    honeypot_hits: AtomicU64,
+    slowed_requests: AtomicU64,
+}
+
+impl ScanGuard {
+    pub fn new(kvs_pool: Arc<KvsPool>, config: ScanGuardConfig) -> Self {
+        Self {
+            kvs_pool,
+            config,
+            flagged_clients: AtomicU64::new(0),
+            honeypot_hits: AtomicU64::new(0),
+            slowed_requests: AtomicU64::new(0),
+        }
+    }
+
+    pub fn action(&self) -> ScanGuardAction {
+        self.config.action
+    }
+
+    pub fn tarpit_delay(&self) -> Duration {
+        self.config.tarpit_delay
+    }
+
+    /// How long the client remains flagged, if it is.
+    pub async fn flagged_for(&self, ip: IpAddr) -> Result<Option<Duration>, RateLimitError> {
+        let mut conn = self.kvs_pool.get().await?;
+        ttl_remaining(&mut conn, &flagged_key(ip))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Counts a request slowed down because its client is flagged.
+    pub fn record_slowed(&self) {
+        self.slowed_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that the client asked for `key`, which no link has, flagging
+    /// it when `key` is a honeypot or it has asked for too many such keys
+    /// within the window.
+    pub async fn record_miss(&self, ip: IpAddr, key: &str) -> Result<(), RateLimitError> {
+        if self
+            .config
+            .honeypot_keys
+            .iter()
+            .any(|honeypot| honeypot == key)
+        {
+            self.honeypot_hits.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(target: "audit", %ip, key, "honeypot key requested");
+            return self.flag(ip, None).await;
+        }
+
+        let misses_key = misses_key(ip);
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let window_ms = self.config.window.as_millis() as u64;
+        let mut conn = self.kvs_pool.get().await?;
+
+        // the same key asked for again is one miss, so only distinct keys
+        // count, and each falls out of the window on its own
+        let (misses,): (u64,) = redis::pipe()
+            .atomic()
+            .zrembyscore(&misses_key, 0, now_ms.saturating_sub(window_ms))
+            .ignore()
+            .zadd(&misses_key, key, now_ms)
+            .ignore()
+            .zcard(&misses_key)
+            .expire(&misses_key, self.config.window.as_secs() as i64)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+
+        if misses <= self.config.max_misses {
+            return Ok(());
+        }
+        self.flag(ip, Some(misses)).await?;
+        // a fresh window once the block ends, rather than flagging again on
+        // the next miss
+        conn.del(&misses_key).await.map_err(Into::into)
+    }
+
+    async fn flag(&self, ip: IpAddr, misses: Option<u64>) -> Result<(), RateLimitError> {
+        self.flagged_clients.fetch_add(1, Ordering::Relaxed);
+        tracing::warn!(
+            target: "audit",
+            %ip,
+            misses,
+            block_secs = self.config.block.as_secs(),
+            "client flagged for scanning keys"
+        );
+
+        let mut conn = self.kvs_pool.get().await?;
+        conn.set_ex(flagged_key(ip), "", self.config.block.as_secs())
+            .await
+            .map_err(Into::into)
+    }
+
+    /// What this instance has caught since it started.
+    pub fn stats(&self) -> ScanGuardStats {
+        ScanGuardStats {
+            flagged_clients: self.flagged_clients.load(Ordering::Relaxed),
+            honeypot_hits: self.honeypot_hits.load(Ordering::Relaxed),
+            slowed_requests: self.slowed_requests.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn misses_key(ip: IpAddr) -> String {
+    format!("scan-misses:{ip}")
+}
+
+fn flagged_key(ip: IpAddr) -> String {
+    format!("scan-flagged:{ip}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_kvs::MemoryKvs;
+
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(203, 0, 113, 7));
+
+    fn guard(max_misses: u64) -> ScanGuard {
+        ScanGuard::new(
+            Arc::new(KvsPool::Memory(MemoryKvs::default())),
+            ScanGuardConfig {
+                max_misses,
+                window: Duration::from_secs(60),
+                block: Duration::from_secs(600),
+                action: ScanGuardAction::Throttle,
+                tarpit_delay: Duration::from_secs(1),
+                honeypot_keys: vec![String::from("wp-admin")],
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn flags_clients_past_the_distinct_misses_allowed() {
+        let guard = guard(2);
+        for key in ["a", "b"] {
+            guard.record_miss(CLIENT, key).await.unwrap();
+        }
+        assert_eq!(guard.flagged_for(CLIENT).await.unwrap(), None);
+
+        guard.record_miss(CLIENT, "c").await.unwrap();
+        assert_eq!(
+            guard.flagged_for(CLIENT).await.unwrap(),
+            Some(Duration::from_secs(600))
+        );
+        assert_eq!(guard.stats().flagged_clients, 1);
+    }
+
+    #[tokio::test]
+    async fn counts_a_key_asked_for_again_once() {
+        let guard = guard(2);
+        for _ in 0..5 {
+            guard.record_miss(CLIENT, "typo").await.unwrap();
+        }
+        assert_eq!(guard.flagged_for(CLIENT).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn flags_clients_asking_for_a_honeypot_at_once() {
+        let guard = guard(10);
+        guard.record_miss(CLIENT, "wp-admin").await.unwrap();
+        assert!(guard.flagged_for(CLIENT).await.unwrap().is_some());
+        assert_eq!(guard.stats().honeypot_hits, 1);
+
+        let other = IpAddr::V4(std::net::Ipv4Addr::new(203, 0, 113, 8));
+        assert_eq!(guard.flagged_for(other).await.unwrap(), None);
+    }
+}
@@ -1,12 +1,43 @@
 use std::ops::Deref;
 
-use axum::response::{IntoResponse, Response};
+use axum::response::Response;
+use migration::MigratorTrait;
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, ModelTrait, QueryFilter,
-    QueryOrder, QuerySelect, Set,
+    sea_query::{
+        Alias, Expr, IntoCondition, NullOrdering, OnConflict, Order, Query, SelectStatement,
+    },
+    sqlx::postgres::PgListener,
+    ActiveModelTrait, ColumnTrait, Condition, ConnectOptions, ConnectionTrait, DatabaseConnection,
+    DbBackend, DbErr, EntityTrait, ModelTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QuerySelect, QueryTrait, Set, Statement, TransactionTrait,
 };
+use sha2::{Digest, Sha256};
 
-use crate::{models::url_redirects, responses::UrlRedirect};
+use crate::{
+    analytics::{AnalyticsError, AnalyticsStore},
+    click_buffer::Click,
+    click_partitions::{next_month, partition_month, partition_name},
+    config::{DatabaseConfig, KeyGenerationMode, KeyPolicyConfig},
+    error::{localized_problem, problem, ProblemType},
+    i18n::{Message, MessageId},
+    key_generator::KeyGenerator,
+    link_cache::LINK_CHANGES_CHANNEL,
+    models::{
+        bio_page_links, bio_pages, campaigns, click_count_write_backs, clicks, link_templates,
+        notification_preferences, plans, scheduled_targets, slack_accounts, tenant_settings,
+        url_redirect_aliases, url_redirect_revisions, url_redirect_tags, url_redirects, user_plans,
+    },
+    reload::Reloadable,
+    requests::{
+        LinkSort, LinkState, NewBioPage, NewTemplate, PageCursor, PlanLimits, TenantOverrides,
+    },
+    responses::{
+        AppLink, AppLinks, BioLink, BioPage, Campaign, DryRun, DryRunAction, LinkAlias,
+        LinkTemplate, NotificationPreferences, Plan, PublicLink, Revision, Rollout,
+        ScheduledTarget, SocialPreview, TagSummary, TenantSettings, UrlRedirect,
+    },
+    tenant,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum InsertError {
@@ -14,16 +45,33 @@ pub enum InsertError {
     Database(sea_orm::DbErr),
     #[error("already exists")]
     KeyAlreadyExists,
+    #[error("link limit of the owner's plan reached")]
+    LinkLimitReached,
+    #[error("link limit of the tenant reached")]
+    TenantLinkLimitReached,
+    #[error("handle already taken")]
+    HandleTaken,
+    #[error("key of the revision is no longer allowed: {0}")]
+    RevisionKeyRejected(RedirectKeyValidationFailed),
 }
 
 impl From<sea_orm::DbErr> for InsertError {
     fn from(error: sea_orm::DbErr) -> Self {
         match error.sql_err() {
+            // Postgres names the index, SQLite the table and columns
             Some(sea_orm::SqlErr::UniqueConstraintViolation(key))
-                if key.contains("url_redirects_key_key") =>
+                if key.contains("idx_url_redirects_tenant_id_key")
+                    || key.contains("idx_url_redirect_aliases_tenant_id_key")
+                    || key.ends_with("url_redirects.key")
+                    || key.ends_with("url_redirect_aliases.key") =>
             {
                 Self::KeyAlreadyExists
             }
+            Some(sea_orm::SqlErr::UniqueConstraintViolation(key))
+                if key.contains("bio_pages_handle_key") || key.ends_with("bio_pages.handle") =>
+            {
+                Self::HandleTaken
+            }
             _ => Self::Database(error),
         }
     }
@@ -32,13 +80,27 @@ impl From<sea_orm::DbErr> for InsertError {
 impl From<InsertError> for Response {
     fn from(value: InsertError) -> Self {
         match value {
-            InsertError::Database(_) => (
-                http::StatusCode::INTERNAL_SERVER_ERROR,
-                "internal server error",
+            InsertError::Database(_) => problem(ProblemType::Internal, "internal server error"),
+            InsertError::KeyAlreadyExists => localized_problem(
+                ProblemType::KeyAlreadyExists,
+                Message::new(MessageId::KeyAlreadyExists),
             ),
-            InsertError::KeyAlreadyExists => (http::StatusCode::CONFLICT, "key already exists"),
+            InsertError::LinkLimitReached => localized_problem(
+                ProblemType::LinkLimitReached,
+                Message::new(MessageId::LinkLimitReached),
+            ),
+            InsertError::TenantLinkLimitReached => localized_problem(
+                ProblemType::LinkLimitReached,
+                Message::new(MessageId::TenantLinkLimitReached),
+            ),
+            InsertError::HandleTaken => localized_problem(
+                ProblemType::HandleTaken,
+                Message::new(MessageId::HandleTaken),
+            ),
+            InsertError::RevisionKeyRejected(error) => {
+                localized_problem(ProblemType::UnrestorableRevision, error.message())
+            }
         }
-        .into_response()
     }
 }
 
@@ -51,36 +113,123 @@ pub enum QueryError {
 impl From<QueryError> for Response {
     fn from(value: QueryError) -> Self {
         tracing::error!(error = %value, "service internal server error");
-        (
-            http::StatusCode::INTERNAL_SERVER_ERROR,
-            "internal server error",
-        )
-            .into_response()
+        problem(ProblemType::Internal, "internal server error")
     }
 }
 
+#[derive(Debug, thiserror::Error)]
 pub enum RedirectKeyValidationFailed {
-    TooLong,
+    #[error("too short, minimum length of a key is {0}")]
+    TooShort(usize),
+    #[error("too long, maximum length of a key is {0}")]
+    TooLong(usize),
+    #[error("invalid characters: {}", .0.iter().collect::<String>())]
     InvalidCharacters(Vec<char>),
+    #[error("must not be only dots")]
+    OnlyDots,
+    #[error("taken by a route of the service")]
+    Reserved,
+}
+
+impl RedirectKeyValidationFailed {
+    pub fn message(&self) -> Message {
+        match self {
+            Self::TooShort(min) => Message::new(MessageId::KeyTooShort).with_arg("min", min),
+            Self::TooLong(max) => Message::new(MessageId::KeyTooLong).with_arg("max", max),
+            Self::InvalidCharacters(chars) => Message::new(MessageId::KeyInvalidCharacters)
+                .with_arg("characters", chars.iter().collect::<String>()),
+            Self::OnlyDots => Message::new(MessageId::KeyOnlyDots),
+            Self::Reserved => Message::new(MessageId::KeyReserved),
+        }
+    }
 }
 
 impl From<RedirectKeyValidationFailed> for Response {
     fn from(value: RedirectKeyValidationFailed) -> Self {
-        match value {
-            RedirectKeyValidationFailed::TooLong => (
-                http::StatusCode::BAD_REQUEST,
-                "too long, maximum length of a key is 100",
-            )
-                .into_response(),
-            RedirectKeyValidationFailed::InvalidCharacters(chars) => {
-                let invalid_chars: String = chars.into_iter().collect();
-                (
-                    http::StatusCode::BAD_REQUEST,
-                    format!("invalid characters: {}", invalid_chars),
-                )
-                    .into_response()
-            }
+        localized_problem(ProblemType::ValidationFailed, value.message())
+    }
+}
+
+/// Whether `c` may appear in a key under the default policy.
+pub fn is_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+/// The rules keys picked by users must follow.
+#[derive(Debug, Clone)]
+pub struct KeyPolicy {
+    min_length: usize,
+    max_length: usize,
+    allowed_characters: String,
+    allow_dots: bool,
+    allow_tildes: bool,
+    reserved_keys: Vec<String>,
+}
+
+impl Default for KeyPolicy {
+    fn default() -> Self {
+        Self::new(&KeyPolicyConfig::default())
+    }
+}
+
+impl KeyPolicy {
+    pub fn new(config: &KeyPolicyConfig) -> Self {
+        Self {
+            min_length: config.min_length,
+            max_length: config.max_length,
+            allowed_characters: config.allowed_characters.clone(),
+            allow_dots: config.allow_dots,
+            allow_tildes: config.allow_tildes,
+            reserved_keys: Vec::new(),
+        }
+    }
+
+    /// Rejects `reserved_keys` as well, which routes of the service take.
+    pub fn with_reserved_keys(mut self, reserved_keys: Vec<String>) -> Self {
+        self.reserved_keys = reserved_keys;
+        self
+    }
+
+    /// Whether `key` is taken by a route, so a link of that key would never
+    /// redirect.
+    pub fn is_reserved(&self, key: &str) -> bool {
+        self.reserved_keys.iter().any(|reserved| reserved == key)
+    }
+
+    /// Whether `c` may appear in a key: letters and digits always, the rest
+    /// as configured.
+    pub fn allows(&self, c: char) -> bool {
+        match c {
+            '.' => self.allow_dots,
+            '~' => self.allow_tildes,
+            c => c.is_ascii_alphanumeric() || self.allowed_characters.contains(c),
+        }
+    }
+
+    pub fn parse(&self, key: String) -> Result<RedirectKey, RedirectKeyValidationFailed> {
+        let length = key.chars().count();
+        if length < self.min_length {
+            return Err(RedirectKeyValidationFailed::TooShort(self.min_length));
+        }
+        if length > self.max_length {
+            return Err(RedirectKeyValidationFailed::TooLong(self.max_length));
+        }
+
+        let invalid_chars: Vec<char> = key.chars().filter(|c| !self.allows(*c)).collect();
+        if !invalid_chars.is_empty() {
+            return Err(RedirectKeyValidationFailed::InvalidCharacters(
+                invalid_chars,
+            ));
+        }
+        // `.` and `..` are path segments, which clients resolve away
+        if key.chars().all(|c| c == '.') {
+            return Err(RedirectKeyValidationFailed::OnlyDots);
         }
+        if self.is_reserved(&key) {
+            return Err(RedirectKeyValidationFailed::Reserved);
+        }
+
+        Ok(RedirectKey(key))
     }
 }
 
@@ -95,34 +244,214 @@ impl Deref for RedirectKey {
     }
 }
 
-impl TryFrom<String> for RedirectKey {
-    type Error = RedirectKeyValidationFailed;
+// Generated keys are long enough that a collision is rare, so a handful of
+// retries never runs out in practice.
+const GENERATED_KEY_ATTEMPTS: usize = 5;
 
-    fn try_from(value: String) -> Result<Self, Self::Error> {
-        if value.len() > 100 {
-            return Err(RedirectKeyValidationFailed::TooLong);
-        }
+// Keys are unique across links and aliases together, while the database only
+// enforces it within each table.
+async fn key_is_alias(conn: &impl ConnectionTrait, key: &str) -> Result<bool, DbErr> {
+    Ok(url_redirect_aliases::Entity::find()
+        .filter(in_tenant(url_redirect_aliases::Column::TenantId))
+        .filter(url_redirect_aliases::Column::Key.eq(key))
+        .count(conn)
+        .await?
+        > 0)
+}
 
-        let invalid_chars: Vec<char> = value
-            .chars()
-            .filter(|c| !c.is_ascii_alphanumeric() && *c != '-' && *c != '_')
-            .collect();
+// Keys are unique within a tenant, so other tenants' keys are never revealed
+// as taken.
+async fn key_is_taken(conn: &impl ConnectionTrait, key: &str) -> Result<bool, DbErr> {
+    let used_by_link = url_redirects::Entity::find()
+        .filter(in_tenant(url_redirects::Column::TenantId))
+        .filter(url_redirects::Column::Key.eq(key))
+        .count(conn)
+        .await?
+        > 0;
+    Ok(used_by_link || key_is_alias(conn, key).await?)
+}
 
-        if !invalid_chars.is_empty() {
-            return Err(RedirectKeyValidationFailed::InvalidCharacters(
-                invalid_chars,
-            ));
-        }
+// Keeps the current key and target of `url` as its next revision, before
+// they are replaced.
+async fn record_revision(
+    conn: &impl ConnectionTrait,
+    url: &url_redirects::Model,
+    changed_by: &str,
+) -> Result<(), DbErr> {
+    let revision = url_redirect_revisions::Entity::find()
+        .filter(url_redirect_revisions::Column::UrlRedirectId.eq(url.id))
+        .count(conn)
+        .await?
+        + 1;
+    url_redirect_revisions::ActiveModel {
+        id: Set(uuid::Uuid::new_v4()),
+        url_redirect_id: Set(url.id),
+        revision: Set(i32::try_from(revision).unwrap_or(i32::MAX)),
+        key: Set(url.key.clone()),
+        target: Set(url.target.clone()),
+        changed_by: Set(changed_by.to_string()),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?;
+    Ok(())
+}
+
+// Links are listed never-accessed first, then by access time, then by key;
+// this picks those past the link last accessed at `last_accessed_at` with `key`.
+fn after_last_accessed(
+    last_accessed_at: Option<sea_orm::prelude::DateTimeWithTimeZone>,
+    key: String,
+) -> Condition {
+    let later_key = url_redirects::Column::Key.gt(key);
+    match last_accessed_at {
+        None => Condition::any()
+            .add(url_redirects::Column::LastAccessedAt.is_not_null())
+            .add(
+                Condition::all()
+                    .add(url_redirects::Column::LastAccessedAt.is_null())
+                    .add(later_key),
+            ),
+        Some(at) => Condition::any()
+            .add(url_redirects::Column::LastAccessedAt.gt(at))
+            .add(
+                Condition::all()
+                    .add(url_redirects::Column::LastAccessedAt.eq(at))
+                    .add(later_key),
+            ),
+    }
+}
+
+// The mirror of `after_last_accessed`: those listed before the link.
+fn before_last_accessed(
+    last_accessed_at: Option<sea_orm::prelude::DateTimeWithTimeZone>,
+    key: String,
+) -> Condition {
+    let earlier_key = url_redirects::Column::Key.lt(key);
+    match last_accessed_at {
+        None => Condition::all()
+            .add(url_redirects::Column::LastAccessedAt.is_null())
+            .add(earlier_key),
+        Some(at) => Condition::any()
+            .add(url_redirects::Column::LastAccessedAt.is_null())
+            .add(url_redirects::Column::LastAccessedAt.lt(at))
+            .add(
+                Condition::all()
+                    .add(url_redirects::Column::LastAccessedAt.eq(at))
+                    .add(earlier_key),
+            ),
+    }
+}
+
+// Links are listed newest first, then by key; this picks those past the link
+// created at `created_at` with `key`.
+fn after_created(created_at: sea_orm::prelude::DateTimeWithTimeZone, key: String) -> Condition {
+    Condition::any()
+        .add(url_redirects::Column::CreatedAt.lt(created_at))
+        .add(
+            Condition::all()
+                .add(url_redirects::Column::CreatedAt.eq(created_at))
+                .add(url_redirects::Column::Key.gt(key)),
+        )
+}
+
+// The mirror of `after_created`: those listed before the link.
+fn before_created(created_at: sea_orm::prelude::DateTimeWithTimeZone, key: String) -> Condition {
+    Condition::any()
+        .add(url_redirects::Column::CreatedAt.gt(created_at))
+        .add(
+            Condition::all()
+                .add(url_redirects::Column::CreatedAt.eq(created_at))
+                .add(url_redirects::Column::Key.lt(key)),
+        )
+}
 
-        Ok(Self(value))
+// Tags belong to their links, so an owner's tags are those of their links.
+fn owned_link_ids(user_email: &str) -> SelectStatement {
+    Query::select()
+        .column(url_redirects::Column::Id)
+        .from(url_redirects::Entity)
+        .and_where(url_redirects::Column::UserEmail.eq(user_email))
+        .cond_where(in_tenant(url_redirects::Column::TenantId))
+        .to_owned()
+}
+
+// Within a request only the current tenant's rows are seen, while background
+// tasks and the CLI work across every tenant.
+fn in_tenant(column: impl ColumnTrait) -> Condition {
+    match tenant::current() {
+        Some(tenant) => Condition::all().add(column.eq(tenant.id.clone())),
+        None => Condition::all(),
     }
 }
 
+/// What renaming a tag did.
+pub enum TagRename {
+    Renamed(u64),
+    /// None of the owner's links has the tag.
+    NotFound,
+    /// Some of the owner's links already have the new name; merge instead.
+    Taken,
+}
+
+// Pinned links are listed first, each group by key; this picks those past the
+// link with `key`.
+fn after_pinned(pinned: bool, key: String) -> Condition {
+    let later_key = url_redirects::Column::Key.gt(key);
+    let condition = if pinned {
+        Condition::any()
+    } else {
+        Condition::all()
+    };
+    condition
+        .add(url_redirects::Column::Pinned.eq(false))
+        .add(later_key)
+}
+
+// The mirror of `after_pinned`: those listed before the link.
+fn before_pinned(pinned: bool, key: String) -> Condition {
+    let earlier_key = url_redirects::Column::Key.lt(key);
+    let condition = if pinned {
+        Condition::all()
+    } else {
+        Condition::any()
+    };
+    condition
+        .add(url_redirects::Column::Pinned.eq(true))
+        .add(earlier_key)
+}
+
+/// Which of an owner's links to list.
+#[derive(Debug, Clone, Default)]
+pub struct LinkFilter {
+    pub state: LinkState,
+    /// Only links with this tag.
+    pub tag: Option<String>,
+    /// Only links to this target, as given or once normalized.
+    pub target: Option<String>,
+    /// Only links to this lowercase domain or its subdomains.
+    pub domain: Option<String>,
+    pub created_after: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_before: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only links flagged as inactive, or only those not.
+    pub flagged: Option<bool>,
+}
+
+/// A page of a list, in its order, and whether there is more on either
+/// side of it.
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub has_prev: bool,
+    pub has_next: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct NewUrlRedirect {
     user_email: String,
     key: RedirectKey,
     target: String,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 impl NewUrlRedirect {
@@ -131,8 +460,45 @@ impl NewUrlRedirect {
             user_email,
             key,
             target,
+            expires_at: None,
         }
     }
+
+    /// The redirect stops resolving after `expires_at`.
+    pub fn expiring_at(mut self, expires_at: chrono::DateTime<chrono::Utc>) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+}
+
+/// `target` as the same page is spelled however it was written: the URL
+/// parser's canonical form, without a fragment or a trailing slash.
+fn normalize_target(target: &str) -> String {
+    let target = target.trim();
+    let Ok(mut url) = url::Url::parse(target) else {
+        return target.to_owned();
+    };
+    url.set_fragment(None);
+    if url.query() == Some("") {
+        url.set_query(None);
+    }
+    if url.path().len() > 1 && url.path().ends_with('/') {
+        let path = url.path().trim_end_matches('/').to_owned();
+        url.set_path(&path);
+    }
+    url.into()
+}
+
+// Normalized targets are looked up by hash, as they can outgrow an index entry.
+fn normalized_target_hash(target: &str) -> String {
+    format!("{:x}", Sha256::digest(normalize_target(target)))
+}
+
+/// The lowercase domain `target` points at, for finding links by domain.
+fn target_host(target: &str) -> Option<String> {
+    let url = url::Url::parse(target.trim()).ok()?;
+    let host = url.host_str()?.trim_end_matches('.');
+    Some(host.to_ascii_lowercase())
 }
 
 impl From<NewUrlRedirect> for url_redirects::ActiveModel {
@@ -141,46 +507,325 @@ impl From<NewUrlRedirect> for url_redirects::ActiveModel {
             id: Set(uuid::Uuid::new_v4()),
             user_email: Set(value.user_email),
             key: Set(value.key.0),
+            normalized_target_hash: Set(Some(normalized_target_hash(&value.target))),
+            target_host: Set(target_host(&value.target)),
             target: Set(value.target),
+            expires_at: Set(value.expires_at.map(Into::into)),
+            tenant_id: Set(tenant::current_id()),
             ..Default::default()
         }
     }
 }
 
+/// Owner recorded for links created without signing in. It has no `@`, so
+/// it can never be a real user's email.
+pub const ANONYMOUS_OWNER: &str = "anonymous";
+
+/// Plan of every owner without one assigned.
+pub const DEFAULT_PLAN: &str = "free";
+
 pub struct UrlService {
     db: DatabaseConnection,
+    key_generator: KeyGenerator,
+    /// Holds the key policy, which can change while running.
+    reloadable: Reloadable,
+    public_base_url: Option<url::Url>,
+    redirect_prefix: String,
 }
 
 impl UrlService {
-    pub async fn new(postgres_url: &str) -> Result<Self, DbErr> {
+    pub async fn new(config: &DatabaseConfig) -> Result<Self, DbErr> {
+        let mut options = ConnectOptions::new(&config.url);
+        if let Some(max_connections) = config.max_connections {
+            options.max_connections(max_connections);
+        }
+        if let Some(min_connections) = config.min_connections {
+            options.min_connections(min_connections);
+        }
+        if let Some(acquire_timeout) = config.acquire_timeout {
+            options.acquire_timeout(acquire_timeout);
+        }
+        if let Some(threshold) = config.slow_statement_threshold {
+            options.sqlx_slow_statements_logging_settings(log::LevelFilter::Warn, threshold);
+        }
+        if let Some(statement_timeout) = config.statement_timeout {
+            // postgres applies this per session, so every pooled connection gets it on connect
+            let statement_timeout = statement_timeout.as_millis().to_string();
+            options.map_sqlx_postgres_opts(move |opts| {
+                opts.options([("statement_timeout", statement_timeout.as_str())])
+            });
+        }
+
         Ok(Self {
-            db: sea_orm::Database::connect(postgres_url).await?,
+            db: sea_orm::Database::connect(options).await?,
+            key_generator: KeyGenerator::default(),
+            reloadable: Reloadable::default(),
+            public_base_url: None,
+            redirect_prefix: String::new(),
         })
     }
+
+    pub fn with_key_generator(mut self, key_generator: KeyGenerator) -> Self {
+        self.key_generator = key_generator;
+        self
+    }
+
+    pub fn with_reloadable(mut self, reloadable: Reloadable) -> Self {
+        self.reloadable = reloadable;
+        self
+    }
+
+    pub fn with_public_base_url(mut self, public_base_url: Option<url::Url>) -> Self {
+        self.public_base_url = public_base_url;
+        self
+    }
+
+    pub fn with_redirect_prefix(mut self, redirect_prefix: String) -> Self {
+        self.redirect_prefix = redirect_prefix;
+        self
+    }
+
+    /// The full short URL of `key`, its redirect path under the current
+    /// tenant's base URL or else the installation's, or just that path when
+    /// neither is configured.
+    pub fn short_url(&self, key: &str) -> String {
+        let path = format!("{}/{key}", self.redirect_prefix);
+        let tenant = tenant::current();
+        tenant
+            .as_ref()
+            .and_then(|tenant| tenant.public_base_url.as_ref())
+            .or(self.public_base_url.as_ref())
+            // joined relative to the base, which may be served under a path
+            .and_then(|base| base.join(&format!(".{path}")).ok())
+            .map_or(path, String::from)
+    }
+
+    /// The rules keys picked by users are checked against.
+    pub fn key_policy(&self) -> KeyPolicy {
+        self.reloadable.key_policy()
+    }
+
+    pub async fn run_migrations(&self) -> Result<(), DbErr> {
+        migration::Migrator::up(&self.db, None).await?;
+        self.index_targets().await
+    }
+
+    /// Fills in the normalized target hash and host of links from before
+    /// they were kept, which the migrations adding them cannot.
+    async fn index_targets(&self) -> Result<(), DbErr> {
+        // a backfill of the whole installation, every tenant's links
+        // included, wherever it is called from
+        loop {
+            let links: Vec<(uuid::Uuid, String)> = url_redirects::Entity::find()
+                .select_only()
+                .column(url_redirects::Column::Id)
+                .column(url_redirects::Column::Target)
+                .filter(url_redirects::Column::NormalizedTargetHash.is_null())
+                .limit(500)
+                .into_tuple()
+                .all(&self.db)
+                .await?;
+            if links.is_empty() {
+                return Ok(());
+            }
+
+            let txn = self.db.begin().await?;
+            for (id, target) in links {
+                url_redirects::Entity::update_many()
+                    .col_expr(
+                        url_redirects::Column::NormalizedTargetHash,
+                        Expr::value(normalized_target_hash(&target)),
+                    )
+                    .col_expr(
+                        url_redirects::Column::TargetHost,
+                        Expr::value(target_host(&target)),
+                    )
+                    .filter(url_redirects::Column::Id.eq(id))
+                    .exec(&txn)
+                    .await?;
+            }
+            txn.commit().await?;
+        }
+    }
+
+    /// Subscribes to the ids of changed links. The listener holds one of the
+    /// pool's connections for as long as it lives.
+    pub async fn listen_for_link_changes(&self) -> Result<PgListener, sea_orm::sqlx::Error> {
+        let mut listener = PgListener::connect_with(self.db.get_postgres_connection_pool()).await?;
+        listener.listen(LINK_CHANGES_CHANNEL).await?;
+        Ok(listener)
+    }
 }
 
 impl UrlService {
     pub async fn list_by_email(
         &self,
         user_email: &str,
-        after: Option<String>,
+        filter: LinkFilter,
+        sort: LinkSort,
+        cursor: Option<PageCursor>,
+        limit: u64,
+    ) -> Result<Page<UrlRedirect>, QueryError> {
+        self.list_all(Some(user_email), filter, sort, cursor, limit)
+            .await
+    }
+
+    /// The links of `owner`, or everyone's, for admins.
+    pub async fn list_all(
+        &self,
+        owner: Option<&str>,
+        filter: LinkFilter,
+        sort: LinkSort,
+        cursor: Option<PageCursor>,
         limit: u64,
-    ) -> Result<Vec<UrlRedirect>, QueryError> {
+    ) -> Result<Page<UrlRedirect>, QueryError> {
+        let archived = match filter.state {
+            LinkState::Active => url_redirects::Column::ArchivedAt.is_null(),
+            LinkState::Archived => url_redirects::Column::ArchivedAt.is_not_null(),
+        };
+        let owned = owner.map(|owner| url_redirects::Column::UserEmail.eq(owner));
+        // one more than asked for tells whether there is another page
         let mut query = url_redirects::Entity::find()
-            .filter(url_redirects::Column::UserEmail.eq(user_email))
-            .order_by_asc(url_redirects::Column::Key)
-            .limit(limit);
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .apply_if(owned.clone(), |query, owned| query.filter(owned))
+            .filter(archived)
+            .apply_if(filter.created_after, |query, after| {
+                query.filter(url_redirects::Column::CreatedAt.gte(after))
+            })
+            .apply_if(filter.created_before, |query, before| {
+                query.filter(url_redirects::Column::CreatedAt.lt(before))
+            })
+            .apply_if(filter.flagged, |query, flagged| {
+                if flagged {
+                    query.filter(url_redirects::Column::InactiveSince.is_not_null())
+                } else {
+                    query.filter(url_redirects::Column::InactiveSince.is_null())
+                }
+            })
+            .limit(limit + 1);
 
-        if let Some(key) = after {
-            query = query.filter(url_redirects::Column::Key.gt(key));
+        if let Some(domain) = filter.domain {
+            // hosts are letters, digits, `-` and `.`, none of them special to LIKE
+            query = query.filter(
+                Condition::any()
+                    .add(url_redirects::Column::TargetHost.like(format!("%.{domain}")))
+                    .add(url_redirects::Column::TargetHost.eq(domain)),
+            );
+        }
+        if let Some(target) = filter.target {
+            query = query.filter(
+                Condition::any()
+                    .add(
+                        url_redirects::Column::NormalizedTargetHash
+                            .eq(normalized_target_hash(&target)),
+                    )
+                    .add(url_redirects::Column::Target.eq(target)),
+            );
+        }
+        if let Some(tag) = filter.tag {
+            query = query.filter(
+                url_redirects::Column::Id.in_subquery(
+                    Query::select()
+                        .column(url_redirect_tags::Column::UrlRedirectId)
+                        .from(url_redirect_tags::Entity)
+                        .and_where(url_redirect_tags::Column::Tag.eq(tag))
+                        .to_owned(),
+                ),
+            );
+        }
+        // paging back walks the list in reverse from the cursor
+        let backward = matches!(cursor, Some(PageCursor::Before(_)));
+        let cursor_given = cursor.is_some();
+        let (order, nulls) = if backward {
+            (Order::Desc, NullOrdering::Last)
+        } else {
+            (Order::Asc, NullOrdering::First)
+        };
+        // pinned links and new ones come first, against the order of the key
+        let reversed = if backward { Order::Asc } else { Order::Desc };
+        query = match sort {
+            LinkSort::Key => query,
+            LinkSort::LastAccessed => query.order_by_with_nulls(
+                url_redirects::Column::LastAccessedAt,
+                order.clone(),
+                nulls,
+            ),
+            LinkSort::Pinned => query.order_by(url_redirects::Column::Pinned, reversed),
+            LinkSort::Newest => query.order_by(url_redirects::Column::CreatedAt, reversed),
         }
+        .order_by(url_redirects::Column::Key, order);
 
-        Ok(query
+        if let Some(cursor) = cursor {
+            let key = match &cursor {
+                PageCursor::After(key) | PageCursor::Before(key) => key.clone(),
+            };
+            let position = match sort {
+                LinkSort::Key => {
+                    if backward {
+                        url_redirects::Column::Key.lt(key).into_condition()
+                    } else {
+                        url_redirects::Column::Key.gt(key).into_condition()
+                    }
+                }
+                LinkSort::LastAccessed | LinkSort::Pinned | LinkSort::Newest => {
+                    // the cursor stays a key; its link's sort value places it
+                    let link = url_redirects::Entity::find()
+                        .filter(in_tenant(url_redirects::Column::TenantId))
+                        .apply_if(owned, |query, owned| query.filter(owned))
+                        .filter(url_redirects::Column::Key.eq(&key))
+                        .one(&self.db)
+                        .await?;
+                    let Some(link) = link else {
+                        return Ok(Page {
+                            items: Vec::new(),
+                            has_prev: false,
+                            has_next: false,
+                        });
+                    };
+                    match (sort, backward) {
+                        (LinkSort::Pinned, false) => after_pinned(link.pinned, key),
+                        (LinkSort::Pinned, true) => before_pinned(link.pinned, key),
+                        (LinkSort::Newest, false) => after_created(link.created_at, key),
+                        (LinkSort::Newest, true) => before_created(link.created_at, key),
+                        (_, false) => after_last_accessed(link.last_accessed_at, key),
+                        (_, true) => before_last_accessed(link.last_accessed_at, key),
+                    }
+                }
+            };
+            query = query.filter(position);
+        }
+
+        let mut items: Vec<UrlRedirect> = query
             .all(&self.db)
             .await?
             .into_iter()
-            .map(Into::into)
-            .collect())
+            .map(|url| self.redirect(url))
+            .collect();
+        let has_more = items.len() as u64 > limit;
+        items.truncate(limit as usize);
+
+        if backward {
+            items.reverse();
+            Ok(Page {
+                items,
+                has_prev: has_more,
+                has_next: true,
+            })
+        } else {
+            Ok(Page {
+                items,
+                has_prev: cursor_given,
+                has_next: has_more,
+            })
+        }
+    }
+
+    pub async fn get_by_id(&self, id: uuid::Uuid) -> Result<Option<UrlRedirect>, QueryError> {
+        Ok(url_redirects::Entity::find_by_id(id)
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .one(&self.db)
+            .await?
+            .map(|url| self.redirect(url)))
     }
 
     pub async fn get_by_id_and_email(
@@ -189,35 +834,272 @@ impl UrlService {
         email: &str,
     ) -> Result<Option<UrlRedirect>, QueryError> {
         Ok(url_redirects::Entity::find()
+            .filter(in_tenant(url_redirects::Column::TenantId))
             .filter(url_redirects::Column::Id.eq(id))
             .filter(url_redirects::Column::UserEmail.eq(email))
             .one(&self.db)
             .await?
-            .map(Into::into))
+            .map(|url| self.redirect(url)))
+    }
+
+    /// The owner's link with `key` or an alias `key`, archived and expired
+    /// ones included.
+    pub async fn get_by_key_and_email(
+        &self,
+        key: &str,
+        email: &str,
+    ) -> Result<Option<UrlRedirect>, QueryError> {
+        Ok(url_redirects::Entity::find()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(
+                Condition::any()
+                    .add(url_redirects::Column::Key.eq(key))
+                    .add(
+                        url_redirects::Column::Id.in_subquery(
+                            Query::select()
+                                .column(url_redirect_aliases::Column::UrlRedirectId)
+                                .from(url_redirect_aliases::Entity)
+                                .and_where(url_redirect_aliases::Column::Key.eq(key))
+                                .to_owned(),
+                        ),
+                    ),
+            )
+            .filter(url_redirects::Column::UserEmail.eq(email))
+            .one(&self.db)
+            .await?
+            .map(|url| self.redirect(url)))
     }
 
     pub async fn get_by_key(&self, key: &str) -> Result<Option<UrlRedirect>, QueryError> {
         Ok(url_redirects::Entity::find()
-            .filter(url_redirects::Column::Key.eq(key))
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(
+                Condition::any()
+                    .add(url_redirects::Column::Key.eq(key))
+                    .add(
+                        url_redirects::Column::Id.in_subquery(
+                            Query::select()
+                                .column(url_redirect_aliases::Column::UrlRedirectId)
+                                .from(url_redirect_aliases::Entity)
+                                .and_where(url_redirect_aliases::Column::Key.eq(key))
+                                .to_owned(),
+                        ),
+                    ),
+            )
+            .filter(url_redirects::Column::ArchivedAt.is_null())
+            .filter(
+                Condition::any()
+                    .add(url_redirects::Column::ExpiresAt.is_null())
+                    .add(url_redirects::Column::ExpiresAt.gt(chrono::Utc::now())),
+            )
             .one(&self.db)
             .await?
-            .map(Into::into))
+            .map(|url| self.redirect(url)))
     }
 
     pub async fn create(&self, new_url: NewUrlRedirect) -> Result<UrlRedirect, InsertError> {
+        // anonymous links are bounded by their own rate limit and expiry
+        if new_url.user_email != ANONYMOUS_OWNER {
+            self.check_link_limit(&new_url.user_email).await?;
+        }
+        if key_is_alias(&self.db, &new_url.key).await? {
+            return Err(InsertError::KeyAlreadyExists);
+        }
+
         url_redirects::ActiveModel::from(new_url)
             .insert(&self.db)
             .await
-            .map(Into::into)
+            .map(|url| self.redirect(url))
             .map_err(Into::into)
     }
 
+    /// Runs every check creating `new_url` would, without creating it.
+    pub async fn check_create(&self, new_url: &NewUrlRedirect) -> Result<DryRun, InsertError> {
+        if new_url.user_email != ANONYMOUS_OWNER {
+            self.check_link_limit(&new_url.user_email).await?;
+        }
+        if key_is_taken(&self.db, &new_url.key).await? {
+            return Err(InsertError::KeyAlreadyExists);
+        }
+
+        Ok(DryRun::new(
+            DryRunAction::Create,
+            new_url.key.to_string(),
+            new_url.target.clone(),
+        ))
+    }
+
+    /// Creates the redirect under a generated key, retrying when the key is
+    /// already taken.
+    pub async fn create_with_generated_key(
+        &self,
+        user_email: String,
+        target: String,
+        expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<UrlRedirect, InsertError> {
+        let mut attempts = 0;
+        loop {
+            let key = self.generate_key().await?;
+            let mut new_url = NewUrlRedirect::new(user_email.clone(), key, target.clone());
+            if let Some(expires_at) = expires_at {
+                new_url = new_url.expiring_at(expires_at);
+            }
+
+            attempts += 1;
+            match self.create(new_url).await {
+                Err(InsertError::KeyAlreadyExists) if attempts < GENERATED_KEY_ATTEMPTS => {
+                    tracing::debug!("generated key already taken, retrying");
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn generate_key(&self) -> Result<RedirectKey, DbErr> {
+        let key_policy = self.key_policy();
+        match self.key_generator.mode() {
+            KeyGenerationMode::Random => loop {
+                let key = self.key_generator.generate();
+                if !key_policy.is_reserved(&key) {
+                    return Ok(RedirectKey(key));
+                }
+            },
+            // ids spelling a blocked word or a reserved key are skipped for
+            // good
+            KeyGenerationMode::Sequential => loop {
+                let id = self.next_key_id().await?;
+                if let Some(key) = self.key_generator.sequential(id) {
+                    if !key_policy.is_reserved(&key) {
+                        return Ok(RedirectKey(key));
+                    }
+                }
+            },
+        }
+    }
+
+    async fn next_key_id(&self) -> Result<u64, DbErr> {
+        let backend = self.db.get_database_backend();
+        let sql = match backend {
+            DbBackend::Sqlite => "INSERT INTO url_redirect_key_seq DEFAULT VALUES RETURNING id",
+            _ => "SELECT nextval('url_redirect_key_seq') AS id",
+        };
+        let row = self
+            .db
+            .query_one(Statement::from_string(backend, sql))
+            .await?
+            .ok_or_else(|| DbErr::RecordNotFound(String::from("url_redirect_key_seq")))?;
+        let id: i64 = row.try_get("", "id")?;
+        if backend == DbBackend::Sqlite {
+            // the autoincrement counter lives on without the rows
+            self.db
+                .execute(Statement::from_sql_and_values(
+                    backend,
+                    "DELETE FROM url_redirect_key_seq WHERE id = ?",
+                    [id.into()],
+                ))
+                .await?;
+        }
+        // both start at 1 and never go negative
+        Ok(id as u64)
+    }
+
+    /// Keys derived from `target` that the key policy allows and no link
+    /// uses yet.
+    pub async fn suggest_keys(&self, target: &url::Url) -> Result<Vec<String>, QueryError> {
+        let mut keys = self.key_generator.suggestions(target);
+        let key_policy = self.key_policy();
+        keys.retain(|key| key_policy.parse(key.clone()).is_ok());
+        let mut taken: Vec<String> = url_redirects::Entity::find()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .select_only()
+            .column(url_redirects::Column::Key)
+            .filter(url_redirects::Column::Key.is_in(keys.clone()))
+            .into_tuple()
+            .all(&self.db)
+            .await?;
+        taken.extend(
+            url_redirect_aliases::Entity::find()
+                .filter(in_tenant(url_redirect_aliases::Column::TenantId))
+                .select_only()
+                .column(url_redirect_aliases::Column::Key)
+                .filter(url_redirect_aliases::Column::Key.is_in(keys.clone()))
+                .into_tuple::<String>()
+                .all(&self.db)
+                .await?,
+        );
+
+        Ok(keys
+            .into_iter()
+            .filter(|key| !taken.contains(key))
+            .collect())
+    }
+
+    pub async fn count_created_since(
+        &self,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<u64, QueryError> {
+        Ok(url_redirects::Entity::find()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::CreatedAt.gte(since))
+            .count(&self.db)
+            .await?)
+    }
+
+    /// How many links there are, how many of them are archived, and how many
+    /// users own one, leaving out links made without signing in.
+    pub async fn link_totals(&self) -> Result<(u64, u64, u64), QueryError> {
+        let links = url_redirects::Entity::find()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .count(&self.db)
+            .await?;
+        let archived = url_redirects::Entity::find()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::ArchivedAt.is_not_null())
+            .count(&self.db)
+            .await?;
+        let users: Option<i64> = url_redirects::Entity::find()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .select_only()
+            .column_as(
+                Expr::col(url_redirects::Column::UserEmail).count_distinct(),
+                "users",
+            )
+            .filter(url_redirects::Column::UserEmail.ne(ANONYMOUS_OWNER))
+            .into_tuple()
+            .one(&self.db)
+            .await?;
+
+        Ok((links, archived, users.unwrap_or(0).max(0) as u64))
+    }
+
+    /// The owners who created the most links since `since`, with their count.
+    pub async fn top_creators_since(
+        &self,
+        since: chrono::DateTime<chrono::Utc>,
+        limit: u64,
+    ) -> Result<Vec<(String, i64)>, QueryError> {
+        Ok(url_redirects::Entity::find()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .select_only()
+            .column(url_redirects::Column::UserEmail)
+            .column_as(url_redirects::Column::Id.count(), "links")
+            .filter(url_redirects::Column::CreatedAt.gte(since))
+            .group_by(url_redirects::Column::UserEmail)
+            .order_by_desc(Expr::col(Alias::new("links")))
+            .order_by_asc(url_redirects::Column::UserEmail)
+            .limit(limit)
+            .into_tuple()
+            .all(&self.db)
+            .await?)
+    }
+
     pub async fn delete(
         &self,
         user_email: &str,
         id: uuid::Uuid,
     ) -> Result<Option<UrlRedirect>, QueryError> {
         let url = url_redirects::Entity::find_by_id(id)
+            .filter(in_tenant(url_redirects::Column::TenantId))
             .filter(url_redirects::Column::UserEmail.eq(user_email))
             .one(&self.db)
             .await?;
@@ -225,33 +1107,1909 @@ impl UrlService {
         let Some(url) = url else { return Ok(None) };
 
         url.clone().delete(&self.db).await?;
-        Ok(Some(url.into()))
+        Ok(Some(self.redirect(url)))
     }
 
-    pub async fn update(
+    /// Deletes the anonymous links that have expired, or every expired link
+    /// when `all`, returning how many there were. Expired links no longer
+    /// redirect, but keep their row and key until purged.
+    pub async fn purge_expired(&self, all: bool) -> Result<u64, QueryError> {
+        let mut purged = url_redirects::Entity::delete_many()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::ExpiresAt.lte(chrono::Utc::now()));
+        if !all {
+            purged = purged.filter(url_redirects::Column::UserEmail.eq(ANONYMOUS_OWNER));
+        }
+
+        Ok(purged.exec(&self.db).await?.rows_affected)
+    }
+
+    /// Creates a copy of the link under `key`, or a generated key. The copy
+    /// keeps the target, tags, app links, social preview, campaign and
+    /// settings (public, indexing, kept when inactive), and the expiry unless
+    /// it has passed; an ongoing rollout stays with the original.
+    pub async fn clone_url(
         &self,
         id: uuid::Uuid,
-        new_url: NewUrlRedirect,
+        user_email: &str,
+        key: Option<RedirectKey>,
     ) -> Result<Option<UrlRedirect>, InsertError> {
         let url = url_redirects::Entity::find_by_id(id)
-            .filter(url_redirects::Column::UserEmail.eq(new_url.user_email))
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
             .one(&self.db)
             .await?;
 
         let Some(url) = url else { return Ok(None) };
+        self.check_link_limit(&url.user_email).await?;
 
-        let mut active_model = url_redirects::ActiveModel::from(url);
-        active_model.key = Set(new_url.key.0);
-        active_model.target = Set(new_url.target);
-        active_model.updated_at = Set(chrono::Utc::now().into());
+        let mut attempts = 0;
+        loop {
+            let generated = key.is_none();
+            let key = match &key {
+                Some(key) => key.clone(),
+                None => self.generate_key().await?,
+            };
 
-        let url = active_model.update(&self.db).await?;
-        Ok(Some(url.into()))
+            attempts += 1;
+            match self.insert_clone(&url, key).await {
+                Err(InsertError::KeyAlreadyExists)
+                    if generated && attempts < GENERATED_KEY_ATTEMPTS =>
+                {
+                    tracing::debug!("generated key already taken, retrying");
+                }
+                result => return result.map(Some),
+            }
+        }
     }
-}
 
-impl From<url_redirects::Model> for UrlRedirect {
-    fn from(value: url_redirects::Model) -> Self {
-        Self::new(value.id, value.key, value.target)
+    /// Inserts the copy of `url` under `key` along with its tags, so a clone
+    /// never shows up without them.
+    async fn insert_clone(
+        &self,
+        url: &url_redirects::Model,
+        key: RedirectKey,
+    ) -> Result<UrlRedirect, InsertError> {
+        let txn = self.db.begin().await?;
+        if key_is_alias(&txn, &key).await? {
+            return Err(InsertError::KeyAlreadyExists);
+        }
+
+        let mut new_url = NewUrlRedirect::new(url.user_email.clone(), key, url.target.clone());
+        // a copy that has already expired would be of no use
+        if let Some(expires_at) = url.expires_at.filter(|at| *at > chrono::Utc::now()) {
+            new_url = new_url.expiring_at(expires_at.into());
+        }
+        let mut cloned = url_redirects::ActiveModel::from(new_url);
+        cloned.campaign_id = Set(url.campaign_id);
+        cloned.ios_deep_link = Set(url.ios_deep_link.clone());
+        cloned.ios_fallback_url = Set(url.ios_fallback_url.clone());
+        cloned.android_deep_link = Set(url.android_deep_link.clone());
+        cloned.android_fallback_url = Set(url.android_fallback_url.clone());
+        cloned.social_title = Set(url.social_title.clone());
+        cloned.social_description = Set(url.social_description.clone());
+        cloned.social_image = Set(url.social_image.clone());
+        cloned.public = Set(url.public);
+        cloned.allow_indexing = Set(url.allow_indexing);
+        cloned.keep_when_inactive = Set(url.keep_when_inactive);
+        let cloned = cloned.insert(&txn).await?;
+
+        let tags = url_redirect_tags::Entity::find()
+            .filter(url_redirect_tags::Column::UrlRedirectId.eq(url.id))
+            .all(&txn)
+            .await?;
+        if !tags.is_empty() {
+            let tags = tags.into_iter().map(|tag| url_redirect_tags::ActiveModel {
+                url_redirect_id: Set(cloned.id),
+                tag: Set(tag.tag),
+                ..Default::default()
+            });
+            url_redirect_tags::Entity::insert_many(tags)
+                .exec_without_returning(&txn)
+                .await?;
+        }
+
+        txn.commit().await?;
+        Ok(self.redirect(cloned))
+    }
+
+    /// Archives or restores the link. Archived links keep their row and
+    /// statistics but no longer redirect or show up in the default listing.
+    pub async fn set_archived(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+        archived: bool,
+    ) -> Result<Option<UrlRedirect>, QueryError> {
+        let url = url_redirects::Entity::find_by_id(id)
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?;
+
+        let Some(url) = url else { return Ok(None) };
+        if url.archived_at.is_some() == archived {
+            return Ok(Some(self.redirect(url)));
+        }
+
+        let now = chrono::Utc::now();
+        let mut active_model = url_redirects::ActiveModel::from(url);
+        active_model.archived_at = Set(archived.then(|| now.into()));
+        // an unarchived link starts over rather than being archived again
+        active_model.inactive_since = Set(None);
+        active_model.updated_at = Set(now.into());
+
+        let url = active_model.update(&self.db).await?;
+        Ok(Some(self.redirect(url)))
+    }
+
+    /// Sends `rollout.percent` percent of the traffic to `rollout.target`, or
+    /// all of it back to the current target when `rollout` is `None`.
+    pub async fn set_rollout(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+        rollout: Option<Rollout>,
+    ) -> Result<Option<UrlRedirect>, QueryError> {
+        let url = url_redirects::Entity::find_by_id(id)
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?;
+
+        let Some(url) = url else { return Ok(None) };
+
+        let mut active_model = url_redirects::ActiveModel::from(url);
+        active_model.rollout_percent = Set(rollout.as_ref().map(|rollout| rollout.percent.into()));
+        active_model.rollout_target = Set(rollout.map(|rollout| rollout.target));
+        active_model.updated_at = Set(chrono::Utc::now().into());
+
+        let url = active_model.update(&self.db).await?;
+        Ok(Some(self.redirect(url)))
+    }
+
+    /// The target change scheduled for the link; `None` when there is none,
+    /// or the link does not exist or belongs to someone else.
+    pub async fn scheduled_target(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+    ) -> Result<Option<ScheduledTarget>, QueryError> {
+        Ok(scheduled_targets::Entity::find_by_id(id)
+            .inner_join(url_redirects::Entity)
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?
+            .map(Into::into))
+    }
+
+    /// Points the link at `target` from `apply_at` on, replacing any change
+    /// scheduled before. `None` when the link does not exist or belongs to
+    /// someone else.
+    pub async fn schedule_target(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+        target: String,
+        apply_at: chrono::DateTime<chrono::Utc>,
+        scheduled_by: &str,
+    ) -> Result<Option<ScheduledTarget>, QueryError> {
+        if !self.is_owner(id, user_email).await? {
+            return Ok(None);
+        }
+
+        let change = scheduled_targets::ActiveModel {
+            url_redirect_id: Set(id),
+            target: Set(target),
+            apply_at: Set(apply_at.into()),
+            scheduled_by: Set(scheduled_by.to_string()),
+            created_at: Set(chrono::Utc::now().into()),
+        };
+        let change = scheduled_targets::Entity::insert(change)
+            .on_conflict(
+                OnConflict::column(scheduled_targets::Column::UrlRedirectId)
+                    .update_columns([
+                        scheduled_targets::Column::Target,
+                        scheduled_targets::Column::ApplyAt,
+                        scheduled_targets::Column::ScheduledBy,
+                        scheduled_targets::Column::CreatedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec_with_returning(&self.db)
+            .await?;
+
+        Ok(Some(change.into()))
+    }
+
+    /// Drops the target change scheduled for the link and returns it; `None`
+    /// when there is none, or the link does not exist or belongs to someone
+    /// else.
+    pub async fn cancel_scheduled_target(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+    ) -> Result<Option<ScheduledTarget>, QueryError> {
+        if !self.is_owner(id, user_email).await? {
+            return Ok(None);
+        }
+
+        Ok(scheduled_targets::Entity::delete_many()
+            .filter(scheduled_targets::Column::UrlRedirectId.eq(id))
+            .exec_with_returning(&self.db)
+            .await?
+            .pop()
+            .map(Into::into))
+    }
+
+    /// Lists the link in the public directory, or takes it out along with
+    /// its permission to be indexed.
+    pub async fn set_public(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+        public: bool,
+    ) -> Result<Option<UrlRedirect>, QueryError> {
+        let url = url_redirects::Entity::find_by_id(id)
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?;
+
+        let Some(url) = url else { return Ok(None) };
+
+        let mut active_model = url_redirects::ActiveModel::from(url);
+        active_model.public = Set(public);
+        if !public {
+            active_model.allow_indexing = Set(false);
+        }
+        active_model.updated_at = Set(chrono::Utc::now().into());
+
+        let url = active_model.update(&self.db).await?;
+        Ok(Some(self.redirect(url)))
+    }
+
+    pub async fn set_pinned(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+        pinned: bool,
+    ) -> Result<Option<UrlRedirect>, QueryError> {
+        let url = url_redirects::Entity::find_by_id(id)
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?;
+
+        let Some(url) = url else { return Ok(None) };
+        if url.pinned == pinned {
+            return Ok(Some(self.redirect(url)));
+        }
+
+        let mut active_model = url_redirects::ActiveModel::from(url);
+        active_model.pinned = Set(pinned);
+        active_model.updated_at = Set(chrono::Utc::now().into());
+
+        let url = active_model.update(&self.db).await?;
+        Ok(Some(self.redirect(url)))
+    }
+
+    /// Exempts the link from the inactive link policy, or makes it subject
+    /// to it again.
+    pub async fn set_keep_when_inactive(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+        keep: bool,
+    ) -> Result<Option<UrlRedirect>, QueryError> {
+        let url = url_redirects::Entity::find_by_id(id)
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?;
+
+        let Some(url) = url else { return Ok(None) };
+
+        let mut active_model = url_redirects::ActiveModel::from(url);
+        active_model.keep_when_inactive = Set(keep);
+        active_model.inactive_since = Set(None);
+        active_model.updated_at = Set(chrono::Utc::now().into());
+
+        let url = active_model.update(&self.db).await?;
+        Ok(Some(self.redirect(url)))
+    }
+
+    /// Public links that currently redirect, of everyone or of one owner.
+    pub async fn list_public(
+        &self,
+        user_email: Option<&str>,
+        after: Option<String>,
+        limit: u64,
+    ) -> Result<Vec<PublicLink>, QueryError> {
+        let mut query = url_redirects::Entity::find()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::Public.eq(true))
+            .filter(url_redirects::Column::ArchivedAt.is_null())
+            .filter(
+                Condition::any()
+                    .add(url_redirects::Column::ExpiresAt.is_null())
+                    .add(url_redirects::Column::ExpiresAt.gt(chrono::Utc::now())),
+            )
+            .order_by_asc(url_redirects::Column::Key)
+            .limit(limit);
+
+        if let Some(user_email) = user_email {
+            query = query.filter(url_redirects::Column::UserEmail.eq(user_email));
+        }
+        if let Some(key) = after {
+            query = query.filter(url_redirects::Column::Key.gt(key));
+        }
+
+        Ok(query
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    pub async fn set_allow_indexing(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+        allow_indexing: bool,
+    ) -> Result<Option<UrlRedirect>, QueryError> {
+        let url = url_redirects::Entity::find_by_id(id)
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?;
+
+        let Some(url) = url else { return Ok(None) };
+        // search engines may only index links that are public anyway
+        if allow_indexing && !url.public {
+            return Ok(Some(self.redirect(url)));
+        }
+
+        let mut active_model = url_redirects::ActiveModel::from(url);
+        active_model.allow_indexing = Set(allow_indexing);
+        active_model.updated_at = Set(chrono::Utc::now().into());
+
+        let url = active_model.update(&self.db).await?;
+        Ok(Some(self.redirect(url)))
+    }
+
+    pub async fn set_app_links(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+        app_links: AppLinks,
+    ) -> Result<Option<UrlRedirect>, QueryError> {
+        let url = url_redirects::Entity::find_by_id(id)
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?;
+
+        let Some(url) = url else { return Ok(None) };
+
+        let (ios_deep_link, ios_fallback_url) = app_links.ios.map_or((None, None), |link| {
+            (Some(link.deep_link), link.fallback_url)
+        });
+        let (android_deep_link, android_fallback_url) =
+            app_links.android.map_or((None, None), |link| {
+                (Some(link.deep_link), link.fallback_url)
+            });
+        let mut active_model = url_redirects::ActiveModel::from(url);
+        active_model.ios_deep_link = Set(ios_deep_link);
+        active_model.ios_fallback_url = Set(ios_fallback_url);
+        active_model.android_deep_link = Set(android_deep_link);
+        active_model.android_fallback_url = Set(android_fallback_url);
+        active_model.updated_at = Set(chrono::Utc::now().into());
+
+        let url = active_model.update(&self.db).await?;
+        Ok(Some(self.redirect(url)))
+    }
+
+    /// Shows `social_preview` to crawlers unfurling the link, or what the
+    /// target says about itself when it is empty.
+    pub async fn set_social_preview(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+        social_preview: SocialPreview,
+    ) -> Result<Option<UrlRedirect>, QueryError> {
+        let url = url_redirects::Entity::find_by_id(id)
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?;
+
+        let Some(url) = url else { return Ok(None) };
+
+        let mut active_model = url_redirects::ActiveModel::from(url);
+        active_model.social_title = Set(social_preview.title);
+        active_model.social_description = Set(social_preview.description);
+        active_model.social_image = Set(social_preview.image);
+        active_model.updated_at = Set(chrono::Utc::now().into());
+
+        let url = active_model.update(&self.db).await?;
+        Ok(Some(self.redirect(url)))
+    }
+
+    /// Applies the new key and target, keeping the previous ones as a
+    /// revision attributed to `changed_by`.
+    pub async fn update(
+        &self,
+        id: uuid::Uuid,
+        new_url: NewUrlRedirect,
+        changed_by: &str,
+    ) -> Result<Option<UrlRedirect>, InsertError> {
+        let txn = self.db.begin().await?;
+        let url = url_redirects::Entity::find_by_id(id)
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(new_url.user_email))
+            .one(&txn)
+            .await?;
+
+        let Some(url) = url else { return Ok(None) };
+
+        if url.key != *new_url.key && key_is_alias(&txn, &new_url.key).await? {
+            return Err(InsertError::KeyAlreadyExists);
+        }
+        if url.key != *new_url.key || url.target != new_url.target {
+            record_revision(&txn, &url, changed_by).await?;
+        }
+
+        let mut active_model = url_redirects::ActiveModel::from(url);
+        active_model.key = Set(new_url.key.0);
+        active_model.normalized_target_hash = Set(Some(normalized_target_hash(&new_url.target)));
+        active_model.target_host = Set(target_host(&new_url.target));
+        active_model.target = Set(new_url.target);
+        active_model.updated_at = Set(chrono::Utc::now().into());
+
+        let url = active_model.update(&txn).await?;
+        txn.commit().await?;
+        Ok(Some(self.redirect(url)))
+    }
+
+    /// Runs every check updating the link `id` to `new_url` would, without
+    /// updating it. `None` when the link does not exist.
+    pub async fn check_update(
+        &self,
+        id: uuid::Uuid,
+        new_url: &NewUrlRedirect,
+    ) -> Result<Option<DryRun>, InsertError> {
+        let url = url_redirects::Entity::find_by_id(id)
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(&new_url.user_email))
+            .one(&self.db)
+            .await?;
+        let Some(url) = url else { return Ok(None) };
+
+        if url.key != *new_url.key && key_is_taken(&self.db, &new_url.key).await? {
+            return Err(InsertError::KeyAlreadyExists);
+        }
+        let action = if url.key != *new_url.key || url.target != new_url.target {
+            DryRunAction::Update
+        } else {
+            DryRunAction::Unchanged
+        };
+
+        Ok(Some(DryRun::new(
+            action,
+            new_url.key.to_string(),
+            new_url.target.clone(),
+        )))
+    }
+
+    /// Restores the key and target of an earlier revision, which records the
+    /// current ones as a new revision in turn. `None` when the link or the
+    /// revision does not exist. The key must still pass the current policy,
+    /// which may have been tightened or come to reserve it since.
+    pub async fn revert(
+        &self,
+        id: uuid::Uuid,
+        user_email: String,
+        revision: i32,
+        changed_by: &str,
+    ) -> Result<Option<UrlRedirect>, InsertError> {
+        let revision = url_redirect_revisions::Entity::find()
+            .filter(url_redirect_revisions::Column::UrlRedirectId.eq(id))
+            .filter(url_redirect_revisions::Column::Revision.eq(revision))
+            .one(&self.db)
+            .await?;
+
+        let Some(revision) = revision else {
+            return Ok(None);
+        };
+
+        let key = self
+            .key_policy()
+            .parse(revision.key)
+            .map_err(InsertError::RevisionKeyRejected)?;
+        let new_url = NewUrlRedirect::new(user_email, key, revision.target);
+        self.update(id, new_url, changed_by).await
+    }
+
+    async fn is_owner(&self, id: uuid::Uuid, user_email: &str) -> Result<bool, DbErr> {
+        Ok(url_redirects::Entity::find_by_id(id)
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
+            .count(&self.db)
+            .await?
+            > 0)
+    }
+
+    /// Extra keys of the link, oldest first; `None` when the link does not
+    /// exist or belongs to someone else.
+    pub async fn list_aliases(
+        &self,
+        id: uuid::Uuid,
+        user_email: &str,
+    ) -> Result<Option<Vec<LinkAlias>>, QueryError> {
+        if !self.is_owner(id, user_email).await? {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            url_redirect_aliases::Entity::find()
+                .filter(url_redirect_aliases::Column::UrlRedirectId.eq(id))
+                .order_by_asc(url_redirect_aliases::Column::CreatedAt)
+                .all(&self.db)
+                .await?
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        ))
+    }
+
+    /// Makes `key` redirect to the link as well.
+    pub async fn add_alias(
+        &self,
+        id: uuid::Uuid,
+        user_email: &str,
+        key: RedirectKey,
+    ) -> Result<Option<LinkAlias>, InsertError> {
+        if !self.is_owner(id, user_email).await? {
+            return Ok(None);
+        }
+
+        let used_by_link = url_redirects::Entity::find()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::Key.eq(&*key))
+            .count(&self.db)
+            .await?
+            > 0;
+        if used_by_link {
+            return Err(InsertError::KeyAlreadyExists);
+        }
+
+        let alias = url_redirect_aliases::ActiveModel {
+            id: Set(uuid::Uuid::new_v4()),
+            url_redirect_id: Set(id),
+            key: Set(key.0),
+            tenant_id: Set(tenant::current_id()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        Ok(Some(alias.into()))
+    }
+
+    pub async fn delete_alias(
+        &self,
+        id: uuid::Uuid,
+        user_email: &str,
+        key: &str,
+    ) -> Result<Option<LinkAlias>, QueryError> {
+        if !self.is_owner(id, user_email).await? {
+            return Ok(None);
+        }
+
+        let alias = url_redirect_aliases::Entity::find()
+            .filter(url_redirect_aliases::Column::UrlRedirectId.eq(id))
+            .filter(url_redirect_aliases::Column::Key.eq(key))
+            .one(&self.db)
+            .await?;
+
+        let Some(alias) = alias else { return Ok(None) };
+        alias.clone().delete(&self.db).await?;
+        Ok(Some(alias.into()))
+    }
+
+    /// Previous versions of the link, oldest first; `None` when the link
+    /// does not exist or belongs to someone else.
+    pub async fn history(
+        &self,
+        id: uuid::Uuid,
+        user_email: &str,
+    ) -> Result<Option<Vec<Revision>>, QueryError> {
+        if !self.is_owner(id, user_email).await? {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            url_redirect_revisions::Entity::find()
+                .filter(url_redirect_revisions::Column::UrlRedirectId.eq(id))
+                .order_by_asc(url_redirect_revisions::Column::Revision)
+                .all(&self.db)
+                .await?
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        ))
+    }
+}
+
+impl UrlService {
+    pub async fn create_template(
+        &self,
+        user_email: String,
+        template: NewTemplate,
+    ) -> Result<LinkTemplate, QueryError> {
+        let template = link_templates::ActiveModel {
+            id: Set(uuid::Uuid::new_v4()),
+            user_email: Set(user_email),
+            name: Set(template.name),
+            target_pattern: Set(template.target_pattern),
+            default_ttl_secs: Set(template
+                .default_ttl_secs
+                .map(|secs| i64::try_from(secs).unwrap_or(i64::MAX))),
+            tenant_id: Set(tenant::current_id()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+
+        Ok(template.into())
+    }
+
+    pub async fn list_templates(&self, user_email: &str) -> Result<Vec<LinkTemplate>, QueryError> {
+        Ok(link_templates::Entity::find()
+            .filter(in_tenant(link_templates::Column::TenantId))
+            .filter(link_templates::Column::UserEmail.eq(user_email))
+            .order_by_asc(link_templates::Column::Name)
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    pub async fn get_template(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+    ) -> Result<Option<LinkTemplate>, QueryError> {
+        Ok(link_templates::Entity::find_by_id(id)
+            .filter(in_tenant(link_templates::Column::TenantId))
+            .filter(link_templates::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?
+            .map(Into::into))
+    }
+
+    pub async fn delete_template(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+    ) -> Result<Option<LinkTemplate>, QueryError> {
+        let template = link_templates::Entity::find_by_id(id)
+            .filter(in_tenant(link_templates::Column::TenantId))
+            .filter(link_templates::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?;
+
+        let Some(template) = template else {
+            return Ok(None);
+        };
+
+        template.clone().delete(&self.db).await?;
+        Ok(Some(template.into()))
+    }
+}
+
+impl UrlService {
+    pub async fn get_bio_page(&self, user_email: &str) -> Result<Option<BioPage>, QueryError> {
+        let page = bio_pages::Entity::find()
+            .filter(in_tenant(bio_pages::Column::TenantId))
+            .filter(bio_pages::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?;
+
+        let Some(page) = page else { return Ok(None) };
+        Ok(Some(self.load_bio_page(page, false).await?))
+    }
+
+    /// The page as visitors see it, without links that no longer redirect.
+    pub async fn public_bio_page(&self, handle: &str) -> Result<Option<BioPage>, QueryError> {
+        let page = bio_pages::Entity::find()
+            .filter(in_tenant(bio_pages::Column::TenantId))
+            .filter(bio_pages::Column::Handle.eq(handle))
+            .one(&self.db)
+            .await?;
+
+        let Some(page) = page else { return Ok(None) };
+        Ok(Some(self.load_bio_page(page, true).await?))
+    }
+
+    async fn load_bio_page(
+        &self,
+        page: bio_pages::Model,
+        active_only: bool,
+    ) -> Result<BioPage, DbErr> {
+        let mut query = bio_page_links::Entity::find()
+            .filter(bio_page_links::Column::BioPageId.eq(page.id))
+            .find_also_related(url_redirects::Entity)
+            .order_by_asc(bio_page_links::Column::Position);
+        if active_only {
+            query = query
+                .filter(url_redirects::Column::ArchivedAt.is_null())
+                .filter(
+                    Condition::any()
+                        .add(url_redirects::Column::ExpiresAt.is_null())
+                        .add(url_redirects::Column::ExpiresAt.gt(chrono::Utc::now())),
+                );
+        }
+
+        let links = query
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .filter_map(|(link, url)| url.map(|url| BioLink::new(link, url.key)))
+            .collect();
+        Ok(BioPage::new(page, links))
+    }
+
+    /// Creates or replaces the owner's page. `None` when one of the links does
+    /// not exist or belongs to someone else.
+    pub async fn save_bio_page(
+        &self,
+        user_email: &str,
+        new_page: NewBioPage,
+    ) -> Result<Option<BioPage>, InsertError> {
+        let txn = self.db.begin().await?;
+
+        let mut url_ids: Vec<uuid::Uuid> = new_page.links.iter().map(|link| link.url_id).collect();
+        url_ids.sort_unstable();
+        url_ids.dedup();
+        let owned = url_redirects::Entity::find()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::Id.is_in(url_ids.clone()))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
+            .count(&txn)
+            .await?;
+        if owned != url_ids.len() as u64 {
+            return Ok(None);
+        }
+
+        let existing = bio_pages::Entity::find()
+            .filter(in_tenant(bio_pages::Column::TenantId))
+            .filter(bio_pages::Column::UserEmail.eq(user_email))
+            .one(&txn)
+            .await?;
+        let page = match existing {
+            Some(page) => {
+                bio_page_links::Entity::delete_many()
+                    .filter(bio_page_links::Column::BioPageId.eq(page.id))
+                    .exec(&txn)
+                    .await?;
+                let mut active_model = bio_pages::ActiveModel::from(page);
+                active_model.handle = Set(new_page.handle);
+                active_model.title = Set(new_page.title);
+                active_model.updated_at = Set(chrono::Utc::now().into());
+                active_model.update(&txn).await?
+            }
+            None => {
+                bio_pages::ActiveModel {
+                    id: Set(uuid::Uuid::new_v4()),
+                    user_email: Set(user_email.to_string()),
+                    handle: Set(new_page.handle),
+                    title: Set(new_page.title),
+                    tenant_id: Set(tenant::current_id()),
+                    ..Default::default()
+                }
+                .insert(&txn)
+                .await?
+            }
+        };
+
+        for (position, link) in new_page.links.into_iter().enumerate() {
+            bio_page_links::ActiveModel {
+                id: Set(uuid::Uuid::new_v4()),
+                bio_page_id: Set(page.id),
+                url_redirect_id: Set(link.url_id),
+                position: Set(i32::try_from(position).unwrap_or(i32::MAX)),
+                title: Set(link.title),
+                icon_url: Set(link.icon_url),
+            }
+            .insert(&txn)
+            .await?;
+        }
+
+        txn.commit().await?;
+        Ok(Some(self.load_bio_page(page, false).await?))
+    }
+
+    pub async fn delete_bio_page(&self, user_email: &str) -> Result<Option<BioPage>, QueryError> {
+        let page = bio_pages::Entity::find()
+            .filter(in_tenant(bio_pages::Column::TenantId))
+            .filter(bio_pages::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?;
+
+        let Some(page) = page else { return Ok(None) };
+        let deleted = self.load_bio_page(page.clone(), false).await?;
+        page.delete(&self.db).await?;
+        Ok(Some(deleted))
+    }
+
+    pub async fn create_campaign(
+        &self,
+        user_email: String,
+        name: String,
+    ) -> Result<Campaign, QueryError> {
+        let campaign = campaigns::ActiveModel {
+            id: Set(uuid::Uuid::new_v4()),
+            user_email: Set(user_email),
+            name: Set(name),
+            tenant_id: Set(tenant::current_id()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+
+        Ok(campaign.into())
+    }
+
+    pub async fn list_campaigns(&self, user_email: &str) -> Result<Vec<Campaign>, QueryError> {
+        Ok(campaigns::Entity::find()
+            .filter(in_tenant(campaigns::Column::TenantId))
+            .filter(campaigns::Column::UserEmail.eq(user_email))
+            .order_by_asc(campaigns::Column::Name)
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    pub async fn get_campaign(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+    ) -> Result<Option<Campaign>, QueryError> {
+        Ok(campaigns::Entity::find_by_id(id)
+            .filter(in_tenant(campaigns::Column::TenantId))
+            .filter(campaigns::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?
+            .map(Into::into))
+    }
+
+    /// Deletes the campaign; its links stay, outside of any campaign.
+    pub async fn delete_campaign(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+    ) -> Result<Option<Campaign>, QueryError> {
+        let campaign = campaigns::Entity::find_by_id(id)
+            .filter(in_tenant(campaigns::Column::TenantId))
+            .filter(campaigns::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?;
+
+        let Some(campaign) = campaign else {
+            return Ok(None);
+        };
+
+        campaign.clone().delete(&self.db).await?;
+        Ok(Some(campaign.into()))
+    }
+
+    pub async fn campaign_link_ids(&self, id: uuid::Uuid) -> Result<Vec<uuid::Uuid>, QueryError> {
+        Ok(url_redirects::Entity::find()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .select_only()
+            .column(url_redirects::Column::Id)
+            .filter(url_redirects::Column::CampaignId.eq(id))
+            .into_tuple()
+            .all(&self.db)
+            .await?)
+    }
+
+    /// Moves the link into the campaign, or out of any with `None`. `None`
+    /// when the link or the campaign does not exist or belongs to someone else.
+    pub async fn set_campaign(
+        &self,
+        user_email: &str,
+        id: uuid::Uuid,
+        campaign_id: Option<uuid::Uuid>,
+    ) -> Result<Option<UrlRedirect>, QueryError> {
+        if let Some(campaign_id) = campaign_id {
+            if self.get_campaign(user_email, campaign_id).await?.is_none() {
+                return Ok(None);
+            }
+        }
+
+        let url = url_redirects::Entity::find_by_id(id)
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
+            .one(&self.db)
+            .await?;
+
+        let Some(url) = url else { return Ok(None) };
+
+        let mut active_model = url_redirects::ActiveModel::from(url);
+        active_model.campaign_id = Set(campaign_id);
+        active_model.updated_at = Set(chrono::Utc::now().into());
+
+        let url = active_model.update(&self.db).await?;
+        Ok(Some(self.redirect(url)))
+    }
+}
+
+impl UrlService {
+    async fn check_link_limit(&self, user_email: &str) -> Result<(), InsertError> {
+        let tenant_max_links = tenant::settings().and_then(|settings| settings.max_links);
+        if let Some(max_links) = tenant_max_links {
+            let links = url_redirects::Entity::find()
+                .filter(in_tenant(url_redirects::Column::TenantId))
+                .count(&self.db)
+                .await?;
+            if links >= u64::try_from(max_links).unwrap_or(0) {
+                return Err(InsertError::TenantLinkLimitReached);
+            }
+        }
+
+        let Some(max_links) = self.plan_for(user_email).await?.max_links else {
+            return Ok(());
+        };
+
+        let links = url_redirects::Entity::find()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
+            .count(&self.db)
+            .await?;
+        if links >= u64::try_from(max_links).unwrap_or(0) {
+            return Err(InsertError::LinkLimitReached);
+        }
+
+        Ok(())
+    }
+
+    async fn plan_for(&self, user_email: &str) -> Result<plans::Model, DbErr> {
+        let name = user_plans::Entity::find_by_id(user_email)
+            .one(&self.db)
+            .await?
+            .map_or_else(|| DEFAULT_PLAN.to_string(), |assigned| assigned.plan);
+
+        // a missing default plan means nobody has limits yet
+        Ok(plans::Entity::find_by_id(name.as_str())
+            .one(&self.db)
+            .await?
+            .unwrap_or(plans::Model {
+                name,
+                max_links: None,
+                max_custom_domains: None,
+                analytics_retention_days: None,
+            }))
+    }
+
+    pub async fn user_plan(&self, user_email: &str) -> Result<Plan, QueryError> {
+        Ok(self.plan_for(user_email).await?.into())
+    }
+
+    pub async fn list_plans(&self) -> Result<Vec<Plan>, QueryError> {
+        Ok(plans::Entity::find()
+            .order_by_asc(plans::Column::Name)
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Creates the plan or replaces the limits of an existing one.
+    pub async fn save_plan(&self, name: String, limits: PlanLimits) -> Result<Plan, QueryError> {
+        let plan = plans::Model {
+            name,
+            max_links: limits.max_links.map(i64::from),
+            max_custom_domains: limits.max_custom_domains.map(i64::from),
+            analytics_retention_days: limits.analytics_retention_days.map(i64::from),
+        };
+
+        plans::Entity::insert(plans::ActiveModel::from(plan.clone()))
+            .on_conflict(
+                OnConflict::column(plans::Column::Name)
+                    .update_columns([
+                        plans::Column::MaxLinks,
+                        plans::Column::MaxCustomDomains,
+                        plans::Column::AnalyticsRetentionDays,
+                    ])
+                    .to_owned(),
+            )
+            .exec(&self.db)
+            .await?;
+
+        Ok(plan.into())
+    }
+
+    /// Moves the user to `plan`; `None` when there is no such plan.
+    pub async fn assign_plan(
+        &self,
+        user_email: String,
+        plan: String,
+    ) -> Result<Option<Plan>, QueryError> {
+        let Some(plan) = plans::Entity::find_by_id(plan.as_str())
+            .one(&self.db)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let assignment = user_plans::ActiveModel {
+            user_email: Set(user_email),
+            plan: Set(plan.name.clone()),
+            updated_at: Set(chrono::Utc::now().into()),
+        };
+        user_plans::Entity::insert(assignment)
+            .on_conflict(
+                OnConflict::column(user_plans::Column::UserEmail)
+                    .update_columns([user_plans::Column::Plan, user_plans::Column::UpdatedAt])
+                    .to_owned(),
+            )
+            .exec(&self.db)
+            .await?;
+
+        Ok(Some(plan.into()))
+    }
+}
+
+#[axum::async_trait]
+impl AnalyticsStore for UrlService {
+    async fn insert_clicks(&self, batch: Vec<Click>) -> Result<(), AnalyticsError> {
+        let batch = batch.into_iter().map(|click| clicks::ActiveModel {
+            url_redirect_id: Set(click.url_redirect_id),
+            clicked_at: Set(click.clicked_at.into()),
+            ..Default::default()
+        });
+        clicks::Entity::insert_many(batch)
+            .exec_without_returning(&self.db)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl UrlService {
+    /// Moves the access time of every link clicked in `batch` up to the
+    /// batch's last click, so it is only as precise as the flush interval.
+    pub async fn record_accesses(&self, batch: &[Click]) -> Result<(), QueryError> {
+        let Some(accessed_at) = batch.iter().map(|click| click.clicked_at).max() else {
+            return Ok(());
+        };
+        let mut ids: Vec<uuid::Uuid> = batch.iter().map(|click| click.url_redirect_id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+
+        // accessed links are no longer inactive
+        url_redirects::Entity::update_many()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .col_expr(
+                url_redirects::Column::LastAccessedAt,
+                Expr::value(sea_orm::prelude::DateTimeWithTimeZone::from(accessed_at)),
+            )
+            .col_expr(
+                url_redirects::Column::InactiveSince,
+                Expr::value(Option::<sea_orm::prelude::DateTimeWithTimeZone>::None),
+            )
+            .filter(url_redirects::Column::Id.is_in(ids))
+            .filter(
+                Condition::any()
+                    .add(url_redirects::Column::LastAccessedAt.is_null())
+                    .add(url_redirects::Column::LastAccessedAt.lt(accessed_at)),
+            )
+            .exec(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Adds `counts` to the click counts of their links, as the write-back
+    /// `id`. A write-back applied before is skipped, so one interrupted after
+    /// this can be retried safely. Returns whether the counts were added.
+    pub async fn apply_click_counts(
+        &self,
+        id: uuid::Uuid,
+        counts: &[(uuid::Uuid, i64)],
+    ) -> Result<bool, QueryError> {
+        let txn = self.db.begin().await?;
+        if click_count_write_backs::Entity::find_by_id(id)
+            .one(&txn)
+            .await?
+            .is_some()
+        {
+            return Ok(false);
+        }
+        click_count_write_backs::ActiveModel {
+            id: Set(id),
+            applied_at: Set(chrono::Utc::now().into()),
+        }
+        .insert(&txn)
+        .await?;
+
+        // one update per link and write-back, however often it was clicked
+        for (url_redirect_id, count) in counts {
+            url_redirects::Entity::update_many()
+                .col_expr(
+                    url_redirects::Column::ClickCount,
+                    Expr::col(url_redirects::Column::ClickCount).add(*count),
+                )
+                .filter(url_redirects::Column::Id.eq(*url_redirect_id))
+                .exec(&txn)
+                .await?;
+        }
+
+        // only interrupted write-backs are ever looked up, and those are
+        // retried within seconds
+        click_count_write_backs::Entity::delete_many()
+            .filter(
+                click_count_write_backs::Column::AppliedAt
+                    .lt(chrono::Utc::now() - chrono::Duration::days(1)),
+            )
+            .exec(&txn)
+            .await?;
+
+        txn.commit().await?;
+        Ok(true)
+    }
+
+    /// Creates the partition holding `month`'s clicks, unless it exists.
+    /// SQLite keeps every click in one table, so there is nothing to create.
+    pub async fn create_click_partition(&self, month: chrono::NaiveDate) -> Result<(), QueryError> {
+        if self.db.get_database_backend() == DbBackend::Sqlite {
+            return Ok(());
+        }
+
+        // names and bounds come from dates, never from input, so they can be
+        // spliced into the statement
+        self.db
+            .execute_unprepared(&format!(
+                "CREATE TABLE IF NOT EXISTS {} PARTITION OF clicks \
+                 FOR VALUES FROM ('{month} 00:00:00+00') TO ('{} 00:00:00+00')",
+                partition_name(month),
+                next_month(month),
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    /// The months that have a partition, oldest first. With SQLite, the
+    /// months that have clicks.
+    pub async fn click_partitions(&self) -> Result<Vec<chrono::NaiveDate>, QueryError> {
+        if self.db.get_database_backend() == DbBackend::Sqlite {
+            // timestamps are stored as RFC 3339 text, so the month is a prefix
+            let rows = self
+                .db
+                .query_all(Statement::from_string(
+                    DbBackend::Sqlite,
+                    "SELECT DISTINCT substr(clicked_at, 1, 7) AS month FROM clicks ORDER BY month",
+                ))
+                .await?;
+            return Ok(rows
+                .iter()
+                .filter_map(|row| row.try_get::<String>("", "month").ok())
+                .filter_map(|month| {
+                    chrono::NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d").ok()
+                })
+                .collect());
+        }
+
+        let rows = self
+            .db
+            .query_all(Statement::from_string(
+                DbBackend::Postgres,
+                "SELECT child.relname AS name FROM pg_inherits \
+                 JOIN pg_class child ON child.oid = pg_inherits.inhrelid \
+                 WHERE pg_inherits.inhparent = 'clicks'::regclass",
+            ))
+            .await?;
+
+        let mut months = rows
+            .iter()
+            .filter_map(|row| row.try_get::<String>("", "name").ok())
+            .filter_map(|name| partition_month(&name))
+            .collect::<Vec<_>>();
+        months.sort();
+        Ok(months)
+    }
+
+    /// Up to `limit` clicks between `from` and `to`, ordered by time and id,
+    /// starting after the click at `after`.
+    pub async fn clicks_page(
+        &self,
+        from: chrono::DateTime<chrono::FixedOffset>,
+        to: chrono::DateTime<chrono::FixedOffset>,
+        after: Option<(chrono::DateTime<chrono::FixedOffset>, i64)>,
+        limit: u64,
+    ) -> Result<Vec<clicks::Model>, QueryError> {
+        let mut query = clicks::Entity::find()
+            .filter(clicks::Column::ClickedAt.gte(from))
+            .filter(clicks::Column::ClickedAt.lt(to));
+        if let Some((clicked_at, id)) = after {
+            query = query.filter(
+                Condition::any()
+                    .add(clicks::Column::ClickedAt.gt(clicked_at))
+                    .add(
+                        Condition::all()
+                            .add(clicks::Column::ClickedAt.eq(clicked_at))
+                            .add(clicks::Column::Id.gt(id)),
+                    ),
+            );
+        }
+
+        Ok(query
+            .order_by_asc(clicks::Column::ClickedAt)
+            .order_by_asc(clicks::Column::Id)
+            .limit(limit)
+            .all(&self.db)
+            .await?)
+    }
+
+    /// Drops `month`'s partition together with its clicks.
+    pub async fn drop_click_partition(&self, month: chrono::NaiveDate) -> Result<(), QueryError> {
+        if self.db.get_database_backend() == DbBackend::Sqlite {
+            let start = |month: chrono::NaiveDate| month.and_time(chrono::NaiveTime::MIN).and_utc();
+            clicks::Entity::delete_many()
+                .filter(clicks::Column::ClickedAt.gte(start(month)))
+                .filter(clicks::Column::ClickedAt.lt(start(next_month(month))))
+                .exec(&self.db)
+                .await?;
+            return Ok(());
+        }
+
+        self.db
+            .execute_unprepared(&format!("DROP TABLE IF EXISTS {}", partition_name(month)))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Deletes the clicks on links whose owner's plan keeps analytics for
+    /// fewer days than have passed since, returning how many there were.
+    /// Clicks on the links of other owners are kept as long as their
+    /// partition is.
+    pub async fn purge_clicks_past_plan_retention(&self) -> Result<u64, QueryError> {
+        let plans = plans::Entity::find()
+            .filter(plans::Column::AnalyticsRetentionDays.is_not_null())
+            .all(&self.db)
+            .await?;
+
+        let mut purged = 0;
+        for plan in plans {
+            let Some(days) = plan.analytics_retention_days else {
+                continue;
+            };
+            let cutoff = chrono::Utc::now() - chrono::Duration::days(days.max(0));
+
+            let assigned = Query::select()
+                .column(user_plans::Column::UserEmail)
+                .from(user_plans::Entity)
+                .and_where(user_plans::Column::Plan.eq(plan.name.as_str()))
+                .to_owned();
+            let mut owners =
+                Condition::any().add(url_redirects::Column::UserEmail.in_subquery(assigned));
+            // owners without a plan of their own are on the default one
+            if plan.name == DEFAULT_PLAN {
+                owners = owners.add(
+                    url_redirects::Column::UserEmail.not_in_subquery(
+                        Query::select()
+                            .column(user_plans::Column::UserEmail)
+                            .from(user_plans::Entity)
+                            .to_owned(),
+                    ),
+                );
+            }
+
+            purged += clicks::Entity::delete_many()
+                .filter(clicks::Column::ClickedAt.lt(cutoff))
+                .filter(
+                    clicks::Column::UrlRedirectId.in_subquery(
+                        Query::select()
+                            .column(url_redirects::Column::Id)
+                            .from(url_redirects::Entity)
+                            .cond_where(owners)
+                            .to_owned(),
+                    ),
+                )
+                .exec(&self.db)
+                .await?
+                .rows_affected;
+        }
+
+        Ok(purged)
+    }
+}
+
+/// A link whose owner has yet to hear that it expired.
+pub struct ExpiredLink {
+    pub owner: String,
+    pub key: String,
+    pub target: String,
+}
+
+/// A link just pointed at the target scheduled for it.
+pub struct AppliedTarget {
+    pub owner: String,
+    pub key: String,
+    pub target: String,
+    pub scheduled_by: String,
+}
+
+/// A link just flagged or archived for inactivity.
+pub struct InactiveLink {
+    pub owner: String,
+    pub key: String,
+    pub target: String,
+}
+
+impl From<url_redirects::Model> for InactiveLink {
+    fn from(link: url_redirects::Model) -> Self {
+        Self {
+            owner: link.user_email,
+            key: link.key,
+            target: link.target,
+        }
+    }
+}
+
+impl UrlService {
+    pub async fn notification_preferences(
+        &self,
+        user_email: &str,
+    ) -> Result<NotificationPreferences, QueryError> {
+        Ok(notification_preferences::Entity::find_by_id(user_email)
+            .one(&self.db)
+            .await?
+            .map(Into::into)
+            .unwrap_or_default())
+    }
+
+    pub async fn set_notification_preferences(
+        &self,
+        user_email: String,
+        link_expired: bool,
+    ) -> Result<NotificationPreferences, QueryError> {
+        let preferences = notification_preferences::ActiveModel {
+            user_email: Set(user_email),
+            link_expired: Set(link_expired),
+            updated_at: Set(chrono::Utc::now().into()),
+        };
+        let preferences = notification_preferences::Entity::insert(preferences)
+            .on_conflict(
+                OnConflict::column(notification_preferences::Column::UserEmail)
+                    .update_columns([
+                        notification_preferences::Column::LinkExpired,
+                        notification_preferences::Column::UpdatedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec_with_returning(&self.db)
+            .await?;
+
+        Ok(preferences.into())
+    }
+
+    /// The settings `tenant_id` overrides, none when it has never saved any.
+    pub async fn tenant_settings(&self, tenant_id: &str) -> Result<TenantSettings, QueryError> {
+        Ok(tenant_settings::Entity::find_by_id(tenant_id)
+            .one(&self.db)
+            .await?
+            .map(Into::into)
+            .unwrap_or_default())
+    }
+
+    /// Replaces every setting `tenant_id` overrides.
+    pub async fn save_tenant_settings(
+        &self,
+        tenant_id: String,
+        overrides: TenantOverrides,
+    ) -> Result<TenantSettings, QueryError> {
+        let settings = tenant_settings::ActiveModel {
+            tenant_id: Set(tenant_id),
+            allowed_origins: Set(overrides.allowed_origins.map(|origins| origins.join("\n"))),
+            max_links: Set(overrides.max_links.map(i64::from)),
+            redirect_status: Set(overrides
+                .redirect_status
+                .and_then(|status| i16::try_from(status).ok())),
+            not_found_page: Set(overrides.not_found_page),
+            updated_at: Set(chrono::Utc::now().into()),
+        };
+        let settings = tenant_settings::Entity::insert(settings)
+            .on_conflict(
+                OnConflict::column(tenant_settings::Column::TenantId)
+                    .update_columns([
+                        tenant_settings::Column::AllowedOrigins,
+                        tenant_settings::Column::MaxLinks,
+                        tenant_settings::Column::RedirectStatus,
+                        tenant_settings::Column::NotFoundPage,
+                        tenant_settings::Column::UpdatedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec_with_returning(&self.db)
+            .await?;
+
+        Ok(settings.into())
+    }
+
+    /// Marks links that expired since the last call as notified and returns
+    /// them. Marking and reading happen in one statement, so with several
+    /// instances each link is still returned only once.
+    pub async fn claim_expired_links(&self) -> Result<Vec<ExpiredLink>, QueryError> {
+        let claimed = url_redirects::Entity::update_many()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .col_expr(url_redirects::Column::ExpiryNotified, Expr::value(true))
+            .filter(url_redirects::Column::ExpiresAt.lte(chrono::Utc::now()))
+            .filter(url_redirects::Column::ExpiryNotified.eq(false))
+            .exec_with_returning(&self.db)
+            .await?;
+
+        Ok(claimed
+            .into_iter()
+            .map(|link| ExpiredLink {
+                owner: link.user_email,
+                key: link.key,
+                target: link.target,
+            })
+            .collect())
+    }
+
+    /// Points links at the targets scheduled for them whose time has come,
+    /// keeping the old targets as revisions changed by whoever scheduled
+    /// them, and returns the links changed. Changes are claimed by deleting
+    /// them, so with several instances each is applied once.
+    pub async fn apply_scheduled_targets(&self) -> Result<Vec<AppliedTarget>, QueryError> {
+        let txn = self.db.begin().await?;
+        let due = scheduled_targets::Entity::delete_many()
+            .filter(scheduled_targets::Column::ApplyAt.lte(chrono::Utc::now()))
+            .exec_with_returning(&txn)
+            .await?;
+
+        let mut applied = Vec::with_capacity(due.len());
+        for change in due {
+            let url = url_redirects::Entity::find_by_id(change.url_redirect_id)
+                .filter(in_tenant(url_redirects::Column::TenantId))
+                .one(&txn)
+                .await?;
+            // changed by hand to the same target in the meantime
+            let Some(url) = url.filter(|url| url.target != change.target) else {
+                continue;
+            };
+            record_revision(&txn, &url, &change.scheduled_by).await?;
+
+            let mut active_model = url_redirects::ActiveModel::from(url);
+            active_model.normalized_target_hash = Set(Some(normalized_target_hash(&change.target)));
+            active_model.target_host = Set(target_host(&change.target));
+            active_model.target = Set(change.target);
+            active_model.updated_at = Set(chrono::Utc::now().into());
+            let url = active_model.update(&txn).await?;
+
+            applied.push(AppliedTarget {
+                owner: url.user_email,
+                key: url.key,
+                target: url.target,
+                scheduled_by: change.scheduled_by,
+            });
+        }
+
+        txn.commit().await?;
+        Ok(applied)
+    }
+
+    /// Flags active links neither accessed nor created since `idle_since`,
+    /// unless kept, and returns them. Like [`Self::claim_expired_links`],
+    /// each link is returned by one call only.
+    pub async fn flag_inactive_links(
+        &self,
+        idle_since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<InactiveLink>, QueryError> {
+        let flagged = url_redirects::Entity::update_many()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .col_expr(
+                url_redirects::Column::InactiveSince,
+                Expr::value(sea_orm::prelude::DateTimeWithTimeZone::from(
+                    chrono::Utc::now(),
+                )),
+            )
+            .filter(url_redirects::Column::InactiveSince.is_null())
+            .filter(url_redirects::Column::KeepWhenInactive.eq(false))
+            .filter(url_redirects::Column::ArchivedAt.is_null())
+            .filter(
+                Condition::any()
+                    .add(url_redirects::Column::LastAccessedAt.lt(idle_since))
+                    .add(
+                        Condition::all()
+                            .add(url_redirects::Column::LastAccessedAt.is_null())
+                            .add(url_redirects::Column::CreatedAt.lt(idle_since)),
+                    ),
+            )
+            .exec_with_returning(&self.db)
+            .await?;
+
+        Ok(flagged.into_iter().map(Into::into).collect())
+    }
+
+    /// Archives the links flagged before `flagged_before` and returns them.
+    pub async fn archive_inactive_links(
+        &self,
+        flagged_before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<InactiveLink>, QueryError> {
+        let now = sea_orm::prelude::DateTimeWithTimeZone::from(chrono::Utc::now());
+        let archived = url_redirects::Entity::update_many()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .col_expr(url_redirects::Column::ArchivedAt, Expr::value(now))
+            .col_expr(url_redirects::Column::UpdatedAt, Expr::value(now))
+            .filter(url_redirects::Column::InactiveSince.lt(flagged_before))
+            .filter(url_redirects::Column::KeepWhenInactive.eq(false))
+            .filter(url_redirects::Column::ArchivedAt.is_null())
+            .exec_with_returning(&self.db)
+            .await?;
+
+        Ok(archived.into_iter().map(Into::into).collect())
+    }
+}
+
+impl UrlService {
+    /// The owner's tags by name, each with the number of links carrying it.
+    pub async fn list_tags(&self, user_email: &str) -> Result<Vec<TagSummary>, QueryError> {
+        let tags: Vec<(String, i64)> = url_redirect_tags::Entity::find()
+            .select_only()
+            .column(url_redirect_tags::Column::Tag)
+            .column_as(url_redirect_tags::Column::UrlRedirectId.count(), "links")
+            .filter(
+                url_redirect_tags::Column::UrlRedirectId.in_subquery(owned_link_ids(user_email)),
+            )
+            .group_by(url_redirect_tags::Column::Tag)
+            .order_by_asc(url_redirect_tags::Column::Tag)
+            .into_tuple()
+            .all(&self.db)
+            .await?;
+
+        Ok(tags
+            .into_iter()
+            .map(|(tag, links)| TagSummary::new(tag, links.max(0) as u64))
+            .collect())
+    }
+
+    /// Tags the links with `ids`, returning how many did not have the tag
+    /// yet. `None` when one of them does not exist or belongs to someone
+    /// else, in which case none is tagged.
+    pub async fn tag_links(
+        &self,
+        user_email: &str,
+        tag: &str,
+        mut ids: Vec<uuid::Uuid>,
+    ) -> Result<Option<u64>, QueryError> {
+        ids.sort_unstable();
+        ids.dedup();
+        let txn = self.db.begin().await?;
+
+        let owned = url_redirects::Entity::find()
+            .filter(in_tenant(url_redirects::Column::TenantId))
+            .filter(url_redirects::Column::Id.is_in(ids.clone()))
+            .filter(url_redirects::Column::UserEmail.eq(user_email))
+            .count(&txn)
+            .await?;
+        if owned != ids.len() as u64 {
+            return Ok(None);
+        }
+        if ids.is_empty() {
+            return Ok(Some(0));
+        }
+
+        let tags = ids.into_iter().map(|id| url_redirect_tags::ActiveModel {
+            url_redirect_id: Set(id),
+            tag: Set(tag.to_string()),
+            ..Default::default()
+        });
+        let tagged = url_redirect_tags::Entity::insert_many(tags)
+            .on_conflict(
+                OnConflict::columns([
+                    url_redirect_tags::Column::UrlRedirectId,
+                    url_redirect_tags::Column::Tag,
+                ])
+                .do_nothing()
+                .to_owned(),
+            )
+            .exec_without_returning(&txn)
+            .await?;
+
+        txn.commit().await?;
+        Ok(Some(tagged))
+    }
+
+    /// Takes the tag off those of the owner's links with `ids` that have it,
+    /// returning how many did.
+    pub async fn untag_links(
+        &self,
+        user_email: &str,
+        tag: &str,
+        ids: Vec<uuid::Uuid>,
+    ) -> Result<u64, QueryError> {
+        let deleted = url_redirect_tags::Entity::delete_many()
+            .filter(url_redirect_tags::Column::Tag.eq(tag))
+            .filter(url_redirect_tags::Column::UrlRedirectId.is_in(ids))
+            .filter(
+                url_redirect_tags::Column::UrlRedirectId.in_subquery(owned_link_ids(user_email)),
+            )
+            .exec(&self.db)
+            .await?;
+
+        Ok(deleted.rows_affected)
+    }
+
+    /// Renames the tag on all of the owner's links, unless some already
+    /// have the new name.
+    pub async fn rename_tag(
+        &self,
+        user_email: &str,
+        tag: &str,
+        name: &str,
+    ) -> Result<TagRename, QueryError> {
+        let txn = self.db.begin().await?;
+
+        let taken = url_redirect_tags::Entity::find()
+            .filter(url_redirect_tags::Column::Tag.eq(name))
+            .filter(
+                url_redirect_tags::Column::UrlRedirectId.in_subquery(owned_link_ids(user_email)),
+            )
+            .count(&txn)
+            .await?;
+        if taken > 0 {
+            return Ok(TagRename::Taken);
+        }
+
+        let renamed = url_redirect_tags::Entity::update_many()
+            .col_expr(url_redirect_tags::Column::Tag, Expr::value(name))
+            .filter(url_redirect_tags::Column::Tag.eq(tag))
+            .filter(
+                url_redirect_tags::Column::UrlRedirectId.in_subquery(owned_link_ids(user_email)),
+            )
+            .exec(&txn)
+            .await?;
+
+        txn.commit().await?;
+        Ok(match renamed.rows_affected {
+            0 => TagRename::NotFound,
+            renamed => TagRename::Renamed(renamed),
+        })
+    }
+
+    /// Replaces the tag with `into` on all of the owner's links, returning
+    /// how many had it, or `None` when none did.
+    pub async fn merge_tag(
+        &self,
+        user_email: &str,
+        tag: &str,
+        into: &str,
+    ) -> Result<Option<u64>, QueryError> {
+        let txn = self.db.begin().await?;
+
+        // links with both keep the one they already have under `into`
+        let dropped = url_redirect_tags::Entity::delete_many()
+            .filter(url_redirect_tags::Column::Tag.eq(tag))
+            .filter(
+                url_redirect_tags::Column::UrlRedirectId.in_subquery(owned_link_ids(user_email)),
+            )
+            .filter(
+                url_redirect_tags::Column::UrlRedirectId.in_subquery(
+                    Query::select()
+                        .column(url_redirect_tags::Column::UrlRedirectId)
+                        .from(url_redirect_tags::Entity)
+                        .and_where(url_redirect_tags::Column::Tag.eq(into))
+                        .to_owned(),
+                ),
+            )
+            .exec(&txn)
+            .await?;
+        let renamed = url_redirect_tags::Entity::update_many()
+            .col_expr(url_redirect_tags::Column::Tag, Expr::value(into))
+            .filter(url_redirect_tags::Column::Tag.eq(tag))
+            .filter(
+                url_redirect_tags::Column::UrlRedirectId.in_subquery(owned_link_ids(user_email)),
+            )
+            .exec(&txn)
+            .await?;
+
+        txn.commit().await?;
+        Ok(match dropped.rows_affected + renamed.rows_affected {
+            0 => None,
+            merged => Some(merged),
+        })
+    }
+}
+
+impl UrlService {
+    /// The account a Slack user has linked, if any.
+    pub async fn slack_account_owner(
+        &self,
+        team_id: &str,
+        user_id: &str,
+    ) -> Result<Option<String>, QueryError> {
+        Ok(
+            slack_accounts::Entity::find_by_id((team_id.to_string(), user_id.to_string()))
+                .one(&self.db)
+                .await?
+                .map(|account| account.user_email),
+        )
+    }
+
+    /// Links a Slack user to `user_email`, replacing any earlier link.
+    pub async fn link_slack_account(
+        &self,
+        team_id: String,
+        user_id: String,
+        user_email: String,
+    ) -> Result<(), QueryError> {
+        let account = slack_accounts::ActiveModel {
+            team_id: Set(team_id),
+            user_id: Set(user_id),
+            user_email: Set(user_email),
+            created_at: Set(chrono::Utc::now().into()),
+        };
+        slack_accounts::Entity::insert(account)
+            .on_conflict(
+                OnConflict::columns([
+                    slack_accounts::Column::TeamId,
+                    slack_accounts::Column::UserId,
+                ])
+                .update_columns([
+                    slack_accounts::Column::UserEmail,
+                    slack_accounts::Column::CreatedAt,
+                ])
+                .to_owned(),
+            )
+            .exec(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Unlinks every Slack user linked to `user_email`.
+    pub async fn unlink_slack_accounts(&self, user_email: &str) -> Result<u64, QueryError> {
+        Ok(slack_accounts::Entity::delete_many()
+            .filter(slack_accounts::Column::UserEmail.eq(user_email))
+            .exec(&self.db)
+            .await?
+            .rows_affected)
+    }
+}
+
+impl UrlService {
+    /// `value` as answered to clients, with its short URL spelled out.
+    fn redirect(&self, value: url_redirects::Model) -> UrlRedirect {
+        let short_url = self.short_url(&value.key);
+        let rollout = value
+            .rollout_target
+            .zip(value.rollout_percent)
+            .map(|(target, percent)| Rollout {
+                target,
+                percent: u8::try_from(percent).unwrap_or(0),
+            });
+        let app_link = |deep_link: Option<String>, fallback_url| {
+            deep_link.map(|deep_link| AppLink {
+                deep_link,
+                fallback_url,
+            })
+        };
+        let app_links = AppLinks {
+            ios: app_link(value.ios_deep_link, value.ios_fallback_url),
+            android: app_link(value.android_deep_link, value.android_fallback_url),
+        };
+        let app_links =
+            (app_links.ios.is_some() || app_links.android.is_some()).then_some(app_links);
+        let social_preview = SocialPreview {
+            title: value.social_title,
+            description: value.social_description,
+            image: value.social_image,
+        };
+        let social_preview = (!social_preview.is_empty()).then_some(social_preview);
+        let owner = (value.user_email != ANONYMOUS_OWNER).then_some(value.user_email);
+        UrlRedirect::new(
+            value.id,
+            value.key,
+            short_url,
+            value.target,
+            value.created_at,
+            value.updated_at,
+            value.expires_at,
+        )
+        .with_owner(owner)
+        .with_rollout(rollout)
+        .with_app_links(app_links)
+        .with_social_preview(social_preview)
+        .with_allow_indexing(value.allow_indexing)
+        .with_public(value.public)
+        .with_archived_at(value.archived_at)
+        .with_campaign_id(value.campaign_id)
+        .with_last_accessed_at(value.last_accessed_at)
+        .with_clicks(value.click_count.max(0) as u64)
+        .with_inactivity(value.inactive_since, value.keep_when_inactive)
+        .with_pinned(value.pinned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    /// A service on a fresh in-memory SQLite database, which lives as long as
+    /// its one connection.
+    async fn service() -> UrlService {
+        let mut config = Config::for_tests("").database;
+        config.max_connections = Some(1);
+        config.min_connections = Some(1);
+        let service = UrlService::new(&config).await.unwrap();
+        service.run_migrations().await.unwrap();
+        service
+    }
+
+    async fn link(service: &UrlService, owner: &str, key: &str) -> uuid::Uuid {
+        let new_url = NewUrlRedirect::new(
+            owner.to_owned(),
+            RedirectKey(key.to_owned()),
+            format!("https://example.com/{key}"),
+        );
+        service.create(new_url).await.unwrap().id
+    }
+
+    fn limits(analytics_retention_days: Option<u32>) -> PlanLimits {
+        PlanLimits {
+            max_links: None,
+            max_custom_domains: None,
+            analytics_retention_days,
+        }
+    }
+
+    #[tokio::test]
+    async fn purges_clicks_past_the_owner_plan_retention() {
+        let service = service().await;
+        service
+            .save_plan(String::from(DEFAULT_PLAN), limits(Some(30)))
+            .await
+            .unwrap();
+        service
+            .save_plan(String::from("pro"), limits(None))
+            .await
+            .unwrap();
+        service
+            .assign_plan(String::from("pro@example.com"), String::from("pro"))
+            .await
+            .unwrap();
+        let free = link(&service, "free@example.com", "free").await;
+        let pro = link(&service, "pro@example.com", "pro").await;
+
+        let now = chrono::Utc::now();
+        let clicks = [free, pro].into_iter().flat_map(|url_redirect_id| {
+            [40, 1].map(|days| Click {
+                url_redirect_id,
+                clicked_at: now - chrono::Duration::days(days),
+            })
+        });
+        service.insert_clicks(clicks.collect()).await.unwrap();
+
+        assert_eq!(service.purge_clicks_past_plan_retention().await.unwrap(), 1);
+        let left = clicks::Entity::find().all(&service.db).await.unwrap();
+        assert_eq!(left.len(), 3);
+        let cutoff = now - chrono::Duration::days(30);
+        assert!(left
+            .iter()
+            .all(|click| click.url_redirect_id == pro || click.clicked_at > cutoff));
     }
 }
@@ -0,0 +1,111 @@
+use std::{sync::Arc, time::Duration};
+
+use http::{header::COOKIE, HeaderMap, HeaderValue};
+use rand::{distributions::Alphanumeric, Rng};
+use redis::AsyncCommands;
+use sha2::{Digest, Sha256};
+
+use crate::{authenthication::AuthenticationError, csrf, kvs::KvsPool};
+
+pub const SESSION_COOKIE: &str = "session";
+
+/// What a session cookie stands for: the credentials a bearer client would
+/// have sent itself.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Session {
+    pub authorization: String,
+    pub provider: Option<String>,
+}
+
+/// Browser sessions kept in the KVS, so the access token never reaches
+/// JavaScript.
+pub struct SessionStore {
+    kvs_pool: Arc<KvsPool>,
+    ttl: Duration,
+}
+
+impl SessionStore {
+    pub fn new(kvs_pool: Arc<KvsPool>, ttl: Duration) -> Self {
+        Self { kvs_pool, ttl }
+    }
+
+    /// Stores the session and returns its id.
+    pub async fn create(&self, session: &Session) -> Result<String, AuthenticationError> {
+        let id = random_token();
+        let value = serde_json::to_string(session)
+            .map_err(|error| AuthenticationError::Internal(Box::new(error)))?;
+
+        let mut conn = self.kvs_pool.get().await?;
+        conn.set_ex::<_, _, ()>(session_key(&id), value, self.ttl.as_secs())
+            .await?;
+
+        Ok(id)
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<Session>, AuthenticationError> {
+        let mut conn = self.kvs_pool.get().await?;
+        let value: Option<String> = conn.get(session_key(id)).await?;
+
+        value
+            .map(|value| serde_json::from_str(&value))
+            .transpose()
+            .map_err(|error| AuthenticationError::Internal(Box::new(error)))
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<(), AuthenticationError> {
+        let mut conn = self.kvs_pool.get().await?;
+        conn.del(session_key(id)).await.map_err(Into::into)
+    }
+
+    pub fn cookie(&self, id: &str) -> HeaderValue {
+        cookie_header(id, self.ttl.as_secs())
+    }
+
+    /// A fresh CSRF cookie that lives as long as the session cookie.
+    pub fn csrf_cookie(&self) -> HeaderValue {
+        csrf::cookie(&random_token(), self.ttl.as_secs())
+    }
+}
+
+/// 43 alphanumerics, a little over 256 bits of randomness.
+fn random_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(43)
+        .map(char::from)
+        .collect()
+}
+
+/// A `Set-Cookie` value that makes the browser drop the session cookie.
+pub fn expired_cookie() -> HeaderValue {
+    cookie_header("", 0)
+}
+
+fn cookie_header(id: &str, max_age: u64) -> HeaderValue {
+    // ids are alphanumeric, so the value is always a valid header
+    HeaderValue::from_str(&format!(
+        "{SESSION_COOKIE}={id}; Path=/; Max-Age={max_age}; HttpOnly; Secure; SameSite=Lax"
+    ))
+    .expect("session cookie is a valid header value")
+}
+
+/// The session id from the request's `Cookie` headers, if any.
+pub fn session_id(headers: &HeaderMap) -> Option<&str> {
+    cookie(headers, SESSION_COOKIE)
+}
+
+pub fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(cookie_name, _)| *cookie_name == name)
+        .map(|(_, value)| value)
+}
+
+// Only a hash of the id is stored, like tokens in the introspection cache.
+fn session_key(id: &str) -> String {
+    format!("session-sha256:{:x}", Sha256::digest(id))
+}
@@ -0,0 +1,194 @@
+use std::sync::Arc;
+
+use hmac::{Hmac, Mac};
+use http::HeaderMap;
+use rand::{distributions::Alphanumeric, Rng};
+use redis::AsyncCommands;
+use sha2::Sha256;
+
+use crate::{kvs::KvsPool, rate_limit::RateLimitError};
+
+const SIGNATURE_HEADER: &str = "x-slack-signature";
+const TIMESTAMP_HEADER: &str = "x-slack-request-timestamp";
+// Slack's own recommendation, so a captured request cannot be replayed later.
+const MAX_REQUEST_AGE_SECS: i64 = 5 * 60;
+pub const LINK_CODE_TTL_SECS: u64 = 10 * 60;
+
+/// The fields of a slash-command payload we use.
+#[derive(Debug, Default)]
+pub struct SlashCommand {
+    pub team_id: String,
+    pub user_id: String,
+    pub text: String,
+}
+
+impl SlashCommand {
+    /// Reads the form-encoded body Slack posts.
+    pub fn parse(body: &[u8]) -> Self {
+        let mut command = Self::default();
+        for (name, value) in url::form_urlencoded::parse(body) {
+            match name.as_ref() {
+                "team_id" => command.team_id = value.into_owned(),
+                "user_id" => command.user_id = value.into_owned(),
+                "text" => command.text = value.into_owned(),
+                _ => {}
+            }
+        }
+
+        command
+    }
+}
+
+/// Slash commands from one Slack app. Slack users are tied to accounts with a
+/// short-lived code: `/shorten link` hands one out in Slack, and the signed-in
+/// user redeems it here.
+pub struct Slack {
+    signing_secret: String,
+    kvs_pool: Arc<KvsPool>,
+}
+
+impl Slack {
+    pub fn new(signing_secret: String, kvs_pool: Arc<KvsPool>) -> Self {
+        Self {
+            signing_secret,
+            kvs_pool,
+        }
+    }
+
+    /// Whether the request was signed with our secret in the last few minutes.
+    pub fn verify(&self, headers: &HeaderMap, body: &[u8]) -> bool {
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        let (Some(timestamp), Some(signature)) =
+            (header(TIMESTAMP_HEADER), header(SIGNATURE_HEADER))
+        else {
+            return false;
+        };
+        let fresh = timestamp.parse::<i64>().is_ok_and(|timestamp| {
+            (chrono::Utc::now().timestamp() - timestamp).abs() <= MAX_REQUEST_AGE_SECS
+        });
+        let Some(signature) = signature
+            .strip_prefix("v0=")
+            .and_then(|signature| hex::decode(signature).ok())
+        else {
+            return false;
+        };
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.signing_secret.as_bytes())
+            .expect("hmac accepts keys of any length");
+        mac.update(b"v0:");
+        mac.update(timestamp.as_bytes());
+        mac.update(b":");
+        mac.update(body);
+        fresh && mac.verify_slice(&signature).is_ok()
+    }
+
+    pub async fn issue_link_code(&self, command: &SlashCommand) -> Result<String, RateLimitError> {
+        let code: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(12)
+            .map(char::from)
+            .collect();
+        let value = format!("{}:{}", command.team_id, command.user_id);
+
+        let mut conn = self.kvs_pool.get().await?;
+        conn.set_ex::<_, _, ()>(link_code_key(&code), value, LINK_CODE_TTL_SECS)
+            .await?;
+
+        Ok(code)
+    }
+
+    /// The Slack team and user a code was issued to. Codes work only once.
+    pub async fn redeem_link_code(
+        &self,
+        code: &str,
+    ) -> Result<Option<(String, String)>, RateLimitError> {
+        let mut conn = self.kvs_pool.get().await?;
+        let value: Option<String> = conn.get_del(link_code_key(code)).await?;
+
+        Ok(value.and_then(|value| {
+            value
+                .split_once(':')
+                .map(|(team_id, user_id)| (team_id.to_string(), user_id.to_string()))
+        }))
+    }
+}
+
+fn link_code_key(code: &str) -> String {
+    format!("slack-link-code:{code}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_kvs::MemoryKvs;
+
+    const SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+    const BODY: &[u8] = b"team_id=T1&user_id=U2&command=%2Fshorten&text=https%3A%2F%2Fexample.com";
+
+    fn slack() -> Slack {
+        Slack::new(
+            String::from(SECRET),
+            Arc::new(KvsPool::Memory(MemoryKvs::default())),
+        )
+    }
+
+    fn signed(timestamp: i64, secret: &str, body: &[u8]) -> HeaderMap {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("v0:{timestamp}:").as_bytes());
+        mac.update(body);
+        let signature = hex::encode(mac.finalize().into_bytes());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(TIMESTAMP_HEADER, timestamp.to_string().parse().unwrap());
+        headers.insert(SIGNATURE_HEADER, format!("v0={signature}").parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn accepts_requests_freshly_signed_with_the_secret() {
+        let now = chrono::Utc::now().timestamp();
+        assert!(slack().verify(&signed(now, SECRET, BODY), BODY));
+    }
+
+    #[test]
+    fn refuses_requests_signed_otherwise() {
+        let now = chrono::Utc::now().timestamp();
+        let slack = slack();
+        assert!(!slack.verify(&signed(now, "another secret", BODY), BODY));
+        assert!(!slack.verify(&signed(now, SECRET, BODY), b"team_id=T1&text=other"));
+        assert!(!slack.verify(&HeaderMap::new(), BODY));
+
+        let mut unprefixed = signed(now, SECRET, BODY);
+        let signature = unprefixed[SIGNATURE_HEADER].to_str().unwrap()[3..].to_string();
+        unprefixed.insert(SIGNATURE_HEADER, signature.parse().unwrap());
+        assert!(!slack.verify(&unprefixed, BODY));
+    }
+
+    #[test]
+    fn refuses_replayed_requests() {
+        let stale = chrono::Utc::now().timestamp() - MAX_REQUEST_AGE_SECS - 1;
+        assert!(!slack().verify(&signed(stale, SECRET, BODY), BODY));
+    }
+
+    #[test]
+    fn reads_slash_commands() {
+        let command = SlashCommand::parse(BODY);
+        assert_eq!(command.team_id, "T1");
+        assert_eq!(command.user_id, "U2");
+        assert_eq!(command.text, "https://example.com");
+    }
+
+    #[tokio::test]
+    async fn redeems_link_codes_once() {
+        let slack = slack();
+        let code = slack
+            .issue_link_code(&SlashCommand::parse(BODY))
+            .await
+            .unwrap();
+        assert_eq!(
+            slack.redeem_link_code(&code).await.unwrap(),
+            Some((String::from("T1"), String::from("U2")))
+        );
+        assert_eq!(slack.redeem_link_code(&code).await.unwrap(), None);
+    }
+}
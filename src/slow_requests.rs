@@ -0,0 +1,39 @@
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+/// Logs a warning for every request that takes longer than the threshold.
+/// It runs inside the request span, so the requester recorded there by the
+/// authentication extractor is included.
+pub async fn log_slow_requests(
+    State(threshold): State<Duration>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let method = request.method().clone();
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+    let elapsed = start.elapsed();
+
+    if elapsed > threshold {
+        tracing::warn!(
+            %method,
+            route,
+            status = response.status().as_u16(),
+            duration_ms = elapsed.as_millis() as u64,
+            "slow request"
+        );
+    }
+
+    response
+}
@@ -0,0 +1,92 @@
+use axum::response::{Html, IntoResponse, Response};
+use http::{
+    header::{USER_AGENT, VARY},
+    HeaderMap, HeaderValue,
+};
+
+use crate::{not_found::escape_html, responses::SocialPreview};
+
+// What the unfurlers of the common social and chat platforms put in their
+// User-Agent, lowercased.
+const CRAWLERS: [&str; 14] = [
+    "facebookexternalhit",
+    "facebookcatalog",
+    "twitterbot",
+    "linkedinbot",
+    "slackbot",
+    "discordbot",
+    "telegrambot",
+    "whatsapp",
+    "pinterest",
+    "redditbot",
+    "skypeuripreview",
+    "embedly",
+    "vkshare",
+    "mastodon",
+];
+
+/// Whether the request comes from a crawler unfurling the link for a social
+/// platform, going by its User-Agent.
+pub fn is_crawler(headers: &HeaderMap) -> bool {
+    let Some(user_agent) = headers
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    let user_agent = user_agent.to_ascii_lowercase();
+    CRAWLERS.iter().any(|crawler| user_agent.contains(crawler))
+}
+
+/// A page carrying `preview` as Open Graph tags, for crawlers. Anyone taken
+/// for a crawler by mistake is still sent on to `target`.
+pub fn response(preview: &SocialPreview, target: &str) -> Response {
+    let target = escape_html(target);
+    let mut tags = vec![format!(r#"<meta property="og:url" content="{target}">"#)];
+    for (property, value) in [
+        ("og:title", &preview.title),
+        ("og:description", &preview.description),
+        ("og:image", &preview.image),
+    ] {
+        if let Some(value) = value {
+            tags.push(format!(
+                r#"<meta property="{property}" content="{}">"#,
+                escape_html(value)
+            ));
+        }
+    }
+    let card = match preview.image {
+        Some(_) => "summary_large_image",
+        None => "summary",
+    };
+    tags.push(format!(r#"<meta name="twitter:card" content="{card}">"#));
+
+    let page = format!(
+        r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+{tags}
+<meta http-equiv="refresh" content="0; url={target}">
+</head>
+<body>
+<p><a href="{target}">Continue</a></p>
+</body>
+</html>
+"#,
+        title = escape_html(preview.title.as_deref().unwrap_or_default()),
+        tags = tags.join("\n"),
+    );
+    let mut response = Html(page).into_response();
+    vary_by_user_agent(&mut response);
+    response
+}
+
+/// Marks `response` as depending on the User-Agent, so caches do not hand
+/// the crawlers' page to people or the redirect to crawlers.
+pub fn vary_by_user_agent(response: &mut Response) {
+    response
+        .headers_mut()
+        .append(VARY, HeaderValue::from_static("user-agent"));
+}
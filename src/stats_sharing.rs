@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use uuid::Uuid;
+
+// Keeps these tokens from being mistaken for any other we might sign.
+const AUDIENCE: &str = "shared-stats";
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Claims {
+    /// The link whose stats the token reveals.
+    sub: Uuid,
+    aud: String,
+    exp: i64,
+}
+
+/// Signs and checks tokens granting read access to one link's stats without
+/// an account. They are self-contained, so they stay valid until they expire.
+pub struct StatsSharing {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+}
+
+impl StatsSharing {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            encoding_key: EncodingKey::from_secret(secret.as_bytes()),
+            decoding_key: DecodingKey::from_secret(secret.as_bytes()),
+        }
+    }
+
+    pub fn issue(
+        &self,
+        id: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> jsonwebtoken::errors::Result<String> {
+        let claims = Claims {
+            sub: id,
+            aud: String::from(AUDIENCE),
+            exp: expires_at.timestamp(),
+        };
+        jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &self.encoding_key)
+    }
+
+    /// The link `token` grants access to, if it is genuine and unexpired.
+    pub fn verify(&self, token: &str) -> Option<Uuid> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_audience(&[AUDIENCE]);
+        validation.leeway = 0;
+
+        jsonwebtoken::decode::<Claims>(token, &self.decoding_key, &validation)
+            .ok()
+            .map(|data| data.claims.sub)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grants_access_to_the_link_it_was_issued_for() {
+        let sharing = StatsSharing::new("secret");
+        let id = Uuid::new_v4();
+        let token = sharing
+            .issue(id, Utc::now() + chrono::Duration::hours(1))
+            .unwrap();
+        assert_eq!(sharing.verify(&token), Some(id));
+    }
+
+    #[test]
+    fn refuses_expired_tokens() {
+        let sharing = StatsSharing::new("secret");
+        let token = sharing
+            .issue(Uuid::new_v4(), Utc::now() - chrono::Duration::seconds(1))
+            .unwrap();
+        assert_eq!(sharing.verify(&token), None);
+    }
+
+    #[test]
+    fn refuses_tokens_signed_with_another_secret() {
+        let token = StatsSharing::new("another secret")
+            .issue(Uuid::new_v4(), Utc::now() + chrono::Duration::hours(1))
+            .unwrap();
+        assert_eq!(StatsSharing::new("secret").verify(&token), None);
+        assert_eq!(StatsSharing::new("secret").verify("not a token"), None);
+    }
+
+    #[test]
+    fn refuses_tokens_for_another_audience() {
+        let claims = Claims {
+            sub: Uuid::new_v4(),
+            aud: String::from("session"),
+            exp: (Utc::now() + chrono::Duration::hours(1)).timestamp(),
+        };
+        let token = jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        assert_eq!(StatsSharing::new("secret").verify(&token), None);
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{header::HOST, uri::Authority, HeaderName, StatusCode};
+
+use crate::{
+    client_ip::TrustedProxies,
+    config::{TenancyConfig, TenantConfig},
+    responses::TenantSettings,
+    service::{QueryError, UrlService},
+    Services,
+};
+
+/// How long each instance keeps a tenant's settings before reading them
+/// again, so changes made through other instances show up.
+const SETTINGS_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
+struct Current {
+    tenant: Arc<TenantConfig>,
+    settings: Arc<TenantSettings>,
+}
+
+tokio::task_local! {
+    static CURRENT: Current;
+}
+
+/// The tenant the current request is served for. There is none outside
+/// requests, like in background tasks and the CLI, which work across every
+/// tenant, nor in installations without tenancy.
+pub fn current() -> Option<Arc<TenantConfig>> {
+    CURRENT.try_with(|current| current.tenant.clone()).ok()
+}
+
+/// What the current tenant overrides of the installation's settings.
+pub fn settings() -> Option<Arc<TenantSettings>> {
+    CURRENT.try_with(|current| current.settings.clone()).ok()
+}
+
+/// The id recorded on rows made for the current tenant, empty without one.
+pub fn current_id() -> String {
+    current()
+        .map(|tenant| tenant.id.clone())
+        .unwrap_or_default()
+}
+
+/// Whether `user` may act within the current tenant. Identities without an
+/// email, like service accounts, only may within tenants that list them.
+pub fn admits(user: &str) -> bool {
+    let Some(tenant) = current() else {
+        return true;
+    };
+    let Some((_, domain)) = user.rsplit_once('@') else {
+        return tenant
+            .service_accounts
+            .iter()
+            .any(|account| account == user);
+    };
+
+    tenant.email_domains.is_empty()
+        || tenant
+            .email_domains
+            .iter()
+            .any(|allowed| domain.eq_ignore_ascii_case(allowed))
+}
+
+/// `key` made unique across tenants, for caches shared by all of them.
+pub fn scoped_key(key: &str) -> String {
+    match current() {
+        Some(tenant) => format!("{}/{key}", tenant.id),
+        None => key.to_owned(),
+    }
+}
+
+pub struct Tenants {
+    tenants: Vec<Arc<TenantConfig>>,
+    header: Option<HeaderName>,
+    trusted_proxies: Arc<TrustedProxies>,
+    settings: Mutex<HashMap<String, (Instant, Arc<TenantSettings>)>>,
+}
+
+impl Tenants {
+    pub fn new(config: TenancyConfig, trusted_proxies: Arc<TrustedProxies>) -> Self {
+        Self {
+            tenants: config.tenants.into_iter().map(Arc::new).collect(),
+            // validated with the config
+            header: config
+                .header
+                .and_then(|header| HeaderName::from_bytes(header.as_bytes()).ok()),
+            trusted_proxies,
+            settings: Mutex::default(),
+        }
+    }
+
+    /// The settings of `tenant_id`, read from the database once they have
+    /// been cached for [`SETTINGS_TTL`].
+    async fn settings(
+        &self,
+        url: &UrlService,
+        tenant_id: &str,
+    ) -> Result<Arc<TenantSettings>, QueryError> {
+        let cached = self
+            .settings
+            .lock()
+            .expect("tenant settings lock poisoned")
+            .get(tenant_id)
+            .filter(|(read_at, _)| read_at.elapsed() < SETTINGS_TTL)
+            .map(|(_, settings)| settings.clone());
+        if let Some(settings) = cached {
+            return Ok(settings);
+        }
+
+        let settings = Arc::new(url.tenant_settings(tenant_id).await?);
+        self.settings
+            .lock()
+            .expect("tenant settings lock poisoned")
+            .insert(tenant_id.to_owned(), (Instant::now(), settings.clone()));
+        Ok(settings)
+    }
+
+    /// Drops the cached settings of `tenant_id` after they changed here.
+    pub fn forget_settings(&self, tenant_id: &str) {
+        self.settings
+            .lock()
+            .expect("tenant settings lock poisoned")
+            .remove(tenant_id);
+    }
+
+    /// The tenant named by the header when a trusted proxy sent it, or else
+    /// the one serving the host the request came to.
+    fn resolve(&self, peer: Option<IpAddr>, request: &Request) -> Option<Arc<TenantConfig>> {
+        let named = self
+            .header
+            .as_ref()
+            .filter(|_| peer.is_some_and(|peer| self.trusted_proxies.contains(&peer)))
+            .and_then(|header| request.headers().get(header))
+            .and_then(|value| value.to_str().ok());
+        if let Some(id) = named {
+            let id = id.trim();
+            return self.tenants.iter().find(|tenant| tenant.id == id).cloned();
+        }
+
+        // HTTP/2 requests carry the host in the URI rather than a header
+        let host = match request.headers().get(HOST) {
+            Some(host) => host.to_str().ok()?.parse::<Authority>().ok()?,
+            None => request.uri().authority()?.clone(),
+        };
+        let host = host.host().to_ascii_lowercase();
+        self.tenants
+            .iter()
+            .find(|tenant| tenant.hosts.contains(&host))
+            .cloned()
+    }
+}
+
+/// Serves each request within its tenant and the settings it overrides.
+/// Requests for no known tenant are refused, so nothing ever runs across
+/// tenants on their behalf.
+pub async fn scope(State(service): State<Arc<Services>>, request: Request, next: Next) -> Response {
+    let Some(tenants) = &service.tenants else {
+        return next.run(request).await;
+    };
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let Some(tenant) = tenants.resolve(peer, &request) else {
+        return (StatusCode::NOT_FOUND, "unknown tenant").into_response();
+    };
+    let settings = match tenants.settings(&service.url, &tenant.id).await {
+        Ok(settings) => settings,
+        Err(error) => return error.into(),
+    };
+
+    CURRENT
+        .scope(Current { tenant, settings }, next.run(request))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+
+    use super::*;
+
+    fn tenant(id: &str, hosts: &[&str]) -> TenantConfig {
+        TenantConfig {
+            id: id.to_owned(),
+            hosts: hosts.iter().map(|host| host.to_string()).collect(),
+            email_domains: Vec::new(),
+            public_base_url: None,
+            service_accounts: Vec::new(),
+        }
+    }
+
+    fn within<T>(tenant: TenantConfig, f: impl FnOnce() -> T) -> T {
+        let current = Current {
+            tenant: Arc::new(tenant),
+            settings: Arc::default(),
+        };
+        CURRENT.sync_scope(current, f)
+    }
+
+    fn tenants(header: Option<&str>) -> Tenants {
+        Tenants::new(
+            TenancyConfig {
+                header: header.map(String::from),
+                tenants: vec![
+                    tenant("acme", &["go.acme.test"]),
+                    tenant("initech", &["initech.test"]),
+                ],
+            },
+            Arc::new(TrustedProxies::new(vec!["10.0.0.0/8".parse().unwrap()])),
+        )
+    }
+
+    fn request(host: &str, tenant: Option<&str>) -> Request {
+        let mut request = Request::builder().uri("/urls").header(HOST, host);
+        if let Some(tenant) = tenant {
+            request = request.header("x-tenant", tenant);
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    fn resolved(tenants: &Tenants, peer: &str, request: &Request) -> Option<String> {
+        tenants
+            .resolve(Some(peer.parse().unwrap()), request)
+            .map(|tenant| tenant.id.clone())
+    }
+
+    #[test]
+    fn admits_anyone_outside_a_tenant() {
+        assert!(admits("someone@example.com"));
+        assert!(admits("service:ci"));
+    }
+
+    #[test]
+    fn admits_users_of_the_tenant_email_domains() {
+        let mut acme = tenant("acme", &["go.acme.test"]);
+        acme.email_domains = vec![String::from("acme.test")];
+
+        within(acme, || {
+            assert!(admits("someone@acme.test"));
+            assert!(admits("someone@ACME.test"));
+            assert!(!admits("someone@initech.test"));
+            assert!(!admits("someone@evil.acme.test"));
+        });
+        within(tenant("open", &["open.test"]), || {
+            assert!(admits("someone@anywhere.test"));
+        });
+    }
+
+    #[test]
+    fn admits_only_the_service_accounts_a_tenant_lists() {
+        let mut acme = tenant("acme", &["go.acme.test"]);
+        acme.service_accounts = vec![String::from("service:ci")];
+
+        within(acme, || {
+            assert!(admits("service:ci"));
+            assert!(!admits("service:deploy"));
+        });
+        within(tenant("initech", &["initech.test"]), || {
+            assert!(!admits("service:ci"));
+        });
+    }
+
+    #[test]
+    fn scopes_shared_keys_to_the_tenant() {
+        assert_eq!(scoped_key("docs"), "docs");
+        within(tenant("acme", &["go.acme.test"]), || {
+            assert_eq!(scoped_key("docs"), "acme/docs");
+            assert_eq!(current_id(), "acme");
+        });
+        assert_eq!(current_id(), "");
+    }
+
+    #[test]
+    fn resolves_the_tenant_by_host() {
+        let tenants = tenants(None);
+
+        assert_eq!(
+            resolved(&tenants, "192.0.2.1", &request("GO.acme.test:8080", None)),
+            Some(String::from("acme"))
+        );
+        assert_eq!(
+            resolved(&tenants, "192.0.2.1", &request("unknown.test", None)),
+            None
+        );
+    }
+
+    #[test]
+    fn believes_the_tenant_header_from_trusted_proxies_only() {
+        let tenants = tenants(Some("x-tenant"));
+        let request = request("go.acme.test", Some("initech"));
+
+        assert_eq!(
+            resolved(&tenants, "10.0.0.1", &request),
+            Some(String::from("initech"))
+        );
+        assert_eq!(
+            resolved(&tenants, "192.0.2.1", &request),
+            Some(String::from("acme"))
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use crate::{kvs::KvsPool, rate_limit::RateLimitError};
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use uuid::Uuid;
+
+// Comfortably longer than the longest report period.
+const RETENTION_SECS: i64 = 400 * 24 * 60 * 60;
+
+// Hourly counts only back the last day's total.
+const HOURLY_RETENTION_SECS: i64 = 25 * 60 * 60;
+
+/// Counts redirects served per UTC day, overall for usage reports and per
+/// link for campaign statistics.
+pub struct RedirectCounter {
+    kvs_pool: Arc<KvsPool>,
+}
+
+impl RedirectCounter {
+    pub fn new(kvs_pool: Arc<KvsPool>) -> Self {
+        Self { kvs_pool }
+    }
+
+    pub async fn record(&self, id: Uuid) -> Result<(), RateLimitError> {
+        let now = Utc::now();
+        let today = now.date_naive();
+        let key = day_key(today);
+        let link_key = link_day_key(id, today);
+        let hour_key = hour_key(now);
+        let mut conn = self.kvs_pool.get().await?;
+
+        redis::pipe()
+            .incr(&key, 1)
+            .ignore()
+            .expire(&key, RETENTION_SECS)
+            .ignore()
+            .incr(&hour_key, 1)
+            .ignore()
+            .expire(&hour_key, HOURLY_RETENTION_SECS)
+            .ignore()
+            .incr(&link_key, 1)
+            .ignore()
+            .expire(&link_key, RETENTION_SECS)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Adds `count` redirects to `id` on a past day, for seeded data.
+    pub async fn record_on(
+        &self,
+        id: Uuid,
+        day: NaiveDate,
+        count: u64,
+    ) -> Result<(), RateLimitError> {
+        let key = day_key(day);
+        let link_key = link_day_key(id, day);
+        let mut conn = self.kvs_pool.get().await?;
+
+        redis::pipe()
+            .incr(&key, count)
+            .ignore()
+            .expire(&key, RETENTION_SECS)
+            .ignore()
+            .incr(&link_key, count)
+            .ignore()
+            .expire(&link_key, RETENTION_SECS)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Redirects served from the start of `since`'s day until now.
+    pub async fn served_since(&self, since: DateTime<Utc>) -> Result<u64, RateLimitError> {
+        let today = Utc::now().date_naive();
+        let keys: Vec<String> = since
+            .date_naive()
+            .iter_days()
+            .take_while(|day| *day <= today)
+            .map(day_key)
+            .collect();
+
+        let mut conn = self.kvs_pool.get().await?;
+        // MGET spelled out, as `mget` sends a plain GET for a single key
+        let counts: Vec<Option<u64>> = redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+
+        Ok(counts.into_iter().flatten().sum())
+    }
+
+    /// Redirects served in the current hour and the 23 before it.
+    pub async fn served_last_day(&self) -> Result<u64, RateLimitError> {
+        let now = Utc::now();
+        let keys: Vec<String> = (0..24)
+            .map(|hours| hour_key(now - chrono::Duration::hours(hours)))
+            .collect();
+
+        let mut conn = self.kvs_pool.get().await?;
+        let counts: Vec<Option<u64>> = redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+
+        Ok(counts.into_iter().flatten().sum())
+    }
+
+    /// Redirects to any of `ids` on each day from `since`'s day until today.
+    pub async fn served_daily(
+        &self,
+        ids: &[Uuid],
+        since: DateTime<Utc>,
+    ) -> Result<Vec<(NaiveDate, u64)>, RateLimitError> {
+        let today = Utc::now().date_naive();
+        let days: Vec<NaiveDate> = since
+            .date_naive()
+            .iter_days()
+            .take_while(|day| *day <= today)
+            .collect();
+        if ids.is_empty() {
+            return Ok(days.into_iter().map(|day| (day, 0)).collect());
+        }
+
+        let keys: Vec<String> = days
+            .iter()
+            .flat_map(|day| ids.iter().map(|id| link_day_key(*id, *day)))
+            .collect();
+        let mut conn = self.kvs_pool.get().await?;
+        let counts: Vec<Option<u64>> = redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+
+        Ok(days
+            .into_iter()
+            .zip(counts.chunks(ids.len()))
+            .map(|(day, counts)| (day, counts.iter().flatten().sum()))
+            .collect())
+    }
+}
+
+/// Midnight UTC `days - 1` days ago, so a period of one day is today so far.
+pub fn period_start(days: u64) -> DateTime<Utc> {
+    Utc::now()
+        .date_naive()
+        .checked_sub_days(Days::new(days.saturating_sub(1)))
+        .unwrap_or(NaiveDate::MIN)
+        .and_time(chrono::NaiveTime::MIN)
+        .and_utc()
+}
+
+fn day_key(day: NaiveDate) -> String {
+    format!("redirects:{day}")
+}
+
+fn hour_key(at: DateTime<Utc>) -> String {
+    format!("redirects:hour:{}", at.format("%Y-%m-%dT%H"))
+}
+
+fn link_day_key(id: Uuid, day: NaiveDate) -> String {
+    format!("redirects:{id}:{day}")
+}
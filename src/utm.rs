@@ -0,0 +1,31 @@
+use crate::requests::UtmParams;
+
+/// Sets the UTM parameters on `base`, replacing any it already carries so a
+/// link never reports two sources. Values are trimmed and lowercased, as
+/// analytics tools count `Newsletter` and `newsletter` as different sources.
+pub fn compose(base: &url::Url, params: &UtmParams) -> url::Url {
+    let fields = [
+        ("utm_source", Some(&params.utm_source)),
+        ("utm_medium", Some(&params.utm_medium)),
+        ("utm_campaign", Some(&params.utm_campaign)),
+        ("utm_term", params.utm_term.as_ref()),
+        ("utm_content", params.utm_content.as_ref()),
+    ];
+
+    let mut url = base.clone();
+    let kept: Vec<(String, String)> = base
+        .query_pairs()
+        .filter(|(name, _)| !fields.iter().any(|(field, _)| name == field))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(kept)
+        .extend_pairs(fields.iter().filter_map(|(name, value)| {
+            value
+                .map(|value| value.trim().to_lowercase())
+                .filter(|value| !value.is_empty())
+                .map(|value| (*name, value))
+        }));
+    url
+}
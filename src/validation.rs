@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use axum::response::Response;
+
+use crate::{
+    error::{problem, InvalidFields, ProblemType},
+    i18n::{Message, MessageId},
+    requests::{NewRollout, NewUrl, ScheduleTarget},
+    responses::{Rollout, SocialPreview},
+    service::{KeyPolicy, NewUrlRedirect},
+};
+
+/// Problems with a request's fields, collected so a client learns about all
+/// of them at once rather than one per attempt.
+#[derive(Debug, Clone, Default)]
+pub struct FieldErrors(BTreeMap<&'static str, Vec<Message>>);
+
+impl FieldErrors {
+    pub fn add(&mut self, field: &'static str, message: Message) {
+        self.0.entry(field).or_default().push(message);
+    }
+
+    /// The problems of each field, worded by `text`.
+    pub fn render(&self, text: impl Fn(&Message) -> String) -> InvalidFields {
+        InvalidFields(
+            self.0
+                .iter()
+                .map(|(field, messages)| (*field, messages.iter().map(&text).collect()))
+                .collect(),
+        )
+    }
+}
+
+impl From<FieldErrors> for Response {
+    fn from(errors: FieldErrors) -> Self {
+        let fields = errors.render(Message::english);
+        let mut response = problem(ProblemType::ValidationFailed, fields.summary());
+        response.extensions_mut().insert(fields);
+        response.extensions_mut().insert(errors);
+        response
+    }
+}
+
+/// Checks every field of a link being created or replaced, its key against
+/// `key_policy`.
+pub fn new_url(
+    key_policy: &KeyPolicy,
+    user_email: String,
+    new_url: NewUrl,
+) -> Result<NewUrlRedirect, FieldErrors> {
+    let mut errors = FieldErrors::default();
+    let key = key_policy
+        .parse(new_url.key)
+        .map_err(|error| errors.add("key", error.message()))
+        .ok();
+    if !is_http_url(&new_url.target) {
+        errors.add("target", Message::new(MessageId::NotHttpUrl));
+    }
+
+    match key {
+        Some(key) if errors.0.is_empty() => {
+            Ok(NewUrlRedirect::new(user_email, key, new_url.target))
+        }
+        _ => Err(errors),
+    }
+}
+
+/// Checks a target change being scheduled: where it points, and that its
+/// time is yet to come.
+pub fn schedule_target(schedule: &ScheduleTarget) -> Result<(), FieldErrors> {
+    let mut errors = FieldErrors::default();
+    if !is_http_url(&schedule.target) {
+        errors.add("target", Message::new(MessageId::NotHttpUrl));
+    }
+    if schedule.at <= chrono::Utc::now() {
+        errors.add("at", Message::new(MessageId::NotInFuture));
+    }
+
+    if errors.0.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Checks the target a share of a link's traffic is moved to, giving the
+/// rollout with that target as parsed, so it is stored the way a redirect
+/// sends it.
+pub fn rollout(rollout: NewRollout) -> Result<Rollout, FieldErrors> {
+    let target = target(&rollout.target)?;
+
+    Ok(Rollout {
+        target: target.into(),
+        percent: rollout.percent,
+    })
+}
+
+/// Checks what a link shows when shared; crawlers fetch its image, so that
+/// must be a web URL.
+pub fn social_preview(preview: &SocialPreview) -> Result<(), FieldErrors> {
+    let mut errors = FieldErrors::default();
+    if preview
+        .image
+        .as_deref()
+        .is_some_and(|image| !is_http_url(image))
+    {
+        errors.add("image", Message::new(MessageId::NotHttpUrl));
+    }
+
+    if errors.0.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Checks a link's target on its own, for requests that take nothing else
+/// the user picked, giving it as parsed.
+pub fn target(target: &str) -> Result<url::Url, FieldErrors> {
+    http_url(target).ok_or_else(|| {
+        let mut errors = FieldErrors::default();
+        errors.add("target", Message::new(MessageId::NotHttpUrl));
+        errors
+    })
+}
+
+/// Whether `target` is an absolute http or https URL, the only kind links
+/// may point at.
+pub(crate) fn is_http_url(target: &str) -> bool {
+    http_url(target).is_some()
+}
+
+fn http_url(target: &str) -> Option<url::Url> {
+    url::Url::parse(target)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+}
@@ -0,0 +1,116 @@
+#![allow(dead_code)] // each test binary uses its own share of the helpers
+
+use std::{fs, net::SocketAddr, path::PathBuf};
+
+use reqwest::{redirect::Policy, RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use url_shortener::{build_router, build_services, config::Config};
+
+pub const USER: &str = "Dev someone@example.com";
+
+/// The app served on a free port with a fresh SQLite database and an
+/// in-process KVS.
+pub struct TestServer {
+    pub base: String,
+    client: reqwest::Client,
+}
+
+impl TestServer {
+    /// Starts a server configured by the minimal settings plus `config`,
+    /// TOML appended to them, keeping its files under a directory named
+    /// after the test.
+    pub async fn start(name: &str, config: &str) -> Self {
+        let _ = fs::remove_dir_all(dir(name));
+        Self::restart(name, config).await
+    }
+
+    /// Like [`TestServer::start`], but on the database an earlier server of
+    /// the test left behind.
+    pub async fn restart(name: &str, config: &str) -> Self {
+        let dir = dir(name);
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("config.toml");
+        fs::write(
+            &config_path,
+            format!(
+                r#"
+port = 1
+kvs_url = "memory://"
+auth_mode = "dev"
+client_id = "client"
+client_secret = "secret"
+redirect_uri = "https://example.com"
+allowed_origins = ["http://localhost:3000"]
+run_migrations = true
+{config}
+
+[database]
+url = "sqlite://{}?mode=rwc"
+"#,
+                dir.join("db.sqlite").display()
+            ),
+        )
+        .unwrap();
+
+        let config = Config::load(Some(&config_path)).unwrap();
+        let router = build_router(build_services(config).await.unwrap());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                router.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap();
+        });
+
+        Self {
+            base: format!("http://{address}"),
+            client: reqwest::Client::builder()
+                .redirect(Policy::none())
+                .build()
+                .unwrap(),
+        }
+    }
+
+    pub fn get(&self, path: &str) -> RequestBuilder {
+        self.client.get(format!("{}{path}", self.base))
+    }
+
+    pub fn post(&self, path: &str) -> RequestBuilder {
+        self.client.post(format!("{}{path}", self.base))
+    }
+
+    pub fn patch(&self, path: &str) -> RequestBuilder {
+        self.client.patch(format!("{}{path}", self.base))
+    }
+
+    pub fn put(&self, path: &str) -> RequestBuilder {
+        self.client.put(format!("{}{path}", self.base))
+    }
+
+    /// Creates the link as [`USER`], answering it as JSON.
+    pub async fn create(&self, key: &str, target: &str) -> Value {
+        let response = self
+            .post("/urls")
+            .header("Authorization", USER)
+            .json(&serde_json::json!({ "key": key, "target": target }))
+            .send()
+            .await
+            .unwrap();
+        json(response, StatusCode::OK).await
+    }
+}
+
+fn dir(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+/// The body of `response` as JSON, once it is checked to be `status`.
+pub async fn json(response: Response, status: StatusCode) -> Value {
+    let actual = response.status();
+    let body = response.text().await.unwrap();
+    assert_eq!(actual, status, "{body}");
+    serde_json::from_str(&body).unwrap()
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::{TestServer, USER};
+use reqwest::{header::LOCATION, StatusCode};
+use serde_json::json;
+
+#[tokio::test]
+async fn redirects_created_links() {
+    let server = TestServer::start("redirects_created_links", "").await;
+
+    let url = server.create("docs", "https://example.com/docs").await;
+    assert_eq!(url["short_url"], "/urls/redirect/docs");
+
+    let response = server.get("/urls/redirect/docs").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(response.headers()[LOCATION], "https://example.com/docs");
+}
+
+#[tokio::test]
+async fn answers_not_found_for_unknown_keys() {
+    let server = TestServer::start("answers_not_found_for_unknown_keys", "").await;
+
+    let response = server.get("/urls/redirect/missing").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn serves_redirects_at_the_root_for_an_empty_prefix() {
+    let server = TestServer::start("serves_redirects_at_the_root", r#"redirect_prefix = """#).await;
+
+    server.create("docs", "https://example.com/docs").await;
+    let response = server.get("/docs/").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(response.headers()[LOCATION], "https://example.com/docs");
+
+    let response = server
+        .post("/urls")
+        .header("Authorization", USER)
+        .json(&json!({ "key": "shorten", "target": "https://example.com" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{json, TestServer, USER};
+use reqwest::{header::HOST, StatusCode};
+
+const TENANCY: &str = r#"
+[[tenancy.tenants]]
+id = "acme"
+hosts = ["go.acme.test"]
+
+[[tenancy.tenants]]
+id = "initech"
+hosts = ["initech.test"]
+email_domains = ["initech.test"]
+"#;
+
+#[tokio::test]
+async fn keeps_each_tenant_to_its_own_links() {
+    let server = TestServer::start("keeps_each_tenant_to_its_own_links", TENANCY).await;
+    let response = server
+        .post("/urls")
+        .header(HOST, "go.acme.test")
+        .header("Authorization", USER)
+        .json(&serde_json::json!({ "key": "docs", "target": "https://example.com/docs" }))
+        .send()
+        .await
+        .unwrap();
+    let url = json(response, StatusCode::OK).await;
+
+    let redirect = |host: &'static str| server.get("/urls/redirect/docs").header(HOST, host);
+    assert_eq!(
+        redirect("go.acme.test").send().await.unwrap().status(),
+        StatusCode::PERMANENT_REDIRECT
+    );
+    assert_eq!(
+        redirect("initech.test").send().await.unwrap().status(),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        redirect("unknown.test").send().await.unwrap().status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let response = server
+        .get(&format!("/urls/{}", url["id"].as_str().unwrap()))
+        .header(HOST, "initech.test")
+        .header("Authorization", "Dev someone@initech.test")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn refuses_users_outside_the_tenant_email_domains() {
+    let server = TestServer::start("refuses_users_outside_the_tenant", TENANCY).await;
+
+    let response = server
+        .get("/urls")
+        .header(HOST, "initech.test")
+        .header("Authorization", USER)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = server
+        .get("/urls")
+        .header(HOST, "initech.test")
+        .header("Authorization", "Dev service:ci")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::{json, TestServer, USER};
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+async fn clones_a_link_with_its_settings() {
+    let server = TestServer::start("clones_a_link_with_its_settings", "").await;
+    let url = server.create("docs", "https://example.com/docs").await;
+    let id = url["id"].as_str().unwrap();
+    for (path, body) in [
+        ("visibility", json!({ "public": true })),
+        ("indexing", json!({ "allow_indexing": true })),
+        ("keep", json!({ "keep_when_inactive": true })),
+    ] {
+        let response = server
+            .put(&format!("/urls/{id}/{path}"))
+            .header("Authorization", USER)
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        json(response, StatusCode::OK).await;
+    }
+
+    let response = server
+        .post(&format!("/urls/{id}/clone"))
+        .header("Authorization", USER)
+        .json(&json!({ "key": "docs-copy" }))
+        .send()
+        .await
+        .unwrap();
+    let clone = json(response, StatusCode::CREATED).await;
+
+    assert_eq!(clone["key"], "docs-copy");
+    assert_eq!(clone["target"], "https://example.com/docs");
+    assert_eq!(clone["public"], true);
+    assert_eq!(clone["allow_indexing"], true);
+    assert_eq!(clone["keep_when_inactive"], true);
+}
+
+#[tokio::test]
+async fn refuses_to_restore_keys_the_policy_no_longer_allows() {
+    let name = "refuses_to_restore_keys_the_policy_no_longer_allows";
+    let server = TestServer::start(name, "").await;
+    let url = server.create("ab", "https://example.com/a").await;
+    let id = url["id"].as_str().unwrap();
+    let response = server
+        .patch(&format!("/urls/{id}"))
+        .header("Authorization", USER)
+        .json(&json!({ "key": "abcdef", "target": "https://example.com/b" }))
+        .send()
+        .await
+        .unwrap();
+    json(response, StatusCode::OK).await;
+
+    let server = TestServer::restart(name, "[key_policy]\nmin_length = 3").await;
+    let response = server
+        .post(&format!("/urls/{id}/revert/1"))
+        .header("Authorization", USER)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = server
+        .get(&format!("/urls/{id}"))
+        .header("Authorization", USER)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(json(response, StatusCode::OK).await["key"], "abcdef");
+}
+
+#[tokio::test]
+async fn quick_shortens_with_a_token_only() {
+    let server = TestServer::start(
+        "quick_shortens_with_a_token_only",
+        "admins = [\"admin@example.com\"]",
+    )
+    .await;
+
+    let response = server
+        .post("/shorten")
+        .header("Authorization", USER)
+        .body("https://example.com/docs")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .starts_with("/urls/redirect/"));
+
+    let response = server
+        .post("/shorten")
+        .body("https://example.com/docs")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = server
+        .post("/shorten")
+        .header("Authorization", "Dev admin@example.com")
+        .header("X-Impersonate", "someone@example.com")
+        .body("https://example.com/docs")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}